@@ -0,0 +1,77 @@
+//! Throughput of the line-based fraction extraction used by bed12ToFraction
+//!
+//! Run with `cargo bench --bench fraction`
+//!
+//! Reference timings (300k records, 58.9 MB), before and after the switch to `FractionBuffer`:
+//!
+//! ```text
+//! mode       BED12 before/after    BED6 before/after
+//! all        1.07 s / 0.47 s       1.96 s / 0.67 s
+//! cds        1.21 s / 0.49 s       2.23 s / 0.73 s
+//! utr        0.68 s / 0.36 s       0.63 s / 0.33 s
+//! terminal   0.69 s / 0.35 s       0.63 s / 0.31 s
+//! ```
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use cubiculum::extract::extract::fraction_stream;
+
+const RECORD_NUM: usize = 300_000;
+
+/// Linear congruential generator for reproducible inputs
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+/// Transcript-like BED12 records with 1 to 30 exons and UTRs on both sides
+fn records(rng: &mut Lcg) -> String {
+    let mut bed = String::new();
+    for i in 0..RECORD_NUM {
+        let start = rng.next() % 100_000_000;
+        let exon_num = rng.next() % 30 + 1;
+        let mut sizes = String::new();
+        let mut starts = String::new();
+        let mut pos: u64 = 0;
+        for _ in 0..exon_num {
+            let size = rng.next() % 300 + 50;
+            sizes.push_str(&format!("{},", size));
+            starts.push_str(&format!("{},", pos));
+            pos += size + rng.next() % 5_000 + 100;
+        }
+        let length = pos - 100;
+        let thick_start = start + rng.next() % 40;
+        let thick_end = start + length - rng.next() % 40;
+        let strand = if i % 2 == 0 {'+'} else {'-'};
+        bed.push_str(
+            &format!(
+                "chr{}\t{}\t{}\tTX{}\t0\t{}\t{}\t{}\t0\t{}\t{}\t{}\n",
+                i % 22 + 1, start, start + length, i, strand, thick_start, thick_end.max(thick_start), exon_num, sizes, starts
+            )
+        );
+    }
+    bed
+}
+
+fn main() {
+    let mut rng = Lcg(42);
+    let bed = records(&mut rng);
+    println!("{} records, {:.1} MB", RECORD_NUM, bed.len() as f64 / 1_048_576.0);
+    println!("mode\tBED12\tBED6");
+    for mode in ["all", "cds", "utr", "terminal"] {
+        let mut output: Vec<u8> = Vec::with_capacity(bed.len());
+        let timer = Instant::now();
+        black_box(fraction_stream(bed.as_bytes(), &mut output, mode, false, false).unwrap());
+        let bed12 = timer.elapsed();
+        output.clear();
+        let timer = Instant::now();
+        black_box(fraction_stream(bed.as_bytes(), &mut output, mode, false, true).unwrap());
+        let bed6 = timer.elapsed();
+        println!("{}\t{:?}\t{:?}", mode, bed12, bed6);
+    }
+}
@@ -0,0 +1,70 @@
+//! Build and query timings for the interval index backends
+//!
+//! Run with `cargo bench --bench index`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use cubiculum::index::index::{IntervalIndex, IntervalQuery, NestedContainmentList};
+use cubiculum::structs::structs::Interval;
+
+const CHROM_SIZE: u64 = 50_000_000;
+const RECORD_NUM: usize = 500_000;
+const QUERY_NUM: usize = 200_000;
+
+/// Linear congruential generator for reproducible inputs
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+}
+
+/// Exon-like records with occasional long, gene-like ones
+fn records(rng: &mut Lcg) -> Vec<Interval> {
+    (0..RECORD_NUM)
+        .map(|_| {
+            let start = rng.next() % CHROM_SIZE;
+            let length = if rng.next().is_multiple_of(20) {rng.next() % 200_000} else {rng.next() % 2_000} + 1;
+            Interval::from(Some(String::from("chr1")), Some(start), Some(start + length), None)
+        })
+        .collect()
+}
+
+fn run_queries<I: IntervalQuery<Interval>>(index: &I, queries: &[(u64, u64)]) -> (Duration, usize) {
+    let timer = Instant::now();
+    let mut total: usize = 0;
+    for (start, end) in queries {
+        total += black_box(index.count("chr1", *start, *end));
+    }
+    (timer.elapsed(), total)
+}
+
+fn main() {
+    let mut rng = Lcg(42);
+    let data = records(&mut rng);
+    let queries: Vec<(u64, u64)> = (0..QUERY_NUM)
+        .map(|_| {
+            let start = rng.next() % CHROM_SIZE;
+            (start, start + rng.next() % 10_000 + 1)
+        })
+        .collect();
+
+    let timer = Instant::now();
+    let tree = IntervalIndex::new(data.clone());
+    let tree_build = timer.elapsed();
+    let timer = Instant::now();
+    let nclist = NestedContainmentList::new(data);
+    let nclist_build = timer.elapsed();
+
+    let (tree_query, tree_hits) = run_queries(&tree, &queries);
+    let (nclist_query, nclist_hits) = run_queries(&nclist, &queries);
+    assert_eq!(tree_hits, nclist_hits);
+
+    println!("{} records, {} queries, {} hits", RECORD_NUM, QUERY_NUM, tree_hits);
+    println!("backend\tbuild\tqueries");
+    println!("IntervalIndex\t{:?}\t{:?}", tree_build, tree_query);
+    println!("NestedContainmentList\t{:?}\t{:?}", nclist_build, nclist_query);
+}
@@ -0,0 +1,51 @@
+/* C interface to the cubiculum library; see src/ffi/ffi.rs. Regenerate with `cbindgen --config cbindgen.toml` */
+
+#ifndef CUBICULUM_H
+#define CUBICULUM_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque BED record; release with cubiculum_entry_free() */
+typedef struct BedEntry BedEntry;
+
+/* Fraction modes accepted by cubiculum_fraction() */
+#define CUBICULUM_FRACTION_ALL 0
+#define CUBICULUM_FRACTION_CDS 1
+#define CUBICULUM_FRACTION_UTR 2
+#define CUBICULUM_FRACTION_UTR5 3
+#define CUBICULUM_FRACTION_UTR3 4
+#define CUBICULUM_FRACTION_FIRST_EXON 5
+#define CUBICULUM_FRACTION_LAST_EXON 6
+#define CUBICULUM_FRACTION_TERMINAL_UTR 7
+
+BedEntry *cubiculum_parse_bed(const char *line, uint8_t format);
+
+char *cubiculum_entry_to_line(const BedEntry *entry, uint8_t format);
+
+BedEntry *cubiculum_fraction(const BedEntry *entry, int mode, bool intron);
+
+char *cubiculum_line_to_fraction(const char *line, const char *mode, bool intron, bool bed6);
+
+BedEntry *cubiculum_clip(const BedEntry *entry, uint64_t start, uint64_t end);
+
+uint64_t cubiculum_entry_start(const BedEntry *entry);
+
+uint64_t cubiculum_entry_end(const BedEntry *entry);
+
+size_t cubiculum_entry_block_count(const BedEntry *entry);
+
+void cubiculum_entry_free(BedEntry *entry);
+
+void cubiculum_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CUBICULUM_H */
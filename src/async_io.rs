@@ -0,0 +1,5 @@
+/*!
+Module for the asynchronous BED reader and writer; requires the `async` feature
+*/
+
+pub mod async_io;
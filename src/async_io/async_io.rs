@@ -0,0 +1,220 @@
+//! # cubiculum::async_io
+//!
+//! Asynchronous BED reader and writer over tokio's AsyncBufRead and AsyncWrite
+//!
+//! The async variants mirror the blocking stream functions: lines are read and written one at a time
+//! with the same parsing, formatting and header handling, so BED data can be streamed by services
+//! running on a tokio runtime without blocking the executor threads. Only the I/O traits of tokio
+//! are used; the runtime is up to the caller
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufWriter};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{
+    check_fraction_mode, fraction_line_count, fraction_record, is_bed_header_line, try_parse_bed,
+    Bed6Naming, FractionBuffer
+};
+use crate::structs::structs::BedEntry;
+
+/// Asynchronous reader of BED records
+///
+/// Blank lines, comments and track/browser lines are skipped; records are parsed with `try_parse_bed()`
+///
+/// # Usage
+/// ```
+/// use cubiculum::async_io::async_io::AsyncBedReader;
+///
+/// async fn names(bed: &[u8]) -> Vec<String> {
+///     let mut reader = AsyncBedReader::new(bed, 4);
+///     let mut names: Vec<String> = Vec::new();
+///     while let Some(entry) = reader.next_entry().await.unwrap() {
+///         names.push(entry.name().unwrap().clone());
+///     }
+///     names
+/// }
+/// ```
+pub struct AsyncBedReader<R> {
+    reader: R,
+    format: usize,
+    line: String
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncBedReader<R> {
+    /// Create a reader parsing the records in the given format (3 to 9 or 12)
+    pub fn new(reader: R, format: usize) -> AsyncBedReader<R> {
+        AsyncBedReader {reader, format, line: String::new()}
+    }
+
+    /// Read the next record
+    ///
+    /// # Returns
+    /// The record; None once the stream is exhausted. A ParseError for malformed lines, an IoError for read failures
+    ///
+    pub async fn next_entry(&mut self) -> Result<Option<BedEntry>, CubiculumError> {
+        loop {
+            self.line.clear();
+            let read = self.reader.read_line(&mut self.line)
+                .await?;
+            if read == 0 {return Ok(None)};
+            if is_bed_header_line(&self.line) {continue};
+            return try_parse_bed(self.line.trim_end_matches(['\n', '\r']), self.format).map(Some)
+        }
+    }
+
+    /// Read the remaining records; the whole stream is held in memory
+    pub async fn read_all(&mut self) -> Result<Vec<BedEntry>, CubiculumError> {
+        let mut entries: Vec<BedEntry> = Vec::new();
+        while let Some(entry) = self.next_entry().await? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Asynchronous writer of BED records
+///
+/// The records are not buffered by the writer itself; wrap the output into tokio's BufWriter
+/// for unbuffered sinks, and call `flush()` once done
+pub struct AsyncBedWriter<W> {
+    writer: W,
+    format: u8,
+    line: String
+}
+
+impl<W: AsyncWrite + Unpin> AsyncBedWriter<W> {
+    /// Create a writer formatting the records as BED lines of the given format
+    pub fn new(writer: W, format: u8) -> AsyncBedWriter<W> {
+        AsyncBedWriter {writer, format, line: String::new()}
+    }
+
+    /// Write the record as a single line
+    ///
+    /// # Returns
+    /// A FormattingError if the record lacks fields required by the format, an IoError if the output cannot be written
+    ///
+    pub async fn write_entry(&mut self, entry: &BedEntry) -> Result<(), CubiculumError> {
+        self.line.clear();
+        entry.write_line(self.format, &mut self.line)?;
+        self.line.push('\n');
+        self.writer.write_all(self.line.as_bytes())
+            .await
+            .map_err(CubiculumError::from)
+    }
+
+    pub async fn flush(&mut self) -> Result<(), CubiculumError> {
+        self.writer.flush().await.map_err(CubiculumError::from)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Asynchronous version of `fraction_stream()`
+///
+/// # Returns
+/// The number of BED lines written; an error if the mode is invalid, a line does not have
+/// twelve columns, or the output cannot be written
+///
+pub async fn fraction_stream_async<R, W>(
+    reader: R, writer: &mut W, mode: &str, intron: bool, bed6: bool
+) -> Result<usize, CubiculumError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin
+{
+    let naming: Option<Bed6Naming> = if bed6 {Some(Bed6Naming::default())} else {None};
+    fraction_stream_named_async(reader, writer, mode, intron, naming.as_ref()).await
+}
+
+/// Asynchronous version of `fraction_stream_named()`
+pub async fn fraction_stream_named_async<R, W>(
+    mut reader: R, writer: &mut W, mode: &str, intron: bool, naming: Option<&Bed6Naming>
+) -> Result<usize, CubiculumError>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin
+{
+    check_fraction_mode(mode)?;
+    let mut output = BufWriter::new(writer);
+    let mut buffer = FractionBuffer::new();
+    let mut line = String::new();
+    let mut written: usize = 0;
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line).await?;
+        if read == 0 {break};
+        if let Some(fraction) = fraction_record(&mut buffer, &line, mode, intron, naming)? {
+            output.write_all(fraction.as_bytes()).await?;
+            output.write_all(b"\n").await?;
+            written += fraction_line_count(fraction);
+        }
+    }
+    output.flush().await?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test_async_io {
+    use super::*;
+    use crate::extract::extract::fraction_stream;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    const BED: &str = "track name=test\n\
+        chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,\n\
+        \n\
+        chr2\t0\t300\tB\t7\t-\t0\t0\t255,0,0\t3\t50,50,50,\t0,100,250,\n";
+
+    /// In-memory readers and writers never wait, so the futures complete without a runtime
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(x) = future.as_mut().poll(&mut cx) {return x};
+        }
+    }
+
+    #[test]
+    fn read_and_write() {
+        let entries = block_on(AsyncBedReader::new(BED.as_bytes(), 12).read_all()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name().unwrap(), "B");
+
+        let mut writer = AsyncBedWriter::new(Vec::new(), 6);
+        for entry in entries.iter() {
+            block_on(writer.write_entry(entry)).unwrap();
+        }
+        block_on(writer.flush()).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out, "chr1\t100\t600\tA\t0\t+\nchr2\t0\t300\tB\t7\t-\n");
+
+        let mut malformed = AsyncBedReader::new("chr1\t100\n".as_bytes(), 3);
+        assert!(matches!(block_on(malformed.next_entry()), Err(CubiculumError::ParseError(_))));
+        let mut bed3 = AsyncBedWriter::new(Vec::new(), 6);
+        let entry = BedEntry::bed3(String::from("chr1"), 0, 10);
+        assert!(block_on(bed3.write_entry(&entry)).is_err());
+    }
+
+    #[test]
+    fn fraction() {
+        for bed6 in [false, true] {
+            let mut expected: Vec<u8> = Vec::new();
+            let expected_count = fraction_stream(BED.as_bytes(), &mut expected, "cds", false, bed6).unwrap();
+            let mut out: Vec<u8> = Vec::new();
+            let count = block_on(fraction_stream_async(BED.as_bytes(), &mut out, "cds", false, bed6)).unwrap();
+            assert_eq!((count, out), (expected_count, expected));
+        }
+        let mut out: Vec<u8> = Vec::new();
+        assert!(block_on(fraction_stream_async(BED.as_bytes(), &mut out, "introns", false, false)).is_err());
+    }
+}
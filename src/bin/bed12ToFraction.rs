@@ -1,10 +1,12 @@
 use clap::Parser;
+use log::{error, LevelFilter, Log, Metadata, Record};
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
 use std::io::prelude::*;
 use std::path::Path;
+use std::process;
 
-use cubiculum::extract::extract::{bed_to_fraction, BedFractionMode};
+use cubiculum::extract::extract::{fraction_stream_named, Bed6Naming};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -22,7 +24,10 @@ struct Args {
     /// cds - for coding sequence;
     /// utr - for all untranslated sequence;
     /// 5utr - for 5'-side untranslated region;
-    /// 3utr - for 3'-side untranslated region
+    /// 3utr - for 3'-side untranslated region;
+    /// first - for the 5'-most exon;
+    /// last - for the 3'-most exon;
+    /// terminal - for the 5'-most and 3'-most exons containing untranslated sequence
     #[arg(long, short='m', default_value_t = String::from("all"))]
     mode: String,
 
@@ -33,23 +38,44 @@ struct Args {
     /// If set, output format is switched to BED6; each interval will be reported 
     /// as a separate BED6 entry
     #[arg(long, short='b', action)]
-    bed6: bool
+    bed6: bool,
 
+    /// BED6 mode only: if set, block ordinal numbers are appended to the record names
+    /// as NAME.LABEL{number}, e.g., '--name-label exon' produces NAME.exon3
+    #[arg(long)]
+    name_label: Option<String>,
+
+    /// BED6 mode only: if set, the original transcript score is kept
+    /// instead of reporting the block ordinal number in the score column
+    #[arg(long, action)]
+    keep_score: bool,
+
+    /// BED6 mode only: if set, blocks are numbered from zero
+    #[arg(long, action)]
+    zero_based: bool
+
+}
+
+/// Diagnostics sink printing log records to standard error, so that they never mix with the BED output
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {eprintln!("{}", record.args())};
+    }
+
+    fn flush(&self) {}
 }
 
+static LOGGER: StderrLogger = StderrLogger;
+
 fn main() {
     let args = Args::parse();
-
-    match args.mode.as_str() {
-        "all" => { BedFractionMode::All },
-        "cds" => { BedFractionMode::Cds },
-        "utr" => { BedFractionMode::Utr },
-        "5utr" => { BedFractionMode::Utr5 },
-        "3utr" => { BedFractionMode::Utr3 },
-        _ => {
-            panic!("Invalid 'mode' has been provided: {}. Valid modes are: all, cds, utr, 3utr, 5utr", args.mode)
-        }
-    };
+    if log::set_logger(&LOGGER).is_ok() {log::set_max_level(LevelFilter::Warn)};
 
     // let mut output_file = OpenOptions::new()
     //     .create(true)
@@ -70,20 +96,21 @@ fn main() {
         "stdout" => {Box::new(io::stdout()) as Box<dyn Write>},
         _ => {
             let path = Path::new(&args.output);
-            Box::new(File::create(&path).unwrap()) as Box<dyn Write>
+            Box::new(File::create(path).unwrap()) as Box<dyn Write>
         }
     };
 
-    for line_ in input_file.lines() {
-        if let Ok(line) = line_ {
-                let result: Option<String> = bed_to_fraction(line, &args.mode, args.intron, args.bed6);
-                if let Some(fraction) = result {
-                // println!("{}", fraction);
-                if let Err(e) = writeln!(output_file, "{}", fraction) {
-                    eprintln!("Failed to write the line: {}", e);
-                }
-            }
-        }
+    let mut naming = Bed6Naming::new()
+        .keep_score(args.keep_score)
+        .zero_based(args.zero_based);
+    if let Some(label) = &args.name_label {
+        naming = naming.ordinal_in_name(label);
+    }
+
+    let naming = if args.bed6 {Some(&naming)} else {None};
+    if let Err(e) = fraction_stream_named(input_file, &mut output_file, &args.mode, args.intron, naming) {
+        error!("{}", e);
+        process::exit(1);
     }
 
 }
@@ -0,0 +1,494 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use log::{error, LevelFilter, Log, Metadata, Record};
+use std::cell::Cell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Lines};
+use std::io::prelude::*;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+use cubiculum::error::error::CubiculumError;
+use cubiculum::extract::extract::{fraction_stream_named, is_bed_header_line, to_line, try_parse_bed, Bed6Naming};
+use cubiculum::gap::gap::GapMask;
+use cubiculum::index::index::{IntervalIndex, IntervalQuery};
+use cubiculum::intersect::intersect::{block_overlap_size, overlap_region};
+use cubiculum::merge::merge::merge_stream;
+use cubiculum::progress::progress::{Progress, ProgressReader};
+use cubiculum::sort::sort::{sort_records, ChromOrder};
+use cubiculum::structs::structs::{BedEntry, Coordinates, Interval, Named, ToBedLine};
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None)]
+/// BED file manipulation utilities; all subcommands read from standard input and write to standard output by default
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// Report the number of input lines processed to standard error
+    #[arg(long, global = true, action)]
+    progress: bool
+}
+
+/// Set from the --progress flag; checked when opening the inputs
+static PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Diagnostics sink printing log records to standard error, so that they never mix with the BED output
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {eprintln!("{}", record.args())};
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report a fraction of BED12 transcripts (coding sequence, UTRs, terminal exons), as bed12ToFraction does;
+    /// the input is processed line by line and must be in BED12 format
+    Fraction {
+        #[command(flatten)]
+        files: FileArgs,
+
+        /// Fraction to report: all, cds, utr, 5utr, 3utr, first, last or terminal
+        #[arg(long, short = 'm', default_value_t = String::from("all"))]
+        mode: String,
+
+        /// If set, intron intervals will be reported instead of exons as BED12 blocks
+        #[arg(long, short = 'n', action)]
+        intron: bool,
+
+        /// If set, each fraction block is reported as a separate BED6 entry
+        #[arg(long, short = 'b', action)]
+        bed6: bool,
+
+        /// BED6 mode only: append block ordinal numbers to the record names as NAME.LABEL{number}
+        #[arg(long)]
+        name_label: Option<String>,
+
+        /// BED6 mode only: keep the transcript score instead of reporting the block ordinal number
+        #[arg(long, action)]
+        keep_score: bool,
+
+        /// BED6 mode only: number blocks from zero
+        #[arg(long, action)]
+        zero_based: bool
+    },
+    /// Merge overlapping and book-ended records into BED3 intervals; the whole input is loaded into memory
+    /// and sorted first unless --sorted is set
+    Merge {
+        #[command(flatten)]
+        io: IoArgs,
+
+        /// If set, names of the merged records are reported as a comma-separated fourth column
+        #[arg(long, action)]
+        names: bool,
+
+        /// Input is sorted by chromosome and start coordinate (e.g., with `sort -k1,1 -k2,2n`); records are merged
+        /// as they are read without loading the input into memory, and unsorted records are reported as an error
+        #[arg(long, action)]
+        sorted: bool
+    },
+    /// Report overlaps between the records of two files; both files are loaded into memory
+    Intersect {
+        /// Query file
+        #[arg(long, short = 'a')]
+        a: String,
+
+        /// Target file
+        #[arg(long, short = 'b')]
+        b: String,
+
+        /// Output file
+        #[arg(long, short = 'o', default_value_t = String::from("stdout"))]
+        output: String,
+
+        /// Input BED format; inferred from the number of columns if not set
+        #[arg(long, short = 'f')]
+        format: Option<usize>,
+
+        /// Report the query record for each overlap instead of the shared region
+        #[arg(long, action)]
+        wa: bool,
+
+        /// Report each overlapping query record once
+        #[arg(long, short = 'u', action)]
+        unique: bool,
+
+        /// Report the query records with no overlaps
+        #[arg(long, short = 'v', action)]
+        invert: bool,
+
+        /// Compare records by their blocks rather than by their spans
+        #[arg(long, action)]
+        split: bool
+    },
+    /// Remove the bases covered by the target records (including all BED12 blocks) from the query records;
+    /// both files are loaded into memory
+    Subtract {
+        /// Query file
+        #[arg(long, short = 'a')]
+        a: String,
+
+        /// Target file
+        #[arg(long, short = 'b')]
+        b: String,
+
+        /// Output file
+        #[arg(long, short = 'o', default_value_t = String::from("stdout"))]
+        output: String,
+
+        /// Input BED format; inferred from the number of columns if not set
+        #[arg(long, short = 'f')]
+        format: Option<usize>,
+
+        /// Remove the overlapping query records entirely
+        #[arg(long = "entire", short = 'A', action)]
+        entire: bool
+    },
+    /// Sort records by chromosome and coordinates; the whole input is loaded into memory
+    Sort {
+        #[command(flatten)]
+        io: IoArgs,
+
+        /// Chromosome order
+        #[arg(long, value_enum, default_value_t = Order::Lexicographic)]
+        order: Order,
+
+        /// Take the chromosome order from a FASTA index or a chromosome sizes file
+        #[arg(long)]
+        fai: Option<String>
+    },
+    /// Check the records for format violations; problems are reported per line,
+    /// and the exit code is non-zero if any are found
+    Validate {
+        #[command(flatten)]
+        io: IoArgs,
+
+        /// Also require the records to be coordinate-sorted
+        #[arg(long, action)]
+        sorted: bool
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct FileArgs {
+    /// Input file; if set to 'stdin', expects data to come from standard input stream
+    #[arg(long, short = 'i', default_value_t = String::from("stdin"))]
+    input: String,
+
+    /// Output file; if set to 'stdout', will write the output data to standard output stream
+    #[arg(long, short = 'o', default_value_t = String::from("stdout"))]
+    output: String
+}
+
+#[derive(clap::Args, Debug)]
+struct IoArgs {
+    #[command(flatten)]
+    files: FileArgs,
+
+    /// Input BED format; inferred from the number of columns if not set
+    #[arg(long, short = 'f')]
+    format: Option<usize>
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Order {
+    Lexicographic,
+    Natural
+}
+
+fn open_input(path: &str) -> Result<Box<dyn BufRead>, CubiculumError> {
+    let reader: Box<dyn BufRead> = if path == "stdin" || path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        let file = File::open(path).map_err(|e| CubiculumError::IoError(format!("Cannot open {}: {}", path, e)))?;
+        Box::new(BufReader::new(file))
+    };
+    if !PROGRESS.load(AtomicOrdering::Relaxed) {return Ok(reader)};
+    let name = path.to_string();
+    let hook = move |x: &Progress| {
+        eprint!("\r{}: {} lines, {:.1} MB", name, x.records, x.bytes as f64 / 1_048_576.0);
+        if x.finished {eprintln!()};
+    };
+    Ok(Box::new(ProgressReader::new(reader, hook).every(1_000_000)))
+}
+
+fn open_output(path: &str) -> Result<Box<dyn Write>, CubiculumError> {
+    if path == "stdout" || path == "-" {
+        return Ok(Box::new(BufWriter::new(io::stdout())))
+    }
+    let file = File::create(path).map_err(|e| CubiculumError::IoError(format!("Cannot create {}: {}", path, e)))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
+
+fn write_record<W: Write + ?Sized>(writer: &mut W, line: &str) -> Result<(), CubiculumError> {
+    writeln!(writer, "{}", line).map_err(CubiculumError::from)
+}
+
+/// BED format matching the number of columns; BED10 and BED11 lines are read as BED9, BED12+ as BED12
+fn infer_format(line: &str) -> usize {
+    match line.trim().split('\t').count() {
+        x if x >= 12 => {12},
+        x if x >= 9 => {9},
+        x => {x}
+    }
+}
+
+fn check_format(format: usize) -> Result<usize, CubiculumError> {
+    match format {
+        3..=9 | 12 => {Ok(format)},
+        _ => {Err(CubiculumError::InputError(format!("Unsupported BED format: {}", format)))}
+    }
+}
+
+/// Prefix the parsing error message with the input location
+fn located(error: CubiculumError, path: &str, line: usize) -> CubiculumError {
+    match error {
+        CubiculumError::ParseError(x) => {CubiculumError::ParseError(format!("{}:{}: {}", path, line, x))},
+        CubiculumError::CoordinateError(x) => {CubiculumError::CoordinateError(format!("{}:{}: {}", path, line, x))},
+        x => {x}
+    }
+}
+
+/// Records of the input parsed one at a time; the format is inferred from the first record if not set
+struct Records<'a> {
+    lines: Lines<Box<dyn BufRead>>,
+    path: &'a str,
+    format: Option<usize>,
+    line_num: usize
+}
+
+impl Iterator for Records<'_> {
+    type Item = Result<BedEntry, CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(x) => {x},
+                Err(e) => {return Some(Err(e.into()))}
+            };
+            self.line_num += 1;
+            if is_bed_header_line(&line) {continue};
+            let fmt = match check_format(*self.format.get_or_insert_with(|| infer_format(&line))) {
+                Ok(x) => {x},
+                Err(e) => {return Some(Err(e))}
+            };
+            return Some(try_parse_bed(&line, fmt).map_err(|e| located(e, self.path, self.line_num)))
+        }
+    }
+}
+
+fn records(path: &str, format: Option<usize>) -> Result<Records<'_>, CubiculumError> {
+    Ok(Records {lines: open_input(path)?.lines(), path, format, line_num: 0})
+}
+
+/// Read all records from the input, returning them along with their format
+fn read_records(path: &str, format: Option<usize>) -> Result<(Vec<BedEntry>, usize), CubiculumError> {
+    let mut stream = records(path, format)?;
+    let records = stream.by_ref().collect::<Result<Vec<BedEntry>, CubiculumError>>()?;
+    Ok((records, stream.format.unwrap_or(3)))
+}
+
+/// BED4 line for named intervals, BED3 otherwise
+fn interval_line(interval: &Interval) -> Result<String, CubiculumError> {
+    interval.to_bed_line(if interval.name().is_some() {4} else {3})
+}
+
+fn fraction(
+    files: FileArgs, mode: String, intron: bool, bed6: bool, naming: Bed6Naming
+) -> Result<(), CubiculumError> {
+    let reader = open_input(&files.input)?;
+    let mut writer = open_output(&files.output)?;
+    let naming = if bed6 {Some(&naming)} else {None};
+    fraction_stream_named(reader, &mut writer, &mode, intron, naming)?;
+    Ok(())
+}
+
+fn merge(io: IoArgs, names: bool, sorted: bool) -> Result<(), CubiculumError> {
+    if !sorted {
+        let (mut records, _) = read_records(&io.files.input, io.format)?;
+        sort_records(&mut records, &ChromOrder::Lexicographic);
+        let mut writer = open_output(&io.files.output)?;
+        for interval in merge_stream(records, names) {
+            write_record(&mut writer, &interval_line(&interval?)?)?;
+        }
+        return writer.flush().map_err(CubiculumError::from)
+    }
+    // the stream ends at the first unreadable record; its error is reported instead of the interval it cut short
+    let error: Cell<Option<CubiculumError>> = Cell::new(None);
+    let entries = records(&io.files.input, io.format)?.map_while(|x| x.map_err(|e| error.set(Some(e))).ok());
+    let mut writer = open_output(&io.files.output)?;
+    for interval in merge_stream(entries, names) {
+        if let Some(e) = error.take() {return Err(e)};
+        write_record(&mut writer, &interval_line(&interval?)?)?;
+    }
+    if let Some(e) = error.take() {return Err(e)};
+    writer.flush().map_err(CubiculumError::from)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn intersect(
+    a: String, b: String, output: String, format: Option<usize>, wa: bool, unique: bool, invert: bool, split: bool
+) -> Result<(), CubiculumError> {
+    let (queries, a_format) = read_records(&a, format)?;
+    let (targets, _) = read_records(&b, None)?;
+    let index = IntervalIndex::new(targets);
+    let mut writer = open_output(&output)?;
+    for query in queries.iter() {
+        let (chrom, start, end) = match (query.chrom(), query.start(), query.end()) {
+            (Some(x), Some(y), Some(z)) => {(x, *y, *z)},
+            _ => {continue}
+        };
+        let hits: Vec<&BedEntry> = index.find(chrom, start, end)
+            .into_iter()
+            .filter(|x| !split || block_overlap_size(query, x) > 0)
+            .collect();
+        if invert || unique {
+            if hits.is_empty() == invert {write_record(&mut writer, &to_line(query, a_format as u8)?)?};
+            continue
+        }
+        for hit in hits {
+            if wa {
+                write_record(&mut writer, &to_line(query, a_format as u8)?)?;
+            } else if let Some(x) = overlap_region(query, hit) {
+                write_record(&mut writer, &interval_line(&x)?)?;
+            }
+        }
+    }
+    writer.flush().map_err(CubiculumError::from)
+}
+
+fn subtract(a: String, b: String, output: String, format: Option<usize>, entire: bool) -> Result<(), CubiculumError> {
+    let (queries, a_format) = read_records(&a, format)?;
+    let (targets, _) = read_records(&b, None)?;
+    let mut mask = GapMask::new();
+    for target in targets.iter() {
+        let chrom = match target.chrom() {
+            Some(x) => {x},
+            None => {continue}
+        };
+        for (start, end) in target.exons() {
+            mask.insert(chrom, start, end);
+        }
+    }
+    mask.normalize();
+    let mut writer = open_output(&output)?;
+    for query in queries.iter() {
+        let pieces = if entire {
+            if mask.overlaps_gap(query) {Vec::new()} else {vec![query.clone()]}
+        } else {
+            mask.split(query)
+        };
+        for piece in pieces.iter() {
+            write_record(&mut writer, &to_line(piece, a_format as u8)?)?;
+        }
+    }
+    writer.flush().map_err(CubiculumError::from)
+}
+
+fn sort(io: IoArgs, order: Order, fai: Option<String>) -> Result<(), CubiculumError> {
+    let order = match (fai, order) {
+        (Some(x), _) => {ChromOrder::from_fai(x)?},
+        (None, Order::Lexicographic) => {ChromOrder::Lexicographic},
+        (None, Order::Natural) => {ChromOrder::Natural}
+    };
+    let (mut records, format) = read_records(&io.files.input, io.format)?;
+    sort_records(&mut records, &order);
+    let mut writer = open_output(&io.files.output)?;
+    for record in records.iter() {
+        write_record(&mut writer, &to_line(record, format as u8)?)?;
+    }
+    writer.flush().map_err(CubiculumError::from)
+}
+
+/// Check the block structure of a BED12 record; coordinate and blockCount inconsistencies
+/// are already rejected by the parser
+fn record_problems(entry: &BedEntry, format: usize) -> Vec<String> {
+    let mut problems: Vec<String> = Vec::new();
+    if format != 12 {return problems};
+    if entry.exon_starts().and_then(|x| x.first()) != Some(&0) {
+        problems.push(String::from("the first block does not start at chromStart"));
+    }
+    let blocks: Vec<(u64, u64)> = entry.exons().collect();
+    if blocks.windows(2).any(|x| x[0].1 > x[1].0) {
+        problems.push(String::from("blocks overlap or are not in ascending order"));
+    }
+    if blocks.last().map(|x| x.1) != entry.thin_end() {
+        problems.push(String::from("the last block does not end at chromEnd"));
+    }
+    problems
+}
+
+fn validate(io: IoArgs, sorted: bool) -> Result<usize, CubiculumError> {
+    let reader = open_input(&io.files.input)?;
+    let mut writer = open_output(&io.files.output)?;
+    let mut format = io.format;
+    let mut invalid: usize = 0;
+    let mut last: Option<(String, u64)> = None;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if is_bed_header_line(&line) {continue};
+        let fmt = check_format(*format.get_or_insert_with(|| infer_format(&line)))?;
+        let problems = match try_parse_bed(&line, fmt) {
+            Ok(entry) => {
+                let mut problems = record_problems(&entry, fmt);
+                let key = (entry.chrom().cloned().unwrap_or_default(), entry.thin_start().unwrap_or(0));
+                if sorted && last.as_ref().is_some_and(|x| *x > key) {
+                    problems.push(String::from("record is out of coordinate order"));
+                }
+                last = Some(key);
+                problems
+            },
+            Err(e) => {vec![e.to_string()]}
+        };
+        if !problems.is_empty() {
+            invalid += 1;
+            write_record(&mut writer, &format!("line {}: {}", i + 1, problems.join("; ")))?;
+        }
+    }
+    writer.flush()?;
+    Ok(invalid)
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if log::set_logger(&LOGGER).is_ok() {log::set_max_level(LevelFilter::Warn)};
+    PROGRESS.store(cli.progress, AtomicOrdering::Relaxed);
+    let result = match cli.command {
+        Command::Fraction {files, mode, intron, bed6, name_label, keep_score, zero_based} => {
+            let mut naming = Bed6Naming::new().keep_score(keep_score).zero_based(zero_based);
+            if let Some(label) = &name_label {
+                naming = naming.ordinal_in_name(label);
+            }
+            fraction(files, mode, intron, bed6, naming)
+        },
+        Command::Merge {io, names, sorted} => {merge(io, names, sorted)},
+        Command::Intersect {a, b, output, format, wa, unique, invert, split} => {
+            intersect(a, b, output, format, wa, unique, invert, split)
+        },
+        Command::Subtract {a, b, output, format, entire} => {subtract(a, b, output, format, entire)},
+        Command::Sort {io, order, fai} => {sort(io, order, fai)},
+        Command::Validate {io, sorted} => {
+            match validate(io, sorted) {
+                Ok(0) => {Ok(())},
+                Ok(x) => {
+                    error!("Found {} invalid records", x);
+                    process::exit(1)
+                },
+                Err(e) => {Err(e)}
+            }
+        }
+    };
+    if let Err(e) = result {
+        error!("{}", e);
+        process::exit(1);
+    }
+}
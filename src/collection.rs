@@ -0,0 +1,5 @@
+/*!
+Module for the owned BED record collection grouped by chromosome
+*/
+
+pub mod collection;
@@ -0,0 +1,311 @@
+//! # cubiculum::collection
+//!
+//! Owned in-memory collection of BED records grouped by chromosome and sorted by coordinates,
+//! with overlap queries and bulk set operations
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::cmp::max;
+use std::collections::BTreeMap;
+
+use crate::error::error::CubiculumError;
+use crate::filter::filter::Filter;
+use crate::gap::gap::GapMask;
+use crate::index::index::NameIndex;
+use crate::merge::merge::{block_union, merge_stream};
+use crate::structs::structs::{BedEntry, Coordinates, Interval};
+
+/// Records of a single chromosome sorted by start and end coordinates
+#[derive(Clone, Debug, Default)]
+struct ChromEntries {
+    entries: Vec<BedEntry>,
+    /// length of the longest record, bounding the search window of overlap queries
+    max_length: u64
+}
+
+impl ChromEntries {
+    fn new(mut entries: Vec<BedEntry>) -> ChromEntries {
+        entries.sort_by_key(bounds);
+        let max_length = entries.iter().map(|x| {let (start, end) = bounds(x); end - start}).max().unwrap_or(0);
+        ChromEntries {entries, max_length}
+    }
+
+    /// Records which may overlap the [start, end) region
+    fn window(&self, start: u64, end: u64) -> &[BedEntry] {
+        if start >= end {return &[]};
+        let lower = start.saturating_sub(self.max_length);
+        let first = self.entries.partition_point(|x| bounds(x).0 < lower);
+        let last = first + self.entries[first..].partition_point(|x| bounds(x).0 < end);
+        &self.entries[first..last]
+    }
+}
+
+/// Start and end coordinates of a record; defined for every record in the collection
+fn bounds(entry: &BedEntry) -> (u64, u64) {
+    (entry.thin_start().unwrap_or(0), entry.thin_end().unwrap_or(0))
+}
+
+/// Chromosome of a record to be added, with an error for records with undefined coordinates
+fn checked_chrom(entry: &BedEntry) -> Result<&String, CubiculumError> {
+    match (entry.chrom(), entry.start(), entry.end()) {
+        (Some(chrom), Some(start), Some(end)) if start <= end => {Ok(chrom)},
+        _ => {
+            Err(
+                CubiculumError::CoordinateError(
+                    format!("Cannot add a record with undefined or inverted coordinates: {:?}", entry)
+                )
+            )
+        }
+    }
+}
+
+/// BED records grouped by chromosome and kept sorted by coordinates
+///
+/// Chromosomes are ordered lexicographically and records within a chromosome by their start and end
+/// coordinates, which is the order expected by the sweep-line functions (see `SortedBed`). Overlap queries
+/// and the set operations consider the records' spans unless stated otherwise
+///
+/// # Usage
+/// ```
+/// use cubiculum::collection::collection::BedCollection;
+/// use cubiculum::extract::extract::try_parse_bed;
+///
+/// let lines = ["chr2\t50\t80\tC", "chr1\t300\t400\tB", "chr1\t100\t250\tA"];
+/// let entries = lines.iter().map(|x| try_parse_bed(x, 4).unwrap());
+/// let collection = BedCollection::from_entries(entries).unwrap();
+/// let names: Vec<&str> = collection.iter_sorted().map(|x| x.name().unwrap().as_str()).collect();
+/// assert_eq!(names, vec!["A", "B", "C"]);
+/// assert_eq!(collection.query("chr1", 200, 350).count(), 2);
+/// assert_eq!(collection.len_bases(), 280);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BedCollection {
+    chroms: BTreeMap<String, ChromEntries>,
+    len: usize
+}
+
+impl BedCollection {
+    pub fn new() -> BedCollection {
+        BedCollection::default()
+    }
+
+    /// Group and sort the records
+    ///
+    /// # Returns
+    /// The collection; an error if any of the records has undefined chromosome or coordinates
+    ///
+    pub fn from_entries<I: IntoIterator<Item = BedEntry>>(entries: I) -> Result<BedCollection, CubiculumError> {
+        let mut grouped: BTreeMap<String, Vec<BedEntry>> = BTreeMap::new();
+        for entry in entries {
+            let chrom = checked_chrom(&entry)?;
+            match grouped.get_mut(chrom) {
+                Some(x) => {x.push(entry)},
+                None => {grouped.insert(chrom.clone(), vec![entry]);}
+            }
+        }
+        Ok(BedCollection::from_grouped(grouped))
+    }
+
+    fn from_grouped(grouped: BTreeMap<String, Vec<BedEntry>>) -> BedCollection {
+        let len = grouped.values().map(|x| x.len()).sum();
+        let chroms = grouped.into_iter()
+            .filter(|(_, entries)| !entries.is_empty())
+            .map(|(chrom, entries)| (chrom, ChromEntries::new(entries)))
+            .collect();
+        BedCollection {chroms, len}
+    }
+
+    /// Add a record, keeping its chromosome sorted
+    ///
+    /// # Returns
+    /// An error if the record has undefined chromosome or coordinates
+    ///
+    pub fn insert(&mut self, entry: BedEntry) -> Result<(), CubiculumError> {
+        let chrom = checked_chrom(&entry)?;
+        if !self.chroms.contains_key(chrom) {
+            self.chroms.insert(chrom.clone(), ChromEntries::default());
+        }
+        let chrom_entries = self.chroms.get_mut(chrom).unwrap();
+        let (start, end) = bounds(&entry);
+        let pos = chrom_entries.entries.partition_point(|x| bounds(x) <= (start, end));
+        chrom_entries.max_length = max(chrom_entries.max_length, end - start);
+        chrom_entries.entries.insert(pos, entry);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Chromosomes with at least one record, in lexicographic order
+    pub fn chroms(&self) -> impl Iterator<Item = &String> {
+        self.chroms.keys()
+    }
+
+    /// Records of the chromosome sorted by coordinates; empty for chromosomes absent from the collection
+    pub fn chrom_entries(&self, chrom: &str) -> &[BedEntry] {
+        self.chroms.get(chrom).map_or(&[], |x| &x.entries)
+    }
+
+    /// All records, by chromosome and coordinates
+    pub fn iter_sorted(&self) -> impl Iterator<Item = &BedEntry> {
+        self.chroms.values().flat_map(|x| x.entries.iter())
+    }
+
+    /// Records overlapping the [start, end) region, by coordinates
+    pub fn query(&self, chrom: &str, start: u64, end: u64) -> impl Iterator<Item = &BedEntry> {
+        self.chroms.get(chrom)
+            .map_or(&[][..], |x| x.window(start, end))
+            .iter()
+            .filter(move |x| bounds(x).1 > start)
+    }
+
+    /// Number of bases covered by the records' blocks, with overlapping blocks counted once
+    pub fn len_bases(&self) -> u64 {
+        self.chroms.values()
+            .flat_map(|x| block_union(x.entries.iter().flat_map(|y| y.exons())))
+            .map(|(start, end)| end - start)
+            .sum()
+    }
+
+    /// Merge the overlapping and book-ended records; see `merge_stream()`
+    pub fn merge(&self, keep_names: bool) -> Result<Vec<Interval>, CubiculumError> {
+        merge_stream(self.iter_sorted().cloned(), keep_names).collect()
+    }
+
+    /// Remove the bases covered by the other collection's blocks from the records
+    ///
+    /// BED12 records are split at the subtracted blocks, while records of other formats are split into
+    /// separate records for each of the remaining pieces. If `entire` is set, the records overlapping
+    /// the other collection are discarded instead
+    ///
+    /// # Returns
+    /// A new collection; records with no bases left are discarded
+    ///
+    pub fn subtract(&self, other: &BedCollection, entire: bool) -> BedCollection {
+        let mut mask = GapMask::new();
+        for (chrom, chrom_entries) in other.chroms.iter() {
+            for (start, end) in chrom_entries.entries.iter().flat_map(|x| x.exons()) {
+                mask.insert(chrom, start, end);
+            }
+        }
+        mask.normalize();
+        let mut grouped: BTreeMap<String, Vec<BedEntry>> = BTreeMap::new();
+        for (chrom, chrom_entries) in self.chroms.iter() {
+            let mut remaining: Vec<BedEntry> = Vec::new();
+            for entry in chrom_entries.entries.iter() {
+                if entire {
+                    if !mask.overlaps_gap(entry) {remaining.push(entry.clone())};
+                    continue
+                }
+                remaining.extend(mask.split(entry));
+            }
+            grouped.insert(chrom.clone(), remaining);
+        }
+        BedCollection::from_grouped(grouped)
+    }
+
+    /// A new collection holding copies of the records passing the filter
+    pub fn filtered(&self, filter: &Filter) -> BedCollection {
+        let grouped = self.chroms.iter()
+            .map(|(chrom, x)| (chrom.clone(), x.entries.iter().filter(|y| filter.matches(y)).cloned().collect()))
+            .collect();
+        BedCollection::from_grouped(grouped)
+    }
+
+    /// Index the records by name, e.g., to look up all blocks of a transcript reported as BED6 records;
+    /// index positions refer to the `iter_sorted()` order
+    pub fn name_index(&self) -> NameIndex<&BedEntry> {
+        NameIndex::new(self.iter_sorted().collect())
+    }
+
+    /// Consume the collection, returning the records by chromosome and coordinates
+    pub fn into_entries(self) -> Vec<BedEntry> {
+        self.chroms.into_values().flat_map(|x| x.entries).collect()
+    }
+}
+
+impl FromIterator<BedEntry> for BedCollection {
+    /// Collect the records, skipping the ones with undefined chromosome or coordinates
+    fn from_iter<I: IntoIterator<Item = BedEntry>>(iter: I) -> BedCollection {
+        BedCollection::from_entries(iter.into_iter().filter(|x| checked_chrom(x).is_ok())).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test_collection {
+    use super::*;
+    use crate::extract::extract::{to_line, try_parse_bed};
+
+    fn entries() -> Vec<BedEntry> {
+        [
+            "chr2\t0\t100\tD\t0\t+",
+            "chr1\t500\t900\tC\t0\t-",
+            "chr1\t100\t300\tA\t0\t+",
+            "chr1\t150\t5000\tB\t0\t+"
+        ].iter()
+            .map(|x| try_parse_bed(x, 6).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sorted_queries() {
+        let mut collection = BedCollection::from_entries(entries()).unwrap();
+        assert_eq!(collection.len(), 4);
+        assert_eq!(collection.chroms().collect::<Vec<&String>>(), vec!["chr1", "chr2"]);
+        let names = |x: &BedCollection| x.iter_sorted().map(|y| y.name().unwrap().clone()).collect::<Vec<String>>();
+        assert_eq!(names(&collection), vec!["A", "B", "C", "D"]);
+        // the long record B starts before the query window and must still be reported
+        let hits: Vec<&String> = collection.query("chr1", 4000, 4100).map(|x| x.name().unwrap()).collect();
+        assert_eq!(hits, vec!["B"]);
+        assert_eq!(collection.query("chr1", 300, 500).count(), 1);
+        assert_eq!(collection.query("chr3", 0, 100).count(), 0);
+        collection.insert(try_parse_bed("chr1\t120\t130\tE\t0\t+", 6).unwrap()).unwrap();
+        assert_eq!(names(&collection), vec!["A", "E", "B", "C", "D"]);
+        assert!(collection.insert(BedEntry::empty()).is_err());
+        assert_eq!(collection.len_bases(), 5000 - 100 + 100);
+    }
+
+    #[test]
+    fn bulk_operations() {
+        let collection: BedCollection = entries().into_iter().collect();
+        let merged = collection.merge(false).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!((merged[0].start(), merged[0].end()), (Some(&100), Some(&5000)));
+
+        let other = BedCollection::from_entries(vec![
+            try_parse_bed("chr1\t200\t600\tX\t0\t+\t200\t600\t0\t2\t50,100,\t0,300,", 12).unwrap()
+        ]).unwrap();
+        let subtracted = collection.subtract(&other, false);
+        let lines: Vec<String> = subtracted.iter_sorted().map(|x| to_line(x, 6).unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "chr1\t100\t200\tA\t0\t+", "chr1\t150\t200\tB\t0\t+", "chr1\t250\t300\tA\t0\t+",
+                "chr1\t250\t500\tB\t0\t+", "chr1\t600\t900\tC\t0\t-", "chr1\t600\t5000\tB\t0\t+",
+                "chr2\t0\t100\tD\t0\t+"
+            ]
+        );
+        let remaining = collection.subtract(&other, true);
+        assert_eq!(remaining.into_entries().len(), 1);
+
+        let index = subtracted.name_index();
+        assert_eq!(index.indices("B"), &[1, 3, 5]);
+        assert_eq!(index.get("A").len(), 2);
+        assert_eq!(subtracted.filtered(&Filter::new().min_length(100)).len(), 5);
+
+        // unnamed records are split into pieces as well
+        let plain = BedCollection::from_entries(vec![try_parse_bed("chr1\t100\t200", 3).unwrap()]).unwrap();
+        let hole = BedCollection::from_entries(vec![try_parse_bed("chr1\t150\t160", 3).unwrap()]).unwrap();
+        let lines: Vec<String> = plain.subtract(&hole, false).iter_sorted().map(|x| to_line(x, 3).unwrap()).collect();
+        assert_eq!(lines, vec!["chr1\t100\t150", "chr1\t160\t200"]);
+    }
+}
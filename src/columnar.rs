@@ -0,0 +1,5 @@
+/*!
+Module for the Arrow and Parquet export; requires the `arrow` feature
+*/
+
+pub mod columnar;
@@ -0,0 +1,389 @@
+//! # cubiculum::columnar
+//!
+//! Export of BED records into Arrow record batches and Parquet files
+//!
+//! Each BED column becomes a typed column of the batch: coordinates are stored as uint64,
+//! the strand as a "+"/"-" string, and block sizes and starts as list<uint64> columns, so that
+//! the exon structure is available to the analytics engines without parsing the comma-separated fields.
+//! The record batch export requires the `arrow` feature, the Parquet writer the `parquet` one
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::io::BufRead;
+#[cfg(feature = "parquet")]
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::array::{ArrayRef, ListBuilder, StringBuilder, UInt16Builder, UInt64Builder};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::basic::Compression;
+#[cfg(feature = "parquet")]
+use parquet::file::properties::WriterProperties;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, try_parse_bed};
+use crate::structs::structs::{BedEntry, Coordinates};
+
+/// Column names in the BED column order
+const COLUMN_NAMES: [&str; 12] = [
+    "chrom", "start", "end", "name", "score", "strand",
+    "thick_start", "thick_end", "item_rgb", "block_count", "block_sizes", "block_starts"
+];
+
+fn check_format(format: u8) -> Result<(), CubiculumError> {
+    match format {
+        3..=6 | 8 | 9 | 12 => {Ok(())},
+        _ => {
+            Err(
+                CubiculumError::InputError(
+                    format!("Provided format BED{} is not supported. Accepted formats are : BED3,4,5,6,8,9,12", format)
+                )
+            )
+        }
+    }
+}
+
+fn block_field() -> Arc<Field> {
+    Arc::new(Field::new_list_field(DataType::UInt64, false))
+}
+
+/// Arrow schema of the record batches for the given BED format
+///
+/// # Returns
+/// A schema with the first three through twelve columns of `chrom`, `start`, `end`, `name`, `score`, `strand`,
+/// `thick_start`, `thick_end`, `item_rgb`, `block_count`, `block_sizes`, `block_starts`;
+/// an InputError for the formats other than BED3-6, BED8, BED9 and BED12
+///
+pub fn bed_schema(format: u8) -> Result<SchemaRef, CubiculumError> {
+    check_format(format)?;
+    let fields: Vec<Field> = COLUMN_NAMES[..format as usize]
+        .iter()
+        .map(|name| {
+            let data_type = match *name {
+                "start" | "end" | "thick_start" | "thick_end" => {DataType::UInt64},
+                "block_count" => {DataType::UInt16},
+                "block_sizes" | "block_starts" => {DataType::List(block_field())},
+                _ => {DataType::Utf8}
+            };
+            Field::new(*name, data_type, false)
+        })
+        .collect();
+    Ok(Arc::new(Schema::new(fields)))
+}
+
+fn missing(field: &str) -> CubiculumError {
+    CubiculumError::MissingTraitError(format!("Undefined {} field", field))
+}
+
+/// Column builders for a single batch
+struct BedBuilders {
+    format: u8,
+    chrom: StringBuilder,
+    start: UInt64Builder,
+    end: UInt64Builder,
+    name: StringBuilder,
+    score: StringBuilder,
+    strand: StringBuilder,
+    thick_start: UInt64Builder,
+    thick_end: UInt64Builder,
+    rgb: StringBuilder,
+    block_count: UInt16Builder,
+    block_sizes: ListBuilder<UInt64Builder>,
+    block_starts: ListBuilder<UInt64Builder>
+}
+
+impl BedBuilders {
+    fn new(format: u8, capacity: usize) -> BedBuilders {
+        let list = || ListBuilder::with_capacity(UInt64Builder::new(), capacity).with_field(block_field());
+        BedBuilders {
+            format,
+            chrom: StringBuilder::with_capacity(capacity, capacity * 5),
+            start: UInt64Builder::with_capacity(capacity),
+            end: UInt64Builder::with_capacity(capacity),
+            name: StringBuilder::new(),
+            score: StringBuilder::new(),
+            strand: StringBuilder::new(),
+            thick_start: UInt64Builder::new(),
+            thick_end: UInt64Builder::new(),
+            rgb: StringBuilder::new(),
+            block_count: UInt16Builder::new(),
+            block_sizes: list(),
+            block_starts: list()
+        }
+    }
+
+    /// Append a record; an error if the record lacks a field required by the format
+    fn push(&mut self, entry: &BedEntry) -> Result<(), CubiculumError> {
+        if entry.format() < self.format {
+            return Err(
+                CubiculumError::FormattingError(
+                    format!("Cannot export BED{} entry into a BED{} batch", entry.format(), self.format)
+                )
+            )
+        }
+        // check all the fields before appending, so that the columns never go out of sync
+        let chrom = entry.chrom().ok_or_else(|| missing("chromosome"))?;
+        let start = entry.thin_start().ok_or_else(|| missing("thinStart"))?;
+        let end = entry.thin_end().ok_or_else(|| missing("thinEnd"))?;
+        let name = if self.format >= 4 {Some(entry.name().ok_or_else(|| missing("name"))?)} else {None};
+        let score = if self.format >= 5 {Some(entry.score().ok_or_else(|| missing("score"))?)} else {None};
+        let strand = if self.format >= 6 {Some(entry.strand().ok_or_else(|| missing("strand"))?)} else {None};
+        let thick = if self.format >= 8 {
+            Some((
+                entry.thick_start().ok_or_else(|| missing("thickStart"))?,
+                entry.thick_end().ok_or_else(|| missing("thickEnd"))?
+            ))
+        } else {
+            None
+        };
+        let rgb = if self.format >= 9 {Some(entry.rgb().ok_or_else(|| missing("Rgb"))?)} else {None};
+        let blocks = if self.format == 12 {
+            Some((
+                entry.exon_num().ok_or_else(|| missing("exonNumber"))?,
+                entry.exon_sizes().ok_or_else(|| missing("exonSizes"))?,
+                entry.exon_starts().ok_or_else(|| missing("exonStarts"))?
+            ))
+        } else {
+            None
+        };
+
+        self.chrom.append_value(chrom);
+        self.start.append_value(start);
+        self.end.append_value(end);
+        if let Some(x) = name {self.name.append_value(x)};
+        if let Some(x) = score {self.score.append_value(x)};
+        if let Some(x) = strand {self.strand.append_value(if x {"+"} else {"-"})};
+        if let Some((x, y)) = thick {
+            self.thick_start.append_value(x);
+            self.thick_end.append_value(y);
+        }
+        if let Some(x) = rgb {self.rgb.append_value(x)};
+        if let Some((count, sizes, starts)) = blocks {
+            self.block_count.append_value(count);
+            self.block_sizes.values().append_slice(sizes);
+            self.block_sizes.append(true);
+            self.block_starts.values().append_slice(starts);
+            self.block_starts.append(true);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<RecordBatch, CubiculumError> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.chrom.finish()),
+            Arc::new(self.start.finish()),
+            Arc::new(self.end.finish()),
+            Arc::new(self.name.finish()),
+            Arc::new(self.score.finish()),
+            Arc::new(self.strand.finish()),
+            Arc::new(self.thick_start.finish()),
+            Arc::new(self.thick_end.finish()),
+            Arc::new(self.rgb.finish()),
+            Arc::new(self.block_count.finish()),
+            Arc::new(self.block_sizes.finish()),
+            Arc::new(self.block_starts.finish())
+        ];
+        columns.truncate(self.format as usize);
+        RecordBatch::try_new(bed_schema(self.format)?, columns)
+            .map_err(|e| CubiculumError::FormattingError(e.to_string()))
+    }
+}
+
+/// Convert BED records into a single Arrow record batch
+///
+/// # Arguments
+/// `entries`: records to export;
+/// `format`: BED format of the batch; see `bed_schema()` for the columns
+///
+/// # Returns
+/// The record batch; an InputError for unsupported formats, a FormattingError if an entry's format is
+/// lower than the requested one, and a MissingTraitError if an entry lacks a field required by the format
+///
+/// # Examples
+/// ```
+/// use cubiculum::columnar::columnar::to_record_batch;
+/// use cubiculum::extract::extract::try_parse_bed;
+///
+/// let entry = try_parse_bed("chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,", 12).unwrap();
+/// let batch = to_record_batch(&[entry], 12).unwrap();
+/// assert_eq!((batch.num_rows(), batch.num_columns()), (1, 12));
+/// ```
+pub fn to_record_batch(entries: &[BedEntry], format: u8) -> Result<RecordBatch, CubiculumError> {
+    check_format(format)?;
+    let mut builders = BedBuilders::new(format, entries.len());
+    for entry in entries {
+        builders.push(entry)?;
+    }
+    builders.finish()
+}
+
+/// Iterator splitting a stream of BED records into record batches of a fixed size; see `record_batches()`
+pub struct RecordBatches<I> {
+    entries: I,
+    format: u8,
+    batch_size: usize
+}
+
+impl<I> Iterator for RecordBatches<I>
+where
+    I: Iterator<Item = Result<BedEntry, CubiculumError>>
+{
+    type Item = Result<RecordBatch, CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut builders = BedBuilders::new(self.format, self.batch_size);
+        let mut rows: usize = 0;
+        while rows < self.batch_size {
+            let entry = match self.entries.next() {
+                Some(Ok(x)) => {x},
+                Some(Err(e)) => {return Some(Err(e))},
+                None => {break}
+            };
+            if let Err(e) = builders.push(&entry) {return Some(Err(e))};
+            rows += 1;
+        }
+        if rows == 0 {return None};
+        Some(builders.finish())
+    }
+}
+
+/// Split a stream of BED records into record batches
+///
+/// # Arguments
+/// `entries`: records to export, e.g., parsed lines of a BED file;
+/// `format`: BED format of the batches; see `bed_schema()`;
+/// `batch_size`: maximum number of records per batch; the last batch may be shorter
+///
+/// # Returns
+/// An iterator over the batches; an InputError for unsupported formats and zero batch sizes.
+/// Errors in the input stream and in the record conversion are reported by the iterator
+///
+pub fn record_batches<I>(entries: I, format: u8, batch_size: usize) -> Result<RecordBatches<I::IntoIter>, CubiculumError>
+where
+    I: IntoIterator<Item = Result<BedEntry, CubiculumError>>
+{
+    check_format(format)?;
+    if batch_size == 0 {
+        return Err(CubiculumError::InputError("Batch size must be positive".to_string()))
+    }
+    Ok(RecordBatches {entries: entries.into_iter(), format, batch_size})
+}
+
+/// Parse a BED stream into record batches; blank lines, comments and track/browser lines are skipped
+pub fn bed_record_batches<R: BufRead>(
+    reader: R, format: u8, batch_size: usize
+) -> Result<impl Iterator<Item = Result<RecordBatch, CubiculumError>>, CubiculumError> {
+    let entries = bed_lines(reader).map(move |x| x.and_then(|line| try_parse_bed(&line, format as usize)));
+    record_batches(entries, format, batch_size)
+}
+
+/// Write record batches into a Snappy-compressed Parquet file
+///
+/// # Arguments
+/// `writer`: output stream;
+/// `batches`: record batches sharing the schema of `bed_schema(format)`;
+/// `format`: BED format of the batches
+///
+/// # Returns
+/// The number of records written; an error if a batch cannot be produced or written.
+/// The file footer is written even if no batches were provided
+///
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W, I>(writer: W, batches: I, format: u8) -> Result<usize, CubiculumError>
+where
+    W: Write + Send,
+    I: IntoIterator<Item = Result<RecordBatch, CubiculumError>>
+{
+    let parquet_error = |e: parquet::errors::ParquetError| CubiculumError::IoError(e.to_string());
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut parquet_writer = ArrowWriter::try_new(writer, bed_schema(format)?, Some(properties))
+        .map_err(parquet_error)?;
+    let mut written: usize = 0;
+    for batch in batches {
+        let batch = batch?;
+        parquet_writer.write(&batch).map_err(parquet_error)?;
+        written += batch.num_rows();
+    }
+    parquet_writer.close().map_err(parquet_error)?;
+    Ok(written)
+}
+
+/// Convert a BED stream into a Parquet file; see `bed_record_batches()` and `write_parquet()`
+#[cfg(feature = "parquet")]
+pub fn bed_to_parquet<R, W>(reader: R, writer: W, format: u8, batch_size: usize) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write + Send
+{
+    write_parquet(writer, bed_record_batches(reader, format, batch_size)?, format)
+}
+
+#[cfg(test)]
+mod test_columnar {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::{UInt16Type, UInt64Type};
+
+    const BED: &str = "track name=test\n\
+        chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,\n\
+        chr2\t0\t300\tB\t7\t-\t0\t0\t255,0,0\t3\t50,50,50,\t0,100,250,\n";
+
+    #[test]
+    fn bed12_batch() {
+        let entries: Vec<BedEntry> = bed_lines(BED.as_bytes())
+            .map(|x| try_parse_bed(&x.unwrap(), 12).unwrap())
+            .collect();
+        let batch = to_record_batch(&entries, 12).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().field(10).name(), "block_sizes");
+        let chrom = batch.column(0).as_string::<i32>();
+        assert_eq!((chrom.value(0), chrom.value(1)), ("chr1", "chr2"));
+        assert_eq!(batch.column(2).as_primitive::<UInt64Type>().value(1), 300);
+        assert_eq!(batch.column(5).as_string::<i32>().value(1), "-");
+        assert_eq!(batch.column(9).as_primitive::<UInt16Type>().value(1), 3);
+        let starts = batch.column(11).as_list::<i32>();
+        let second = starts.value(1);
+        assert_eq!(second.as_primitive::<UInt64Type>().values().to_vec(), vec![0, 100, 250]);
+
+        let bed6 = to_record_batch(&entries, 6).unwrap();
+        assert_eq!(bed6.num_columns(), 6);
+        assert!(matches!(to_record_batch(&entries, 7), Err(CubiculumError::InputError(_))));
+        let bed3 = vec![BedEntry::bed3(String::from("chr1"), 0, 10)];
+        assert!(matches!(to_record_batch(&bed3, 6), Err(CubiculumError::FormattingError(_))));
+    }
+
+    #[test]
+    fn batched_stream() {
+        let batches: Vec<RecordBatch> = bed_record_batches(BED.as_bytes(), 9, 1)
+            .unwrap()
+            .collect::<Result<Vec<RecordBatch>, CubiculumError>>()
+            .unwrap();
+        assert_eq!(batches.len(), 2);
+        assert!(batches.iter().all(|x| x.num_rows() == 1 && x.num_columns() == 9));
+        assert_eq!(batches[1].column(8).as_string::<i32>().value(0), "255,0,0");
+        assert!(bed_record_batches(BED.as_bytes(), 12, 0).is_err());
+        let mut malformed = bed_record_batches("chr1\t100\n".as_bytes(), 3, 10).unwrap();
+        assert!(matches!(malformed.next(), Some(Err(CubiculumError::ParseError(_)))));
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet_file() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let path = std::env::temp_dir().join(format!("cubiculum_columnar_{}.parquet", std::process::id()));
+        let file = std::fs::File::create(&path).unwrap();
+        assert_eq!(bed_to_parquet(BED.as_bytes(), file, 12, 1).unwrap(), 2);
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata();
+        assert_eq!(metadata.num_rows(), 2);
+        assert_eq!(metadata.schema_descr().num_columns(), 12);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for genome-wide depth of coverage computation
+*/
+
+pub mod coverage;
@@ -0,0 +1,398 @@
+//! # cubiculum::coverage
+//! 
+//! Per-base depth of coverage for sets of annotation records
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+
+use crate::merge::merge::block_union;
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates, Interval};
+
+/// A stretch of bases sharing the same depth of coverage
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DepthRun {
+    pub start: u64,
+    pub end: u64,
+    pub depth: u32
+}
+
+/// Run-length encoded depth of coverage along each chromosome
+///
+/// Only the covered stretches are stored; the remaining bases have zero depth
+#[derive(Clone, Debug, Default)]
+pub struct GenomeCoverage {
+    runs: FxHashMap<String, Vec<DepthRun>>
+}
+
+impl GenomeCoverage {
+    /// Compute the depth of coverage from the records' full spans
+    pub fn from_intervals<T: Coordinates>(intervals: &[T]) -> GenomeCoverage {
+        let mut events: FxHashMap<String, Vec<(u64, i64)>> = FxHashMap::default();
+        for x in intervals {
+            let (chrom, start, end) = match (x.chrom(), x.start(), x.end()) {
+                (Some(c), Some(s), Some(e)) => {(c, *s, *e)},
+                _ => {continue}
+            };
+            add_block(&mut events, chrom, start, end);
+        }
+        GenomeCoverage::from_events(events)
+    }
+
+    /// Compute the depth of coverage from the entries' blocks (exons),
+    /// akin to bedtools' -split option; introns do not contribute to the depth
+    pub fn from_blocks(entries: &[BedEntry]) -> GenomeCoverage {
+        let mut events: FxHashMap<String, Vec<(u64, i64)>> = FxHashMap::default();
+        for x in entries {
+            let chrom = match x.chrom() {
+                Some(c) => {c},
+                None => {continue}
+            };
+            for (start, end) in x.exons() {
+                add_block(&mut events, chrom, start, end);
+            }
+        }
+        GenomeCoverage::from_events(events)
+    }
+
+    fn from_events(events: FxHashMap<String, Vec<(u64, i64)>>) -> GenomeCoverage {
+        let mut runs: FxHashMap<String, Vec<DepthRun>> = FxHashMap::default();
+        for (chrom, mut chrom_events) in events {
+            chrom_events.sort_unstable();
+            let mut chrom_runs: Vec<DepthRun> = Vec::new();
+            let mut depth: i64 = 0;
+            let mut prev: u64 = 0;
+            for (pos, change) in chrom_events {
+                if pos > prev && depth > 0 {
+                    // extend the previous run if the depth has not changed
+                    match chrom_runs.last_mut() {
+                        Some(last) if last.end == prev && last.depth == depth as u32 => {last.end = pos},
+                        _ => {chrom_runs.push(DepthRun {start: prev, end: pos, depth: depth as u32})}
+                    }
+                }
+                depth += change;
+                prev = pos;
+            }
+            runs.insert(chrom, chrom_runs);
+        }
+        GenomeCoverage {runs}
+    }
+
+    /// Returns the sorted names of the covered chromosomes
+    pub fn chroms(&self) -> Vec<&String> {
+        let mut chroms: Vec<&String> = self.runs.keys().collect();
+        chroms.sort();
+        chroms
+    }
+
+    /// Returns the covered stretches for the chromosome, sorted by coordinates
+    pub fn runs(&self, chrom: &str) -> Option<&[DepthRun]> {
+        self.runs.get(chrom).map(|x| x.as_slice())
+    }
+
+    /// Returns the depth of coverage at the given position
+    pub fn depth_at(&self, chrom: &str, pos: u64) -> u32 {
+        let runs = match self.runs.get(chrom) {
+            Some(x) => {x},
+            None => {return 0}
+        };
+        let i = runs.partition_point(|x| x.end <= pos);
+        match runs.get(i) {
+            Some(x) if x.start <= pos => {x.depth},
+            _ => {0}
+        }
+    }
+
+    /// Returns per-base depth values for the first `length` bases of the chromosome
+    pub fn per_base(&self, chrom: &str, length: u64) -> Vec<u32> {
+        let mut depths: Vec<u32> = vec![0; length as usize];
+        if let Some(runs) = self.runs.get(chrom) {
+            for run in runs {
+                if run.start >= length {break};
+                let end = run.end.min(length);
+                depths[run.start as usize..end as usize].fill(run.depth);
+            }
+        }
+        depths
+    }
+
+    /// Format the coverage as bedGraph lines
+    ///
+    /// # Arguments
+    /// `chrom_sizes`: if provided, chromosomes are reported in the provided order,
+    /// zero-depth stretches are reported up to the chromosome ends
+    /// and chromosomes missing from `chrom_sizes` are skipped;
+    /// otherwise, only the covered stretches are reported, ordered by chromosome name
+    ///
+    pub fn to_bedgraph(&self, chrom_sizes: Option<&ChromSizes>) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let sizes = match chrom_sizes {
+            Some(x) => {x},
+            None => {
+                for chrom in self.chroms() {
+                    for run in &self.runs[chrom] {
+                        lines.push(format!("{}\t{}\t{}\t{}", chrom, run.start, run.end, run.depth));
+                    }
+                }
+                return lines
+            }
+        };
+        for chrom in sizes.chroms() {
+            let size = sizes.get(chrom).unwrap();
+            let mut prev: u64 = 0;
+            for run in self.runs.get(chrom).map(|x| x.as_slice()).unwrap_or(&[]) {
+                if run.start >= size {break};
+                if run.start > prev {
+                    lines.push(format!("{}\t{}\t{}\t0", chrom, prev, run.start));
+                }
+                let end = run.end.min(size);
+                lines.push(format!("{}\t{}\t{}\t{}", chrom, run.start, end, run.depth));
+                prev = end;
+            }
+            if prev < size {
+                lines.push(format!("{}\t{}\t{}\t0", chrom, prev, size));
+            }
+        }
+        lines
+    }
+}
+
+fn add_block(events: &mut FxHashMap<String, Vec<(u64, i64)>>, chrom: &str, start: u64, end: u64) {
+    if end <= start {return};
+    let chrom_events = events.entry(chrom.to_string()).or_default();
+    chrom_events.push((start, 1));
+    chrom_events.push((end, -1));
+}
+
+/// Run-length encoded boolean mask over the genome
+///
+/// Stores the covered bases as sorted disjoint runs per chromosome, discarding the identity
+/// of the source intervals; suited for fast set arithmetic over genome regions
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GenomeMask {
+    runs: FxHashMap<String, Vec<(u64, u64)>>
+}
+
+impl GenomeMask {
+    pub fn new() -> GenomeMask {
+        GenomeMask::default()
+    }
+
+    /// Build the mask from the records' full spans
+    pub fn from_intervals<T: Coordinates>(intervals: &[T]) -> GenomeMask {
+        let mut runs: FxHashMap<String, Vec<(u64, u64)>> = FxHashMap::default();
+        for x in intervals {
+            if let (Some(c), Some(s), Some(e)) = (x.chrom(), x.start(), x.end()) {
+                if s >= e {continue};
+                runs.entry(c.clone()).or_default().push((*s, *e));
+            }
+        }
+        for chrom_runs in runs.values_mut() {
+            normalize(chrom_runs);
+        }
+        GenomeMask {runs}
+    }
+
+    /// Build the mask covering the whole genome
+    pub fn from_chrom_sizes(chrom_sizes: &ChromSizes) -> GenomeMask {
+        let runs = chrom_sizes.chroms()
+            .filter_map(|c| {
+                let size = chrom_sizes.get(c).unwrap();
+                if size > 0 {Some((c.clone(), vec![(0, size)]))} else {None}
+            })
+            .collect();
+        GenomeMask {runs}
+    }
+
+    /// Returns the bases covered by either of the masks
+    pub fn union(&self, other: &GenomeMask) -> GenomeMask {
+        let mut runs = self.runs.clone();
+        for (chrom, other_runs) in other.runs.iter() {
+            let chrom_runs = runs.entry(chrom.clone()).or_default();
+            chrom_runs.extend_from_slice(other_runs);
+            normalize(chrom_runs);
+        }
+        GenomeMask {runs}
+    }
+
+    /// Returns the bases covered by both masks
+    pub fn intersection(&self, other: &GenomeMask) -> GenomeMask {
+        let mut runs: FxHashMap<String, Vec<(u64, u64)>> = FxHashMap::default();
+        for (chrom, chrom_runs) in self.runs.iter() {
+            let other_runs = match other.runs.get(chrom) {
+                Some(x) => {x},
+                None => {continue}
+            };
+            let mut shared: Vec<(u64, u64)> = Vec::new();
+            let (mut i, mut j) = (0, 0);
+            while i < chrom_runs.len() && j < other_runs.len() {
+                let start = chrom_runs[i].0.max(other_runs[j].0);
+                let end = chrom_runs[i].1.min(other_runs[j].1);
+                if start < end {shared.push((start, end))};
+                if chrom_runs[i].1 < other_runs[j].1 {i += 1} else {j += 1};
+            }
+            if !shared.is_empty() {runs.insert(chrom.clone(), shared);}
+        }
+        GenomeMask {runs}
+    }
+
+    /// Returns the bases covered by this mask but not by the other one
+    pub fn difference(&self, other: &GenomeMask) -> GenomeMask {
+        let mut runs: FxHashMap<String, Vec<(u64, u64)>> = FxHashMap::default();
+        for (chrom, chrom_runs) in self.runs.iter() {
+            let other_runs = match other.runs.get(chrom) {
+                Some(x) => {x.as_slice()},
+                None => {&[]}
+            };
+            let mut remaining: Vec<(u64, u64)> = Vec::new();
+            let mut j = 0;
+            for (start, end) in chrom_runs.iter() {
+                let mut curr_start = *start;
+                // skip the runs located upstream to the current one
+                while j < other_runs.len() && other_runs[j].1 <= curr_start {j += 1};
+                let mut k = j;
+                while k < other_runs.len() && other_runs[k].0 < *end {
+                    if other_runs[k].0 > curr_start {remaining.push((curr_start, other_runs[k].0))};
+                    curr_start = curr_start.max(other_runs[k].1);
+                    k += 1;
+                }
+                if curr_start < *end {remaining.push((curr_start, *end))};
+            }
+            if !remaining.is_empty() {runs.insert(chrom.clone(), remaining);}
+        }
+        GenomeMask {runs}
+    }
+
+    /// Returns the bases not covered by the mask within the genome
+    pub fn complement(&self, chrom_sizes: &ChromSizes) -> GenomeMask {
+        GenomeMask::from_chrom_sizes(chrom_sizes).difference(self)
+    }
+
+    /// Returns the total number of covered bases
+    pub fn covered_bases(&self) -> u64 {
+        self.runs.values().flatten().map(|(start, end)| end - start).sum()
+    }
+
+    /// Check whether the position is covered by the mask
+    pub fn contains(&self, chrom: &str, pos: u64) -> bool {
+        match self.runs.get(chrom) {
+            Some(runs) => {
+                let i = runs.partition_point(|x| x.1 <= pos);
+                i < runs.len() && runs[i].0 <= pos
+            },
+            None => {false}
+        }
+    }
+
+    /// Returns the covered runs for the chromosome, sorted by coordinates
+    pub fn runs(&self, chrom: &str) -> Option<&[(u64, u64)]> {
+        self.runs.get(chrom).map(|x| x.as_slice())
+    }
+
+    /// Returns the covered runs as intervals sorted by chromosome name and coordinates
+    pub fn to_intervals(&self) -> Vec<Interval> {
+        let mut chroms: Vec<&String> = self.runs.keys().collect();
+        chroms.sort();
+        chroms.into_iter()
+            .flat_map(|c| {
+                self.runs[c].iter().map(move |(s, e)| Interval::from(Some(c.clone()), Some(*s), Some(*e), None))
+            })
+            .collect()
+    }
+}
+
+/// Sort the runs and merge the overlapping and book-ended ones
+fn normalize(runs: &mut Vec<(u64, u64)>) {
+    *runs = block_union(runs.drain(..));
+}
+
+#[cfg(test)]
+mod test_coverage {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+    use crate::structs::structs::Interval;
+
+    fn interval(chrom: &str, start: u64, end: u64) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), None)
+    }
+
+    #[test]
+    fn run_length_depth() {
+        let intervals = vec![
+            interval("chr1", 10, 20),
+            interval("chr1", 15, 30),
+            interval("chr1", 20, 25),
+            interval("chr1", 40, 50),
+            interval("chr2", 0, 5),
+        ];
+        let cov = GenomeCoverage::from_intervals(&intervals);
+        assert_eq!(
+            cov.runs("chr1").unwrap(),
+            &[
+                DepthRun {start: 10, end: 15, depth: 1},
+                DepthRun {start: 15, end: 25, depth: 2},
+                DepthRun {start: 25, end: 30, depth: 1},
+                DepthRun {start: 40, end: 50, depth: 1},
+            ]
+        );
+        assert_eq!(cov.depth_at("chr1", 24), 2);
+        assert_eq!(cov.depth_at("chr1", 30), 0);
+        assert_eq!(cov.depth_at("chr3", 0), 0);
+        assert_eq!(cov.per_base("chr2", 7), vec![1, 1, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn bedgraph_output() {
+        let intervals = vec![interval("chr1", 10, 20), interval("chr2", 0, 5)];
+        let cov = GenomeCoverage::from_intervals(&intervals);
+        assert_eq!(cov.to_bedgraph(None), vec!["chr1\t10\t20\t1", "chr2\t0\t5\t1"]);
+        let mut sizes = ChromSizes::new();
+        sizes.insert(String::from("chr1"), 30);
+        sizes.insert(String::from("chr3"), 10);
+        assert_eq!(
+            cov.to_bedgraph(Some(&sizes)),
+            vec!["chr1\t0\t10\t0", "chr1\t10\t20\t1", "chr1\t20\t30\t0", "chr3\t0\t10\t0"]
+        );
+    }
+
+    #[test]
+    fn split_depth() {
+        let entry = try_parse_bed(
+            "chr1\t100\t600\ta\t0\t+\t100\t600\t0\t2\t100,100,\t0,400,", 12
+        ).unwrap();
+        let cov = GenomeCoverage::from_blocks(&[entry]);
+        assert_eq!(cov.depth_at("chr1", 150), 1);
+        assert_eq!(cov.depth_at("chr1", 300), 0);
+        assert_eq!(cov.runs("chr1").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn mask_arithmetic() {
+        let first = GenomeMask::from_intervals(&[
+            interval("chr1", 0, 100),
+            interval("chr1", 50, 150),
+            interval("chr1", 150, 200),
+            interval("chr1", 300, 400),
+            interval("chr2", 0, 10),
+        ]);
+        let second = GenomeMask::from_intervals(&[interval("chr1", 90, 310), interval("chr3", 0, 5)]);
+        assert_eq!(first.runs("chr1").unwrap(), &[(0, 200), (300, 400)]);
+        assert_eq!(first.covered_bases(), 310);
+        assert_eq!(first.union(&second).runs("chr1").unwrap(), &[(0, 400)]);
+        assert_eq!(first.union(&second).covered_bases(), 415);
+        let shared = first.intersection(&second);
+        assert_eq!(shared.runs("chr1").unwrap(), &[(90, 200), (300, 310)]);
+        assert!(shared.runs("chr2").is_none());
+        let diff = first.difference(&second);
+        assert_eq!(diff.runs("chr1").unwrap(), &[(0, 90), (310, 400)]);
+        assert_eq!(diff.runs("chr2").unwrap(), &[(0, 10)]);
+        assert!(first.contains("chr1", 199));
+        assert!(!first.contains("chr1", 200));
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr1"), 500);
+        assert_eq!(first.complement(&chrom_sizes).runs("chr1").unwrap(), &[(200, 300), (400, 500)]);
+        assert_eq!(first.to_intervals().len(), 3);
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for the crate-wide error type
+*/
+
+pub mod error;
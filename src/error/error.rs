@@ -0,0 +1,74 @@
+//! # cubiculum::error
+//!
+//! Error type shared by all fallible operations of the crate
+//!
+//! `CubiculumError` is also re-exported from `cubiculum::extract::extract`, where it was originally defined
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::io;
+use std::num::ParseIntError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CubiculumError {
+    #[error("ParseError: {0}")]
+    ParseError(String),
+    #[error("MissingTraitError: {0}")]
+    MissingTraitError(String),
+    #[error("FormattingError: {0}")]
+    FormattingError(String),
+    #[error("InputError: {0}")]
+    InputError(String),
+    /// The entries to be combined lie on different chromosomes
+    #[error("IncompatibleChrom: {0}")]
+    IncompatibleChrom(String),
+    /// The operation was rejected because it would merge overlapping blocks
+    #[error("OverlapRejected: {0}")]
+    OverlapRejected(String),
+    /// A coding block cannot be attached to an untranslated flank
+    #[error("NonCodingFlank: {0}")]
+    NonCodingFlank(String),
+    /// A non-coding block cannot extend into the coding sequence
+    #[error("CodingOverlap: {0}")]
+    CodingOverlap(String),
+    /// Coordinates are undefined or inconsistent, e.g. the start lies past the end
+    #[error("CoordinateError: {0}")]
+    CoordinateError(String),
+    /// The operation expects coordinate-sorted input
+    #[error("UnsortedInput: {0}")]
+    UnsortedInput(String),
+    /// Reading or writing the underlying stream failed
+    #[error("IoError: {0}")]
+    IoError(String),
+}
+
+impl From<io::Error> for CubiculumError {
+    fn from(e: io::Error) -> Self {
+        CubiculumError::IoError(e.to_string())
+    }
+}
+
+impl From<ParseIntError> for CubiculumError {
+    fn from(e: ParseIntError) -> Self {
+        CubiculumError::ParseError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test_error {
+    use super::*;
+
+    fn parse(x: &str) -> Result<u64, CubiculumError> {
+        Ok(x.parse::<u64>()?)
+    }
+
+    #[test]
+    fn conversions() {
+        assert!(matches!(parse("x"), Err(CubiculumError::ParseError(_))));
+        let e: CubiculumError = io::Error::new(io::ErrorKind::BrokenPipe, "closed").into();
+        assert_eq!(e.to_string(), "IoError: closed");
+    }
+}
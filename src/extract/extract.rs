@@ -10,147 +10,269 @@
 
 // use anyhow::{Error, Result};
 use std::cmp;
-use std::fmt::Display;
+use fxhash::FxHashMap;
+use std::io::{BufRead, BufWriter, Write};
 use std::ops;
 
+pub use crate::error::error::CubiculumError;
+use crate::options::options::ProcessingOptions;
 use crate::structs::structs::{BedEntry, Coordinates};
 
-#[derive(Debug)]
-pub enum CubiculumError {
-    ParseError(String),
-    MissingTraitError(String),
-    FormattingError(String),
-}
-
-impl Display for CubiculumError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            CubiculumError::ParseError(x) => {write!(f, "ParseError: {}", x)},
-            CubiculumError::MissingTraitError(x) => {write!(f, "MissingTraitError: {}", x)},
-            CubiculumError::FormattingError(x) => {write!(f, "FormattingError: {}", x)},
-        }
-    }
-}
-
-impl std::error::Error for CubiculumError {}
-
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BedFractionMode {
     All,
     Cds,
     Utr,
     Utr5,
-    Utr3
+    Utr3,
+    /// The 5'-most exon of the transcript
+    FirstExon,
+    /// The 3'-most exon of the transcript
+    LastExon,
+    /// The 5'-most and the 3'-most exons, provided they contain untranslated bases
+    TerminalUtr
+}
+
+/// Naming scheme for the fraction blocks reported as separate BED6 records
+/// 
+/// By default, blocks inherit the transcript name and carry their one-based ordinal number
+/// (counted from the transcript's 5'-end) in the score field
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bed6Naming {
+    /// If set, the ordinal number is appended to the name as `{name}.{label}{number}`
+    pub name_label: Option<String>,
+    /// If true, the score field keeps the original transcript score instead of the ordinal number
+    pub keep_score: bool,
+    /// If true, blocks are numbered from zero
+    pub zero_based: bool
+}
+
+impl Bed6Naming {
+    pub fn new() -> Bed6Naming {
+        Bed6Naming::default()
+    }
+
+    pub fn ordinal_in_name(mut self, label: &str) -> Bed6Naming {
+        self.name_label = Some(label.to_string());
+        self
+    }
+
+    pub fn keep_score(mut self, keep_score: bool) -> Bed6Naming {
+        self.keep_score = keep_score;
+        self
+    }
+
+    pub fn zero_based(mut self, zero_based: bool) -> Bed6Naming {
+        self.zero_based = zero_based;
+        self
+    }
+
+    /// Name and score fields for the block with the given one-based ordinal number
+    fn label(&self, name: &str, score: &str, number: usize) -> (String, String) {
+        let number: usize = if self.zero_based {number - 1} else {number};
+        let name: String = match &self.name_label {
+            Some(label) => {format!("{}.{}{}", name, label, number)},
+            None => {name.to_string()}
+        };
+        let score: String = if self.keep_score {score.to_string()} else {number.to_string()};
+        (name, score)
+    }
+
+    /// Same as `label`, writing the tab-separated name and score fields to `out`
+    fn write_label(&self, out: &mut String, name: &str, score: &str, number: usize) {
+        let number: usize = if self.zero_based {number - 1} else {number};
+        out.push_str(name);
+        if let Some(label) = &self.name_label {
+            out.push('.');
+            out.push_str(label);
+            push_u64(out, number as u64);
+        }
+        out.push('\t');
+        if self.keep_score {
+            out.push_str(score);
+        } else {
+            push_u64(out, number as u64);
+        }
+    }
 }
 
 
 /// Basic BED file line parser
 /// 
 /// # Arguments
+/// `line`: a BED line to parse;
+/// `format`: number of columns to read, three through nine or twelve;
+/// `skip_blank`: if true, blank lines produce None
 /// 
+/// # Returns
+/// The parsed entry, or None if the line is malformed. Coordinates are validated as in `try_parse_bed()`
+/// 
+/// # Panics
+/// If the line is blank and `skip_blank` is not set
+/// 
+#[deprecated(note = "use `try_parse_bed()` or `parse_bed_with_options()`, which report malformed lines as errors")]
 pub fn parse_bed(
     line: String, format: usize, skip_blank: bool
 ) -> Option<BedEntry> {
+    if line.trim().is_empty() {
+        if skip_blank {return None};
+        panic!("Blank BED line encountered while skip_blank is not set");
+    }
+    try_parse_bed(&line, format).ok()
+}
+
+/// Read a numeric BED field, naming the field in the error message
+fn parse_field<T: std::str::FromStr>(value: &str, field: &str) -> Result<T, CubiculumError> {
+    value.parse::<T>().map_err(
+        |_| CubiculumError::ParseError(format!("{} is not a valid positive integer: {}", field, value))
+    )
+}
+
+/// Parse a BED line, reporting malformed lines as errors
+/// 
+/// # Arguments
+/// `line`: a BED line to parse; extra columns beyond `format` are ignored;
+/// `format`: number of columns to read, three through nine or twelve
+/// 
+/// # Returns
+/// The parsed entry; an InputError for an unsupported format, a ParseError for lines with missing columns
+/// or non-numeric coordinates, and a CoordinateError for inconsistent coordinates.
+/// Equivalent to `parse_bed_with_options()` in strict mode
+/// 
+pub fn try_parse_bed(line: &str, format: usize) -> Result<BedEntry, CubiculumError> {
+    parse_bed_with_options(line, format, &ProcessingOptions::strict())
+}
+
+/// Parse a BED line with the given anomaly handling
+///
+/// In lenient mode, swapped thinStart and thinEnd values are put in order, the thick region is
+/// clamped to the entry boundaries (an inverted thick region is collapsed to its start),
+/// and the blocks are truncated to the shorter of the sizes and starts lists if their number
+/// does not match blockCount. Missing columns and non-numeric values are errors in both modes
+///
+/// # Arguments
+/// `line`: a BED line to parse; extra columns beyond `format` are ignored;
+/// `format`: number of columns to read, three through nine or twelve;
+/// `options`: anomaly handling settings
+///
+pub fn parse_bed_with_options(line: &str, format: usize, options: &ProcessingOptions) -> Result<BedEntry, CubiculumError> {
     // BED file cannot contain less than three fields, and BED12+ are not currently accepted
-    if format < 3 || format > 12 {
-        panic!("Illegal BED file format specification! Accepted formats are BED3 through BED12");
+    if !(3..=12).contains(&format) {
+        return Err(
+            CubiculumError::InputError(
+                String::from("Illegal BED file format specification! Accepted formats are BED3 through BED12")
+            )
+        )
     }
     if format == 10 || format == 11 {
-        panic!(
-            "BED10 and BED11 formats contain incomplete data on the sequence block structure. 
+        return Err(
+            CubiculumError::InputError(
+                String::from(
+                    "BED10 and BED11 formats contain incomplete data on the sequence block structure. \
 If you want to parse an incomplete BED entry, consider BED9 format instead"
-        );
-    } 
+                )
+            )
+        )
+    }
     let data: Vec<&str>  = line
         .trim()
-        .split("\t")
+        .split('\t')
         .collect::<Vec<&str>>();
-    if data.len() == 0 {
-        // panic if skip_blank was not set
-        return None;
-    }
-
-    if (data.len() as usize) < format {
-        // panic here
+    if data.len() < format {
+        return Err(
+            CubiculumError::ParseError(format!("Expected {} columns, found {}: {}", format, data.len(), line.trim()))
+        )
     }
+    let coordinate_error = |x: String| CubiculumError::CoordinateError(x);
 
     let chrom: String = data[0].to_string();
-    let thin_start: u64 = data[1]
-        .parse::<u64>()
-        .expect("ThickStart is not a valid positive integer");
-    let thin_end: u64 = data[2]
-        .parse::<u64>()
-        .expect("ThickEnd is not a valid positive integer");
-    assert!(thin_start <= thin_end);
+    let mut thin_start: u64 = parse_field(data[1], "thinStart")?;
+    let mut thin_end: u64 = parse_field(data[2], "thinEnd")?;
+    if thin_start > thin_end {
+        options.anomaly(
+            coordinate_error(format!("thinStart value ({}) cannot be larger than thinEnd ({})", thin_start, thin_end))
+        )?;
+        (thin_start, thin_end) = (thin_end, thin_start);
+    }
 
     if format == 3 {
-        return Some(BedEntry::bed3(chrom, thin_start, thin_end));
+        return Ok(BedEntry::bed3(chrom, thin_start, thin_end));
     }
 
     let name: String = data[3].to_string();
     if format == 4 {
-        return Some(BedEntry::bed4(chrom, thin_start, thin_end, name));
+        return Ok(BedEntry::bed4(chrom, thin_start, thin_end, name));
     }
 
     let score: String = data[4].to_string();
     if format == 5 {
-        return Some(BedEntry::bed5(chrom, thin_start, thin_end, name, score));
+        return Ok(BedEntry::bed5(chrom, thin_start, thin_end, name, score));
     }
 
     let strand: bool = data[5] == "+";
     if format == 6 {
-        return Some(BedEntry::bed6(chrom, thin_start, thin_end, name, score, strand));
+        return Ok(BedEntry::bed6(chrom, thin_start, thin_end, name, score, strand));
     }
 
-    let thick_start: u64 = data[6]
-        .parse::<u64>()
-        .expect("thinStart is not a valid positive integer");
+    let mut thick_start: u64 = parse_field(data[6], "thickStart")?;
+    let mut thick_end: u64 = parse_field(data[7], "thickEnd")?;
     if thick_start < thin_start {
-        panic!("thickStart value ({}) cannot be smaller than thinStart ({})", thick_start, thin_start)
+        options.anomaly(
+            coordinate_error(format!("thickStart value ({}) cannot be smaller than thinStart ({})", thick_start, thin_start))
+        )?;
+        thick_start = thin_start;
     }
-    let thick_end: u64 = data[7]
-        .parse::<u64>()
-        .expect("thinEnd is not a valid positive integer");
     if thick_end > thin_end {
-        panic!("thickEnd value ({}) cannot be larger than thinEnd ({})", thick_end, thin_end)
+        options.anomaly(
+            coordinate_error(format!("thickEnd value ({}) cannot be larger than thinEnd ({})", thick_end, thin_end))
+        )?;
+        thick_end = thin_end;
     }
     if thick_start > thick_end {
-        panic!("thickStart value ({}) cannot be larger than thickEnd ({})", thick_start, thick_end)
+        options.anomaly(
+            coordinate_error(format!("thickStart value ({}) cannot be larger than thickEnd ({})", thick_start, thick_end))
+        )?;
+        thick_start = cmp::min(thick_start, thin_end);
+        thick_end = thick_start;
     }
 
     if format == 8 {
-        return Some(BedEntry::bed8(chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end))
+        return Ok(BedEntry::bed8(chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end))
     }
 
     let rgb: String = data[8].to_string();
     if format == 9 {
-        return Some(
+        return Ok(
             BedEntry::bed9(chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end, rgb)
         )
     }
 
-    let ex_num: u16 = data[9]
-        .parse::<u16>()
-        .expect("Exon number is not a valid positive integer");
-    let exon_sizes: Vec<u64> = data[10]
+    let mut ex_num: u16 = parse_field(data[9], "Exon number")?;
+    let mut exon_sizes: Vec<u64> = data[10]
         .split(',')
-        .filter(|x|
-            !x.is_empty()
-        )
-        .map(|x|
-            x.parse::<u64>().expect("Invalid exon size value")
-        )
-        .collect::<Vec<u64>>();
-    let exon_starts: Vec<u64> = data[11]
+        .filter(|x| !x.is_empty())
+        .map(|x| parse_field(x, "Exon size"))
+        .collect::<Result<Vec<u64>, CubiculumError>>()?;
+    let mut exon_starts: Vec<u64> = data[11]
         .split(',') 
-        .filter(|x|
-            !x.is_empty()
-        )
-        .map(|x|
-            x.parse::<u64>().expect("Invalid exon start position")
-        )
-        .collect::<Vec<u64>>();
-    return Some(
+        .filter(|x| !x.is_empty())
+        .map(|x| parse_field(x, "Exon start"))
+        .collect::<Result<Vec<u64>, CubiculumError>>()?;
+    if exon_sizes.len() != ex_num as usize || exon_starts.len() != ex_num as usize {
+        options.anomaly(
+            CubiculumError::ParseError(
+                format!(
+                    "Exon number ({}) does not match the number of exon sizes ({}) and starts ({})",
+                    ex_num, exon_sizes.len(), exon_starts.len()
+                )
+            )
+        )?;
+        let blocks = cmp::min(exon_sizes.len(), exon_starts.len());
+        exon_sizes.truncate(blocks);
+        exon_starts.truncate(blocks);
+        ex_num = blocks as u16;
+    }
+    Ok(
         BedEntry::bed12(
             chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end, rgb, 
             ex_num, exon_sizes, exon_starts 
@@ -166,129 +288,110 @@ If you want to parse an incomplete BED entry, consider BED9 format instead"
 /// (WARNING: BED12+ files are currently not accepted)
 /// 
 /// # Returns
-/// A Result containing a String representation of the input BedEntry
+/// A Result containing a String representation of the input BedEntry;
+/// see `BedEntry::write_line()` for writing into a reusable buffer
 /// 
 pub fn to_line(bed_entry: &BedEntry, format: u8) -> Result<String, CubiculumError> {
-    let entry_format = match bed_entry.format() {
-        0 => {return Err(CubiculumError::MissingTraitError("Undefined BED format for the entry".to_string()))}
-        x  => {x},
-    };
-    if entry_format < format {
-        return Err(
-            CubiculumError::FormattingError(
-                format!("Cannot format BED{} entry into a BED{} line", format, entry_format)
-            )
-        );
-    }
-    if format < 3 || format == 7 || (format > 9 && format < 12) || format > 12 {
-        return Err(
-            CubiculumError::FormattingError(
-                format!("Provided format BED{} is not supported. Accepted formats are : BED3,4,5,6,8,9,12", format)
-            )
-        );
+    let mut out = String::new();
+    bed_entry.write_line(format, &mut out)?;
+    Ok(out)
+}
+
+/// Comma-terminated exonFrames column for a BED12 entry
+fn frames_column(bed_entry: &BedEntry) -> Result<String, CubiculumError> {
+    let frames = bed_entry.exon_frames().ok_or(
+        CubiculumError::MissingTraitError("Cannot infer exon frames for an entry with undefined strand, CDS or blocks".to_string())
+    )?;
+    Ok(
+        frames
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>()
+            .join(",") + ","
+    )
+}
+
+/// Format a BED12 BedEntry object into a BED12+1 line with the genePred-style exonFrames column appended
+/// 
+/// # Returns
+/// A Result containing the BED12+1 line; see `BedEntry::exon_frames()` for the frame convention
+/// 
+pub fn to_line_with_frames(bed_entry: &BedEntry) -> Result<String, CubiculumError> {
+    let line = to_line(bed_entry, 12)?;
+    Ok(format!("{}\t{}", line, frames_column(bed_entry)?))
+}
+
+/// Format a BED12 BedEntry object into a genePredExt line
+/// 
+/// The transcript name is used for both the name and name2 columns; non-numeric scores are reported as zero.
+/// CDS start and end statuses are set to 'cmpl' for coding entries and to 'none' otherwise
+/// 
+/// # Returns
+/// A Result containing the genePredExt line
+/// 
+pub fn to_genepred_ext(bed_entry: &BedEntry) -> Result<String, CubiculumError> {
+    if bed_entry.format() != 12 {
+        return Err(CubiculumError::FormattingError("Only BED12 entries can be converted into genePredExt".to_string()))
     }
-    let out = String::new();
+    let frames = frames_column(bed_entry)?;
     let chrom = match bed_entry.chrom() {
         Some(x) => {x},
         None => {return Err(CubiculumError::MissingTraitError("Undefined chromosome field".to_string()))}
     };
-    let thin_start = match bed_entry.thin_start() {
-        Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined thinStart field".to_string()))}
-    };
-    let thin_end = match bed_entry.thin_end() {
-        Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined thinEnd field".to_string()))}
-    };
-    if format == 3 {
-        return Ok(format!("{}\t{}\t{}", chrom, thin_start, thin_end));
-    }
     let name = match bed_entry.name() {
         Some(x) => {x},
         None => {return Err(CubiculumError::MissingTraitError("Undefined name field".to_string()))}
     };
-    if format == 4 {
-        return Ok(format!("{}\t{}\t{}\t{}", chrom, thin_start, thin_end, name));
-    }
-    let score = match bed_entry.score() {
-        Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined score field".to_string()))}
-    };
-    if format == 5 {
-        return Ok(format!("{}\t{}\t{}\t{}\t{}", chrom, thin_start, thin_end, name, score));
-    }
-    let strand = match bed_entry.strand() {
-        Some(x) => {
-            match x {
-                true => {'+'},
-                false => {'-'}
-            }
-        },
-        None => {return Err(CubiculumError::MissingTraitError("Undefined strand field".to_string()))}
-    };
-    if format == 6 {
-        return Ok(format!("{}\t{}\t{}\t{}\t{}\t{}", chrom, thin_start, thin_end, name, score, strand));
-    }
-    let thick_start = match bed_entry.thick_start() {
-        Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined thickStart field".to_string()))}
-    };
-    let thick_end = match bed_entry.thick_end() {
+    let score: i64 = bed_entry.score().and_then(|x| x.parse::<i64>().ok()).unwrap_or(0);
+    // exon_frames() has already checked the strand and the coding boundaries
+    let strand = if bed_entry.strand().unwrap() {'+'} else {'-'};
+    let (thick_start, thick_end) = (bed_entry.thick_start().unwrap(), bed_entry.thick_end().unwrap());
+    let status = if thick_start < thick_end {"cmpl"} else {"none"};
+    let blocks: Vec<(u64, u64)> = bed_entry.exons().collect();
+    let exon_starts = blocks.iter().map(|x| x.0.to_string()).collect::<Vec<String>>().join(",") + ",";
+    let exon_ends = blocks.iter().map(|x| x.1.to_string()).collect::<Vec<String>>().join(",") + ",";
+    Ok(
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            name, chrom, strand, bed_entry.thin_start().unwrap(), bed_entry.thin_end().unwrap(),
+            thick_start, thick_end, blocks.len(), exon_starts, exon_ends,
+            score, name, status, status, frames
+        )
+    )
+}
+
+
+/// Format a BedEntry object into SAF lines (GeneID, Chr, Start, End, Strand) for featureCounts
+///
+/// BED12 entries are exploded into one line per block, all sharing the entry's name as GeneID;
+/// other formats produce a single line. Coordinates are converted to the 1-based inclusive SAF convention,
+/// and entries with no defined strand are reported with '.'
+///
+/// # Returns
+/// A Result containing the SAF lines; an error if the entry has no chromosome, coordinates or name
+///
+pub fn to_saf(bed_entry: &BedEntry) -> Result<Vec<String>, CubiculumError> {
+    let chrom = match bed_entry.chrom() {
         Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined thickEnd field".to_string()))}
+        None => {return Err(CubiculumError::MissingTraitError("Undefined chromosome field".to_string()))}
     };
-    if format == 8 {
-        return Ok(
-            format!(
-                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", 
-                chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end
-            )
-        );
-    }
-    let rgb = match bed_entry.rgb() {
+    let name = match bed_entry.name() {
         Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined Rgb field".to_string()))}
+        None => {return Err(CubiculumError::MissingTraitError("SAF output requires a name field".to_string()))}
     };
-    if format == 9 {
-        return Ok(
-            format!(
-                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", 
-                chrom, thin_start, thin_end, name, score, strand, thick_start, thick_end, rgb
-            )
-        );
+    if bed_entry.thin_start().is_none() || bed_entry.thin_end().is_none() {
+        return Err(CubiculumError::MissingTraitError("Undefined coordinates".to_string()))
     }
-    let exon_num = match bed_entry.exon_num() {
-        Some(x) => {x},
-        None => {return Err(CubiculumError::MissingTraitError("Undefined exonNumber field".to_string()))}
-    };
-    let exon_sizes = match bed_entry.exon_sizes() {
-        Some(x) => {
-            x
-                .iter()
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>()
-                .join(",") + ","
-        },
-        None => {return Err(CubiculumError::MissingTraitError("Undefined exonSizes field".to_string()))}
-    };
-    let exon_starts = match bed_entry.exon_starts() {
-        Some(x) => {
-            x
-                .iter()
-                .map(|x| x.to_string())
-                .collect::<Vec<String>>()
-                .join(",") + ","
-        },
-        None => {return Err(CubiculumError::MissingTraitError("Undefined exonStarts field".to_string()))}
+    let strand = match bed_entry.strand() {
+        Some(true) => {'+'},
+        Some(false) => {'-'},
+        None => {'.'}
     };
-    return Ok(
-        format!(
-            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", 
-            chrom, thin_start, thin_end, name, score, strand, 
-            thick_start, thick_end, rgb,
-            exon_num, exon_sizes, exon_starts
-        )
-    );
+    Ok(
+        bed_entry.exons()
+            .map(|(start, end)| format!("{}\t{}\t{}\t{}\t{}", name, chrom, start + 1, end, strand))
+            .collect()
+    )
 }
 
 
@@ -303,7 +406,7 @@ pub fn to_line(bed_entry: &BedEntry, format: u8) -> Result<String, CubiculumErro
 /// A Result containing the queried fraction as a BedEntry object (format 12)
 /// 
 pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -> Result<Option<BedEntry>, CubiculumError> {
-    let mut thin_start: u64 = match input.thin_start() {
+    let thin_start: u64 = match input.thin_start() {
         Some(x) => {x},
         None => {
             return Err(
@@ -311,7 +414,7 @@ pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -
             )
         }
     };
-    let mut thin_end: u64 = match input.thin_end() {
+    let thin_end: u64 = match input.thin_end() {
         Some(x) => {x},
         None => {
             return Err(
@@ -320,7 +423,7 @@ pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -
         }
     };
 
-    let mut thick_start: u64 = match input.thick_start() {
+    let thick_start: u64 = match input.thick_start() {
         Some(x) => {x},
         None => {
             return Err(
@@ -328,7 +431,7 @@ pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -
             )
         }
     };
-    let mut thick_end: u64 = match input.thick_end() {
+    let thick_end: u64 = match input.thick_end() {
         Some(x) => {x},
         None => {
             return Err(
@@ -336,7 +439,6 @@ pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -
             )
         }
     };
-    let noncoding: bool = thick_start == thick_end;
 
     let exon_sizes = match input.exon_sizes() {
         Some(x) => {x},
@@ -372,187 +474,96 @@ pub fn extract_fraction(input: &BedEntry, mode: BedFractionMode, intron: bool) -
             )
         }
     };
-    let ex_num: u16 = exon_starts.len() as u16;
-
-    let report_up: bool = strand && mode == BedFractionMode::Utr5 || !strand && mode == BedFractionMode::Utr3;
-    let report_down: bool = strand && mode == BedFractionMode::Utr3 || !strand && mode == BedFractionMode::Utr5;
-    let noncoding: bool = (thick_end - thick_start) == 0;
-    let report_coding: bool = !noncoding & (mode == BedFractionMode::Cds);// || mode == BedFractionMode::All);
-
-        // infer the new sequence's start position
-    let mut seq_start: u64 = match mode {
-        BedFractionMode::Cds => thick_start, // will not change down the road
-        // "3utr" => thick_end, // can be further set to the first 3'-UTR exon start
-        _ => thin_start 
-        // for "intron", will be set to the end of the first coding exon;
-        // for utr, can be set to the start of the 3'-UTR
-        // set in stone for 5utr 
-    };
-
-    // create storage objects for updated block coordinates
-    let mut upd_block_starts: Vec<u64> = Vec::new();
-    let mut upd_block_sizes: Vec<u64> = Vec::new();
-
-    let range: ops::Range<u16>= if intron {0..ex_num-1} else {0..ex_num};
-    if range.is_empty() {
-        return Ok(None)
-    };
-    for i in range {
-        let i: usize = i as usize;
-
-        let block_start: u64 = exon_starts[i] + thin_start;
-        let block_end: u64 = block_start + exon_sizes[i];
-
-        // first, check if the current block lies entirely within UTR
-        // save block coordinates if respective mode is set, continue otherwise
-        let upstream_to_cds: bool = block_end <= thick_start;
-        let downstream_to_cds: bool = block_start >= thick_end;
-        let upstream_and_report: bool = upstream_to_cds & (
-            mode == BedFractionMode::Utr || report_up || noncoding
-        );
-        let downstream_and_report: bool = downstream_to_cds & (
-            mode == BedFractionMode::Utr || report_down || noncoding
-        );
-        // current block is either completely upstream or completely downstream to CDS 
-        if upstream_to_cds || downstream_to_cds {
-            if upstream_and_report || downstream_and_report || mode == BedFractionMode::All {
-                if intron {
-                    // update the starting position for the intron track
-                    if upd_block_starts.len() == 0 {seq_start = block_end};
-                    upd_block_starts.push(block_end - seq_start);
-                    upd_block_sizes.push(exon_starts[i+1] + thin_start - block_end);
-                } else {
-                    // update the starting position if the needed sequence portion started only with 3'-UTR
-                    if downstream_to_cds && upd_block_starts.len() == 0 {
-                        seq_start = block_start - thin_start;
-                    }
-                    upd_block_starts.push(block_start - seq_start);
-                    upd_block_sizes.push(block_end - block_start);
-                }
-            };
-            continue;
-        }
-
-        // if we reached this point, the block belongs to
-        // the coding sequence, at least partially
-
-        // for 3'-UTR/5'-UTR on the negative strand, we can safely skip blocks up until the CDS end
-        if report_down && block_end <= thick_end {continue}; 
-
-        // for introns, boundaries are block end and next block's start
-        if intron  {
-            if upd_block_starts.len() == 0 {seq_start = block_end};
-            if (block_end >= thick_end) & report_coding {break};
-            upd_block_starts.push(block_end - seq_start);
-            upd_block_sizes.push(exon_starts[i+1] + thin_start - block_end);
-            continue;
-        };
-
-        if mode == BedFractionMode::All {
-            // if intron {
-            //     if upd_block_starts.len() == 0 {seq_start = block_end};
-            //     upd_block_starts.push(block_end - seq_start);
-            //     upd_block_sizes.push(exon_starts[i+1] + thin_start - block_end);
-            // } else {
-                upd_block_starts.push(block_start - seq_start);
-                upd_block_sizes.push(block_end - block_start);
-            // }
-            continue
-        }
-
-        // for blocks overlapping with the coding sequence, assess their boundaries
-        let upd_block_start: u64 = cmp::max(block_start, thick_start);
-        let upd_block_end: u64 = cmp::min(block_end, thick_end);
-
-        // for 'cds' mode, save the updated coordinates
-        if mode == BedFractionMode::Cds {
-            upd_block_starts.push(upd_block_start - seq_start);
-            upd_block_sizes.push(upd_block_end - upd_block_start);
-            continue
-        }
-
-        // for UTR-related modes, clip the coding part and save the UTR bases
-        if (upd_block_start > block_start) & (mode == BedFractionMode::Utr || report_up) & !intron{
-                upd_block_starts.push(block_start - seq_start);
-                upd_block_sizes.push(upd_block_start - block_start);
-                // for 5'-UTR/3'-UTR on the negative strand, the loop can be safely exited
-                if report_up {break};
-                // continue
-        }
-        if (upd_block_end < block_end) & (mode == BedFractionMode::Utr || report_down) {
-            if intron {
-                if upd_block_starts.len() == 0 {seq_start = block_end};
-                upd_block_starts.push(block_end - seq_start);
-                upd_block_sizes.push(exon_starts[i+1] + thin_start - block_end);
-            } else {
-                if upd_block_starts.len() == 0 {seq_start = upd_block_end};
-                upd_block_starts.push(upd_block_end - seq_start);
-                upd_block_sizes.push(block_end - upd_block_end);    
-            }
-        }
-        // // if the mode was set to 'utr' but no upstream UTR was found so far,
-        // // updating the starting point
-        // if (mode == "utr" || mode == "3utr") && upd_block_sizes.len() == 0 {
-        //     seq_start = thick_end
-        // }
-    };
-
-    assert!(upd_block_starts.len() == upd_block_sizes.len());
-    let upd_block_count: usize = upd_block_sizes.len();
-    if upd_block_count == 0 {return Ok(None)};
-
-    // set the start and end points
-    match (mode, intron) {
-        (BedFractionMode::All, false) => {
-            // pass
-        }
-        (BedFractionMode::Cds, false) => {
-            thin_start = thick_start;
-            thin_end = thick_end;
-        },
-        // (BedFractionMode::Utr, false) => {
-        //     thick_end = thin_end;
-        //     thick_start = thick_end;
-        // },
-        _ => {
-            thin_start = seq_start;
-            thin_end = seq_start + upd_block_starts[..].last().unwrap() + upd_block_sizes[..].last().unwrap();
-            thick_end = thin_end;
-            thick_start = thick_end;
-        }
+    let mut block_sizes: Vec<u64> = Vec::with_capacity(exon_sizes.len());
+    let mut block_starts: Vec<u64> = Vec::with_capacity(exon_starts.len());
+    let layout: FractionLayout = match fraction_layout(
+        thin_start, thin_end, thick_start, thick_end, strand,
+        exon_sizes, exon_starts, mode, intron, &mut block_sizes, &mut block_starts
+    ) {
+        Some(x) => {x},
+        None => {return Ok(None)}
     };
+    let block_count: u16 = block_sizes.len() as u16;
 
-    let mut output = BedEntry::bed12(
-        match input.chrom().clone() {
+    let output = BedEntry::bed12(
+        match input.chrom() {
             Some(x) => {x.clone()},
             None => {String::from("NA")}
         },
-        thin_start,
-        thin_end,
-        match input.name().clone() {
+        layout.thin_start,
+        layout.thin_end,
+        match input.name() {
             Some(x) => {x.clone()},
             None => {String::from("NA")}
         },
-        match input.score().clone() {
+        match input.score() {
             Some(x) => {x.clone()},
             None => {String::from("0")}
         },
         strand,
-        thick_start,
-        thick_end,
-        match input.rgb().clone() {
+        layout.thick_start,
+        layout.thick_end,
+        match input.rgb() {
             Some(x) => {x.clone()},
             None => {String::from("NA")}
         },
-        upd_block_count as u16,
-        upd_block_sizes,
-        upd_block_starts
+        block_count,
+        block_sizes,
+        block_starts
     );
-
     Ok(Some(output))
 }
 
+/// Extract a given fraction from the BED12 BedEntry object and split it into individual BED6 blocks
+/// 
+/// Blocks are reported in ascending coordinate order; as in `bed_to_fraction` with BED6 output,
+/// the score field holds the block's ordinal number counted from the transcript's 5'-end
+/// 
+/// # Arguments
+/// `bed_entry`: BedEntry object in BED12 format to extract data from;
+/// `fraction`: BedFractionMode enum specifying which sequence fraction to extract;
+/// `intron`: boolean value indicating whether intron intervals should be reported instead of exons
+/// 
+/// # Returns
+/// A Result containing the queried fraction as a vector of BedEntry objects (format 6)
+/// 
+pub fn extract_fraction_blocks(
+    input: &BedEntry, mode: BedFractionMode, intron: bool
+) -> Result<Option<Vec<BedEntry>>, CubiculumError> {
+    extract_fraction_blocks_named(input, mode, intron, &Bed6Naming::default())
+}
+
+/// Same as `extract_fraction_blocks` but names the blocks according to the provided `Bed6Naming` scheme
+/// 
+pub fn extract_fraction_blocks_named(
+    input: &BedEntry, mode: BedFractionMode, intron: bool, naming: &Bed6Naming
+) -> Result<Option<Vec<BedEntry>>, CubiculumError> {
+    let fraction: BedEntry = match extract_fraction(input, mode, intron)? {
+        Some(x) => {x},
+        None => {return Ok(None)}
+    };
+    // extract_fraction() has already validated the strand and filled in the name and score fields
+    let strand: bool = fraction.strand().unwrap();
+    let chrom: &String = fraction.chrom().unwrap();
+    let name: &String = fraction.name().unwrap();
+    let score: &String = fraction.score().unwrap();
+    let block_count: usize = fraction.exons().len();
+    let blocks: Vec<BedEntry> = fraction
+        .exons()
+        .enumerate()
+        .map(|(i, (start, end))| {
+            let block_num: usize = if strand {i + 1} else {block_count - i};
+            let (block_name, block_score) = naming.label(name, score, block_num);
+            BedEntry::bed6(
+                chrom.clone(), start, end, block_name, block_score, strand
+            )
+        })
+        .collect();
+    Ok(Some(blocks))
+}
+
 #[cfg(test)]
+#[allow(deprecated)]
 mod test_extract {
     use super::*;
 
@@ -562,13 +573,12 @@ mod test_extract {
         let input: String = String::from("chr9	101360416	101385006	A	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101363015	101370938	A	0	-	101370938	101370938	0	2	5104,2616,	0,5307,");
         let res = extract_fraction(
-            &parse_bed(input, 12, false).unwrap(),
-            BedFractionMode::All,
+            &parse_bed(input.clone(), 12, false).unwrap(),
+            BedFractionMode::Cds,
             true
         ).unwrap().unwrap();
         assert_eq!(expected, to_line(&res, 12).unwrap());
-
-        // assert_eq!(expected, bed_to_fraction(input, "cds", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -585,14 +595,161 @@ mod test_extract {
     #[test]
     fn all_intron_test() {
         let input = String::from("chr19	14789259	14835285	A	0	-	14799173	14800136	0	3	10890,188,212,	0,20546,45814,");
+        let expected = String::from("chr19	14800149	14835073	A	0	-	14835073	14835073	0	2	9656,25080,	0,9844,");
         let res = extract_fraction(
-            &parse_bed(input, 12, false).unwrap(),
+            &parse_bed(input.clone(), 12, false).unwrap(),
             BedFractionMode::All,
             true
         )
             .unwrap()
             .unwrap();
-        println!("{}", to_line(&res, 12).unwrap());
+        assert_eq!(expected, to_line(&res, 12).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "all", true, false).unwrap().unwrap());
+    }
+
+    #[test]
+    fn fraction_blocks_test() {
+        // per-block records must match the BED6 output of bed_to_fraction
+        let input = String::from("chr9	101360416	101385006	A	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
+        let blocks = extract_fraction_blocks(
+            &parse_bed(input.clone(), 12, false).unwrap(),
+            BedFractionMode::Cds,
+            false
+        )
+            .unwrap()
+            .unwrap();
+        let lines: Vec<String> = blocks
+            .iter()
+            .map(|x| to_line(x, 6).unwrap())
+            .collect();
+        assert_eq!(lines.join("\n"), bed_to_fraction(input, "cds", false, true).unwrap().unwrap());
+    }
+
+    #[test]
+    fn named_fraction_blocks_test() {
+        let input = String::from("chr9	101360416	101385006	A	7	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
+        let naming = Bed6Naming::new().ordinal_in_name("exon").keep_score(true).zero_based(true);
+        let blocks = extract_fraction_blocks_named(
+            &parse_bed(input.clone(), 12, false).unwrap(),
+            BedFractionMode::Cds,
+            false,
+            &naming
+        )
+            .unwrap()
+            .unwrap();
+        let names: Vec<&String> = blocks.iter().map(|x| x.name().unwrap()).collect();
+        assert_eq!(names, vec!["A.exon2", "A.exon1", "A.exon0"]);
+        assert!(blocks.iter().all(|x| x.score().unwrap() == "7"));
+        let lines: Vec<String> = blocks
+            .iter()
+            .map(|x| to_line(x, 6).unwrap())
+            .collect();
+        assert_eq!(lines.join("\n"), bed_to_fraction_named(input, "cds", false, &naming).unwrap().unwrap());
+    }
+
+    #[test]
+    fn terminal_exon_modes_test() {
+        // minus strand: the first exon is the rightmost block, and the leftmost block is fully coding
+        let input = String::from("chr1	100	900	A	0	-	100	850	0	3	100,100,100,	0,400,700,");
+        let first = bed_to_fraction(input.clone(), "first", false, false).unwrap().unwrap();
+        assert_eq!(first, "chr1	800	900	A	0	-	800	850	0	1	100,	0,");
+        let last = bed_to_fraction(input.clone(), "last", false, false).unwrap().unwrap();
+        assert_eq!(last, "chr1	100	200	A	0	-	100	200	0	1	100,	0,");
+        let terminal = bed_to_fraction(input.clone(), "terminal", false, true).unwrap().unwrap();
+        assert_eq!(terminal, "chr1	800	900	A	1	-");
+        let entry = parse_bed(input.clone(), 12, false).unwrap();
+        let res = extract_fraction(&entry, BedFractionMode::FirstExon, false).unwrap().unwrap();
+        assert_eq!(to_line(&res, 12).unwrap(), first);
+        assert!(bed_to_fraction(input, "first", true, false).unwrap().is_none());
+
+        // both terminal exons of a non-coding transcript are reported
+        let noncoding = String::from("chr1	100	900	B	0	+	100	100	0	3	100,100,100,	0,400,700,");
+        assert_eq!(
+            bed_to_fraction(noncoding, "terminal", false, false).unwrap().unwrap(),
+            "chr1	100	900	B	0	+	100	100	0	2	100,100,	0,700,"
+        );
+    }
+
+    #[test]
+    fn frames_output_test() {
+        let input = String::from("chr1	100	600	A	0	-	150	350	0	3	100,100,100,	0,200,400,");
+        let entry = parse_bed(input.clone(), 12, false).unwrap();
+        assert_eq!(to_line_with_frames(&entry).unwrap(), format!("{}\t2,0,-1,", input));
+        assert_eq!(
+            to_genepred_ext(&entry).unwrap(),
+            "A	chr1	-	100	600	150	350	3	100,300,500,	200,400,600,	0	A	cmpl	cmpl	2,0,-1,"
+        );
+        let bed6 = parse_bed(String::from("chr1	100	600	A	0	-"), 6, false).unwrap();
+        assert!(to_genepred_ext(&bed6).is_err());
+    }
+
+    #[test]
+    fn saf_output_test() {
+        let entry = parse_bed(String::from("chr1	100	600	A	0	-	150	350	0	2	100,100,	0,400,"), 12, false).unwrap();
+        assert_eq!(to_saf(&entry).unwrap(), vec!["A	chr1	101	200	-", "A	chr1	501	600	-"]);
+        assert!(to_saf(&parse_bed(String::from("chr2	60	70"), 3, false).unwrap()).is_err());
+        let bed = "track name=test\nchr2\t0\t50\tB\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(bed_to_saf(bed.as_bytes(), 4, &mut output, true).unwrap(), 1);
+        assert_eq!(String::from_utf8(output).unwrap(), "GeneID	Chr	Start	End	Strand\nB	chr2	1	50	.\n");
+    }
+
+    #[test]
+    fn fraction_stream_test() {
+        let bed = "# transcripts\nchr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,\n\
+            chr1\t700\t800\tB\t0\t-\t720\t780\t0\t1\t100,\t0,\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(fraction_stream(bed.as_bytes(), &mut output, "cds", false, true).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr1\t150\t200\tA\t1\t+\nchr1\t300\t350\tA\t2\t+\nchr1\t720\t780\tB\t1\t-\n"
+        );
+        let mut output: Vec<u8> = Vec::new();
+        assert!(fraction_stream(bed.as_bytes(), &mut output, "intron", false, false).is_err());
+        assert!(fraction_stream("chr1\t0\t10\n".as_bytes(), &mut output, "all", false, false).is_err());
+        let mut full: &mut [u8] = &mut [];
+        assert!(matches!(fraction_stream(bed.as_bytes(), &mut full, "cds", false, true), Err(CubiculumError::IoError(_))));
+    }
+
+    #[test]
+    fn fraction_buffer_reuse() {
+        let lines = [
+            "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,",
+            "chr2\t700\t800\tB\t5\t-\t720\t780\t0\t1\t100,\t0,",
+            "chr3\t1000\t1900\tC\t0\t-\t1000\t1000\t0\t4\t50,100,50,100,\t0,300,500,800,"
+        ];
+        let naming = Bed6Naming::new().ordinal_in_name("exon").keep_score(true);
+        let mut buffer = FractionBuffer::new();
+        // the buffer must not carry anything over between records of different sizes
+        for _ in 0..2 {
+            for line in lines {
+                for mode in FRACTION_MODES {
+                    for intron in [false, true] {
+                        assert_eq!(
+                            buffer.fraction(line, mode, intron, None).unwrap().map(String::from),
+                            bed_to_fraction(line.to_string(), mode, intron, false).unwrap()
+                        );
+                        assert_eq!(
+                            buffer.fraction(line, mode, intron, Some(&naming)).unwrap().map(String::from),
+                            bed_to_fraction_named(line.to_string(), mode, intron, &naming).unwrap()
+                        );
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            buffer.fraction(lines[0], "all", false, Some(&naming)).unwrap(),
+            Some("chr1\t100\t200\tA.exon1\t0\t+\nchr1\t300\t400\tA.exon2\t0\t+\nchr1\t500\t600\tA.exon3\t0\t+")
+        );
+        for field in ["0", "42", "+7", "18446744073709551615", "18446744073709551616", "", "+", "-1", "1a", " 1"] {
+            assert_eq!(parse_u64(field), field.parse::<u64>().ok());
+        }
+        let mut out = String::new();
+        for value in [0, 9, 10, 1234567890, u64::MAX] {
+            push_u64(&mut out, value);
+            out.push(',');
+        }
+        assert_eq!(out, "0,9,10,1234567890,18446744073709551615,");
     }
 
     #[test]
@@ -607,92 +764,119 @@ mod test_extract {
             .unwrap();
         println!("{}", to_line(&res, 12).unwrap());
     }
+
+    #[test]
+    fn deprecated_parser_test() {
+        // the deprecated parser reports malformed lines as None instead of fixing them
+        let swapped = String::from("chr1\t600\t100\tA\t0\t+");
+        assert!(parse_bed(swapped, 6, false).is_none());
+        assert!(parse_bed(String::from("chr1\t100\t600\tA\t0\t+"), 6, false).is_some());
+        assert!(parse_bed(String::from("  "), 6, true).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn deprecated_parser_blank_line() {
+        parse_bed(String::new(), 3, false);
+    }
 }
 
-/// An optimized version of the above three functions for bed12ToFraction command line utility
-/// 
-/// # Arguments
-/// 
-/// * `line`: a BED12 format line string to parse
-/// * `mode`: fraction of annotated blocks to report [accepted values: "all", "cds", "utr", "3utr", "5utr"]
-/// * `intron`: boolean value specifying whether introns should be reported instead of exons
-/// * `bed6`: boolean value specifying whether the resulting fraction should be split into separate BED6 records
-/// 
-/// 
+/// Boundaries of a transcript fraction, shared by `extract_fraction` and `bed_to_fraction`;
+/// the blocks are written to the caller-provided vectors
+#[derive(Clone, Copy)]
+struct FractionLayout {
+    thin_start: u64,
+    thin_end: u64,
+    thick_start: u64,
+    thick_end: u64
+}
 
-pub fn bed_to_fraction(
-    line: String, mode: &str, intron: bool, bed6: bool
-) -> Option<String> {
-    let mode: BedFractionMode = match mode {
-        "all" => { BedFractionMode::All },
-        "cds" => { BedFractionMode::Cds },
-        "utr" => { BedFractionMode::Utr },
-        "5utr" => { BedFractionMode::Utr5 },
-        "3utr" => { BedFractionMode::Utr3 },
+/// Computes the layout for the terminal exon modes; coding boundaries are clipped to the reported exons
+#[allow(clippy::too_many_arguments)]
+fn terminal_exon_layout(
+    thin_start: u64,
+    thick_start: u64,
+    thick_end: u64,
+    strand: bool,
+    exon_sizes: &[u64],
+    exon_starts: &[u64],
+    mode: BedFractionMode,
+    intron: bool,
+    block_sizes: &mut Vec<u64>,
+    block_starts: &mut Vec<u64>
+) -> Option<FractionLayout> {
+    // terminal modes are defined for exons only
+    if intron || exon_starts.is_empty() {return None};
+    let block = |i: usize| (thin_start + exon_starts[i], thin_start + exon_starts[i] + exon_sizes[i]);
+    let (first, last): (usize, usize) = if strand {(0, exon_starts.len() - 1)} else {(exon_starts.len() - 1, 0)};
+    let noncoding: bool = thick_start == thick_end;
+    let has_utr = |i: usize| noncoding || block(i).0 < thick_start || block(i).1 > thick_end;
+    // at most two exons are reported, so the selection is kept on the stack
+    let (left, right): (usize, usize) = (first.min(last), first.max(last));
+    let selected: [Option<usize>; 2] = match mode {
+        BedFractionMode::FirstExon => {[Some(first), None]},
+        BedFractionMode::LastExon => {[Some(last), None]},
         _ => {
-            panic!("Invalid 'mode' has been provided: {}. Valid modes are: all, cds, utr, 3utr, 5utr", mode)
+            [
+                Some(left).filter(|x| has_utr(*x)),
+                Some(right).filter(|x| *x != left && has_utr(*x))
+            ]
         }
     };
-
-    let data: Vec<&str>  = line
-        .trim()
-        .split("\t")
-        .collect::<Vec<&str>>();
-    if data.len() == 0 {
-        return None;
-    }
-    if data.len() != 12 {
-        panic!("Error: File contains improperly formatted lines. Make sure all lines in the file are in BED12 format");
-    }
-    let chrom: &str = data[0];
-    let mut thin_start: u64 = data[1]
-        .parse::<u64>()
-        .expect("ThickStart is not a valid positive integer");
-    let mut thin_end: u64 = data[2]
-        .parse::<u64>()
-        .expect("ThickEnd is not a valid positive integer");
-    assert!(thin_start <= thin_end);
-    let name: &str = data[3];
-    let score: &str = data[4];
-    let strand_line: &str = data[5];
-    let strand: bool = strand_line == "+";
-    let mut thick_start: u64 = data[6]
-        .parse::<u64>()
-        .expect("thinStart is not a valid positive integer");
-    if thick_start < thin_start {
-        panic!("thickStart value ({}) cannot be smaller than thinStart ({})", thick_start, thin_start)
-    }
-    let mut thick_end: u64 = data[7]
-        .parse::<u64>()
-        .expect("thinEnd is not a valid positive integer");
-    if thick_end > thin_end {
-        panic!("thickEnd value ({}) cannot be larger than thinEnd ({})", thick_end, thin_end)
-    }
-    if thick_start > thick_end {
-        panic!("thickStart value ({}) cannot be larger than thickEnd ({})", thick_start, thick_end)
+    let mut selected = selected.into_iter().flatten();
+    let start_block: usize = selected.next()?;
+    let start: u64 = block(start_block).0;
+    block_sizes.push(exon_sizes[start_block]);
+    block_starts.push(0);
+    let mut end: u64 = block(start_block).1;
+    for i in selected {
+        let (block_start, block_end) = block(i);
+        block_sizes.push(block_end - block_start);
+        block_starts.push(block_start - start);
+        end = block_end;
     }
-    let rgb: &str = data[8];
-    let ex_num: u64 = data[9]
-        .parse::<u64>()
-        .expect("Exon number is not a valid positive integer");
-    let exon_sizes: Vec<u64> = data[10]
-        .split(',')
-        .filter(|x|
-            !x.is_empty()
-        )
-        .map(|x|
-            x.parse::<u64>().expect("Invalid exon size value")
-        )
-        .collect::<Vec<u64>>();
-    let exon_starts: Vec<u64> = data[11]
-        .split(',') 
-        .filter(|x|
-            !x.is_empty()
-        )
-        .map(|x|
-            x.parse::<u64>().expect("Invalid exon start position")
+    Some(
+        FractionLayout {
+            thin_start: start,
+            thin_end: end,
+            thick_start: thick_start.clamp(start, end),
+            thick_end: thick_end.clamp(start, end)
+        }
+    )
+}
+
+/// Computes the coordinates and blocks of the requested transcript fraction
+/// 
+/// Both the BedEntry-based and the line-based fraction extractors delegate here,
+/// so that the in-memory and the command line paths always report the same blocks.
+/// The fraction blocks are written to `upd_block_sizes` and `upd_block_starts`, which are cleared first,
+/// so that the line-based extractor can reuse its storage between records
+/// 
+/// # Returns
+/// None if the transcript contains no blocks belonging to the requested fraction
+/// 
+#[allow(clippy::too_many_arguments)]
+fn fraction_layout(
+    mut thin_start: u64,
+    mut thin_end: u64,
+    mut thick_start: u64,
+    mut thick_end: u64,
+    strand: bool,
+    exon_sizes: &[u64],
+    exon_starts: &[u64],
+    mode: BedFractionMode,
+    intron: bool,
+    upd_block_sizes: &mut Vec<u64>,
+    upd_block_starts: &mut Vec<u64>
+) -> Option<FractionLayout> {
+    upd_block_sizes.clear();
+    upd_block_starts.clear();
+    if let BedFractionMode::FirstExon | BedFractionMode::LastExon | BedFractionMode::TerminalUtr = mode {
+        return terminal_exon_layout(
+            thin_start, thick_start, thick_end, strand, exon_sizes, exon_starts, mode, intron,
+            upd_block_sizes, upd_block_starts
         )
-        .collect::<Vec<u64>>();
+    }
 
     // create shortcuts to control behaviour in UTR-targeted modes
     // the definition of 5' and 3' depends on the strand
@@ -712,15 +896,11 @@ pub fn bed_to_fraction(
         // set in stone for 5utr 
     };
 
-    // create storage objects for updated block coordinates
-    let mut upd_block_starts: Vec<u64> = Vec::new();
-    let mut upd_block_sizes: Vec<u64> = Vec::new();
+    let ex_num: usize = exon_starts.len();
 
-    let range: ops::Range<u64>= if intron {0..ex_num-1} else {0..ex_num};
+    let range: ops::Range<usize> = if intron {0..ex_num.saturating_sub(1)} else {0..ex_num};
     if range.is_empty() {return None};
     for i in range {
-        let i: usize = i as usize;
-
         let block_start: u64 = exon_starts[i] + thin_start;
         let block_end: u64 = block_start + exon_sizes[i];
 
@@ -763,7 +943,8 @@ pub fn bed_to_fraction(
         // for introns, boundaries are block end and next block's start
         if intron & report_coding {
             if upd_block_starts.len() == 0 {seq_start = block_end};
-            if block_end >= thick_end {break};
+            // in 'cds' mode, introns past the last coding block are not reported
+            if (block_end >= thick_end) & (mode == BedFractionMode::Cds) {break};
             upd_block_starts.push(block_end - seq_start);
             upd_block_sizes.push(exon_starts[i+1] + thin_start - block_end);
             continue;
@@ -837,44 +1018,398 @@ pub fn bed_to_fraction(
         }
     };
 
-    // if bed6 output is expected, modify the lines
-    if bed6 {
-        let mut bed6_line: String = String::new();
-        for i in 0..upd_block_count {
-            let block_start: u64 = seq_start + upd_block_starts[i];
-            let block_end: u64 = block_start + upd_block_sizes[i];
-            let block_num: u64 = if strand {i as u64 + 1} else {(upd_block_count - i) as u64};
-            let out_line: String = format!(
-                "{}\t{}\t{}\t{}\t{}\t{}",
-                chrom, block_start, block_end, name, block_num, strand_line
-            );
-            bed6_line.push_str(&out_line);
-            if i < upd_block_count - 1 {
-                bed6_line.push('\n');
+    Some(
+        FractionLayout {
+            thin_start,
+            thin_end,
+            thick_start,
+            thick_end
+        }
+    )
+}
+
+/// An optimized version of the above three functions for bed12ToFraction command line utility
+/// 
+/// # Arguments
+/// 
+/// * `line`: a BED12 format line string to parse
+/// * `mode`: fraction of annotated blocks to report [accepted values: "all", "cds", "utr", "3utr", "5utr", "first", "last", "terminal"]
+/// * `intron`: boolean value specifying whether introns should be reported instead of exons
+/// * `bed6`: boolean value specifying whether the resulting fraction should be split into separate BED6 records
+///   named with the default `Bed6Naming` scheme
+/// 
+/// # Returns
+/// The fraction line(s), None if the fraction is empty; an InputError for an invalid mode,
+/// a ParseError for malformed lines and a CoordinateError for inconsistent coordinates
+/// 
+pub fn bed_to_fraction(
+    line: String, mode: &str, intron: bool, bed6: bool
+) -> Result<Option<String>, CubiculumError> {
+    let naming: Option<Bed6Naming> = if bed6 {Some(Bed6Naming::default())} else {None};
+    fraction_line(&line, mode, intron, naming.as_ref())
+}
+
+/// Same as `bed_to_fraction` with BED6 output, but with a custom naming scheme for the reported blocks
+/// 
+/// # Arguments
+/// 
+/// * `line`: a BED12 format line string to parse
+/// * `mode`: fraction of annotated blocks to report [accepted values: "all", "cds", "utr", "3utr", "5utr", "first", "last", "terminal"]
+/// * `intron`: boolean value specifying whether introns should be reported instead of exons
+/// * `naming`: defines where the block ordinal number is reported and how blocks are numbered
+/// 
+pub fn bed_to_fraction_named(
+    line: String, mode: &str, intron: bool, naming: &Bed6Naming
+) -> Result<Option<String>, CubiculumError> {
+    fraction_line(&line, mode, intron, Some(naming))
+}
+
+/// Same as `bed_to_fraction_named`, validating the line with the given anomaly handling first
+///
+/// The line is parsed with `parse_bed_with_options()` ahead of the fraction extraction; in lenient mode,
+/// the fixed entry is used in place of the original line. BED12 output is produced if `naming` is None,
+/// and BED6 blocks otherwise
+///
+/// # Returns
+/// The fraction line(s), None if the fraction is empty; an error for an invalid mode
+/// or a line that cannot be parsed under the given options
+///
+pub fn bed_to_fraction_with_options(
+    line: String, mode: &str, intron: bool, naming: Option<&Bed6Naming>, options: &ProcessingOptions
+) -> Result<Option<String>, CubiculumError> {
+    check_fraction_mode(mode)?;
+    let entry = parse_bed_with_options(&line, 12, options)?;
+    let line = if options.is_strict() {line} else {to_line(&entry, 12)?};
+    fraction_line(&line, mode, intron, naming)
+}
+
+/// Fraction modes accepted by `bed_to_fraction` and the fraction stream drivers
+pub const FRACTION_MODES: [&str; 8] = ["all", "cds", "utr", "5utr", "3utr", "first", "last", "terminal"];
+
+/// Check the fraction mode name before processing a stream, so that invalid modes are reported before any output
+pub(crate) fn check_fraction_mode(mode: &str) -> Result<(), CubiculumError> {
+    fraction_mode_by_name(mode).map(|_| ())
+}
+
+/// Check whether the line carries no BED record: blank lines, comments and track/browser lines
+pub fn is_bed_header_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.is_empty() || trimmed.starts_with('#') || is_track_line(trimmed)
+}
+
+/// Check whether the line is a UCSC track or browser line, which some streams pass through to the output
+pub(crate) fn is_track_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("track") || trimmed.starts_with("browser")
+}
+
+/// Lines of a BED stream excluding those rejected by `is_bed_header_line()`; read failures are reported as IoError
+pub(crate) fn bed_lines<R: BufRead>(reader: R) -> impl Iterator<Item = Result<String, CubiculumError>> {
+    reader.lines().filter_map(|x| match x {
+        Ok(line) if is_bed_header_line(&line) => {None},
+        Ok(line) => {Some(Ok(line))},
+        Err(e) => {Some(Err(e.into()))}
+    })
+}
+
+/// Fraction of a single stream line; None for skipped lines and empty fractions
+pub(crate) fn fraction_record<'a>(
+    buffer: &'a mut FractionBuffer, line: &str, mode: &str, intron: bool, naming: Option<&Bed6Naming>
+) -> Result<Option<&'a str>, CubiculumError> {
+    if is_bed_header_line(line) {return Ok(None)};
+    let data: [&str; 12] = match bed12_fields(line.trim()) {
+        Some(x) => {x},
+        None => {
+            return Err(CubiculumError::ParseError(format!("Invalid BED12 line: {}", line.trim_end_matches(['\n', '\r']))))
+        }
+    };
+    buffer.fraction_fields(data, fraction_mode_by_name(mode)?, intron, naming)
+}
+
+/// Write a fraction produced by `fraction_record`, returning the number of BED lines written
+pub(crate) fn write_fraction<W: Write>(writer: &mut W, fraction: &str) -> Result<usize, CubiculumError> {
+    writer.write_all(fraction.as_bytes())
+        .and_then(|_| writer.write_all(b"\n"))?;
+    Ok(fraction_line_count(fraction))
+}
+
+/// Number of BED lines in a fraction produced by `fraction_record`
+pub(crate) fn fraction_line_count(fraction: &str) -> usize {
+    fraction.bytes().filter(|x| *x == b'\n').count() + 1
+}
+
+/// Run `bed_to_fraction` over the entire BED12 stream
+///
+/// The output is buffered internally and flushed before returning. Blank lines, comments
+/// and track/browser lines are skipped. See `par_fraction_stream` for the multithreaded version
+///
+/// # Arguments
+/// `reader`: input BED12 stream;
+/// `writer`: output stream;
+/// `mode`: fraction of annotated blocks to report; see `FRACTION_MODES`;
+/// `intron`: whether introns should be reported instead of exons;
+/// `bed6`: whether the fraction blocks are reported as separate BED6 records
+///
+/// # Returns
+/// The number of BED lines written; an error if the mode is invalid, a line does not have
+/// twelve columns, or the output cannot be written
+///
+pub fn fraction_stream<R, W>(reader: R, writer: &mut W, mode: &str, intron: bool, bed6: bool) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    let naming: Option<Bed6Naming> = if bed6 {Some(Bed6Naming::default())} else {None};
+    fraction_stream_named(reader, writer, mode, intron, naming.as_ref())
+}
+
+/// Same as `fraction_stream`, with BED6 output requested by providing a naming scheme for the reported blocks
+pub fn fraction_stream_named<R, W>(
+    mut reader: R, writer: &mut W, mode: &str, intron: bool, naming: Option<&Bed6Naming>
+) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    check_fraction_mode(mode)?;
+    let mut output = BufWriter::new(writer);
+    let mut buffer = FractionBuffer::new();
+    let mut line = String::new();
+    let mut written: usize = 0;
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {break};
+        if let Some(fraction) = fraction_record(&mut buffer, &line, mode, intron, naming)? {
+            written += write_fraction(&mut output, fraction)?;
+        }
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+/// Line-based fraction extraction core; reports BED12 if `naming` is None and BED6 blocks otherwise
+fn fraction_line(
+    line: &str, mode: &str, intron: bool, naming: Option<&Bed6Naming>
+) -> Result<Option<String>, CubiculumError> {
+    Ok(FractionBuffer::new().fraction(line, mode, intron, naming)?.map(String::from))
+}
+
+/// Reusable storage for the line-based fraction extraction
+///
+/// The parsed exons, the fraction blocks and the output line are kept between calls, so once the buffer has grown
+/// to fit the largest record, processing further lines does not allocate. Numbers are parsed
+/// directly from the line slices and the output is formatted in place
+///
+/// # Examples
+/// ```
+/// use cubiculum::extract::extract::FractionBuffer;
+///
+/// let mut buffer = FractionBuffer::new();
+/// let line = "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,";
+/// assert_eq!(buffer.fraction(line, "cds", false, None).unwrap(), Some("chr1\t150\t350\tA\t0\t+\t150\t350\t0\t2\t50,50,\t0,150,"));
+/// assert_eq!(buffer.fraction(line, "5utr", false, None).unwrap(), Some("chr1\t100\t150\tA\t0\t+\t150\t150\t0\t1\t50,\t0,"));
+/// assert!(buffer.fraction(line, "intron", false, None).is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FractionBuffer {
+    exon_sizes: Vec<u64>,
+    exon_starts: Vec<u64>,
+    block_sizes: Vec<u64>,
+    block_starts: Vec<u64>,
+    out: String
+}
+
+impl FractionBuffer {
+    pub fn new() -> FractionBuffer {
+        FractionBuffer::default()
+    }
+
+    /// Same as `bed_to_fraction`, with BED6 output requested by providing a naming scheme
+    ///
+    /// # Returns
+    /// The fraction line(s), borrowed from the buffer until the next call; None if the fraction is empty.
+    /// Invalid modes and malformed lines are reported as errors, as in `bed_to_fraction`
+    ///
+    pub fn fraction(
+        &mut self, line: &str, mode: &str, intron: bool, naming: Option<&Bed6Naming>
+    ) -> Result<Option<&str>, CubiculumError> {
+        let mode: BedFractionMode = fraction_mode_by_name(mode)?;
+        let data: [&str; 12] = bed12_fields(line.trim()).ok_or_else(
+            || CubiculumError::ParseError(format!("Invalid BED12 line: {}", line.trim()))
+        )?;
+        self.fraction_fields(data, mode, intron, naming)
+    }
+
+    /// Fraction extraction core operating on the already split line
+    fn fraction_fields(
+        &mut self, data: [&str; 12], mode: BedFractionMode, intron: bool, naming: Option<&Bed6Naming>
+    ) -> Result<Option<&str>, CubiculumError> {
+        let coordinate_error = |x: String| Err(CubiculumError::CoordinateError(x));
+        let chrom: &str = data[0];
+        let thin_start: u64 = parse_u64_field(data[1], "thinStart")?;
+        let thin_end: u64 = parse_u64_field(data[2], "thinEnd")?;
+        if thin_start > thin_end {
+            return coordinate_error(format!("thinStart value ({}) cannot be larger than thinEnd ({})", thin_start, thin_end))
+        }
+        let name: &str = data[3];
+        let score: &str = data[4];
+        let strand_line: &str = data[5];
+        let strand: bool = strand_line == "+";
+        let thick_start: u64 = parse_u64_field(data[6], "thickStart")?;
+        if thick_start < thin_start {
+            return coordinate_error(format!("thickStart value ({}) cannot be smaller than thinStart ({})", thick_start, thin_start))
+        }
+        let thick_end: u64 = parse_u64_field(data[7], "thickEnd")?;
+        if thick_end > thin_end {
+            return coordinate_error(format!("thickEnd value ({}) cannot be larger than thinEnd ({})", thick_end, thin_end))
+        }
+        if thick_start > thick_end {
+            return coordinate_error(format!("thickStart value ({}) cannot be larger than thickEnd ({})", thick_start, thick_end))
+        }
+        let rgb: &str = data[8];
+        let ex_num: u64 = parse_u64_field(data[9], "Exon number")?;
+        self.exon_sizes.clear();
+        for size in data[10].split(',').filter(|x| !x.is_empty()) {
+            self.exon_sizes.push(parse_u64_field(size, "Exon size")?);
+        }
+        self.exon_starts.clear();
+        for start in data[11].split(',').filter(|x| !x.is_empty()) {
+            self.exon_starts.push(parse_u64_field(start, "Exon start")?);
+        }
+        if ex_num as usize != self.exon_sizes.len() || self.exon_sizes.len() != self.exon_starts.len() {
+            return Err(
+                CubiculumError::ParseError(
+                    format!(
+                        "Exon number ({}) does not match the number of exon sizes ({}) and exon starts ({})",
+                        ex_num, self.exon_sizes.len(), self.exon_starts.len()
+                    )
+                )
+            )
+        }
+
+        let layout: FractionLayout = match fraction_layout(
+            thin_start, thin_end, thick_start, thick_end, strand,
+            &self.exon_sizes, &self.exon_starts, mode, intron, &mut self.block_sizes, &mut self.block_starts
+        ) {
+            Some(x) => {x},
+            None => {return Ok(None)}
+        };
+        let upd_block_count: usize = self.block_sizes.len();
+        let out: &mut String = &mut self.out;
+        out.clear();
+
+        // if bed6 output is expected, report each block as a separate line
+        if let Some(naming) = naming {
+            for i in 0..upd_block_count {
+                let block_start: u64 = layout.thin_start + self.block_starts[i];
+                let block_end: u64 = block_start + self.block_sizes[i];
+                let block_num: usize = if strand {i + 1} else {upd_block_count - i};
+                if i > 0 {out.push('\n')};
+                out.push_str(chrom);
+                out.push('\t');
+                push_u64(out, block_start);
+                out.push('\t');
+                push_u64(out, block_end);
+                out.push('\t');
+                naming.write_label(out, name, score, block_num);
+                out.push('\t');
+                out.push_str(strand_line);
             }
+            return Ok(Some(out.as_str()));
         }
-        return Some(bed6_line);
-    }
-    let size_line: String = upd_block_sizes
-        .iter()
-        .map(|x| x.to_string())
-        .collect::<Vec<String>>()
-        .join(",") + ",";
-    let start_line: String = upd_block_starts
-        .iter()
-        .map(|x| x.to_string())
-        .collect::<Vec<String>>()
-        .join(",") + ",";
-
-    let result: String = format!(
-        "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-        chrom, thin_start, thin_end, name, score, strand_line, 
-        thick_start, thick_end, rgb, upd_block_count, size_line, start_line
-    );
-    return Some(result)
+        for field in [chrom, "\t"] {out.push_str(field)};
+        push_u64(out, layout.thin_start);
+        out.push('\t');
+        push_u64(out, layout.thin_end);
+        for field in ["\t", name, "\t", score, "\t", strand_line, "\t"] {out.push_str(field)};
+        push_u64(out, layout.thick_start);
+        out.push('\t');
+        push_u64(out, layout.thick_end);
+        for field in ["\t", rgb, "\t"] {out.push_str(field)};
+        push_u64(out, upd_block_count as u64);
+        out.push('\t');
+        for size in self.block_sizes.iter() {
+            push_u64(out, *size);
+            out.push(',');
+        }
+        out.push('\t');
+        for start in self.block_starts.iter() {
+            push_u64(out, *start);
+            out.push(',');
+        }
+        Ok(Some(out.as_str()))
+    }
+}
+
+/// Fraction mode by its bed12ToFraction name; an InputError for invalid names
+fn fraction_mode_by_name(mode: &str) -> Result<BedFractionMode, CubiculumError> {
+    match mode {
+        "all" => { Ok(BedFractionMode::All) },
+        "cds" => { Ok(BedFractionMode::Cds) },
+        "utr" => { Ok(BedFractionMode::Utr) },
+        "5utr" => { Ok(BedFractionMode::Utr5) },
+        "3utr" => { Ok(BedFractionMode::Utr3) },
+        "first" => { Ok(BedFractionMode::FirstExon) },
+        "last" => { Ok(BedFractionMode::LastExon) },
+        "terminal" => { Ok(BedFractionMode::TerminalUtr) },
+        _ => {
+            Err(
+                CubiculumError::InputError(
+                    format!("Invalid fraction mode: {}. Valid modes are: {}", mode, FRACTION_MODES.join(", "))
+                )
+            )
+        }
+    }
+}
+
+/// Split a trimmed line into BED12 fields without collecting them; None unless there are exactly twelve
+fn bed12_fields(line: &str) -> Option<[&str; 12]> {
+    let mut data: [&str; 12] = [""; 12];
+    let mut fields = line.split('\t');
+    for field in data.iter_mut() {
+        *field = fields.next()?;
+    }
+    if fields.next().is_some() {return None};
+    Some(data)
+}
 
-    // let result: String = String::new();
-    // return Some(result);
+/// Parse a non-negative integer from a line field, accepting the same input as `str::parse::<u64>()`
+fn parse_u64(field: &str) -> Option<u64> {
+    let digits: &[u8] = match field.as_bytes() {
+        [b'+', rest @ ..] => {rest},
+        x => {x}
+    };
+    if digits.is_empty() {return None};
+    let mut value: u64 = 0;
+    for byte in digits {
+        let digit: u8 = byte.wrapping_sub(b'0');
+        if digit > 9 {return None};
+        value = value.checked_mul(10)?.checked_add(digit as u64)?;
+    }
+    Some(value)
+}
+
+/// Same as `parse_u64`, naming the field in the error message
+fn parse_u64_field(value: &str, field: &str) -> Result<u64, CubiculumError> {
+    parse_u64(value).ok_or_else(
+        || CubiculumError::ParseError(format!("{} is not a valid positive integer: {}", field, value))
+    )
+}
+
+/// Append the decimal representation of `value` to `out`, bypassing the formatting machinery
+fn push_u64(out: &mut String, mut value: u64) {
+    let mut digits: [u8; 20] = [0; 20];
+    let mut i: usize = digits.len();
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {break};
+    }
+    for digit in &digits[i..] {
+        out.push(*digit as char);
+    }
 }
 
 // //////////////
@@ -887,45 +1422,40 @@ mod test_cmd_function {
 
     use super::*;
 
-    // PANIC TESTS
+    // ERROR TESTS
     #[test]
-    #[should_panic]
     fn invalid_mode_test() {
-        // should panic due to an unknown mode name
+        // should fail due to an unknown mode name
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
-        bed_to_fraction(input, "hello", false, false);
+        assert!(matches!(bed_to_fraction(input, "hello", false, false), Err(CubiculumError::InputError(_))));
     }
 
     #[test]
-    #[should_panic]
     fn truncated_line_test(){
-        // should panic due to the input line containing the number of columns different from twelve
+        // should fail due to the input line containing the number of columns different from twelve
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,");
-        bed_to_fraction(input, "cds", false, false);
+        assert!(matches!(bed_to_fraction(input, "cds", false, false), Err(CubiculumError::ParseError(_))));
     }
 
     #[test]
-    #[should_panic]
     fn invalid_value() {
-        // should panic due to one of the numeric fields occupied by a non-numeric value
+        // should fail due to one of the numeric fields occupied by a non-numeric value
         let input: String = String::from("chr9	AAA	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
-        bed_to_fraction(input, "cds", false, false);
+        assert!(matches!(bed_to_fraction(input, "cds", false, false), Err(CubiculumError::ParseError(_))));
     }
 
     #[test]
-    #[should_panic]
     fn negative_length(){
-        // should panic due to start value exceeding end value
+        // should fail due to start value exceeding end value
         let input: String = String::from("chr9	101385006	101360416	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
-        bed_to_fraction(input, "cds", false, false);
+        assert!(matches!(bed_to_fraction(input, "cds", false, false), Err(CubiculumError::CoordinateError(_))));
     }
 
     #[test]
-    #[should_panic]
     fn out_of_boundary_cds() {
-        // should panic due to thickEnd exceeding thinEnd
+        // should fail due to thickEnd exceeding thinEnd
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101385008	0	4	2599,203,525,152,	0,7703,10522,24438,");
-        bed_to_fraction(input, "cds", false, false);
+        assert!(matches!(bed_to_fraction(input, "cds", false, false), Err(CubiculumError::CoordinateError(_))));
     }
 
     // PERFORMANCE TESTS
@@ -934,7 +1464,7 @@ mod test_cmd_function {
         // tests whether the bed_to_fraction can return the same line as provided as input
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
-        assert_eq!(expected, bed_to_fraction(input, "all", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "all", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -942,7 +1472,7 @@ mod test_cmd_function {
         // tests whether "cds" mode returns the same line as input if a record contains coding sequence only
         let input: String = String::from("chr1	149156055	149163998	XM_047439510.1#LOC124904581	0	+	149156055	149163998	0	4	36,75,602,112,	0,2796,6686,7831,");
         let expected: String = String::from("chr1	149156055	149163998	XM_047439510.1#LOC124904581	0	+	149156055	149163998	0	4	36,75,602,112,	0,2796,6686,7831,");
-        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -950,7 +1480,7 @@ mod test_cmd_function {
         // tests the cds mode for a sequence with both merged and intron-separated UTR exons present
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101362427	101371404	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	3	588,203,466,	0,5692,8511,");
-        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -958,7 +1488,7 @@ mod test_cmd_function {
         // tests cds mode on a single exon sequence with arbitrary UTRs
         let input: String = String::from("chr9	129489948	129513686	XM_047424327.1#LINC00963	0	+	129490480	129491083	0	4	1180,177,350,268,	0,3470,13374,23470,");
         let expected: String = String::from("chr9	129490480	129491083	XM_047424327.1#LINC00963	0	+	129490480	129491083	0	1	603,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -966,14 +1496,14 @@ mod test_cmd_function {
         // tests the intron mode
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101363015	101370938	ENST00000259407.7#BAAT	0	-	101370938	101370938	0	2	5104,2616,	0,5307,");
-        assert_eq!(expected, bed_to_fraction(input, "cds", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", true, false).unwrap().unwrap());
     }
 
     #[test]
     fn uuu() {
         // tests the intron mode
         let input: String = String::from("chr19	47403123	47422233	NM_001346148.2#MEIS3	0	-	47406476	47422191	0	13	430,67,84,59,77,149,112,150,51,51,160,173,45,	0,3336,3764,3955,4228,5975,6312,11593,11927,13528,13680,14054,19065,");
-        println!("{}", bed_to_fraction(input, "cds", true, false).unwrap());
+        println!("{}", bed_to_fraction(input, "cds", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -981,14 +1511,14 @@ mod test_cmd_function {
         // tests intron mode for single-exon transcripts; must return None
         let input: String = String::from("chr9	129490480	129491083	XM_047424327.1#LINC00963	0	+	129490480	129491083	0	1	603,	0,");
         // assert_eq!(None, bed_to_fraction(input, "intron", false));
-        assert!(bed_to_fraction(input, "cds", true, false).is_none());
+        assert!(bed_to_fraction(input, "cds", true, false).unwrap().is_none());
     }
 
     #[test]
     fn zero_intron_with_utrs_test() {
         // the same as the test above but in the presence of UTR blocks
         let input: String = String::from("chr9	129489948	129513686	XM_047424327.1#LINC00963	0	+	129490480	129491083	0	4	1180,177,350,268,	0,3470,13374,23470,");
-        assert!(bed_to_fraction(input, "cds", true, false).is_none());
+        assert!(bed_to_fraction(input, "cds", true, false).unwrap().is_none());
     }
 
     #[test]
@@ -996,14 +1526,14 @@ mod test_cmd_function {
         // tests full UTR mode
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101385006	101385006	0	3	2011,59,152,	0,10988,24438,");
-        assert_eq!(expected, bed_to_fraction(input, "utr", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "utr", false, false).unwrap().unwrap());
     }
 
     #[test]
     fn no_utr_test(){
         // tests utr mode for pure CDS record; must return None
         let input: String = String::from("chr9	101362427	101371404	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	3	588,203,466,	0,5692,8511,");
-        assert!(bed_to_fraction(input, "utr", false, false).is_none());
+        assert!(bed_to_fraction(input, "utr", false, false).unwrap().is_none());
     }
 
     #[test]
@@ -1011,7 +1541,7 @@ mod test_cmd_function {
         // tets 5utr mode for a plus-strand transcript
         let input: String = String::from("chr19	45692665	45703987	NM_001163377.2#QPCTL	0	+	45692703	45703049	0	6	245,144,153,100,117,1084,	0,747,5881,6135,9132,10238,");
         let expected: String = String::from("chr19	45692665	45692703	NM_001163377.2#QPCTL	0	+	45692703	45692703	0	1	38,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "5utr", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "5utr", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1019,7 +1549,7 @@ mod test_cmd_function {
         // tets 5utr mode for a minus-strand transcript
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101371404	101385006	ENST00000259407.7#BAAT	0	-	101385006	101385006	0	2	59,152,	0,13450,");
-        assert_eq!(expected, bed_to_fraction(input, "5utr", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "5utr", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1027,7 +1557,7 @@ mod test_cmd_function {
         // tets 3utr mode for a plus-strand transcript
         let input: String = String::from("chr19	45692665	45703987	NM_001163377.2#QPCTL	0	+	45692703	45703049	0	6	245,144,153,100,117,1084,	0,747,5881,6135,9132,10238,");
         let expected: String = String::from("chr19	45703049	45703987	NM_001163377.2#QPCTL	0	+	45703987	45703987	0	1	938,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "3utr", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "3utr", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1035,21 +1565,21 @@ mod test_cmd_function {
         // tets 3utr mode for a minus-strand transcript
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101360416	101362427	ENST00000259407.7#BAAT	0	-	101362427	101362427	0	1	2011,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "3utr", false, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "3utr", false, false).unwrap().unwrap());
     }
 
     #[test]
     fn full_utr_noncoding_test() {
         // tests utr mode performance on pseudogenes; must return the same line as input
         let input: String = String::from("chr1	3205900	3216344	ENSMUST00000162897	0	-	3216344	3216344	0	2	1417,2736,	0,7708,");
-        assert_eq!(input, bed_to_fraction(input.clone(), "utr", false, false).unwrap());
+        assert_eq!(input, bed_to_fraction(input.clone(), "utr", false, false).unwrap().unwrap());
     }
 
     #[test]
     fn fiveprime_utr_noncoding_test(){
         // tests side-bound utr mode for pseudogenes; returns the same string as input
         let input: String = String::from("chr1	3205900	3216344	ENSMUST00000162897	0	-	3216344	3216344	0	2	1417,2736,	0,7708,");
-        assert_eq!(input, bed_to_fraction(input.clone(), "5utr", false, false).unwrap());
+        assert_eq!(input, bed_to_fraction(input.clone(), "5utr", false, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1057,7 +1587,7 @@ mod test_cmd_function {
         // tests intron mode for UTRs only
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101371463	101384854	ENST00000259407.7#BAAT	0	-	101384854	101384854	0	1	13391,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "utr", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "utr", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1065,7 +1595,7 @@ mod test_cmd_function {
         // tests intron mode for 5'-UTRs only; returns the same as the test above
         let input: String = String::from("chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,");
         let expected: String = String::from("chr9	101371463	101384854	ENST00000259407.7#BAAT	0	-	101384854	101384854	0	1	13391,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "5utr", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "5utr", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1073,7 +1603,7 @@ mod test_cmd_function {
         // tests intron mode for 5'-UTR in case of multiple 5'-UTR introns
         let input: String = String::from("chr19	46746056	46758575	ENST00000318584.10#FKRP	0	+	46755450	46756938	0	4	34,62,151,3164,	0,1970,2458,9355,");
         let expected: String = String::from("chr19	46746090	46755411	ENST00000318584.10#FKRP	0	+	46755411	46755411	0	3	1936,426,6746,	0,1998,2575,");
-        assert_eq!(expected, bed_to_fraction(input, "5utr", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "5utr", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1081,7 +1611,7 @@ mod test_cmd_function {
         // tests intron mode for 3'-UTRs only
         let input: String = String::from("chr19	47403123	47422233	NM_001346148.2#MEIS3	0	-	47406476	47422191	0	13	430,67,84,59,77,149,112,150,51,51,160,173,45,	0,3336,3764,3955,4228,5975,6312,11593,11927,13528,13680,14054,19065,");
         let expected: String = String::from("chr19	47403553	47406459	NM_001346148.2#MEIS3	0	-	47406459	47406459	0	1	2906,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "3utr", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "3utr", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1089,7 +1619,7 @@ mod test_cmd_function {
         // same as the test above, but this time the strand is positive
         let input: String = String::from("chr19	47778702	47784682	ENST00000601048.6#SELENOW	0	+	47778785	47781370	0	6	112,25,54,75,99,393,	0,2022,2161,2405,2587,5587,");
         let expected: String = String::from("chr19	47781388	47784289	ENST00000601048.6#SELENOW	0	+	47784289	47784289	0	1	2901,	0,");
-        assert_eq!(expected, bed_to_fraction(input, "3utr", true, false).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "3utr", true, false).unwrap().unwrap());
     }
 
     #[test]
@@ -1097,7 +1627,7 @@ mod test_cmd_function {
     {
         // tests intron for UTRs for a transcript with no UTR introns; None is expected
         let input: String = String::from("chr19	45692665	45703987	NM_001163377.2#QPCTL	0	+	45692703	45703049	0	6	245,144,153,100,117,1084,	0,747,5881,6135,9132,10238,");
-        assert!(bed_to_fraction(input, "utr", true, false).is_none());
+        assert!(bed_to_fraction(input, "utr", true, false).unwrap().is_none());
     }
 
     #[test]
@@ -1111,7 +1641,7 @@ chr19	47781107	47781182	ENST00000601048.6#SELENOW	4	+
 chr19	47781289	47781388	ENST00000601048.6#SELENOW	5	+
 chr19	47784289	47784682	ENST00000601048.6#SELENOW	6	+"
         );
-        assert_eq!(expected, bed_to_fraction(input, "all", false, true).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "all", false, true).unwrap().unwrap());
     }
 
     #[test]
@@ -1124,7 +1654,7 @@ chr19	47780863	47780917	ENST00000601048.6#SELENOW	3	+
 chr19	47781107	47781182	ENST00000601048.6#SELENOW	4	+
 chr19	47781289	47781370	ENST00000601048.6#SELENOW	5	+"
         );
-        assert_eq!(expected, bed_to_fraction(input, "cds", false, true).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", false, true).unwrap().unwrap());
     }
 
     #[test]
@@ -1134,7 +1664,7 @@ chr19	47781289	47781370	ENST00000601048.6#SELENOW	5	+"
             "chr9	101363015	101368119	ENST00000259407.7#BAAT	2	-
 chr9	101368322	101370938	ENST00000259407.7#BAAT	1	-"
         );
-        assert_eq!(expected, bed_to_fraction(input, "cds", true, true).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "cds", true, true).unwrap().unwrap());
     }
 
     #[test]
@@ -1155,7 +1685,436 @@ chr19	47416702	47416803	NM_001346148.2#MEIS3	3	-
 chr19	47416963	47417177	NM_001346148.2#MEIS3	2	-
 chr19	47417350	47422188	NM_001346148.2#MEIS3	1	-"
         );
-        assert_eq!(expected, bed_to_fraction(input, "all", true, true).unwrap());
+        assert_eq!(expected, bed_to_fraction(input, "all", true, true).unwrap().unwrap());
+    }
+
+}
+
+/// Output layouts for splice junction records
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JunctionFormat {
+    /// BED6 line spanning the intron, with the transcript name in the name field
+    Bed,
+    /// STAR SJ.out.tab line with one-based intron coordinates; read-support columns are set to zero
+    StarSj,
+    /// BED12 line with two 1 bp anchor blocks flanking the intron, as used by RNA-seq junction viewers
+    Bed12
+}
+
+/// A splice junction (intron) of an annotated transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Junction {
+    pub chrom: String,
+    /// Zero-based start of the intron (first intronic base)
+    pub start: u64,
+    /// Zero-based, half-open end of the intron
+    pub end: u64,
+    pub strand: Option<bool>,
+    /// Name of the transcript supporting the junction
+    pub name: String,
+    /// Zero-based position of the first intronic base at the donor (5') splice site
+    pub donor: u64,
+    /// Zero-based position of the last intronic base at the acceptor (3') splice site
+    pub acceptor: u64
+}
+
+impl Junction {
+    /// Format the junction into a tab-separated line of the requested layout; BED layouts have zero score
+    pub fn to_line(&self, format: JunctionFormat) -> String {
+        self.scored_line(format, 0)
+    }
+
+    /// Format the junction as `to_line()` does, with the given score for the BED layouts
+    fn scored_line(&self, format: JunctionFormat, score: usize) -> String {
+        let bed_strand = match self.strand {
+            Some(true) => {'+'},
+            Some(false) => {'-'},
+            None => {'.'}
+        };
+        match format {
+            JunctionFormat::Bed => {
+                format!("{}\t{}\t{}\t{}\t{}\t{}", self.chrom, self.start, self.end, self.name, score, bed_strand)
+            },
+            JunctionFormat::StarSj => {
+                let strand = match self.strand {
+                    Some(true) => {1},
+                    Some(false) => {2},
+                    None => {0}
+                };
+                // intron motif cannot be inferred without sequence; annotated flag is always set
+                format!("{}\t{}\t{}\t{}\t0\t1\t0\t0\t0", self.chrom, self.start + 1, self.end, strand)
+            },
+            JunctionFormat::Bed12 => {
+                let start = self.start.saturating_sub(1);
+                let end = self.end + 1;
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t0\t2\t1,1,\t0,{},",
+                    self.chrom, start, end, self.name, score, bed_strand, start, end, end - start - 1
+                )
+            }
+        }
+    }
+}
+
+/// Extract all introns of a BED12 entry as splice junction records
+/// 
+/// # Arguments
+/// `input`: BedEntry object in BED12 format;
+/// 
+/// # Returns
+/// A Result containing the junctions in ascending coordinate order;
+/// the vector is empty for single-block entries
+/// 
+pub fn extract_junctions(input: &BedEntry) -> Result<Vec<Junction>, CubiculumError> {
+    if input.format() != 12 {
+        return Err(
+            CubiculumError::FormattingError("Splice junctions can be extracted from BED12 entries only".to_string())
+        )
+    }
+    let chrom = match input.chrom() {
+        Some(x) => {x.clone()},
+        None => {return Err(CubiculumError::MissingTraitError("Undefined chromosome field".to_string()))}
+    };
+    let name = match input.name() {
+        Some(x) => {x.clone()},
+        None => {return Err(CubiculumError::MissingTraitError("Undefined name field".to_string()))}
+    };
+    let strand = input.strand();
+    let junctions: Vec<Junction> = input.introns()
+        .into_iter()
+        .map(|(intron_start, intron_end)| {
+            let (donor, acceptor) = match strand {
+                Some(false) => {(intron_end - 1, intron_start)},
+                _ => {(intron_start, intron_end - 1)}
+            };
+            Junction {
+                chrom: chrom.clone(),
+                start: intron_start,
+                end: intron_end,
+                strand,
+                name: name.clone(),
+                donor,
+                acceptor
+            }
+        })
+        .collect();
+    Ok(junctions)
+}
+
+/// An intron of an annotated transcript: a splice junction labelled with its position in the transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Intron {
+    pub junction: Junction,
+    /// One-based intron number counted from the transcript's 5'-end
+    pub ordinal: usize,
+    /// One-based number of the exon flanking the intron from the 5'-end
+    pub five_prime_exon: usize,
+    /// One-based number of the exon flanking the intron from the 3'-end
+    pub three_prime_exon: usize
+}
+
+impl Intron {
+    /// Intron length in base pairs
+    pub fn len(&self) -> u64 {
+        self.junction.end - self.junction.start
+    }
+
+    /// Returns true for zero-length introns; `extract_introns` never reports those
+    pub fn is_empty(&self) -> bool {
+        self.junction.end == self.junction.start
+    }
+
+    /// Format the intron as `Junction::to_line()` does, with the intron number as the score of the BED layouts
+    pub fn to_line(&self, format: JunctionFormat) -> String {
+        self.junction.scored_line(format, self.ordinal)
+    }
+}
+
+/// Extract all introns of a BED12 entry as typed records with strand-aware numbering
+/// 
+/// Exons and introns are numbered from the transcript's 5'-end, so that intron `k` lies between
+/// exons `k` and `k + 1`; entries with undefined strand are numbered as plus-strand ones.
+/// Book-ended blocks are not separated by an intron but still count towards exon numbering
+/// 
+/// # Arguments
+/// `input`: BedEntry object in BED12 format;
+/// 
+/// # Returns
+/// A Result containing the introns in ascending coordinate order;
+/// the vector is empty for single-block entries
+/// 
+pub fn extract_introns(input: &BedEntry) -> Result<Vec<Intron>, CubiculumError> {
+    let junctions = extract_junctions(input)?;
+    let blocks: Vec<(u64, u64)> = input.exons().collect();
+    let block_count = blocks.len();
+    let minus = input.strand() == Some(false);
+    let mut introns: Vec<Intron> = Vec::with_capacity(junctions.len());
+    for junction in junctions {
+        // index of the upstream (in coordinate order) flanking block
+        let i = blocks.partition_point(|x| x.1 <= junction.start) - 1;
+        let (five_prime_exon, three_prime_exon) = if minus {
+            (block_count - i - 1, block_count - i)
+        } else {
+            (i + 1, i + 2)
+        };
+        introns.push(
+            Intron {
+                junction,
+                ordinal: five_prime_exon,
+                five_prime_exon,
+                three_prime_exon
+            }
+        );
+    }
+    Ok(introns)
+}
+
+/// Convert the BED12 transcripts from the stream into a junction BED file with one record per intron
+///
+/// Junction records follow the layout used by RNA-seq junction viewers; see `JunctionFormat::Bed12`.
+/// The intron number is reported as the score.
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// The number of junctions written; an error if a line cannot be parsed as BED12
+/// or the output cannot be written
+///
+pub fn junction_bed<R, W>(reader: R, writer: &mut W) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, 12)?;
+        for intron in extract_introns(&entry)? {
+            writeln!(writer, "{}", intron.to_line(JunctionFormat::Bed12))?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Annotate the untranslated blocks of all BED12 transcripts from the stream in a single pass
+///
+/// Each UTR block is written as a BED6 line followed by its side and adjacency to the CDS;
+/// see `BedEntry::utr_blocks()` and `UtrBlock::to_line()`. Non-coding transcripts produce no records.
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// The number of UTR blocks written; an error if a line cannot be parsed as BED12,
+/// a transcript has no defined strand, or the output cannot be written
+///
+pub fn annotate_utrs<R, W>(reader: R, writer: &mut W) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, 12)?;
+        let utrs = entry.utr_blocks().ok_or(
+            CubiculumError::MissingTraitError(format!("Cannot annotate UTRs for an unstranded entry: {}", line))
+        )?;
+        for utr in utrs {
+            let utr_line = utr.to_line().ok_or(
+                CubiculumError::MissingTraitError(format!("Cannot format UTR blocks for an unnamed entry: {}", line))
+            )?;
+            writeln!(writer, "{}", utr_line)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Convert the BED records from the stream into a SAF annotation for featureCounts
+///
+/// See `to_saf()` for the record layout; the "GeneID Chr Start End Strand" header line is written first
+/// if `header` is true. Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// The number of SAF lines written, excluding the header; an error if a line cannot be parsed,
+/// a record has no name, or the output cannot be written
+///
+pub fn bed_to_saf<R, W>(reader: R, format: usize, writer: &mut W, header: bool) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    if header {
+        writeln!(writer, "GeneID\tChr\tStart\tEnd\tStrand")?;
+    }
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, format)?;
+        for saf_line in to_saf(&entry)? {
+            writeln!(writer, "{}", saf_line)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Parse the BED records from the stream, grouping them by name in the order of first occurrence;
+/// blank lines, comments and track/browser lines are skipped
+fn records_by_name<R: BufRead>(reader: R, format: usize) -> Result<Vec<(String, Vec<BedEntry>)>, CubiculumError> {
+    let mut order: FxHashMap<String, usize> = FxHashMap::default();
+    let mut groups: Vec<(String, Vec<BedEntry>)> = Vec::new();
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, format)?;
+        let name = entry.name().cloned().ok_or(
+            CubiculumError::MissingTraitError(format!("Cannot group unnamed record: {}", line))
+        )?;
+        let i = *order.entry(name.clone()).or_insert_with(|| {groups.push((name, Vec::new())); groups.len() - 1});
+        groups[i].1.push(entry);
+    }
+    Ok(groups)
+}
+
+/// Stitch BED6 exon records sharing the name into BED12 transcripts
+///
+/// # Arguments
+/// `exons`: BED6 stream of exon records;
+/// `cds`: optional BED6 stream of coding blocks; for each name, thickStart and thickEnd are set to the smallest start
+/// and the largest end of its coding blocks. Transcripts without coding blocks are written as non-coding;
+/// `writer`: output destination for BED12 lines
+///
+/// # Returns
+/// The number of transcripts written, sorted by chromosome and start coordinate;
+/// an error if any of the streams cannot be parsed or a transcript cannot be assembled (see BedEntry::from_exons())
+///
+pub fn stitch_exons<R, C, W>(exons: R, cds: Option<C>, writer: &mut W) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    C: BufRead,
+    W: Write
+{
+    let mut thick: FxHashMap<String, (u64, u64)> = FxHashMap::default();
+    if let Some(cds) = cds {
+        for (name, blocks) in records_by_name(cds, 6)? {
+            let start = blocks.iter().filter_map(|x| x.thin_start()).min();
+            let end = blocks.iter().filter_map(|x| x.thin_end()).max();
+            if let (Some(x), Some(y)) = (start, end) {
+                thick.insert(name, (x, y));
+            }
+        }
+    }
+    let mut transcripts: Vec<BedEntry> = Vec::new();
+    for (name, blocks) in records_by_name(exons, 6)? {
+        transcripts.push(BedEntry::from_exons(&blocks, thick.get(&name).copied())?);
+    }
+    transcripts.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom()).then(a.thin_start().cmp(&b.thin_start()))
+    );
+    for tr in transcripts.iter() {
+        writeln!(writer, "{}", to_line(tr, 12)?)?;
+    }
+    Ok(transcripts.len())
+}
+
+#[cfg(test)]
+mod test_junctions {
+    use super::*;
+
+    #[test]
+    fn junction_records() {
+        let input = try_parse_bed(
+            "chr9	101360416	101385006	ENST00000259407.7#BAAT	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,",
+            12
+        ).unwrap();
+        let junctions = extract_junctions(&input).unwrap();
+        assert_eq!(junctions.len(), 3);
+        assert_eq!((junctions[0].start, junctions[0].end), (101363015, 101368119));
+        assert_eq!((junctions[0].donor, junctions[0].acceptor), (101368118, 101363015));
+        assert_eq!(
+            junctions[0].to_line(JunctionFormat::Bed),
+            "chr9\t101363015\t101368119\tENST00000259407.7#BAAT\t0\t-"
+        );
+        assert_eq!(
+            junctions[0].to_line(JunctionFormat::StarSj),
+            "chr9\t101363016\t101368119\t2\t0\t1\t0\t0\t0"
+        );
+    }
+
+    #[test]
+    fn intron_records() {
+        let minus = try_parse_bed(
+            "chr9	101360416	101385006	A	0	-	101362427	101371404	0	4	2599,203,525,152,	0,7703,10522,24438,",
+            12
+        ).unwrap();
+        let introns = extract_introns(&minus).unwrap();
+        assert_eq!(introns.len(), 3);
+        assert_eq!((introns[0].ordinal, introns[0].five_prime_exon, introns[0].three_prime_exon), (3, 3, 4));
+        assert_eq!((introns[2].ordinal, introns[2].five_prime_exon, introns[2].three_prime_exon), (1, 1, 2));
+        assert_eq!(introns[0].len(), 5104);
+        assert_eq!(introns[0].to_line(JunctionFormat::Bed), "chr9\t101363015\t101368119\tA\t3\t-");
+        assert_eq!(
+            introns[0].junction.to_line(JunctionFormat::Bed12),
+            "chr9\t101363014\t101368120\tA\t0\t-\t101363014\t101368120\t0\t2\t1,1,\t0,5105,"
+        );
+
+        // book-ended blocks keep their exon numbers but produce no intron
+        let plus = try_parse_bed(
+            "chr1	100	600	B	0	+	100	600	0	3	100,100,100,	0,100,400,",
+            12
+        ).unwrap();
+        let introns = extract_introns(&plus).unwrap();
+        assert_eq!(introns.len(), 1);
+        assert_eq!((introns[0].junction.start, introns[0].junction.end), (300, 500));
+        assert_eq!((introns[0].ordinal, introns[0].five_prime_exon, introns[0].three_prime_exon), (2, 2, 3));
+    }
+
+    #[test]
+    fn junction_bed_records() {
+        let input = "track name=transcripts\n\
+            chr1\t100\t600\tA\t0\t-\t100\t600\t0\t3\t100,100,100,\t0,200,400,\n\
+            chr1\t100\t200\tB\t0\t+\t100\t200\t0\t1\t100,\t0,\n";
+        let mut output: Vec<u8> = Vec::new();
+        let written = junction_bed(input.as_bytes(), &mut output).unwrap();
+        assert_eq!(written, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr1\t199\t301\tA\t2\t-\t199\t301\t0\t2\t1,1,\t0,101,\n\
+            chr1\t399\t501\tA\t1\t-\t399\t501\t0\t2\t1,1,\t0,101,\n"
+        );
+    }
+
+    #[test]
+    fn utr_annotation_records() {
+        let input = "chr1\t100\t600\tA\t0\t+\t150\t600\t0\t2\t100,100,\t0,400,\n\
+            chr1\t100\t600\tB\t0\t+\t100\t100\t0\t2\t100,100,\t0,400,\n\
+            chr1\t100\t600\tC\t0\t+\t300\t400\t0\t3\t100,100,100,\t0,200,400,\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(annotate_utrs(input.as_bytes(), &mut output).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr1\t100\t150\tA\t0\t+\t5UTR\tadjacent\n\
+            chr1\t100\t200\tC\t0\t+\t5UTR\tseparated\n\
+            chr1\t500\t600\tC\t0\t+\t3UTR\tseparated\n"
+        );
+    }
+
+    #[test]
+    fn single_exon_has_no_junctions() {
+        let input = try_parse_bed(
+            "chr9	129490480	129491083	A	0	+	129490480	129491083	0	1	603,	0,",
+            12
+        ).unwrap();
+        assert!(extract_junctions(&input).unwrap().is_empty());
     }
 
+    #[test]
+    fn stitch_exon_records() {
+        let exons = "chr2\t10\t20\tB\t0\t-\nchr1\t500\t600\tA\t0\t+\nchr1\t100\t200\tA\t0\t+\n";
+        let cds = "chr1\t150\t200\tA\t0\t+\nchr1\t500\t550\tA\t0\t+\n";
+        let mut out: Vec<u8> = Vec::new();
+        assert_eq!(stitch_exons(exons.as_bytes(), Some(cds.as_bytes()), &mut out).unwrap(), 2);
+        let lines: Vec<String> = String::from_utf8(out).unwrap().lines().map(|x| x.to_string()).collect();
+        assert_eq!(lines[0], "chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,");
+        assert_eq!(lines[1], "chr2\t10\t20\tB\t0\t-\t10\t10\t0\t1\t10,\t0,");
+    }
 }
@@ -0,0 +1,5 @@
+/*!
+Module for sequence extraction from indexed FASTA files; requires the `fasta` feature
+*/
+
+pub mod fasta;
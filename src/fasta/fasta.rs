@@ -0,0 +1,496 @@
+//! # cubiculum::fasta
+//! 
+//! Sequence extraction for BED records from samtools-indexed (.fai) FASTA files
+//!
+//! BED12 entries are reported as spliced sequences with their blocks concatenated,
+//! and minus-strand entries are reverse-complemented. Base composition can be reported for the same sequences
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, try_parse_bed};
+use crate::structs::structs::{BedEntry, Coordinates};
+
+/// A single .fai index record
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaiRecord {
+    pub length: u64,
+    /// Byte offset of the first base in the FASTA file
+    pub offset: u64,
+    pub line_bases: u64,
+    /// Number of bytes per line, including the line terminator
+    pub line_width: u64
+}
+
+/// Defines the headers of the records written by get_fasta()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FastaName {
+    /// `chrom:start-end`
+    Coordinates,
+    /// Entry name
+    Name,
+    /// `name::chrom:start-end`
+    NameAndCoordinates
+}
+
+/// Reverse-complement a nucleotide sequence, preserving the case; non-ACGTN symbols are kept as is
+pub fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter()
+        .rev()
+        .map(|x| match x {
+            b'A' => {b'T'}, b'C' => {b'G'}, b'G' => {b'C'}, b'T' => {b'A'},
+            b'a' => {b't'}, b'c' => {b'g'}, b'g' => {b'c'}, b't' => {b'a'},
+            _ => {*x}
+        })
+        .collect()
+}
+
+/// Nucleotide counts of a sequence; counting is case-insensitive
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BaseComposition {
+    pub a: u64,
+    pub c: u64,
+    pub g: u64,
+    pub t: u64,
+    pub n: u64,
+    /// Symbols other than A, C, G, T and N
+    pub other: u64
+}
+
+impl BaseComposition {
+    pub fn from_seq(seq: &[u8]) -> BaseComposition {
+        let mut composition = BaseComposition::default();
+        for base in seq {
+            match base.to_ascii_uppercase() {
+                b'A' => {composition.a += 1},
+                b'C' => {composition.c += 1},
+                b'G' => {composition.g += 1},
+                b'T' => {composition.t += 1},
+                b'N' => {composition.n += 1},
+                _ => {composition.other += 1}
+            }
+        }
+        composition
+    }
+
+    /// Total sequence length
+    pub fn len(&self) -> u64 {
+        self.a + self.c + self.g + self.t + self.n + self.other
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fraction of G and C bases over the total sequence length; None for empty sequences
+    pub fn gc_content(&self) -> Option<f64> {
+        if self.is_empty() {return None};
+        Some((self.g + self.c) as f64 / self.len() as f64)
+    }
+
+    /// Fraction of A and T bases over the total sequence length; None for empty sequences
+    pub fn at_content(&self) -> Option<f64> {
+        if self.is_empty() {return None};
+        Some((self.a + self.t) as f64 / self.len() as f64)
+    }
+}
+
+/// A FASTA file with random access to its sequences via the .fai index
+pub struct IndexedFasta<R: Read + Seek> {
+    reader: R,
+    index: FxHashMap<String, FaiRecord>
+}
+
+impl IndexedFasta<BufReader<File>> {
+    /// Open the FASTA file; the index is expected at `{path}.fai`
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<IndexedFasta<BufReader<File>>, CubiculumError> {
+        let path = path.as_ref();
+        let fasta = File::open(path).map_err(
+            |e| CubiculumError::IoError(format!("Cannot open {:?}: {}", path, e))
+        )?;
+        let mut fai_path = path.as_os_str().to_owned();
+        fai_path.push(".fai");
+        let fai = File::open(&fai_path).map_err(
+            |e| CubiculumError::IoError(format!("Cannot open {:?}: {}", fai_path, e))
+        )?;
+        IndexedFasta::from_reader(BufReader::new(fasta), BufReader::new(fai))
+    }
+}
+
+impl<R: Read + Seek> IndexedFasta<R> {
+    /// Create the indexed FASTA out of a seekable FASTA stream and its .fai index
+    pub fn from_reader<I: BufRead>(reader: R, fai: I) -> Result<IndexedFasta<R>, CubiculumError> {
+        let mut index: FxHashMap<String, FaiRecord> = FxHashMap::default();
+        for line in fai.lines() {
+            let line = line?;
+            if line.is_empty() {continue};
+            let data: Vec<&str> = line.split('\t').collect();
+            if data.len() < 5 {
+                return Err(CubiculumError::ParseError(format!("Invalid .fai line: {}", line)))
+            }
+            let mut values: [u64; 4] = [0; 4];
+            for (i, value) in data[1..5].iter().enumerate() {
+                values[i] = value.parse::<u64>().map_err(
+                    |_| CubiculumError::ParseError(format!("Invalid .fai line: {}", line))
+                )?;
+            }
+            if values[2] == 0 && values[0] > 0 {
+                return Err(CubiculumError::ParseError(format!("Invalid .fai line: {}", line)))
+            }
+            index.insert(
+                data[0].to_string(),
+                FaiRecord {length: values[0], offset: values[1], line_bases: values[2], line_width: values[3]}
+            );
+        }
+        Ok(IndexedFasta {reader, index})
+    }
+
+    /// Index record for the sequence
+    pub fn record(&self, chrom: &str) -> Option<&FaiRecord> {
+        self.index.get(chrom)
+    }
+
+    /// Fetch the plus-strand sequence of the zero-based, half-open region
+    pub fn fetch(&mut self, chrom: &str, start: u64, end: u64) -> Result<Vec<u8>, CubiculumError> {
+        let record = self.index.get(chrom).ok_or(
+            CubiculumError::InputError(format!("Sequence {} is missing from the FASTA index", chrom))
+        )?;
+        if start > end || end > record.length {
+            return Err(
+                CubiculumError::InputError(
+                    format!("Region {}:{}-{} lies outside of the sequence of length {}", chrom, start, end, record.length)
+                )
+            )
+        }
+        if start == end {return Ok(Vec::new())};
+        let byte_pos = |pos: u64| record.offset + pos / record.line_bases * record.line_width + pos % record.line_bases;
+        let first = byte_pos(start);
+        let last = byte_pos(end - 1);
+        let mut buffer: Vec<u8> = vec![0; (last - first + 1) as usize];
+        self.reader.seek(SeekFrom::Start(first))?;
+        self.reader.read_exact(&mut buffer)?;
+        buffer.retain(|x| *x != b'\n' && *x != b'\r');
+        Ok(buffer)
+    }
+
+    /// Fetch the plus-strand sequence spanned by the interval
+    pub fn interval_sequence<T: Coordinates>(&mut self, interval: &T) -> Result<Vec<u8>, CubiculumError> {
+        match (interval.chrom(), interval.start(), interval.end()) {
+            (Some(chrom), Some(start), Some(end)) => {self.fetch(chrom, *start, *end)},
+            _ => {Err(CubiculumError::MissingTraitError("Interval coordinates are not fully defined".to_string()))}
+        }
+    }
+
+    /// Fetch the sequence of the entry in the direction of transcription
+    ///
+    /// For BED12 entries, the sequences of all blocks are concatenated; minus-strand entries
+    /// are reverse-complemented, and entries with undefined strand are reported as plus-strand ones
+    pub fn entry_sequence(&mut self, entry: &BedEntry) -> Result<Vec<u8>, CubiculumError> {
+        let chrom = entry.chrom().ok_or(
+            CubiculumError::MissingTraitError("Undefined chromosome field".to_string())
+        )?.clone();
+        let mut seq: Vec<u8> = if entry.format() == 12 {
+            let mut seq: Vec<u8> = Vec::with_capacity(entry.block_length() as usize);
+            for (start, end) in entry.exons() {
+                seq.extend(self.fetch(&chrom, start, end)?);
+            }
+            seq
+        } else {
+            self.interval_sequence(entry)?
+        };
+        if entry.strand() == Some(false) {
+            seq = reverse_complement(&seq);
+        }
+        Ok(seq)
+    }
+
+    /// Nucleotide composition of the entry sequence as reported by `entry_sequence()`
+    pub fn composition(&mut self, entry: &BedEntry) -> Result<BaseComposition, CubiculumError> {
+        Ok(BaseComposition::from_seq(&self.entry_sequence(entry)?))
+    }
+
+    /// GC content of the entry sequence; for BED12 entries, only the blocks are considered
+    pub fn gc_content(&mut self, entry: &BedEntry) -> Result<Option<f64>, CubiculumError> {
+        Ok(self.composition(entry)?.gc_content())
+    }
+}
+
+/// Write the sequences of all BED entries from the stream as FASTA records
+///
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Arguments
+/// `fasta`: indexed FASTA to extract the sequences from;
+/// `reader`, `format`: BED stream and the number of its columns;
+/// `writer`: output stream;
+/// `naming`: record header layout; entry coordinates are reported as chromStart-chromEnd;
+/// `line_width`: if set, sequences are wrapped to the given number of bases per line
+///
+/// # Returns
+/// The number of the records written; an error if a line cannot be parsed,
+/// a sequence cannot be extracted, or the output cannot be written
+///
+pub fn get_fasta<F, R, W>(
+    fasta: &mut IndexedFasta<F>,
+    reader: R,
+    format: usize,
+    writer: &mut W,
+    naming: FastaName,
+    line_width: Option<usize>
+) -> Result<usize, CubiculumError>
+where
+    F: Read + Seek,
+    R: BufRead,
+    W: Write
+{
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, format)?;
+        let seq = fasta.entry_sequence(&entry)?;
+        let coords = format!(
+            "{}:{}-{}", entry.chrom().unwrap(), entry.start().unwrap(), entry.end().unwrap()
+        );
+        let name = entry.name().map(|x| x.as_str()).unwrap_or(".");
+        let header = match naming {
+            FastaName::Coordinates => {coords},
+            FastaName::Name => {name.to_string()},
+            FastaName::NameAndCoordinates => {format!("{}::{}", name, coords)}
+        };
+        writeln!(writer, ">{}", header)?;
+        let width = match line_width {
+            Some(x) if x > 0 => {x},
+            _ => {seq.len().max(1)}
+        };
+        for chunk in seq.chunks(width) {
+            writer.write_all(chunk)?;
+            writeln!(writer)?;
+        }
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Report the nucleotide composition of all BED entries from the stream, similarly to `bedtools nuc`
+///
+/// Each input line is followed by the AT and GC fractions, the counts of A, C, G, T, N and other symbols,
+/// and the sequence length; BED12 entries are assessed over their blocks only.
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// The number of the lines written; an error if a line cannot be parsed,
+/// a sequence cannot be extracted, or the output cannot be written
+///
+pub fn nucleotide_content<F, R, W>(
+    fasta: &mut IndexedFasta<F>, reader: R, format: usize, writer: &mut W
+) -> Result<usize, CubiculumError>
+where
+    F: Read + Seek,
+    R: BufRead,
+    W: Write
+{
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, format)?;
+        let nuc = fasta.composition(&entry)?;
+        writeln!(
+            writer,
+            "{}\t{:.6}\t{:.6}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            line.trim_end(), nuc.at_content().unwrap_or(0.0), nuc.gc_content().unwrap_or(0.0),
+            nuc.a, nuc.c, nuc.g, nuc.t, nuc.n, nuc.other, nuc.len()
+        )?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// Standard genetic code with codons ordered by TCAG at each position
+const CODON_TABLE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+/// Translate a single codon with the standard genetic code; codons with ambiguous bases are translated as 'X'
+pub fn translate_codon(codon: &[u8]) -> u8 {
+    if codon.len() != 3 {return b'X'};
+    let mut index: usize = 0;
+    for base in codon {
+        let value = match base.to_ascii_uppercase() {
+            b'T' | b'U' => {0},
+            b'C' => {1},
+            b'A' => {2},
+            b'G' => {3},
+            _ => {return b'X'}
+        };
+        index = index * 4 + value;
+    }
+    CODON_TABLE[index]
+}
+
+/// Issues found while translating a coding sequence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TranslationWarning {
+    /// The CDS length is not a multiple of three; holds the number of trailing bases left untranslated
+    PartialCodon(usize),
+    /// A stop codon occurs before the last codon; holds its zero-based codon number
+    InternalStop(usize),
+    /// The first codon is not ATG
+    NoStartCodon,
+    /// The last codon is not a stop codon
+    NoStopCodon
+}
+
+/// Protein sequence of a coding entry, along with the issues found during translation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Translation {
+    /// One-letter amino acid sequence; stop codons are reported as '*'
+    pub protein: String,
+    pub warnings: Vec<TranslationWarning>
+}
+
+impl Translation {
+    /// Returns true if the CDS starts with ATG, ends with a stop codon, and has no internal stops or partial codons
+    pub fn is_complete_orf(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+/// Translate the coding sequence of a BED12 entry
+///
+/// The coding parts of the blocks are spliced together and reverse-complemented for minus-strand entries
+///
+/// # Returns
+/// The translation with the warnings for partial codons, internal stop codons, and missing start or stop codons;
+/// an error if the entry is not in BED12 format, is non-coding, has undefined strand,
+/// or its sequence cannot be extracted
+///
+pub fn translate<F: Read + Seek>(entry: &BedEntry, genome: &mut IndexedFasta<F>) -> Result<Translation, CubiculumError> {
+    if entry.format() != 12 {
+        return Err(CubiculumError::FormattingError("Only BED12 entries can be translated".to_string()))
+    }
+    let strand = entry.strand().ok_or(
+        CubiculumError::MissingTraitError("Cannot translate an entry with undefined strand".to_string())
+    )?;
+    let chrom = entry.chrom().ok_or(
+        CubiculumError::MissingTraitError("Undefined chromosome field".to_string())
+    )?.clone();
+    let cds = entry.cds_blocks().ok_or(
+        CubiculumError::InputError(format!("Entry {:?} has no coding sequence", entry.name()))
+    )?;
+    let mut seq: Vec<u8> = Vec::new();
+    for (start, end) in cds {
+        seq.extend(genome.fetch(&chrom, start, end)?);
+    }
+    if !strand {
+        seq = reverse_complement(&seq);
+    }
+
+    let mut warnings: Vec<TranslationWarning> = Vec::new();
+    let protein: Vec<u8> = seq.chunks_exact(3).map(translate_codon).collect();
+    if seq.len() < 3 || !seq[..3].eq_ignore_ascii_case(b"ATG") {
+        warnings.push(TranslationWarning::NoStartCodon);
+    }
+    for (i, aa) in protein.iter().enumerate() {
+        if *aa == b'*' && i + 1 < protein.len() {
+            warnings.push(TranslationWarning::InternalStop(i));
+        }
+    }
+    if protein.last() != Some(&b'*') {
+        warnings.push(TranslationWarning::NoStopCodon);
+    }
+    let trailing = seq.len() % 3;
+    if trailing > 0 {
+        warnings.push(TranslationWarning::PartialCodon(trailing));
+    }
+    Ok(
+        Translation {
+            protein: String::from_utf8(protein).unwrap(),
+            warnings
+        }
+    )
+}
+
+#[cfg(test)]
+mod test_fasta {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+    use std::io::Cursor;
+
+    // two sequences wrapped at 10 bases per line
+    const FASTA: &str = ">chr1\nAAAACCCCGG\nGGTTTTacgt\nNN\n>chr2\nACGT\n";
+    const FAI: &str = "chr1\t22\t6\t10\t11\nchr2\t4\t37\t10\t11\n";
+
+    fn fasta() -> IndexedFasta<Cursor<&'static [u8]>> {
+        IndexedFasta::from_reader(Cursor::new(FASTA.as_bytes()), FAI.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn fetch_across_lines() {
+        let mut fasta = fasta();
+        assert_eq!(fasta.fetch("chr1", 8, 14).unwrap(), b"GGGGTT");
+        assert_eq!(fasta.fetch("chr1", 18, 22).unwrap(), b"gtNN");
+        assert_eq!(fasta.fetch("chr2", 0, 4).unwrap(), b"ACGT");
+        assert!(fasta.fetch("chr1", 20, 23).is_err());
+        assert!(fasta.fetch("chr3", 0, 1).is_err());
+    }
+
+    #[test]
+    fn spliced_minus_strand_records() {
+        let mut fasta = fasta();
+        let bed = "chr1\t0\t20\tA\t0\t-\t0\t20\t0\t2\t4,4,\t0,16,\n";
+        let mut output: Vec<u8> = Vec::new();
+        let written = get_fasta(&mut fasta, bed.as_bytes(), 12, &mut output, FastaName::NameAndCoordinates, Some(5)).unwrap();
+        assert_eq!(written, 1);
+        // AAAA + acgt, reverse-complemented
+        assert_eq!(String::from_utf8(output).unwrap(), ">A::chr1:0-20\nacgtT\nTTT\n");
+    }
+
+    #[test]
+    fn composition_report() {
+        let mut fasta = fasta();
+        let entry = try_parse_bed("chr1\t4\t22\tA\t0\t+", 6).unwrap();
+        let nuc = fasta.composition(&entry).unwrap();
+        assert_eq!((nuc.a, nuc.c, nuc.g, nuc.t, nuc.n, nuc.len()), (1, 5, 5, 5, 2, 18));
+        assert!((fasta.gc_content(&entry).unwrap().unwrap() - 10.0 / 18.0).abs() < 1e-9);
+
+        let bed = "chr2\t0\t4\tB\t0\t-\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(nucleotide_content(&mut fasta, bed.as_bytes(), 6, &mut output).unwrap(), 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr2\t0\t4\tB\t0\t-\t0.500000\t0.500000\t1\t1\t1\t1\t0\t0\t4\n"
+        );
+    }
+
+    #[test]
+    fn translation() {
+        // ATG AAA | tga on the plus strand, split by an intron
+        let fasta_seq = ">chr1\nATGAAAccctga\n>chr2\nTCATTTCATTAG\n";
+        let fai = "chr1\t12\t6\t12\t13\nchr2\t12\t25\t12\t13\n";
+        let mut genome = IndexedFasta::from_reader(Cursor::new(fasta_seq.as_bytes()), fai.as_bytes()).unwrap();
+        let plus = try_parse_bed("chr1\t0\t12\tA\t0\t+\t0\t12\t0\t2\t6,3,\t0,9,", 12).unwrap();
+        let res = translate(&plus, &mut genome).unwrap();
+        assert_eq!(res.protein, "MK*");
+        assert!(res.is_complete_orf());
+
+        // minus strand: the reverse complement of TCATTTCATTA is TAA TGA AAT GA
+        let minus = try_parse_bed("chr2\t0\t12\tB\t0\t-\t0\t11\t0\t1\t12,\t0,", 12).unwrap();
+        let res = translate(&minus, &mut genome).unwrap();
+        assert_eq!(res.protein, "**N");
+        assert_eq!(
+            res.warnings,
+            vec![
+                TranslationWarning::NoStartCodon,
+                TranslationWarning::InternalStop(0),
+                TranslationWarning::InternalStop(1),
+                TranslationWarning::NoStopCodon,
+                TranslationWarning::PartialCodon(2)
+            ]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for the C-compatible interface to the library
+*/
+
+pub mod ffi;
@@ -0,0 +1,270 @@
+//! # cubiculum::ffi
+//!
+//! C-compatible interface for embedding the library into C/C++ tools
+//!
+//! Entries are passed across the boundary as opaque `BedEntry` pointers, and strings as
+//! NUL-terminated UTF-8 buffers. Every entry and string returned by this module is owned by the caller
+//! and must be released with `cubiculum_entry_free()` or `cubiculum_string_free()`, respectively.
+//! Failures, including panics inside the library, are reported as null pointers. The matching header is
+//! `include/cubiculum.h`; it can be regenerated with cbindgen using `cbindgen.toml`. The crate is built as an rlib
+//! by default; a shared or static library for C users is built with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib` (or `staticlib`)
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, UnwindSafe};
+use std::ptr;
+
+use crate::extract::extract::{bed_to_fraction, extract_fraction, to_line, try_parse_bed, BedFractionMode};
+use crate::options::options::ProcessingOptions;
+use crate::structs::structs::{BedEntry, Coordinates};
+
+/// Fraction mode codes accepted by `cubiculum_fraction()`, in the order of `BedFractionMode` variants
+fn fraction_mode(code: c_int) -> Option<BedFractionMode> {
+    match code {
+        0 => {Some(BedFractionMode::All)},
+        1 => {Some(BedFractionMode::Cds)},
+        2 => {Some(BedFractionMode::Utr)},
+        3 => {Some(BedFractionMode::Utr5)},
+        4 => {Some(BedFractionMode::Utr3)},
+        5 => {Some(BedFractionMode::FirstExon)},
+        6 => {Some(BedFractionMode::LastExon)},
+        7 => {Some(BedFractionMode::TerminalUtr)},
+        _ => {None}
+    }
+}
+
+/// Borrow a C string as &str; None for null pointers and invalid UTF-8
+unsafe fn borrowed_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {return None};
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Run the library call, turning its panics into None since unwinding into C code aborts the process
+fn guarded<T, F: FnOnce() -> Option<T> + UnwindSafe>(f: F) -> Option<T> {
+    catch_unwind(f).ok().flatten()
+}
+
+fn owned_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(x) => {x.into_raw()},
+        Err(_) => {ptr::null_mut()}
+    }
+}
+
+fn boxed_entry(entry: Option<BedEntry>) -> *mut BedEntry {
+    match entry {
+        Some(x) => {Box::into_raw(Box::new(x))},
+        None => {ptr::null_mut()}
+    }
+}
+
+/// Parse a BED line of the given format (3 to 9 or 12); null is returned for malformed lines
+///
+/// # Safety
+/// `line` must be null or point to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_parse_bed(line: *const c_char, format: u8) -> *mut BedEntry {
+    let line = match borrowed_str(line) {
+        Some(x) => {x},
+        None => {return ptr::null_mut()}
+    };
+    boxed_entry(guarded(|| try_parse_bed(line, format as usize).ok()))
+}
+
+/// Format the entry as a BED line of the given format
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_entry_to_line(entry: *const BedEntry, format: u8) -> *mut c_char {
+    let entry = match entry.as_ref() {
+        Some(x) => {x},
+        None => {return ptr::null_mut()}
+    };
+    match guarded(|| to_line(entry, format).ok()) {
+        Some(x) => {owned_string(x)},
+        None => {ptr::null_mut()}
+    }
+}
+
+/// Extract a fraction of a BED12 entry as a new BED12 entry
+///
+/// `mode` is one of 0 (all), 1 (CDS), 2 (UTR), 3 (5'-UTR), 4 (3'-UTR), 5 (first exon), 6 (last exon),
+/// 7 (terminal UTR exons); null is returned for an invalid mode or if the fraction is empty
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_fraction(entry: *const BedEntry, mode: c_int, intron: bool) -> *mut BedEntry {
+    let (entry, mode) = match (entry.as_ref(), fraction_mode(mode)) {
+        (Some(x), Some(y)) => {(x, y)},
+        _ => {return ptr::null_mut()}
+    };
+    boxed_entry(guarded(|| extract_fraction(entry, mode, intron).ok().flatten()))
+}
+
+/// Line-based fraction extraction, as done by bed12ToFraction
+///
+/// `mode` takes the bed12ToFraction mode names ("all", "cds", "utr", "5utr", "3utr", "first", "last", "terminal");
+/// if `bed6` is true, the fraction blocks are reported as newline-separated BED6 lines
+///
+/// # Safety
+/// `line` and `mode` must be null or point to NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_line_to_fraction(
+    line: *const c_char, mode: *const c_char, intron: bool, bed6: bool
+) -> *mut c_char {
+    let (line, mode) = match (borrowed_str(line), borrowed_str(mode)) {
+        (Some(x), Some(y)) => {(x, y)},
+        _ => {return ptr::null_mut()}
+    };
+    match guarded(|| bed_to_fraction(line.trim_end().to_string(), mode, intron, bed6).ok().flatten()) {
+        Some(x) => {owned_string(x)},
+        None => {ptr::null_mut()}
+    }
+}
+
+/// Clip the entry to the [start, end) region, returning a new entry;
+/// pass 0 and UINT64_MAX to leave the respective side unclipped. Null is returned if the region
+/// misses all of the entry's blocks
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_clip(entry: *const BedEntry, start: u64, end: u64) -> *mut BedEntry {
+    let entry = match entry.as_ref() {
+        Some(x) => {x},
+        None => {return ptr::null_mut()}
+    };
+    if start >= end {return ptr::null_mut()};
+    boxed_entry(
+        guarded(|| entry.clone().clip_by_with_options(Some(start), Some(end), false, &ProcessingOptions::strict()).ok().flatten())
+    )
+}
+
+/// Entry start coordinate; UINT64_MAX if undefined
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_entry_start(entry: *const BedEntry) -> u64 {
+    guarded(|| entry.as_ref().and_then(|x| x.start().copied())).unwrap_or(u64::MAX)
+}
+
+/// Entry end coordinate; UINT64_MAX if undefined
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_entry_end(entry: *const BedEntry) -> u64 {
+    guarded(|| entry.as_ref().and_then(|x| x.end().copied())).unwrap_or(u64::MAX)
+}
+
+/// Number of entry blocks; one for entries without block structure, zero for null
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_entry_block_count(entry: *const BedEntry) -> usize {
+    guarded(|| entry.as_ref().map(|x| x.exons().len())).unwrap_or(0)
+}
+
+/// Release an entry returned by this module; null is ignored
+///
+/// # Safety
+/// `entry` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_entry_free(entry: *mut BedEntry) {
+    if !entry.is_null() {
+        let _ = catch_unwind(|| drop(Box::from_raw(entry)));
+    }
+}
+
+/// Release a string returned by this module; null is ignored
+///
+/// # Safety
+/// `s` must be null or a pointer returned by this module and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn cubiculum_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        let _ = catch_unwind(|| drop(CString::from_raw(s)));
+    }
+}
+
+#[cfg(test)]
+mod test_ffi {
+    use super::*;
+
+    unsafe fn take_string(s: *mut c_char) -> String {
+        assert!(!s.is_null());
+        let output = CStr::from_ptr(s).to_str().unwrap().to_string();
+        cubiculum_string_free(s);
+        output
+    }
+
+    #[test]
+    fn entry_roundtrip() {
+        let line = CString::new("chr1\t100\t600\tA\t0\t-\t150\t350\t0\t3\t100,100,100,\t0,200,400,").unwrap();
+        unsafe {
+            let entry = cubiculum_parse_bed(line.as_ptr(), 12);
+            assert!(!entry.is_null());
+            assert_eq!((cubiculum_entry_start(entry), cubiculum_entry_end(entry)), (100, 600));
+            assert_eq!(cubiculum_entry_block_count(entry), 3);
+            assert_eq!(take_string(cubiculum_entry_to_line(entry, 12)), line.to_str().unwrap());
+
+            let cds = cubiculum_fraction(entry, 1, false);
+            assert_eq!(take_string(cubiculum_entry_to_line(cds, 12)), "chr1\t150\t350\tA\t0\t-\t150\t350\t0\t2\t50,50,\t0,150,");
+            assert!(cubiculum_fraction(entry, 42, false).is_null());
+
+            let clipped = cubiculum_clip(entry, 250, u64::MAX);
+            assert_eq!(cubiculum_entry_start(clipped), 250);
+            assert_eq!(cubiculum_entry_block_count(clipped), 2);
+
+            cubiculum_entry_free(clipped);
+            cubiculum_entry_free(cds);
+            cubiculum_entry_free(entry);
+            cubiculum_entry_free(ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        let short = CString::new("chr1\t100").unwrap();
+        let malformed = CString::new("chr1\tx\t200").unwrap();
+        unsafe {
+            assert!(cubiculum_parse_bed(ptr::null(), 3).is_null());
+            assert!(cubiculum_parse_bed(short.as_ptr(), 6).is_null());
+            assert!(cubiculum_parse_bed(malformed.as_ptr(), 3).is_null());
+            assert!(cubiculum_entry_to_line(ptr::null(), 3).is_null());
+            assert_eq!(cubiculum_entry_start(ptr::null()), u64::MAX);
+        }
+    }
+
+    #[test]
+    fn unnamed_clip() {
+        let line = CString::new("chr1	100	600").unwrap();
+        unsafe {
+            let entry = cubiculum_parse_bed(line.as_ptr(), 3);
+            let clipped = cubiculum_clip(entry, 250, 400);
+            assert!(!clipped.is_null());
+            assert_eq!(take_string(cubiculum_entry_to_line(clipped, 3)), "chr1	250	400");
+            assert!(cubiculum_clip(entry, 700, 800).is_null());
+            cubiculum_entry_free(clipped);
+            cubiculum_entry_free(entry);
+        }
+    }
+
+    #[test]
+    fn line_fraction() {
+        let line = CString::new("chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,").unwrap();
+        let mode = CString::new("5utr").unwrap();
+        unsafe {
+            let output = take_string(cubiculum_line_to_fraction(line.as_ptr(), mode.as_ptr(), false, false));
+            assert_eq!(output, "chr1\t100\t150\tA\t0\t+\t150\t150\t0\t1\t50,\t0,");
+        }
+    }
+}
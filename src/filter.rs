@@ -0,0 +1,5 @@
+/*!
+Module for composable record filters
+*/
+
+pub mod filter;
@@ -0,0 +1,244 @@
+//! # cubiculum::filter
+//!
+//! Composable record filters replacing ad hoc selection closures
+//!
+//! A `Filter` is assembled from conditions on record length, score, chromosome, name, strand
+//! and coding status; all conditions must hold for a record to pass. Filters can be combined with
+//! `or()` and inverted with the `!` operator, and applied to record iterators, `BedCollection` objects
+//! or `Pipeline` stages. Chromosome and name patterns are shell-style globs, where '*' matches
+//! any sequence of characters and '?' matches a single character
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashSet;
+use std::ops::Not;
+
+use crate::structs::structs::{BedEntry, Coordinates, Scored};
+
+/// A single filter condition
+#[derive(Clone, Debug)]
+enum Condition {
+    MinLength(u64),
+    MaxLength(u64),
+    ScoreRange(f64, f64),
+    Chroms(FxHashSet<String>),
+    ChromPattern(String),
+    NamePattern(String),
+    Strand(bool),
+    Coding,
+    Any(Vec<Filter>),
+    Not(Box<Filter>)
+}
+
+impl Condition {
+    fn matches(&self, entry: &BedEntry) -> bool {
+        match self {
+            Condition::MinLength(x) => {entry.length().is_some_and(|y| y >= *x)},
+            Condition::MaxLength(x) => {entry.length().is_some_and(|y| y <= *x)},
+            Condition::ScoreRange(min, max) => {entry.score_value().is_some_and(|x| x >= *min && x <= *max)},
+            Condition::Chroms(chroms) => {entry.chrom().is_some_and(|x| chroms.contains(x))},
+            Condition::ChromPattern(pattern) => {entry.chrom().is_some_and(|x| glob_match(pattern, x))},
+            Condition::NamePattern(pattern) => {entry.name().is_some_and(|x| glob_match(pattern, x))},
+            Condition::Strand(strand) => {entry.strand() == Some(*strand)},
+            Condition::Coding => {
+                matches!((entry.thick_start(), entry.thick_end()), (Some(x), Some(y)) if x < y)
+            },
+            Condition::Any(filters) => {filters.iter().any(|x| x.matches(entry))},
+            Condition::Not(filter) => {!filter.matches(entry)}
+        }
+    }
+}
+
+/// Record filter; an empty filter passes every record
+///
+/// # Usage
+/// ```
+/// use cubiculum::extract::extract::try_parse_bed;
+/// use cubiculum::filter::filter::Filter;
+///
+/// let lines = [
+///     "chr1\t100\t600\tENST1\t0\t+\t150\t350\t0\t2\t100,100,\t0,400,",
+///     "chr1\t100\t200\tENST2\t0\t-\t200\t200\t0\t1\t100,\t0,",
+///     "chrUn_1\t100\t900\tENST3\t0\t+\t150\t350\t0\t1\t800,\t0,"
+/// ];
+/// let entries: Vec<_> = lines.iter().map(|x| try_parse_bed(x, 12).unwrap()).collect();
+/// let filter = Filter::new().min_length(300).coding_only().not_chrom_matches("chrUn*");
+/// let kept: Vec<_> = filter.apply(entries).collect();
+/// assert_eq!(kept.len(), 1);
+/// assert_eq!(kept[0].name().unwrap(), "ENST1");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    conditions: Vec<Condition>
+}
+
+impl Filter {
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    fn with(mut self, condition: Condition) -> Filter {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Keep records spanning at least `length` bases
+    pub fn min_length(self, length: u64) -> Filter {
+        self.with(Condition::MinLength(length))
+    }
+
+    /// Keep records spanning at most `length` bases
+    pub fn max_length(self, length: u64) -> Filter {
+        self.with(Condition::MaxLength(length))
+    }
+
+    /// Keep records with numeric scores within the inclusive [min, max] range
+    pub fn score_range(self, min: f64, max: f64) -> Filter {
+        self.with(Condition::ScoreRange(min, max))
+    }
+
+    /// Keep records located on the listed chromosomes
+    pub fn chroms<S, I>(self, chroms: I) -> Filter
+    where
+        S: Into<String>,
+        I: IntoIterator<Item = S>
+    {
+        self.with(Condition::Chroms(chroms.into_iter().map(|x| x.into()).collect()))
+    }
+
+    /// Keep records with chromosome names matching the glob pattern
+    pub fn chrom_matches(self, pattern: &str) -> Filter {
+        self.with(Condition::ChromPattern(pattern.to_string()))
+    }
+
+    /// Discard records with chromosome names matching the glob pattern (e.g., 'chrUn*' or '*_random')
+    pub fn not_chrom_matches(self, pattern: &str) -> Filter {
+        self.with(Condition::Not(Box::new(Filter::new().chrom_matches(pattern))))
+    }
+
+    /// Keep records with names matching the glob pattern
+    pub fn name_matches(self, pattern: &str) -> Filter {
+        self.with(Condition::NamePattern(pattern.to_string()))
+    }
+
+    /// Keep records located on the given strand (true for '+'); unstranded records are discarded
+    pub fn strand(self, strand: bool) -> Filter {
+        self.with(Condition::Strand(strand))
+    }
+
+    /// Keep records with non-empty coding sequence
+    pub fn coding_only(self) -> Filter {
+        self.with(Condition::Coding)
+    }
+
+    /// Keep records passing either this or the other filter
+    pub fn or(self, other: Filter) -> Filter {
+        Filter::new().with(Condition::Any(vec![self, other]))
+    }
+
+    /// Check whether the record passes all the conditions
+    pub fn matches(&self, entry: &BedEntry) -> bool {
+        self.conditions.iter().all(|x| x.matches(entry))
+    }
+
+    /// Lazily filter the records
+    pub fn apply<'a, I>(&'a self, entries: I) -> impl Iterator<Item = BedEntry> + 'a
+    where
+        I: IntoIterator<Item = BedEntry>,
+        I::IntoIter: 'a
+    {
+        entries.into_iter().filter(move |x| self.matches(x))
+    }
+}
+
+impl Not for Filter {
+    type Output = Filter;
+
+    /// Keep records failing this filter
+    fn not(self) -> Filter {
+        Filter::new().with(Condition::Not(Box::new(self)))
+    }
+}
+
+/// Shell-style glob matching with '*' and '?' wildcards
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last '*' in the pattern and of the text character it is matched against
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last '*' absorb one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false
+        }
+    }
+    pattern[p..].iter().all(|x| *x == '*')
+}
+
+#[cfg(test)]
+mod test_filter {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+
+    fn entries() -> Vec<BedEntry> {
+        [
+            "chr1\t100\t200\tENST0001.1\t10\t+",
+            "chr1\t100\t1100\tENST0002.1\t500\t-",
+            "chr2\t0\t50\tENSG0003\t.\t+",
+            "chrUn_KI270302v1\t0\t5000\tENST0004.2\t900\t-"
+        ].iter()
+            .map(|x| try_parse_bed(x, 6).unwrap())
+            .collect()
+    }
+
+    fn names(filter: &Filter) -> Vec<String> {
+        filter.apply(entries()).map(|x| x.name().unwrap().clone()).collect()
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(names(&Filter::new()).len(), 4);
+        assert_eq!(names(&Filter::new().min_length(100).max_length(1000)), vec!["ENST0001.1", "ENST0002.1"]);
+        // records without numeric scores never pass a score condition
+        assert_eq!(names(&Filter::new().score_range(0.0, 500.0)), vec!["ENST0001.1", "ENST0002.1"]);
+        assert_eq!(names(&Filter::new().chroms(["chr2"])), vec!["ENSG0003"]);
+        assert_eq!(names(&Filter::new().chrom_matches("chrUn_*")), vec!["ENST0004.2"]);
+        assert_eq!(names(&Filter::new().name_matches("ENST*.1").strand(false)), vec!["ENST0002.1"]);
+        let coding = try_parse_bed("chr1\t0\t100\tA\t0\t+\t10\t90", 8).unwrap();
+        let noncoding = try_parse_bed("chr1\t0\t100\tA\t0\t+\t100\t100", 8).unwrap();
+        assert!(Filter::new().coding_only().matches(&coding));
+        assert!(!Filter::new().coding_only().matches(&noncoding));
+    }
+
+    #[test]
+    fn combinators() {
+        let short_or_unplaced = Filter::new().max_length(100).or(Filter::new().chrom_matches("chrUn*"));
+        assert_eq!(names(&short_or_unplaced), vec!["ENST0001.1", "ENSG0003", "ENST0004.2"]);
+        assert_eq!(names(&!short_or_unplaced), vec!["ENST0002.1"]);
+        assert_eq!(names(&Filter::new().not_chrom_matches("chrUn*").strand(true)), vec!["ENST0001.1", "ENSG0003"]);
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("chr?", "chrX"));
+        assert!(!glob_match("chr?", "chr10"));
+        assert!(glob_match("*_random", "chr1_KI270706v1_random"));
+        assert!(glob_match("*a*b*", "xxaybz"));
+        assert!(!glob_match("*a*b", "xxaybz"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for the conversions between BED records and polars DataFrames; requires the `polars` feature
+*/
+
+pub mod frame;
@@ -0,0 +1,337 @@
+//! # cubiculum::frame
+//!
+//! Conversions between BED records and polars DataFrames
+//!
+//! The frames follow the column layout of the Arrow export: `chrom`, `start`, `end`, `name`, `score`,
+//! `strand`, `thick_start`, `thick_end`, `item_rgb`, `block_count`, `block_sizes`, `block_starts`, with
+//! coordinates stored as u64, the strand as a "+"/"-" string, and block sizes and starts as list[u64] columns.
+//! A frame of the first N of these columns holds BED records of format N
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use polars::prelude::{Column, DataFrame, DataType, NamedFrom, PolarsError, Series};
+
+use crate::collection::collection::BedCollection;
+use crate::error::error::CubiculumError;
+use crate::structs::structs::{BedEntry, Coordinates};
+
+/// Column names in the BED column order
+const COLUMN_NAMES: [&str; 12] = [
+    "chrom", "start", "end", "name", "score", "strand",
+    "thick_start", "thick_end", "item_rgb", "block_count", "block_sizes", "block_starts"
+];
+
+fn check_format(format: u8) -> Result<(), CubiculumError> {
+    match format {
+        3..=6 | 8 | 9 | 12 => {Ok(())},
+        _ => {
+            Err(
+                CubiculumError::InputError(
+                    format!("Provided format BED{} is not supported. Accepted formats are : BED3,4,5,6,8,9,12", format)
+                )
+            )
+        }
+    }
+}
+
+fn missing(field: &str) -> CubiculumError {
+    CubiculumError::MissingTraitError(format!("Undefined {} field", field))
+}
+
+fn input_error(e: PolarsError) -> CubiculumError {
+    CubiculumError::InputError(e.to_string())
+}
+
+fn list_column(name: &str, values: Vec<Series>) -> Column {
+    if values.is_empty() {
+        return Column::new_empty(name.into(), &DataType::List(Box::new(DataType::UInt64)))
+    }
+    Column::new(name.into(), values)
+}
+
+/// Convert BED records into a DataFrame
+///
+/// # Arguments
+/// `entries`: records to convert;
+/// `format`: BED format of the frame; defines the number of columns
+///
+/// # Returns
+/// The DataFrame; an InputError for the formats other than BED3-6, BED8, BED9 and BED12, a FormattingError
+/// if an entry's format is lower than the requested one, and a MissingTraitError if an entry lacks
+/// a field required by the format
+///
+/// # Examples
+/// ```
+/// use cubiculum::frame::frame::{from_dataframe, to_dataframe};
+/// use cubiculum::extract::extract::try_parse_bed;
+///
+/// let entry = try_parse_bed("chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,", 12).unwrap();
+/// let frame = to_dataframe(&[entry.clone()], 12).unwrap();
+/// assert_eq!(frame.shape(), (1, 12));
+/// assert_eq!(from_dataframe(&frame).unwrap(), vec![entry]);
+/// ```
+pub fn to_dataframe(entries: &[BedEntry], format: u8) -> Result<DataFrame, CubiculumError> {
+    check_format(format)?;
+    let rows = entries.len();
+    let mut chrom: Vec<&str> = Vec::with_capacity(rows);
+    let mut start: Vec<u64> = Vec::with_capacity(rows);
+    let mut end: Vec<u64> = Vec::with_capacity(rows);
+    let mut name: Vec<&str> = Vec::new();
+    let mut score: Vec<&str> = Vec::new();
+    let mut strand: Vec<&str> = Vec::new();
+    let mut thick_start: Vec<u64> = Vec::new();
+    let mut thick_end: Vec<u64> = Vec::new();
+    let mut rgb: Vec<&str> = Vec::new();
+    let mut block_count: Vec<u16> = Vec::new();
+    let mut block_sizes: Vec<Series> = Vec::new();
+    let mut block_starts: Vec<Series> = Vec::new();
+    for entry in entries {
+        if entry.format() < format {
+            return Err(
+                CubiculumError::FormattingError(
+                    format!("Cannot convert BED{} entry into a BED{} frame", entry.format(), format)
+                )
+            )
+        }
+        chrom.push(entry.chrom().ok_or_else(|| missing("chromosome"))?);
+        start.push(entry.thin_start().ok_or_else(|| missing("thinStart"))?);
+        end.push(entry.thin_end().ok_or_else(|| missing("thinEnd"))?);
+        if format >= 4 {name.push(entry.name().ok_or_else(|| missing("name"))?)};
+        if format >= 5 {score.push(entry.score().ok_or_else(|| missing("score"))?)};
+        if format >= 6 {
+            let x = entry.strand().ok_or_else(|| missing("strand"))?;
+            strand.push(if x {"+"} else {"-"});
+        }
+        if format >= 8 {
+            thick_start.push(entry.thick_start().ok_or_else(|| missing("thickStart"))?);
+            thick_end.push(entry.thick_end().ok_or_else(|| missing("thickEnd"))?);
+        }
+        if format >= 9 {rgb.push(entry.rgb().ok_or_else(|| missing("Rgb"))?)};
+        if format == 12 {
+            block_count.push(entry.exon_num().ok_or_else(|| missing("exonNumber"))?);
+            let sizes = entry.exon_sizes().ok_or_else(|| missing("exonSizes"))?;
+            block_sizes.push(Series::new("".into(), sizes.as_slice()));
+            let starts = entry.exon_starts().ok_or_else(|| missing("exonStarts"))?;
+            block_starts.push(Series::new("".into(), starts.as_slice()));
+        }
+    }
+    let mut columns: Vec<Column> = vec![
+        Column::new(COLUMN_NAMES[0].into(), chrom),
+        Column::new(COLUMN_NAMES[1].into(), start),
+        Column::new(COLUMN_NAMES[2].into(), end),
+        Column::new(COLUMN_NAMES[3].into(), name),
+        Column::new(COLUMN_NAMES[4].into(), score),
+        Column::new(COLUMN_NAMES[5].into(), strand),
+        Column::new(COLUMN_NAMES[6].into(), thick_start),
+        Column::new(COLUMN_NAMES[7].into(), thick_end),
+        Column::new(COLUMN_NAMES[8].into(), rgb),
+        Column::new(COLUMN_NAMES[9].into(), block_count),
+        list_column(COLUMN_NAMES[10], block_sizes),
+        list_column(COLUMN_NAMES[11], block_starts)
+    ];
+    columns.truncate(format as usize);
+    DataFrame::new(rows, columns).map_err(|e| CubiculumError::FormattingError(e.to_string()))
+}
+
+/// BED format of a frame, inferred from its column names
+fn frame_format(frame: &DataFrame) -> Result<u8, CubiculumError> {
+    let format = frame.width();
+    let names: Vec<&str> = frame.get_column_names().iter().map(|x| x.as_str()).collect();
+    if format > COLUMN_NAMES.len() || names != COLUMN_NAMES[..format] {
+        return Err(
+            CubiculumError::InputError(
+                format!("Frame columns {:?} do not match the BED column layout", names)
+            )
+        )
+    }
+    check_format(format as u8)?;
+    Ok(format as u8)
+}
+
+fn value<T>(x: Option<T>, field: &str, row: usize) -> Result<T, CubiculumError> {
+    x.ok_or_else(|| missing(&format!("{} at row {}", field, row)))
+}
+
+/// Convert a DataFrame into BED records
+///
+/// # Arguments
+/// `frame`: a frame with the columns of `to_dataframe()`; the BED format is inferred from the number of columns
+///
+/// # Returns
+/// The records in the frame order; an InputError if the column names or types do not follow the BED layout
+/// or a strand value is not "+" or "-", and a MissingTraitError for null values
+///
+pub fn from_dataframe(frame: &DataFrame) -> Result<Vec<BedEntry>, CubiculumError> {
+    let format = frame_format(frame)?;
+    let column = |i: usize| frame.column(COLUMN_NAMES[i]).map_err(input_error);
+    let chrom = column(0)?.str().map_err(input_error)?;
+    let start = column(1)?.u64().map_err(input_error)?;
+    let end = column(2)?.u64().map_err(input_error)?;
+    let name = if format >= 4 {Some(column(3)?.str().map_err(input_error)?)} else {None};
+    let score = if format >= 5 {Some(column(4)?.str().map_err(input_error)?)} else {None};
+    let strand = if format >= 6 {Some(column(5)?.str().map_err(input_error)?)} else {None};
+    let thick_start = if format >= 8 {Some(column(6)?.u64().map_err(input_error)?)} else {None};
+    let thick_end = if format >= 8 {Some(column(7)?.u64().map_err(input_error)?)} else {None};
+    let rgb = if format >= 9 {Some(column(8)?.str().map_err(input_error)?)} else {None};
+    let block_count = if format == 12 {Some(column(9)?.u16().map_err(input_error)?)} else {None};
+    let block_sizes = if format == 12 {Some(column(10)?.list().map_err(input_error)?)} else {None};
+    let block_starts = if format == 12 {Some(column(11)?.list().map_err(input_error)?)} else {None};
+
+    let blocks = |series: Option<Series>, field: &str, row: usize| -> Result<Vec<u64>, CubiculumError> {
+        let series = value(series, field, row)?;
+        let values = series.u64().map_err(input_error)?;
+        values.iter().map(|x| value(x, field, row)).collect()
+    };
+    let mut entries: Vec<BedEntry> = Vec::with_capacity(frame.height());
+    for row in 0..frame.height() {
+        let chrom = value(chrom.get(row), "chromosome", row)?.to_string();
+        let start = value(start.get(row), "thinStart", row)?;
+        let end = value(end.get(row), "thinEnd", row)?;
+        if format == 3 {
+            entries.push(BedEntry::bed3(chrom, start, end));
+            continue
+        }
+        let name = value(name.unwrap().get(row), "name", row)?.to_string();
+        if format == 4 {
+            entries.push(BedEntry::bed4(chrom, start, end, name));
+            continue
+        }
+        let score = value(score.unwrap().get(row), "score", row)?.to_string();
+        if format == 5 {
+            entries.push(BedEntry::bed5(chrom, start, end, name, score));
+            continue
+        }
+        let strand = match value(strand.unwrap().get(row), "strand", row)? {
+            "+" => {true},
+            "-" => {false},
+            x => {
+                return Err(
+                    CubiculumError::InputError(format!("Invalid strand value at row {}: {}", row, x))
+                )
+            }
+        };
+        if format == 6 {
+            entries.push(BedEntry::bed6(chrom, start, end, name, score, strand));
+            continue
+        }
+        let thick_start = value(thick_start.unwrap().get(row), "thickStart", row)?;
+        let thick_end = value(thick_end.unwrap().get(row), "thickEnd", row)?;
+        if format == 8 {
+            entries.push(BedEntry::bed8(chrom, start, end, name, score, strand, thick_start, thick_end));
+            continue
+        }
+        let rgb = value(rgb.unwrap().get(row), "Rgb", row)?.to_string();
+        if format == 9 {
+            entries.push(BedEntry::bed9(chrom, start, end, name, score, strand, thick_start, thick_end, rgb));
+            continue
+        }
+        let block_count = value(block_count.unwrap().get(row), "exonNumber", row)?;
+        let sizes = blocks(block_sizes.unwrap().get_as_series(row), "exonSizes", row)?;
+        let starts = blocks(block_starts.unwrap().get_as_series(row), "exonStarts", row)?;
+        entries.push(
+            BedEntry::bed12(
+                chrom, start, end, name, score, strand, thick_start, thick_end, rgb, block_count, sizes, starts
+            )
+        );
+    }
+    Ok(entries)
+}
+
+/// Convert a collection into a DataFrame sorted by chromosome and coordinates
+///
+/// The frame format is the highest one supported by all the records, e.g., a collection
+/// of BED12 and BED6 records is converted into a BED6 frame
+impl TryFrom<&BedCollection> for DataFrame {
+    type Error = CubiculumError;
+
+    fn try_from(collection: &BedCollection) -> Result<DataFrame, CubiculumError> {
+        let entries: Vec<BedEntry> = collection.iter_sorted().cloned().collect();
+        let format = match entries.iter().map(|x| x.format()).min().unwrap_or(3) {
+            x if x >= 12 => {12},
+            9..=11 => {9},
+            8 => {8},
+            7 => {6},
+            x => {x}
+        };
+        to_dataframe(&entries, format)
+    }
+}
+
+/// Convert a DataFrame into a collection; see `from_dataframe()`
+impl TryFrom<&DataFrame> for BedCollection {
+    type Error = CubiculumError;
+
+    fn try_from(frame: &DataFrame) -> Result<BedCollection, CubiculumError> {
+        BedCollection::from_entries(from_dataframe(frame)?)
+    }
+}
+
+#[cfg(test)]
+mod test_frame {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+
+    fn entries() -> Vec<BedEntry> {
+        vec![
+            try_parse_bed("chr2\t0\t300\tB\t7\t-\t0\t0\t255,0,0\t3\t50,50,50,\t0,100,250,", 12).unwrap(),
+            try_parse_bed("chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,", 12).unwrap()
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let entries = entries();
+        for format in [3, 4, 5, 6, 8, 9, 12] {
+            let frame = to_dataframe(&entries, format).unwrap();
+            assert_eq!(frame.shape(), (2, format as usize));
+            let restored = from_dataframe(&frame).unwrap();
+            assert!(restored.iter().all(|x| x.format() == format));
+            assert_eq!(restored[0].thin_end(), Some(300));
+        }
+        let frame = to_dataframe(&entries, 12).unwrap();
+        assert_eq!(from_dataframe(&frame).unwrap(), entries);
+        let empty = to_dataframe(&[], 12).unwrap();
+        assert_eq!(empty.shape(), (0, 12));
+        assert!(from_dataframe(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_frames() {
+        let entries = entries();
+        assert!(matches!(to_dataframe(&entries, 7), Err(CubiculumError::InputError(_))));
+        let bed3 = vec![BedEntry::bed3(String::from("chr1"), 0, 10)];
+        assert!(matches!(to_dataframe(&bed3, 6), Err(CubiculumError::FormattingError(_))));
+
+        let renamed = DataFrame::new_infer_height(vec![
+            Column::new("chr".into(), vec!["chr1"]),
+            Column::new("start".into(), vec![0u64]),
+            Column::new("end".into(), vec![10u64])
+        ]).unwrap();
+        assert!(matches!(from_dataframe(&renamed), Err(CubiculumError::InputError(_))));
+        let nullable = DataFrame::new_infer_height(vec![
+            Column::new("chrom".into(), vec!["chr1"]),
+            Column::new("start".into(), vec![None::<u64>]),
+            Column::new("end".into(), vec![10u64])
+        ]).unwrap();
+        assert!(matches!(from_dataframe(&nullable), Err(CubiculumError::MissingTraitError(_))));
+        let mut stranded = to_dataframe(&entries, 6).unwrap();
+        stranded.replace("strand", Column::new("strand".into(), vec!["+", "."])).unwrap();
+        assert!(matches!(from_dataframe(&stranded), Err(CubiculumError::InputError(_))));
+    }
+
+    #[test]
+    fn collection() {
+        let mut entries = entries();
+        entries.push(try_parse_bed("chr1\t0\t50\tC\t0\t+", 6).unwrap());
+        let collection = BedCollection::from_entries(entries).unwrap();
+        let frame = DataFrame::try_from(&collection).unwrap();
+        assert_eq!(frame.shape(), (3, 6));
+        let names: Vec<Option<&str>> = frame.column("name").unwrap().str().unwrap().iter().collect();
+        assert_eq!(names, vec![Some("C"), Some("A"), Some("B")]);
+        let restored = BedCollection::try_from(&frame).unwrap();
+        assert_eq!(restored.len(), 3);
+        assert_eq!(restored.query("chr1", 120, 130).count(), 1);
+    }
+}
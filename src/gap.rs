@@ -0,0 +1,5 @@
+/*!
+Module for assembly gap annotation and masking of features spanning unresolved sequence
+*/
+
+pub mod gap;
@@ -0,0 +1,330 @@
+//! # cubiculum::gap
+//!
+//! Assembly gap annotation and masking of features spanning unresolved (N) sequence
+//!
+//! Gaps are read from a BED file (e.g., the UCSC gap track) or from the gap lines of an AGP file
+//! and stored as sorted non-overlapping intervals per chromosome
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::cmp::{max, min};
+use std::io::{BufRead, Write};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, is_bed_header_line, is_track_line, to_line, try_parse_bed};
+use crate::merge::merge::block_union;
+use crate::structs::structs::{BedEntry, Coordinates};
+
+/// Assembly gaps indexed by chromosome
+#[derive(Clone, Debug, Default)]
+pub struct GapMask {
+    gaps: FxHashMap<String, Vec<(u64, u64)>>
+}
+
+/// Action applied to features overlapping assembly gaps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GapAction {
+    /// Discard features with at least one gapped base
+    Exclude,
+    /// Remove the gapped bases from the features, discarding the entirely gapped ones
+    Truncate
+}
+
+impl GapMask {
+    pub fn new() -> GapMask {
+        GapMask::default()
+    }
+
+    /// Add a gap; call `normalize()` once all gaps are added
+    pub fn insert(&mut self, chrom: &str, start: u64, end: u64) {
+        if start >= end {return};
+        self.gaps.entry(chrom.to_string()).or_default().push((start, end));
+    }
+
+    /// Sort the gaps and merge the overlapping and book-ended ones
+    pub fn normalize(&mut self) {
+        for gaps in self.gaps.values_mut() {
+            *gaps = block_union(gaps.drain(..));
+        }
+    }
+
+    /// Read the gaps from a BED3+ stream; track, browser and comment lines are skipped
+    pub fn from_bed<R: BufRead>(reader: R) -> Result<GapMask, CubiculumError> {
+        let mut mask = GapMask::new();
+        for line in bed_lines(reader) {
+            let line = line?;
+            let data: Vec<&str> = line.trim().split('\t').collect();
+            if data.len() < 3 {
+                return Err(CubiculumError::ParseError(format!("Gap line has less than three columns: {}", line)))
+            }
+            let start: u64 = data[1].parse::<u64>()
+                .map_err(|_| CubiculumError::ParseError(format!("Invalid gap start: {}", line)))?;
+            let end: u64 = data[2].parse::<u64>()
+                .map_err(|_| CubiculumError::ParseError(format!("Invalid gap end: {}", line)))?;
+            mask.insert(data[0], start, end);
+        }
+        mask.normalize();
+        Ok(mask)
+    }
+
+    /// Read the gaps from an AGP stream
+    ///
+    /// Only the gap lines (component types 'N' and 'U') are considered; AGP coordinates
+    /// are 1-based and inclusive and are converted to the BED convention
+    pub fn from_agp<R: BufRead>(reader: R) -> Result<GapMask, CubiculumError> {
+        let mut mask = GapMask::new();
+        for line in reader.lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {continue};
+            let data: Vec<&str> = trimmed.split('\t').collect();
+            if data.len() < 5 {
+                return Err(CubiculumError::ParseError(format!("AGP line has less than five columns: {}", line)))
+            }
+            if data[4] != "N" && data[4] != "U" {continue};
+            let start: u64 = data[1].parse::<u64>()
+                .map_err(|_| CubiculumError::ParseError(format!("Invalid object start: {}", line)))?;
+            let end: u64 = data[2].parse::<u64>()
+                .map_err(|_| CubiculumError::ParseError(format!("Invalid object end: {}", line)))?;
+            if start == 0 {
+                return Err(CubiculumError::ParseError(format!("AGP coordinates are 1-based: {}", line)))
+            }
+            mask.insert(data[0], start - 1, end);
+        }
+        mask.normalize();
+        Ok(mask)
+    }
+
+    /// Number of (merged) gaps
+    pub fn len(&self) -> usize {
+        self.gaps.values().map(|x| x.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gaps.values().all(|x| x.is_empty())
+    }
+
+    /// Gaps overlapping the [start, end) region, in ascending order
+    pub fn gaps_in(&self, chrom: &str, start: u64, end: u64) -> &[(u64, u64)] {
+        let gaps = match self.gaps.get(chrom) {
+            Some(x) => {x},
+            None => {return &[]}
+        };
+        let first = gaps.partition_point(|x| x.1 <= start);
+        let last = first + gaps[first..].partition_point(|x| x.0 < end);
+        &gaps[first..last]
+    }
+
+    /// Number of gapped bases within the [start, end) region
+    pub fn gap_bases(&self, chrom: &str, start: u64, end: u64) -> u64 {
+        self.gaps_in(chrom, start, end).iter()
+            .map(|x| min(x.1, end) - max(x.0, start))
+            .sum()
+    }
+
+    /// Check whether any of the entry's blocks overlaps a gap; gaps falling into introns are ignored
+    pub fn overlaps_gap(&self, entry: &BedEntry) -> bool {
+        let chrom = match entry.chrom() {
+            Some(x) => {x},
+            None => {return false}
+        };
+        entry.exons().any(|(start, end)| !self.gaps_in(chrom, start, end).is_empty())
+    }
+
+    /// Check whether any base of the interval lies within a gap
+    pub fn overlaps_interval<T: Coordinates>(&self, interval: &T) -> bool {
+        match (interval.chrom(), interval.start(), interval.end()) {
+            (Some(chrom), Some(start), Some(end)) => {!self.gaps_in(chrom, *start, *end).is_empty()},
+            _ => {false}
+        }
+    }
+
+    /// Remove the gapped bases from the entry
+    ///
+    /// For BED12 entries, blocks are split around the gaps they contain; for other formats,
+    /// the entry is trimmed to its outermost ungapped bases, leaving the internal gaps in place.
+    /// Coding boundaries are clamped to the new entry boundaries
+    ///
+    /// # Returns
+    /// The masked entry; None if all of its bases lie within gaps
+    ///
+    pub fn mask(&self, entry: &BedEntry) -> Option<BedEntry> {
+        let chrom = entry.chrom()?;
+        if !self.overlaps_gap(entry) {return Some(entry.clone())};
+        let mut blocks: Vec<(u64, u64)> = Vec::new();
+        for (start, end) in entry.exons() {
+            let mut pos = start;
+            for &(gap_start, gap_end) in self.gaps_in(chrom, start, end) {
+                if gap_start > pos {blocks.push((pos, gap_start))};
+                pos = max(pos, gap_end);
+            }
+            if pos < end {blocks.push((pos, end))};
+        }
+        if blocks.is_empty() {return None};
+        let new_start = blocks[0].0;
+        let new_end = blocks[blocks.len() - 1].1;
+        let thick = match (entry.thick_start(), entry.thick_end()) {
+            (Some(x), Some(y)) => {(x.clamp(new_start, new_end), y.clamp(new_start, new_end))},
+            _ => {(new_start, new_start)}
+        };
+        Some(entry.relocated(chrom.clone(), blocks, thick, entry.strand()))
+    }
+
+    /// Remove the gapped bases from the entry, splitting non-BED12 entries into separate records
+    /// around the internal gaps they cannot represent
+    ///
+    /// BED12 entries are masked as with `mask()`; for other formats, each ungapped piece of the entry
+    /// is reported as a copy of the entry with its coordinates (and coding boundaries, if any) cut to the piece
+    ///
+    /// # Returns
+    /// The masked entry or its pieces in ascending order; an empty vector if all of its bases lie within gaps
+    /// or the entry has undefined chromosome or coordinates
+    ///
+    pub fn split(&self, entry: &BedEntry) -> Vec<BedEntry> {
+        if entry.format() == 12 {return self.mask(entry).into_iter().collect()};
+        let (Some(chrom), Some(&start), Some(&end)) = (entry.chrom(), entry.start(), entry.end()) else {
+            return Vec::new()
+        };
+        let mut pieces: Vec<BedEntry> = Vec::new();
+        let mut push_piece = |piece_start: u64, piece_end: u64| {
+            let thick = match (entry.thick_start(), entry.thick_end()) {
+                (Some(x), Some(y)) => {(x.clamp(piece_start, piece_end), y.clamp(piece_start, piece_end))},
+                _ => {(piece_start, piece_start)}
+            };
+            pieces.push(entry.relocated(chrom.clone(), vec![(piece_start, piece_end)], thick, entry.strand()));
+        };
+        let mut pos = start;
+        for &(gap_start, gap_end) in self.gaps_in(chrom, start, end) {
+            if gap_start > pos {push_piece(pos, gap_start)};
+            pos = max(pos, gap_end);
+        }
+        if pos < end {push_piece(pos, end)};
+        pieces
+    }
+}
+
+/// Filter or truncate the BED records overlapping assembly gaps
+///
+/// Track and browser lines are copied to the output, blank lines and comments are skipped
+///
+/// # Arguments
+/// `mask`: assembly gaps;
+/// `reader`: input BED stream;
+/// `format`: input BED format;
+/// `writer`: output stream;
+/// `action`: whether the gapped records are discarded or truncated
+///
+/// # Returns
+/// The number of records written
+///
+pub fn mask_gaps<R: BufRead, W: Write>(
+    mask: &GapMask, reader: R, format: usize, writer: &mut W, action: GapAction
+) -> Result<usize, CubiculumError> {
+    let mut written: usize = 0;
+    for line in reader.lines() {
+        let line = line?;
+        if is_bed_header_line(&line) {
+            if is_track_line(&line) {
+                writeln!(writer, "{}", line.trim())?;
+            }
+            continue
+        }
+        let entry = try_parse_bed(&line, format)?;
+        let output = match action {
+            GapAction::Exclude => {
+                if mask.overlaps_gap(&entry) {continue};
+                entry
+            },
+            GapAction::Truncate => {
+                match mask.mask(&entry) {
+                    Some(x) => {x},
+                    None => {continue}
+                }
+            }
+        };
+        writeln!(writer, "{}", to_line(&output, format as u8)?)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test_gap {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+
+    const GAPS: &str = "chr1\t100\t200\nchr1\t150\t250\nchr1\t500\t600\nchr2\t0\t50\n";
+
+    const AGP: &str = "# AGP-version 2.0
+chr1\t1\t100\t1\tW\tctg1\t1\t100\t+
+chr1\t101\t250\t2\tN\t150\tscaffold\tyes\tpaired-ends
+chr1\t251\t500\t3\tW\tctg2\t1\t250\t+
+chr1\t501\t600\t4\tU\t100\tcontig\tno\tna
+";
+
+    #[test]
+    fn gap_parsing() {
+        let mask = GapMask::from_bed(GAPS.as_bytes()).unwrap();
+        assert_eq!(mask.len(), 3);
+        assert_eq!(mask.gaps_in("chr1", 0, 1000), &[(100, 250), (500, 600)]);
+        assert_eq!(mask.gap_bases("chr1", 200, 550), 100);
+        let agp = GapMask::from_agp(AGP.as_bytes()).unwrap();
+        assert_eq!(agp.gaps_in("chr1", 0, 1000), mask.gaps_in("chr1", 0, 1000));
+    }
+
+    #[test]
+    fn gap_overlap_and_masking() {
+        let mask = GapMask::from_bed(GAPS.as_bytes()).unwrap();
+        // the second gap falls into the intron
+        let spliced = try_parse_bed(
+            "chr1\t0\t700\tA\t0\t+\t50\t650\t0\t2\t120,100,\t0,600,", 12
+        ).unwrap();
+        assert!(mask.overlaps_gap(&spliced));
+        let masked = mask.mask(&spliced).unwrap();
+        assert_eq!(
+            to_line(&masked, 12).unwrap(),
+            "chr1\t0\t700\tA\t0\t+\t50\t650\t0\t2\t100,100,\t0,600,"
+        );
+        let intronic = try_parse_bed(
+            "chr1\t250\t700\tB\t0\t+\t250\t700\t0\t2\t50,100,\t0,350,", 12
+        ).unwrap();
+        assert!(!mask.overlaps_gap(&intronic));
+        let plain = try_parse_bed("chr1\t50\t550\tC\t0\t-", 6).unwrap();
+        assert_eq!(to_line(&mask.mask(&plain).unwrap(), 6).unwrap(), "chr1\t50\t500\tC\t0\t-");
+        let gapped = try_parse_bed("chr2\t10\t40\tD\t0\t+", 6).unwrap();
+        assert!(mask.mask(&gapped).is_none());
+    }
+
+    #[test]
+    fn gap_splitting() {
+        let mask = GapMask::from_bed(GAPS.as_bytes()).unwrap();
+        // unnamed records are split as well
+        let plain = try_parse_bed("chr1\t50\t550", 3).unwrap();
+        let pieces: Vec<String> = mask.split(&plain).iter().map(|x| to_line(x, 3).unwrap()).collect();
+        assert_eq!(pieces, vec!["chr1\t50\t100", "chr1\t250\t500"]);
+        let coding = try_parse_bed("chr1\t50\t550\tA\t0\t+\t80\t300", 8).unwrap();
+        let pieces: Vec<String> = mask.split(&coding).iter().map(|x| to_line(x, 8).unwrap()).collect();
+        assert_eq!(pieces, vec!["chr1\t50\t100\tA\t0\t+\t80\t100", "chr1\t250\t500\tA\t0\t+\t250\t300"]);
+        let spliced = try_parse_bed(
+            "chr1\t0\t700\tB\t0\t+\t50\t650\t0\t2\t120,100,\t0,600,", 12
+        ).unwrap();
+        assert_eq!(mask.split(&spliced), vec![mask.mask(&spliced).unwrap()]);
+        let gapped = try_parse_bed("chr2\t10\t40", 3).unwrap();
+        assert!(mask.split(&gapped).is_empty());
+    }
+
+    #[test]
+    fn gap_stream() {
+        let mask = GapMask::from_bed(GAPS.as_bytes()).unwrap();
+        let bed = "track name=test\nchr1\t0\t50\nchr1\t90\t120\nchr2\t10\t20\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(mask_gaps(&mask, bed.as_bytes(), 3, &mut output, GapAction::Exclude).unwrap(), 1);
+        assert_eq!(String::from_utf8(output).unwrap(), "track name=test\nchr1\t0\t50\n");
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(mask_gaps(&mask, bed.as_bytes(), 3, &mut output, GapAction::Truncate).unwrap(), 2);
+        assert_eq!(String::from_utf8(output).unwrap(), "track name=test\nchr1\t0\t50\nchr1\t90\t100\n");
+    }
+}
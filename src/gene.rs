@@ -0,0 +1,5 @@
+/*!
+Module for gene-level operations over transcript annotations
+*/
+
+pub mod gene;
@@ -0,0 +1,1251 @@
+//! # cubiculum::gene
+//! 
+//! Gene-level operations over BED12 transcript annotations
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::io::{BufRead, Write};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, to_line, try_parse_bed};
+use crate::intersect::intersect::{block_overlap_size, index_by_chrom};
+use crate::merge::merge::block_union;
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates, Interval, Named, UtrSide};
+use crate::transform::transform::chrom_end;
+
+/// Defines how transcripts are assigned to genes
+#[derive(Clone, Debug)]
+pub enum GeneSource {
+    /// Explicit transcript-to-gene name mapping
+    Map(FxHashMap<String, String>),
+    /// Gene name is the part of the transcript name preceding the last occurrence of the delimiter
+    /// (e.g., '.' for "GENE.1" or '-' for "GENE-201")
+    Delimiter(char),
+    /// Custom transcript name parser
+    Parser(fn(&str) -> Option<String>)
+}
+
+impl GeneSource {
+    /// Infer the gene name for the transcript; returns None if the transcript cannot be assigned to a gene
+    pub fn gene_of(&self, transcript: &str) -> Option<String> {
+        match self {
+            GeneSource::Map(map) => {map.get(transcript).cloned()},
+            GeneSource::Delimiter(delim) => {
+                transcript.rsplit_once(*delim).map(|x| x.0.to_string())
+            },
+            GeneSource::Parser(parser) => {parser(transcript)}
+        }
+    }
+}
+
+/// Collapse transcripts into gene-level union models
+///
+/// Transcripts are grouped by gene, chromosome and strand, so that identically named genes
+/// on different chromosomes or strands produce separate models. Transcripts not assigned to any gene
+/// by `genes` form single-transcript models named after themselves
+///
+/// # Arguments
+/// `transcripts`: a slice of BED12 (or shorter) entries with defined chromosome, strand and name;
+/// `genes`: GeneSource defining the transcript-to-gene assignment
+///
+/// # Returns
+/// A vector of BED12 entries sorted by chromosome and start coordinate; each entry is named after the gene
+/// and has the union of its transcripts' exons as blocks. The coding region is that of the widest CDS,
+/// i.e., the coding transcript with the largest thickEnd - thickStart span (the first one listed on ties);
+/// genes with no coding transcripts have thickStart and thickEnd set to chromStart.
+/// An error is returned if any of the transcripts lacks a chromosome, strand or name,
+/// or if none of a gene's transcripts has any blocks
+///
+pub fn gene_models(transcripts: &[BedEntry], genes: &GeneSource) -> Result<Vec<BedEntry>, CubiculumError> {
+    let mut groups: FxHashMap<(String, String, bool), Vec<&BedEntry>> = FxHashMap::default();
+    for tr in transcripts {
+        let name = tr.name().ok_or(
+            CubiculumError::MissingTraitError("Transcripts must be named to infer gene models".to_string())
+        )?;
+        let chrom = tr.chrom().ok_or(
+            CubiculumError::MissingTraitError(format!("Transcript {} has undefined chromosome", name))
+        )?;
+        let strand = tr.strand().ok_or(
+            CubiculumError::MissingTraitError(format!("Transcript {} has undefined strand", name))
+        )?;
+        let gene = genes.gene_of(name).unwrap_or(name.clone());
+        groups.entry((gene, chrom.clone(), strand)).or_default().push(tr);
+    }
+    let mut out_vec: Vec<BedEntry> = Vec::with_capacity(groups.len());
+    for ((gene, chrom, strand), members) in groups {
+        let union: Vec<(u64, u64)> = block_union(members.iter().flat_map(|x| x.exons()));
+        let (start, end) = match (union.first(), union.last()) {
+            (Some(first), Some(last)) => {(first.0, last.1)},
+            _ => {
+                return Err(
+                    CubiculumError::CoordinateError(format!("None of the transcripts of gene {} has any blocks", gene))
+                )
+            }
+        };
+        let (thick_start, thick_end) = members
+            .iter()
+            .filter_map(|x| match (x.thick_start(), x.thick_end()) {
+                (Some(s), Some(e)) if s < e => {Some((s, e))},
+                _ => {None}
+            })
+            .reduce(|a, b| if b.1 - b.0 > a.1 - a.0 {b} else {a})
+            .unwrap_or((start, start));
+        let exon_sizes: Vec<u64> = union.iter().map(|x| x.1 - x.0).collect();
+        let exon_starts: Vec<u64> = union.iter().map(|x| x.0 - start).collect();
+        out_vec.push(
+            BedEntry::bed12(
+                chrom, start, end, gene, String::from("0"), strand,
+                thick_start, thick_end, String::from("0"),
+                union.len() as u16, exon_sizes, exon_starts
+            )
+        );
+    }
+    out_vec.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom())
+            .then(a.thin_start().cmp(&b.thin_start()))
+            .then(a.name().cmp(&b.name()))
+    );
+    Ok(out_vec)
+}
+
+/// Records sharing a name or a name-derived key, e.g., transcripts of the same gene
+#[derive(Clone, Debug)]
+pub struct NameGroup<'a> {
+    pub key: String,
+    pub members: Vec<&'a BedEntry>
+}
+
+impl NameGroup<'_> {
+    /// Number of records in the group
+    pub fn count(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Error for groups spread over several chromosomes, for which the coordinate aggregates are undefined
+    fn check_chrom(&self) -> Result<&String, CubiculumError> {
+        let chrom = self.members[0].chrom().ok_or(
+            CubiculumError::MissingTraitError(format!("Records of group {} have undefined chromosome", self.key))
+        )?;
+        if self.members.iter().any(|x| x.chrom() != Some(chrom)) {
+            return Err(CubiculumError::InputError(format!("Records of group {} come from different chromosomes", self.key)))
+        }
+        Ok(chrom)
+    }
+
+    /// Region spanned by the group records, named after the group key
+    pub fn span(&self) -> Result<Interval, CubiculumError> {
+        let chrom = self.check_chrom()?;
+        let start = self.members.iter().filter_map(|x| x.thin_start()).min();
+        let end = self.members.iter().filter_map(|x| x.thin_end()).max();
+        Ok(Interval::from(Some(chrom.clone()), start, end, Some(self.key.clone())))
+    }
+
+    /// Union of the group records' blocks, sorted by coordinates
+    pub fn merged_exons(&self) -> Result<Vec<(u64, u64)>, CubiculumError> {
+        self.check_chrom()?;
+        Ok(block_union(self.members.iter().flat_map(|x| x.exons())))
+    }
+
+    /// Number of bases covered by at least one of the group records' blocks
+    pub fn exonic_bases(&self) -> Result<u64, CubiculumError> {
+        Ok(self.merged_exons()?.iter().map(|x| x.1 - x.0).sum())
+    }
+}
+
+/// Group the records by name; see `group_by_key()`
+pub fn group_by_name(entries: &[BedEntry]) -> Vec<NameGroup<'_>> {
+    group_by_key(entries, |x| Some(x.to_string()))
+}
+
+/// Group the records by a key derived from their names
+///
+/// # Arguments
+/// `entries`: records to group;
+/// `key`: key extraction closure (e.g., stripping the '#gene' suffix); records for which it returns None
+/// are left out, as are the unnamed records
+///
+/// # Returns
+/// The groups in the order of the first appearance of their keys, with the records in their input order
+///
+pub fn group_by_key<F>(entries: &[BedEntry], key: F) -> Vec<NameGroup<'_>>
+where
+    F: Fn(&str) -> Option<String>
+{
+    let mut positions: FxHashMap<String, usize> = FxHashMap::default();
+    let mut groups: Vec<NameGroup<'_>> = Vec::new();
+    for entry in entries {
+        let Some(group_key) = entry.name().and_then(|x| key(x)) else {continue};
+        match positions.get(&group_key) {
+            Some(i) => {groups[*i].members.push(entry)},
+            None => {
+                positions.insert(group_key.clone(), groups.len());
+                groups.push(NameGroup {key: group_key, members: vec![entry]});
+            }
+        }
+    }
+    groups
+}
+
+/// gffcompare-style class codes describing the relation of a query transcript to the reference;
+/// variants are declared in the order of precedence
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ClassCode {
+    /// '=': identical intron chain; for single-exon transcripts, identical boundaries
+    Exact,
+    /// 'c': query is contained in the reference, with its intron chain matching a part of the reference chain
+    Contained,
+    /// 'k': query contains the reference, with the reference intron chain matching a part of the query chain
+    Contains,
+    /// 'j': novel isoform sharing at least one splice junction with the reference
+    NovelIsoform,
+    /// 'o': other same-strand exonic overlap
+    Overlap,
+    /// 'x': exonic overlap with a reference on the opposite strand
+    Antisense,
+    /// 'i': query fully falls within a reference intron
+    Intronic,
+    /// 'u': no reference transcript in the vicinity
+    Intergenic
+}
+
+impl ClassCode {
+    /// Returns the single-character gffcompare code
+    pub fn code(&self) -> char {
+        match self {
+            ClassCode::Exact => {'='},
+            ClassCode::Contained => {'c'},
+            ClassCode::Contains => {'k'},
+            ClassCode::NovelIsoform => {'j'},
+            ClassCode::Overlap => {'o'},
+            ClassCode::Antisense => {'x'},
+            ClassCode::Intronic => {'i'},
+            ClassCode::Intergenic => {'u'}
+        }
+    }
+}
+
+/// Classification of a query transcript against the reference set
+#[derive(Clone, Debug)]
+pub struct Classification<'a> {
+    pub query: &'a BedEntry,
+    pub class: ClassCode,
+    /// The reference transcript the class was assigned against; None for intergenic queries
+    pub reference: Option<&'a BedEntry>
+}
+
+fn is_sublist(short: &[(u64, u64)], long: &[(u64, u64)]) -> bool {
+    short.len() <= long.len() && long.windows(short.len()).any(|x| x == short)
+}
+
+fn within(a: &BedEntry, b: &BedEntry) -> bool {
+    a.thin_start() >= b.thin_start() && a.thin_end() <= b.thin_end()
+}
+
+/// Assess the relation of the query to a single reference transcript sharing the chromosome
+fn compare_transcripts(query: &BedEntry, reference: &BedEntry) -> Option<ClassCode> {
+    let exonic = block_overlap_size(query, reference) > 0;
+    let same_strand = match (query.strand(), reference.strand()) {
+        (Some(x), Some(y)) => {x == y},
+        _ => {true}
+    };
+    if !same_strand {
+        return if exonic {Some(ClassCode::Antisense)} else {None};
+    }
+    if !exonic {
+        let (start, end) = (query.thin_start()?, query.thin_end()?);
+        let intronic = reference.introns().iter().any(|x| x.0 <= start && end <= x.1);
+        return if intronic {Some(ClassCode::Intronic)} else {None};
+    }
+    let q_introns = query.introns();
+    let r_introns = reference.introns();
+    match (q_introns.is_empty(), r_introns.is_empty()) {
+        (false, false) => {
+            if q_introns == r_introns {return Some(ClassCode::Exact)};
+            if within(query, reference) && is_sublist(&q_introns, &r_introns) {
+                return Some(ClassCode::Contained)
+            }
+            if within(reference, query) && is_sublist(&r_introns, &q_introns) {
+                return Some(ClassCode::Contains)
+            }
+            if q_introns.iter().any(|x| r_introns.contains(x)) {
+                return Some(ClassCode::NovelIsoform)
+            }
+            Some(ClassCode::Overlap)
+        },
+        (true, _) => {
+            if r_introns.is_empty()
+                && query.thin_start() == reference.thin_start()
+                && query.thin_end() == reference.thin_end() {
+                return Some(ClassCode::Exact)
+            }
+            let (start, end) = (query.thin_start()?, query.thin_end()?);
+            if reference.exons().any(|x| x.0 <= start && end <= x.1) {
+                return Some(ClassCode::Contained)
+            }
+            if r_introns.is_empty() && within(reference, query) {
+                return Some(ClassCode::Contains)
+            }
+            Some(ClassCode::Overlap)
+        },
+        (false, true) => {Some(ClassCode::Overlap)}
+    }
+}
+
+/// Classify query transcripts against a reference annotation, in the manner of gffcompare
+///
+/// Each query is compared to all the reference transcripts overlapping its span on the same chromosome;
+/// the class of the highest precedence (see ClassCode) is reported, ties being resolved in favour of
+/// the reference with the largest exonic overlap
+///
+/// # Returns
+/// A vector of classifications in the order of the queries
+///
+pub fn classify<'a>(queries: &'a [BedEntry], reference: &'a [BedEntry]) -> Vec<Classification<'a>> {
+    let index = index_by_chrom(reference);
+    let mut out_vec: Vec<Classification<'a>> = Vec::with_capacity(queries.len());
+    for query in queries {
+        let mut best: Option<(ClassCode, u64, &'a BedEntry)> = None;
+        if let (Some(chrom), Some(start), Some(end)) = (query.chrom(), query.thin_start(), query.thin_end()) {
+            if let Some(chrom_index) = index.get(chrom) {
+                for i in chrom_index.overlapping(reference, start, end) {
+                    let r = &reference[i];
+                    let class = match compare_transcripts(query, r) {
+                        Some(x) => {x},
+                        None => {continue}
+                    };
+                    let shared = block_overlap_size(query, r);
+                    let better = match best {
+                        Some((c, s, _)) => {class < c || (class == c && shared > s)},
+                        None => {true}
+                    };
+                    if better {best = Some((class, shared, r))};
+                }
+            }
+        }
+        out_vec.push(
+            match best {
+                Some((class, _, r)) => {Classification {query, class, reference: Some(r)}},
+                None => {Classification {query, class: ClassCode::Intergenic, reference: None}}
+            }
+        );
+    }
+    out_vec
+}
+
+/// Defines which transcript termini are reported by terminal_sites()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TerminalSite {
+    Tss,
+    Tes,
+    /// Both sites, reported as `{name}_TSS` and `{name}_TES`
+    Both
+}
+
+/// Write the transcription start and/or end sites of all the entries in the BED stream as BED6 lines
+///
+/// Blank lines, comments and track/browser lines are skipped. Each site inherits the name,
+/// score and strand of its entry
+///
+/// # Returns
+/// The number of the sites written; an error if a line cannot be parsed,
+/// an entry has no defined strand, or the output cannot be written
+///
+pub fn terminal_sites<R, W>(reader: R, writer: &mut W, format: usize, site: TerminalSite) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    let mut written: usize = 0;
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, format)?;
+        let strand = entry.strand().ok_or(
+            CubiculumError::MissingTraitError(format!("Cannot infer terminal sites for an unstranded entry: {}", line))
+        )?;
+        let name = entry.name().cloned().unwrap_or(String::from("."));
+        let score = entry.score().cloned().unwrap_or(String::from("0"));
+        let sites: Vec<(Option<Interval>, String)> = match site {
+            TerminalSite::Tss => {vec![(entry.tss(), name)]},
+            TerminalSite::Tes => {vec![(entry.tes(), name)]},
+            TerminalSite::Both => {
+                vec![(entry.tss(), format!("{}_TSS", name)), (entry.tes(), format!("{}_TES", name))]
+            }
+        };
+        for (interval, site_name) in sites {
+            let interval = match interval {
+                Some(x) => {x},
+                None => {continue}
+            };
+            let bed6 = BedEntry::bed6(
+                interval.chrom().unwrap().clone(), *interval.start().unwrap(), *interval.end().unwrap(),
+                site_name, score.clone(), strand
+            );
+            writeln!(writer, "{}", to_line(&bed6, 6)?)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Create the promoter interval around the transcription start site of the entry
+///
+/// # Arguments
+/// `entry`: the source transcript;
+/// `upstream`: number of bases upstream of the TSS to include;
+/// `downstream`: number of bases downstream of the TSS to include, the TSS itself included;
+/// `chrom_sizes`: optional ChromSizes object to clip the promoter at the chromosome end
+///
+/// # Returns
+/// A promoter interval named after the entry; None if the strand or coordinates are undefined
+/// or the clipped interval is empty
+///
+pub fn promoter(entry: &BedEntry, upstream: u64, downstream: u64, chrom_sizes: Option<&ChromSizes>) -> Option<Interval> {
+    let tss = entry.tss()?;
+    let pos = *tss.start()?;
+    let (start, end) = if entry.strand()? {
+        (pos.saturating_sub(upstream), pos.saturating_add(downstream))
+    } else {
+        ((pos + 1).saturating_sub(downstream), (pos + 1).saturating_add(upstream))
+    };
+    let end = end.min(chrom_end(entry.chrom(), chrom_sizes));
+    if start >= end {return None};
+    Some(Interval::from(entry.chrom().cloned(), Some(start), Some(end), entry.name().cloned()))
+}
+
+/// Create promoter intervals for all the entries, optionally merging the overlapping promoters
+/// of the isoforms of the same gene
+///
+/// # Arguments
+/// `entries`: a slice of transcripts;
+/// `upstream`, `downstream`, `chrom_sizes`: see promoter();
+/// `genes`: if provided, overlapping promoters of transcripts of the same gene, chromosome and strand
+/// are merged and named after the gene
+///
+/// # Returns
+/// A vector of promoters in the input order, or sorted by chromosome and coordinates if merged
+///
+pub fn promoters(
+    entries: &[BedEntry], upstream: u64, downstream: u64,
+    chrom_sizes: Option<&ChromSizes>, genes: Option<&GeneSource>
+) -> Vec<Interval> {
+    let genes = match genes {
+        Some(x) => {x},
+        None => {
+            return entries.iter().filter_map(|x| promoter(x, upstream, downstream, chrom_sizes)).collect()
+        }
+    };
+    let mut groups: FxHashMap<(String, String, bool), Vec<(u64, u64)>> = FxHashMap::default();
+    for entry in entries {
+        let prom = match promoter(entry, upstream, downstream, chrom_sizes) {
+            Some(x) => {x},
+            None => {continue}
+        };
+        let name = match entry.name() {
+            Some(x) => {genes.gene_of(x).unwrap_or(x.clone())},
+            None => {String::from(".")}
+        };
+        groups.entry((name, prom.chrom().unwrap().clone(), entry.strand().unwrap()))
+            .or_default()
+            .push((*prom.start().unwrap(), *prom.end().unwrap()));
+    }
+    let mut out_vec: Vec<Interval> = Vec::new();
+    for ((gene, chrom, _), bounds) in groups {
+        for (start, end) in block_union(bounds) {
+            out_vec.push(Interval::from(Some(chrom.clone()), Some(start), Some(end), Some(gene.clone())));
+        }
+    }
+    out_vec.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom())
+            .then(a.start().cmp(&b.start()))
+            .then(a.end().cmp(&b.end()))
+    );
+    out_vec
+}
+
+/// Genomic feature classes assigned by annotate_peaks()
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FeatureClass {
+    Promoter,
+    Utr5,
+    Cds,
+    /// Exon of a non-coding transcript
+    Exon,
+    Intron,
+    Utr3,
+    /// Region downstream of the transcription end site
+    Downstream,
+    Intergenic
+}
+
+/// Parameters of the peak-to-feature classification
+#[derive(Clone, Debug)]
+pub struct PeakAnnotationOptions {
+    /// Promoter length upstream of the TSS
+    pub promoter_upstream: u64,
+    /// Promoter length downstream of the TSS
+    pub promoter_downstream: u64,
+    /// Length of the downstream region following the TES
+    pub downstream: u64,
+    /// Feature classes in the order of precedence; classes missing from the list are never reported
+    pub precedence: Vec<FeatureClass>
+}
+
+impl Default for PeakAnnotationOptions {
+    fn default() -> PeakAnnotationOptions {
+        PeakAnnotationOptions {
+            promoter_upstream: 1000,
+            promoter_downstream: 100,
+            downstream: 3000,
+            precedence: vec![
+                FeatureClass::Promoter,
+                FeatureClass::Utr5,
+                FeatureClass::Utr3,
+                FeatureClass::Cds,
+                FeatureClass::Exon,
+                FeatureClass::Intron,
+                FeatureClass::Downstream
+            ]
+        }
+    }
+}
+
+/// Result of the peak classification
+#[derive(Clone, Debug)]
+pub struct PeakAnnotation<'a, A> {
+    pub interval: &'a A,
+    pub class: FeatureClass,
+    /// Transcript the class was assigned from; None for intergenic intervals
+    pub transcript: Option<&'a BedEntry>,
+    /// Transcript with the TSS closest to the interval on the same chromosome
+    pub nearest: Option<&'a BedEntry>,
+    /// Distance from the interval to the nearest TSS; zero if the interval contains the TSS
+    pub distance: Option<u64>
+}
+
+fn touches(blocks: &[(u64, u64)], start: u64, end: u64) -> bool {
+    blocks.iter().any(|x| x.0 < end && start < x.1)
+}
+
+/// Collect the feature classes of the transcript overlapped by the interval
+fn transcript_features(
+    tr: &BedEntry, start: u64, end: u64, options: &PeakAnnotationOptions
+) -> Vec<FeatureClass> {
+    let mut classes: Vec<FeatureClass> = Vec::new();
+    let (strand, tr_start, tr_end) = match (tr.strand(), tr.thin_start(), tr.thin_end()) {
+        (Some(x), Some(y), Some(z)) => {(x, y, z)},
+        _ => {return classes}
+    };
+    if let Some(prom) = promoter(tr, options.promoter_upstream, options.promoter_downstream, None) {
+        if touches(&[(*prom.start().unwrap(), *prom.end().unwrap())], start, end) {
+            classes.push(FeatureClass::Promoter)
+        }
+    }
+    let exons: Vec<(u64, u64)> = tr.exons().collect();
+    match tr.cds_blocks() {
+        Some(cds) => {
+            let (thick_start, thick_end) = (tr.thick_start().unwrap(), tr.thick_end().unwrap());
+            let (upstream, downstream) = if strand {
+                (FeatureClass::Utr5, FeatureClass::Utr3)
+            } else {
+                (FeatureClass::Utr3, FeatureClass::Utr5)
+            };
+            let left: Vec<(u64, u64)> = exons.iter()
+                .filter(|x| x.0 < thick_start)
+                .map(|x| (x.0, x.1.min(thick_start)))
+                .collect();
+            let right: Vec<(u64, u64)> = exons.iter()
+                .filter(|x| x.1 > thick_end)
+                .map(|x| (x.0.max(thick_end), x.1))
+                .collect();
+            if touches(&left, start, end) {classes.push(upstream)};
+            if touches(&right, start, end) {classes.push(downstream)};
+            if touches(&cds, start, end) {classes.push(FeatureClass::Cds)};
+        },
+        None => {
+            if touches(&exons, start, end) {classes.push(FeatureClass::Exon)};
+        }
+    }
+    if touches(&tr.introns(), start, end) {classes.push(FeatureClass::Intron)};
+    let downstream = if strand {
+        (tr_end, tr_end.saturating_add(options.downstream))
+    } else {
+        (tr_start.saturating_sub(options.downstream), tr_start)
+    };
+    if touches(&[downstream], start, end) {classes.push(FeatureClass::Downstream)};
+    classes
+}
+
+/// Per-chromosome transcription start sites sorted by position, paired with the transcript indices
+pub(crate) fn tss_by_chrom(transcripts: &[BedEntry]) -> FxHashMap<&String, Vec<(u64, usize)>> {
+    let mut sites: FxHashMap<&String, Vec<(u64, usize)>> = FxHashMap::default();
+    for (i, tr) in transcripts.iter().enumerate() {
+        if let (Some(chrom), Some(tss)) = (tr.chrom(), tr.tss()) {
+            sites.entry(chrom).or_default().push((*tss.start().unwrap(), i));
+        }
+    }
+    for x in sites.values_mut() {
+        x.sort();
+    }
+    sites
+}
+
+/// Unsigned distance between a single-base site and an interval; zero if the interval contains the site
+fn tss_distance(pos: u64, start: u64, end: u64) -> u64 {
+    if pos < start {start - pos} else if pos >= end {pos + 1 - end} else {0}
+}
+
+/// Find the TSS closest to the interval among the sorted sites; returns the transcript index and the distance
+pub(crate) fn nearest_tss(sites: &[(u64, usize)], start: u64, end: u64) -> Option<(usize, u64)> {
+    let distance = |pos: u64| tss_distance(pos, start, end);
+    let split = sites.partition_point(|x| x.0 < start);
+    let before = split.checked_sub(1).map(|i| sites[i]);
+    let after = sites[split..].first().copied();
+    match (before, after) {
+        (Some(x), Some(y)) => {
+            if distance(y.0) < distance(x.0) {Some((y.1, distance(y.0)))} else {Some((x.1, distance(x.0)))}
+        },
+        (Some(x), None) | (None, Some(x)) => {Some((x.1, distance(x.0)))},
+        (None, None) => {None}
+    }
+}
+
+/// Classify arbitrary intervals (e.g., ChIP-seq peaks) by the genomic features of the transcripts they overlap
+///
+/// For each interval, the feature classes of all the overlapped transcripts are collected and the one
+/// with the highest precedence in `options.precedence` is reported; intervals overlapping no feature
+/// are reported as intergenic. Features are assessed regardless of the interval strand
+///
+/// # Returns
+/// A vector of annotations in the order of the intervals, each also containing the transcript
+/// with the closest TSS on the same chromosome
+///
+pub fn annotate_peaks<'a, A>(
+    intervals: &'a [A], transcripts: &'a [BedEntry], options: &PeakAnnotationOptions
+) -> Vec<PeakAnnotation<'a, A>>
+where
+    A: Coordinates
+{
+    let index = index_by_chrom(transcripts);
+    let sites = tss_by_chrom(transcripts);
+    let flank = options.promoter_upstream.max(options.promoter_downstream).max(options.downstream);
+    let mut out_vec: Vec<PeakAnnotation<'a, A>> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        let mut best: Option<(usize, &'a BedEntry)> = None;
+        let mut nearest: Option<(usize, u64)> = None;
+        if let (Some(chrom), Some(start), Some(end)) = (interval.chrom(), interval.start(), interval.end()) {
+            if let Some(chrom_index) = index.get(chrom) {
+                let hits = chrom_index.overlapping(
+                    transcripts, start.saturating_sub(flank), end.saturating_add(flank)
+                );
+                for i in hits {
+                    for class in transcript_features(&transcripts[i], *start, *end, options) {
+                        let rank = match options.precedence.iter().position(|x| *x == class) {
+                            Some(x) => {x},
+                            None => {continue}
+                        };
+                        if best.map(|x| rank < x.0).unwrap_or(true) {
+                            best = Some((rank, &transcripts[i]))
+                        }
+                    }
+                }
+            }
+            nearest = sites.get(chrom).and_then(|x| nearest_tss(x, *start, *end));
+        }
+        out_vec.push(
+            PeakAnnotation {
+                interval,
+                class: best.map(|x| options.precedence[x.0]).unwrap_or(FeatureClass::Intergenic),
+                transcript: best.map(|x| x.1),
+                nearest: nearest.map(|x| &transcripts[x.0]),
+                distance: nearest.map(|x| x.1)
+            }
+        );
+    }
+    out_vec
+}
+
+/// Transcript assigned to a query by nearest_genes()
+#[derive(Clone, Debug)]
+pub struct NearestGene<'a> {
+    pub transcript: &'a BedEntry,
+    /// Gene name inferred with GeneSource; transcript name if no source was provided or the gene is unknown
+    pub gene: Option<String>,
+    /// Signed distance to the TSS in the transcript orientation: negative if the query lies upstream of the TSS,
+    /// positive if downstream, zero if the query contains the TSS
+    pub distance: i64
+}
+
+/// Assign each query interval the transcripts with the closest TSS on the same chromosome
+///
+/// # Arguments
+/// `queries`: a slice of query intervals;
+/// `transcripts`: a slice of stranded transcripts;
+/// `max_distance`: if provided, transcripts with TSS further than this from the query are not reported;
+/// `genes`: if provided, equally near transcripts of the same gene are reported once
+///
+/// # Returns
+/// A vector with one item per query, containing all the equally near transcripts
+/// (in ascending TSS coordinate order); empty if no TSS passes the distance cutoff
+///
+pub fn nearest_genes<'a, A>(
+    queries: &[A], transcripts: &'a [BedEntry], max_distance: Option<u64>, genes: Option<&GeneSource>
+) -> Vec<Vec<NearestGene<'a>>>
+where
+    A: Coordinates
+{
+    let sites = tss_by_chrom(transcripts);
+    let mut out_vec: Vec<Vec<NearestGene<'a>>> = Vec::with_capacity(queries.len());
+    for query in queries {
+        let mut hits: Vec<NearestGene<'a>> = Vec::new();
+        let (chrom, start, end) = match (query.chrom(), query.start(), query.end()) {
+            (Some(c), Some(s), Some(e)) => {(c, *s, *e)},
+            _ => {out_vec.push(hits); continue}
+        };
+        let chrom_sites: &[(u64, usize)] = match sites.get(chrom) {
+            Some(x) => {x},
+            None => {out_vec.push(hits); continue}
+        };
+        let min_distance = match nearest_tss(chrom_sites, start, end) {
+            Some((_, d)) if max_distance.map(|x| d <= x).unwrap_or(true) => {d},
+            _ => {out_vec.push(hits); continue}
+        };
+        let split = chrom_sites.partition_point(|x| x.0 < start);
+        let left = chrom_sites[..split].iter().rev()
+            .take_while(|x| tss_distance(x.0, start, end) == min_distance);
+        let right = chrom_sites[split..].iter()
+            .take_while(|x| tss_distance(x.0, start, end) <= min_distance)
+            .filter(|x| tss_distance(x.0, start, end) == min_distance);
+        let mut tied: Vec<(u64, usize)> = left.chain(right).copied().collect();
+        tied.sort();
+        let mut seen: Vec<String> = Vec::new();
+        for (pos, i) in tied {
+            let tr = &transcripts[i];
+            let gene = tr.name().map(|x| {
+                genes.and_then(|g| g.gene_of(x)).unwrap_or(x.clone())
+            });
+            if genes.is_some() {
+                if let Some(g) = &gene {
+                    if seen.contains(g) {continue};
+                    seen.push(g.clone());
+                }
+            }
+            let downstream = if tr.strand().unwrap() {start > pos} else {end <= pos};
+            let distance = if downstream {min_distance as i64} else {-(min_distance as i64)};
+            hits.push(NearestGene {transcript: tr, gene, distance});
+        }
+        out_vec.push(hits);
+    }
+    out_vec
+}
+
+/// Relation between a nested entry and its host, as reported by nested_entries()
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NestingKind {
+    /// The nested entry lies within a single intron of the host
+    Intronic,
+    /// The nested entry spans over host exons but shares no exonic bases with the host
+    Spanning,
+    /// The nested entry shares exonic bases with the host
+    Exonic
+}
+
+/// Entry lying fully within the span of another entry
+#[derive(Clone, Debug)]
+pub struct NestedEntry<'a> {
+    pub inner: &'a BedEntry,
+    pub outer: &'a BedEntry,
+    pub kind: NestingKind,
+    pub same_strand: bool
+}
+
+/// Report the entries fully contained within the span of other entries (nested genes, intronic pseudogenes, etc.)
+///
+/// Entries with identical spans are not considered nested within each other
+///
+/// # Returns
+/// A vector of inner-outer pairs sorted by the inner entry index, then by the outer entry index
+///
+pub fn nested_entries(entries: &[BedEntry]) -> Vec<NestedEntry<'_>> {
+    let index = index_by_chrom(entries);
+    let mut out_vec: Vec<NestedEntry<'_>> = Vec::new();
+    for (i, inner) in entries.iter().enumerate() {
+        let (chrom, start, end) = match (inner.chrom(), inner.thin_start(), inner.thin_end()) {
+            (Some(c), Some(s), Some(e)) => {(c, s, e)},
+            _ => {continue}
+        };
+        let chrom_index = match index.get(chrom) {
+            Some(x) => {x},
+            None => {continue}
+        };
+        let mut hosts: Vec<usize> = chrom_index.overlapping(entries, start, end)
+            .filter(|j| *j != i)
+            .filter(|j| {
+                let outer = &entries[*j];
+                within(inner, outer) && (outer.thin_start(), outer.thin_end()) != (Some(start), Some(end))
+            })
+            .collect();
+        hosts.sort();
+        for j in hosts {
+            let outer = &entries[j];
+            let kind = if outer.introns().iter().any(|x| x.0 <= start && end <= x.1) {
+                NestingKind::Intronic
+            } else if block_overlap_size(inner, outer) == 0 {
+                NestingKind::Spanning
+            } else {
+                NestingKind::Exonic
+            };
+            let same_strand = inner.strand() == outer.strand();
+            out_vec.push(NestedEntry {inner, outer, kind, same_strand});
+        }
+    }
+    out_vec
+}
+
+/// Effect of the exon skipping on the coding sequence
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FrameConsequence {
+    /// The transcript is non-coding
+    NonCoding,
+    /// Only untranslated bases were removed
+    Untranslated,
+    /// The removed coding sequence length is a multiple of three
+    InFrame,
+    /// The removed coding sequence length is not a multiple of three
+    Frameshift,
+    /// A removed exon contains the start or the end of the coding sequence
+    CdsBoundary
+}
+
+/// Alternative isoform produced by skip_exons()
+#[derive(Clone, Debug)]
+pub struct SkippedIsoform {
+    pub entry: BedEntry,
+    /// Indices of the skipped blocks, in ascending coordinate order
+    pub skipped: Vec<usize>,
+    pub consequence: FrameConsequence
+}
+
+fn skipping_consequence(entry: &BedEntry, skipped: &[usize]) -> FrameConsequence {
+    let (thick_start, thick_end) = match (entry.thick_start(), entry.thick_end()) {
+        (Some(x), Some(y)) if x < y => {(x, y)},
+        _ => {return FrameConsequence::NonCoding}
+    };
+    let blocks: Vec<(u64, u64)> = entry.exons().collect();
+    let mut removed: u64 = 0;
+    for i in skipped {
+        let (start, end) = blocks[*i];
+        let coding_start = start.max(thick_start);
+        let coding_end = end.min(thick_end);
+        if coding_start >= coding_end {continue};
+        if start <= thick_start || end >= thick_end {
+            return FrameConsequence::CdsBoundary
+        }
+        removed += coding_end - coding_start;
+    }
+    match removed {
+        0 => {FrameConsequence::Untranslated},
+        x if x % 3 == 0 => {FrameConsequence::InFrame},
+        _ => {FrameConsequence::Frameshift}
+    }
+}
+
+/// Generate exon-skipping variants of a BED12 entry
+///
+/// # Arguments
+/// `entry`: source BED12 transcript;
+/// `subsets`: sets of block indices (in ascending coordinate order) to skip simultaneously;
+/// if None, each internal exon is skipped individually
+///
+/// # Returns
+/// A vector of variants named `{name}_skip{i}[_{j}...]`, each annotated with the frame consequence;
+/// an error if the entry is not in BED12 format or a subset refers to missing blocks or contains all of them
+///
+pub fn skip_exons(entry: &BedEntry, subsets: Option<&[Vec<usize>]>) -> Result<Vec<SkippedIsoform>, CubiculumError> {
+    let block_num = entry.exons().len();
+    let default_subsets: Vec<Vec<usize>>;
+    let subsets: &[Vec<usize>] = match subsets {
+        Some(x) => {x},
+        None => {
+            default_subsets = (1..block_num.saturating_sub(1)).map(|i| vec![i]).collect();
+            &default_subsets
+        }
+    };
+    let name = entry.name().cloned().unwrap_or(String::from("."));
+    let mut out_vec: Vec<SkippedIsoform> = Vec::with_capacity(subsets.len());
+    for subset in subsets {
+        let mut skipped = subset.clone();
+        skipped.sort();
+        skipped.dedup();
+        if skipped.len() >= block_num {
+            return Err(CubiculumError::InputError(format!("Cannot skip all the blocks of {}", name)))
+        }
+        let mut variant = entry.clone();
+        for i in skipped.iter().rev() {
+            variant.remove_exon(*i)?;
+        }
+        let suffix: Vec<String> = skipped.iter().map(|x| x.to_string()).collect();
+        variant.update_name(&format!("{}_skip{}", name, suffix.join("_")));
+        let consequence = skipping_consequence(entry, &skipped);
+        out_vec.push(SkippedIsoform {entry: variant, skipped, consequence});
+    }
+    Ok(out_vec)
+}
+
+/// Structural metrics of a single BED12 transcript
+#[derive(Clone, Debug, PartialEq)]
+pub struct TranscriptMetrics {
+    pub name: String,
+    pub exon_count: usize,
+    /// Total length of the exonic blocks
+    pub mature_length: u64,
+    pub cds_length: u64,
+    pub utr5_length: u64,
+    pub utr3_length: u64,
+    /// Number of introns; book-ended blocks are not separated by an intron
+    pub intron_count: usize,
+    /// Mean intron length; None for transcripts with no introns
+    pub mean_intron_length: Option<f64>
+}
+
+impl TranscriptMetrics {
+    /// Compute the metrics for a BED12 transcript; an error if the entry is not in BED12 format
+    /// or has no defined strand or coding boundaries
+    pub fn from_entry(entry: &BedEntry) -> Result<TranscriptMetrics, CubiculumError> {
+        if entry.format() != 12 {
+            return Err(CubiculumError::FormattingError("Transcript metrics are defined for BED12 entries only".to_string()))
+        }
+        let name = entry.name().cloned().unwrap_or(String::from("."));
+        let utrs = entry.utr_blocks().ok_or(
+            CubiculumError::MissingTraitError(format!("Cannot infer UTR sides for {}: undefined strand or CDS", name))
+        )?;
+        let utr_length = |side: UtrSide| -> u64 {
+            utrs.iter()
+                .filter(|x| x.side() == Some(&side))
+                .map(|x| x.end().unwrap() - x.start().unwrap())
+                .sum()
+        };
+        let introns = entry.introns();
+        let intron_length: u64 = introns.iter().map(|x| x.1 - x.0).sum();
+        Ok(
+            TranscriptMetrics {
+                exon_count: entry.exons().len(),
+                mature_length: entry.block_length(),
+                cds_length: entry.cds_blocks().map_or(0, |x| x.iter().map(|y| y.1 - y.0).sum()),
+                utr5_length: utr_length(UtrSide::FivePrime),
+                utr3_length: utr_length(UtrSide::ThreePrime),
+                intron_count: introns.len(),
+                mean_intron_length: if introns.is_empty() {None} else {Some(intron_length as f64 / introns.len() as f64)},
+                name
+            }
+        )
+    }
+}
+
+/// Aggregated metrics over a transcript collection
+///
+/// CDS and UTR lengths are averaged over coding transcripts only, and the mean intron length
+/// is computed over all introns of the collection. Means are zero if there is nothing to average
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsReport {
+    pub transcripts: usize,
+    pub coding: usize,
+    pub single_exon: usize,
+    pub mean_exon_count: f64,
+    pub mean_mature_length: f64,
+    pub mean_cds_length: f64,
+    pub mean_utr5_length: f64,
+    pub mean_utr3_length: f64,
+    pub mean_intron_length: f64
+}
+
+impl MetricsReport {
+    pub fn from_metrics(metrics: &[TranscriptMetrics]) -> MetricsReport {
+        let mean = |total: f64, count: usize| if count == 0 {0.0} else {total / count as f64};
+        let coding: Vec<&TranscriptMetrics> = metrics.iter().filter(|x| x.cds_length > 0).collect();
+        let intron_count: usize = metrics.iter().map(|x| x.intron_count).sum();
+        let intron_length: f64 = metrics.iter()
+            .filter_map(|x| x.mean_intron_length.map(|y| y * x.intron_count as f64))
+            .sum();
+        MetricsReport {
+            transcripts: metrics.len(),
+            coding: coding.len(),
+            single_exon: metrics.iter().filter(|x| x.exon_count == 1).count(),
+            mean_exon_count: mean(metrics.iter().map(|x| x.exon_count as f64).sum(), metrics.len()),
+            mean_mature_length: mean(metrics.iter().map(|x| x.mature_length as f64).sum(), metrics.len()),
+            mean_cds_length: mean(coding.iter().map(|x| x.cds_length as f64).sum(), coding.len()),
+            mean_utr5_length: mean(coding.iter().map(|x| x.utr5_length as f64).sum(), coding.len()),
+            mean_utr3_length: mean(coding.iter().map(|x| x.utr3_length as f64).sum(), coding.len()),
+            mean_intron_length: mean(intron_length, intron_count)
+        }
+    }
+}
+
+/// Compute per-transcript metrics and the aggregated report for all BED12 transcripts in the stream
+///
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// Per-transcript metrics in the order of the input and the aggregated report;
+/// an error if a line cannot be parsed as BED12 or a transcript has no defined strand
+///
+pub fn transcript_metrics<R: BufRead>(reader: R) -> Result<(Vec<TranscriptMetrics>, MetricsReport), CubiculumError> {
+    let mut metrics: Vec<TranscriptMetrics> = Vec::new();
+    for line in bed_lines(reader) {
+        let line = line?;
+        let entry = try_parse_bed(&line, 12)?;
+        metrics.push(TranscriptMetrics::from_entry(&entry)?);
+    }
+    let report = MetricsReport::from_metrics(&metrics);
+    Ok((metrics, report))
+}
+
+#[cfg(test)]
+mod test_gene {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+
+    fn bed(line: &str) -> BedEntry {
+        try_parse_bed(line, 12).unwrap()
+    }
+
+    #[test]
+    fn union_models() {
+        let transcripts = vec![
+            bed("chr1\t100\t600\tG1.1\t0\t+\t150\t550\t0\t3\t100,100,100,\t0,200,400,"),
+            bed("chr1\t100\t700\tG1.2\t0\t+\t120\t500\t0\t3\t100,150,100,\t0,250,500,"),
+            bed("chr1\t1000\t1200\tG2.1\t0\t-\t1000\t1000\t0\t1\t200,\t0,"),
+            bed("chr2\t0\t100\tlonely\t0\t+\t0\t0\t0\t1\t100,\t0,"),
+        ];
+        let models = gene_models(&transcripts, &GeneSource::Delimiter('.')).unwrap();
+        assert_eq!(models.len(), 3);
+        let g1 = &models[0];
+        assert_eq!(g1.name().unwrap(), "G1");
+        assert_eq!(g1.exons().collect::<Vec<(u64, u64)>>(), vec![(100, 200), (300, 700)]);
+        // the CDS of G1.1 (150-550) is wider than that of G1.2 (120-500)
+        assert_eq!((g1.thick_start(), g1.thick_end()), (Some(150), Some(550)));
+        assert_eq!((models[1].thick_start(), models[1].thick_end()), (Some(1000), Some(1000)));
+        assert_eq!(models[2].name().unwrap(), "lonely");
+
+        let mut map: FxHashMap<String, String> = FxHashMap::default();
+        map.insert(String::from("G1.1"), String::from("A"));
+        map.insert(String::from("G2.1"), String::from("A"));
+        let models = gene_models(&transcripts, &GeneSource::Map(map)).unwrap();
+        // same gene name on different strands yields separate models
+        assert_eq!(models.iter().filter(|x| x.name().unwrap() == "A").count(), 2);
+
+        let blockless = vec![bed("chr3\t0\t100\tG3.1\t0\t+\t0\t0\t0\t0\t,\t,")];
+        assert!(matches!(
+            gene_models(&blockless, &GeneSource::Delimiter('.')), Err(CubiculumError::CoordinateError(_))
+        ));
+    }
+
+    #[test]
+    fn name_groups() {
+        let entries = vec![
+            bed("chr1\t100\t600\tT1#G1\t0\t+\t150\t550\t0\t3\t100,100,100,\t0,200,400,"),
+            bed("chr1\t1000\t1200\tT2#G2\t0\t-\t1000\t1000\t0\t1\t200,\t0,"),
+            bed("chr1\t100\t700\tT3#G1\t0\t+\t120\t500\t0\t3\t100,150,100,\t0,250,500,"),
+            bed("chr2\t0\t100\tT4#G2\t0\t+\t0\t0\t0\t1\t100,\t0,"),
+        ];
+        assert_eq!(group_by_name(&entries).len(), 4);
+        let groups = group_by_key(&entries, |x| x.split_once('#').map(|y| y.1.to_string()));
+        assert_eq!(groups.iter().map(|x| (x.key.as_str(), x.count())).collect::<Vec<_>>(), vec![("G1", 2), ("G2", 2)]);
+        let span = groups[0].span().unwrap();
+        assert_eq!((span.start(), span.end(), span.name()), (Some(&100), Some(&700), Some("G1")));
+        assert_eq!(groups[0].merged_exons().unwrap(), vec![(100, 200), (300, 700)]);
+        assert_eq!(groups[0].exonic_bases().unwrap(), 500);
+        // G2 transcripts lie on different chromosomes
+        assert!(groups[1].span().is_err());
+        assert!(groups[1].exonic_bases().is_err());
+        let filtered = group_by_key(&entries, |x| x.strip_suffix("#G2").map(|y| y.to_string()));
+        assert_eq!(filtered.iter().map(|x| x.key.as_str()).collect::<Vec<&str>>(), vec!["T2", "T4"]);
+    }
+
+    #[test]
+    fn class_codes() {
+        let reference = vec![
+            bed("chr1\t100\t700\tref1\t0\t+\t100\t700\t0\t3\t100,100,100,\t0,250,500,"),
+            bed("chr1\t2000\t2500\tref2\t0\t-\t2000\t2500\t0\t1\t500,\t0,"),
+        ];
+        let queries = vec![
+            bed("chr1\t120\t680\texact\t0\t+\t120\t680\t0\t3\t80,100,80,\t0,230,480,"),
+            bed("chr1\t300\t680\tcontained\t0\t+\t300\t680\t0\t2\t150,80,\t0,300,"),
+            bed("chr1\t100\t580\tnovel\t0\t+\t100\t580\t0\t3\t100,100,60,\t0,250,420,"),
+            bed("chr1\t210\t330\tintronic\t0\t+\t210\t330\t0\t1\t120,\t0,"),
+            bed("chr1\t2100\t2200\tantisense\t0\t+\t2100\t2200\t0\t1\t100,\t0,"),
+            bed("chr2\t100\t200\tintergenic\t0\t+\t100\t200\t0\t1\t100,\t0,"),
+            bed("chr1\t150\t720\toverlap\t0\t+\t150\t720\t0\t2\t100,70,\t0,500,"),
+        ];
+        let codes: String = classify(&queries, &reference).iter().map(|x| x.class.code()).collect();
+        assert_eq!(codes, "=cjixuo");
+        assert_eq!(classify(&queries, &reference)[0].reference.unwrap().name().unwrap(), "ref1");
+
+        // a single-exon query spanning a spliced reference is not an exact match
+        let unspliced = vec![bed("chr1\t100\t700\tunspliced\t0\t+\t100\t700\t0\t1\t600,\t0,")];
+        assert_eq!(classify(&unspliced, &reference[..1])[0].class, ClassCode::Overlap);
+    }
+
+    #[test]
+    fn tss_tes_sites() {
+        let plus = bed("chr1\t100\t600\ttr1\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,");
+        let minus = bed("chr1\t100\t600\ttr2\t0\t-\t150\t550\t0\t2\t100,100,\t0,400,");
+        assert_eq!(plus.tss().map(|x| (*x.start().unwrap(), *x.end().unwrap())), Some((100, 101)));
+        assert_eq!(minus.tss().map(|x| (*x.start().unwrap(), *x.end().unwrap())), Some((599, 600)));
+        assert_eq!(minus.tes().map(|x| (*x.start().unwrap(), *x.end().unwrap())), Some((100, 101)));
+
+        let input = "track name=test\nchr1\t100\t600\ttr1\t0\t+\nchr1\t100\t600\ttr2\t5\t-\n";
+        let mut out: Vec<u8> = Vec::new();
+        let n = terminal_sites(input.as_bytes(), &mut out, 6, TerminalSite::Both).unwrap();
+        assert_eq!(n, 4);
+        let lines: Vec<String> = String::from_utf8(out).unwrap().lines().map(|x| x.to_string()).collect();
+        assert_eq!(lines[0], "chr1\t100\t101\ttr1_TSS\t0\t+");
+        assert_eq!(lines[2], "chr1\t599\t600\ttr2_TSS\t5\t-");
+    }
+
+    #[test]
+    fn promoter_regions() {
+        let mut sizes = ChromSizes::new();
+        sizes.insert(String::from("chr1"), 1000);
+        let entries = vec![
+            bed("chr1\t500\t900\tG1.1\t0\t+\t500\t900\t0\t1\t400,\t0,"),
+            bed("chr1\t550\t900\tG1.2\t0\t+\t550\t900\t0\t1\t350,\t0,"),
+            bed("chr1\t100\t900\tG2.1\t0\t-\t100\t900\t0\t1\t800,\t0,"),
+        ];
+        let plus = promoter(&entries[0], 100, 10, Some(&sizes)).unwrap();
+        assert_eq!((*plus.start().unwrap(), *plus.end().unwrap()), (400, 510));
+        let minus = promoter(&entries[2], 200, 10, Some(&sizes)).unwrap();
+        assert_eq!((*minus.start().unwrap(), *minus.end().unwrap()), (890, 1000));
+        assert_eq!(promoters(&entries, 100, 10, None, None).len(), 3);
+        let merged = promoters(&entries, 100, 10, Some(&sizes), Some(&GeneSource::Delimiter('.')));
+        let bounds: Vec<(u64, u64, &str)> = merged.iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap(), crate::structs::structs::Named::name(x).unwrap()))
+            .collect();
+        assert_eq!(bounds, vec![(400, 560, "G1"), (890, 1000, "G2")]);
+    }
+
+    #[test]
+    fn peak_classes() {
+        // plus-strand transcript: 5'UTR 1000-1100, CDS 1100-1200 + 1500-1550, 3'UTR 1550-1600
+        let transcripts = vec![
+            bed("chr1\t1000\t1600\ttr1\t0\t+\t1100\t1550\t0\t2\t200,100,\t0,500,"),
+            bed("chr1\t20000\t21000\tnc1\t0\t-\t20000\t20000\t0\t1\t1000,\t0,"),
+        ];
+        let peak = |start: u64, end: u64| Interval::from(Some(String::from("chr1")), Some(start), Some(end), None);
+        let peaks = vec![
+            peak(500, 600), peak(1040, 1060), peak(1150, 1160), peak(1300, 1400),
+            peak(1560, 1570), peak(3000, 3100), peak(10000, 10100), peak(20500, 20600)
+        ];
+        let options = PeakAnnotationOptions {promoter_downstream: 10, ..Default::default()};
+        let result = annotate_peaks(&peaks, &transcripts, &options);
+        let classes: Vec<FeatureClass> = result.iter().map(|x| x.class).collect();
+        assert_eq!(
+            classes,
+            vec![
+                FeatureClass::Promoter, FeatureClass::Utr5, FeatureClass::Cds, FeatureClass::Intron,
+                FeatureClass::Utr3, FeatureClass::Downstream, FeatureClass::Intergenic, FeatureClass::Exon
+            ]
+        );
+        assert_eq!(result[6].nearest.unwrap().name().unwrap(), "tr1");
+        assert_eq!(result[6].distance, Some(9000));
+        assert_eq!(result[7].distance, Some(400));
+        // promoter overlaps take precedence over 5'UTR by default
+        let overlap = vec![peak(990, 1005)];
+        assert_eq!(annotate_peaks(&overlap, &transcripts, &options)[0].class, FeatureClass::Promoter);
+        let custom = PeakAnnotationOptions {precedence: vec![FeatureClass::Utr5, FeatureClass::Promoter], ..options};
+        assert_eq!(annotate_peaks(&overlap, &transcripts, &custom)[0].class, FeatureClass::Utr5);
+    }
+
+    #[test]
+    fn nearest_tss_assignment() {
+        let transcripts = vec![
+            bed("chr1\t1000\t2000\tA.1\t0\t+\t1000\t2000\t0\t1\t1000,\t0,"),
+            bed("chr1\t1000\t1500\tA.2\t0\t+\t1000\t1500\t0\t1\t500,\t0,"),
+            bed("chr1\t500\t800\tB.1\t0\t-\t500\t800\t0\t1\t300,\t0,"),
+        ];
+        let query = |start: u64, end: u64| Interval::from(Some(String::from("chr1")), Some(start), Some(end), None);
+        let queries = vec![query(1100, 1200), query(850, 900), query(5000, 5010), query(1000, 1001)];
+        let hits = nearest_genes(&queries, &transcripts, Some(1000), None);
+        assert_eq!(hits[0].len(), 2);
+        assert_eq!(hits[0][0].distance, 100);
+        // B.1 TSS is at 799, the query lies upstream of the minus-strand transcript
+        assert_eq!(hits[1].len(), 1);
+        assert_eq!(hits[1][0].gene.as_deref(), Some("B.1"));
+        assert_eq!(hits[1][0].distance, -51);
+        assert!(hits[2].is_empty());
+        let collapsed = nearest_genes(&queries, &transcripts, None, Some(&GeneSource::Delimiter('.')));
+        assert_eq!(collapsed[3].len(), 1);
+        assert_eq!(collapsed[3][0].gene.as_deref(), Some("A"));
+        assert_eq!(collapsed[3][0].distance, 0);
+        assert_eq!(collapsed[2][0].distance, 4000);
+    }
+
+    #[test]
+    fn nested_genes() {
+        let entries = vec![
+            bed("chr1\t0\t10000\thost\t0\t+\t0\t10000\t0\t3\t1000,1000,1000,\t0,3000,9000,"),
+            bed("chr1\t1500\t2500\tintronic\t0\t-\t1500\t2500\t0\t1\t1000,\t0,"),
+            bed("chr1\t2000\t8000\tspanning\t0\t+\t2000\t8000\t0\t2\t500,500,\t0,5500,"),
+            bed("chr1\t3500\t5000\texonic\t0\t+\t3500\t5000\t0\t1\t1500,\t0,"),
+            bed("chr1\t9000\t11000\toutside\t0\t+\t9000\t11000\t0\t1\t2000,\t0,"),
+        ];
+        let nested = nested_entries(&entries);
+        let pairs: Vec<(&str, &str, NestingKind)> = nested.iter()
+            .map(|x| (x.inner.name().unwrap().as_str(), x.outer.name().unwrap().as_str(), x.kind))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("intronic", "host", NestingKind::Intronic),
+                ("spanning", "host", NestingKind::Spanning),
+                ("exonic", "host", NestingKind::Exonic),
+                ("exonic", "spanning", NestingKind::Intronic),
+            ]
+        );
+        assert!(!nested[0].same_strand);
+    }
+
+    #[test]
+    fn exon_skipping() {
+        // CDS 150-750 over exons 100-200, 300-390, 500-600, 700-800
+        let entry = bed("chr1\t100\t800\ttr\t0\t+\t150\t750\t0\t4\t100,90,100,100,\t0,200,400,600,");
+        let variants = skip_exons(&entry, None).unwrap();
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].entry.name().unwrap(), "tr_skip1");
+        assert_eq!(variants[0].consequence, FrameConsequence::InFrame);
+        assert_eq!(variants[1].consequence, FrameConsequence::Frameshift);
+        assert_eq!(variants[1].entry.exons().len(), 3);
+        let custom = skip_exons(&entry, Some(&[vec![2, 1], vec![0]])).unwrap();
+        assert_eq!(custom[0].entry.name().unwrap(), "tr_skip1_2");
+        assert_eq!(custom[0].consequence, FrameConsequence::Frameshift);
+        assert_eq!(custom[1].consequence, FrameConsequence::CdsBoundary);
+        assert!(skip_exons(&entry, Some(&[vec![0, 1, 2, 3]])).is_err());
+
+        // CDS 300-590 starts exactly at the second exon and ends exactly at the third one
+        let coding = bed("chr1\t100\t800\ttr\t0\t+\t300\t590\t0\t4\t100,90,90,100,\t0,200,400,600,");
+        let start_skip = skip_exons(&coding, Some(&[vec![1]])).unwrap();
+        assert_eq!(start_skip[0].consequence, FrameConsequence::CdsBoundary);
+        let stop_skip = skip_exons(&coding, Some(&[vec![2]])).unwrap();
+        assert_eq!(stop_skip[0].consequence, FrameConsequence::CdsBoundary);
+    }
+
+    #[test]
+    fn metrics_report() {
+        let input = "chr1\t100\t600\tA\t0\t-\t150\t550\t0\t3\t100,100,100,\t0,200,400,\n\
+            # comment\n\
+            chr1\t100\t400\tB\t0\t+\t100\t100\t0\t2\t100,100,\t0,200,\n";
+        let (metrics, report) = transcript_metrics(input.as_bytes()).unwrap();
+        assert_eq!(metrics.len(), 2);
+        let a = &metrics[0];
+        assert_eq!((a.exon_count, a.mature_length, a.cds_length), (3, 300, 200));
+        assert_eq!((a.utr5_length, a.utr3_length), (50, 50));
+        assert_eq!(a.mean_intron_length, Some(100.0));
+        assert_eq!(metrics[1].cds_length, 0);
+        assert_eq!((report.transcripts, report.coding, report.single_exon), (2, 1, 0));
+        assert_eq!(report.mean_exon_count, 2.5);
+        assert_eq!(report.mean_cds_length, 200.0);
+        assert_eq!(report.mean_intron_length, 100.0);
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for in-memory interval indices supporting repeated overlap queries
+*/
+
+pub mod index;
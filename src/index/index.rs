@@ -0,0 +1,611 @@
+//! # cubiculum::index
+//! 
+//! In-memory interval indices for repeated overlap and stabbing queries, and a name index for record lookups
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::cmp::{max, min};
+
+use crate::error::error::CubiculumError;
+use crate::structs::structs::{Coordinates, Named};
+
+/// A node of the implicit interval tree
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    start: u64,
+    end: u64,
+    /// maximum end coordinate within the node's subtree
+    max_end: u64,
+    /// position of the record in the index storage
+    record: usize
+}
+
+/// Implicit augmented interval tree over a single chromosome
+///
+/// Nodes are stored in a start-sorted array, with the tree structure defined by the array positions
+/// (odd positions at level 0, positions 2^k - 1 + j * 2^(k+1) at level k),
+/// after the cgranges library by Heng Li
+#[derive(Clone, Debug, Default)]
+struct ChromTree {
+    nodes: Vec<Node>,
+    max_level: usize
+}
+
+impl ChromTree {
+    fn new(mut nodes: Vec<Node>) -> ChromTree {
+        nodes.sort_by_key(|x| (x.start, x.end));
+        let n = nodes.len();
+        if n == 0 {return ChromTree::default()};
+        let mut last_i: usize = 0;
+        let mut last: u64 = 0;
+        for i in (0..n).step_by(2) {
+            nodes[i].max_end = nodes[i].end;
+            last_i = i;
+            last = nodes[i].max_end;
+        }
+        let mut k: usize = 1;
+        while (1 << k) <= n {
+            let x = 1 << (k - 1);
+            let first = (x << 1) - 1;
+            let step = x << 2;
+            for i in (first..n).step_by(step) {
+                let left = nodes[i - x].max_end;
+                let right = if i + x < n {nodes[i + x].max_end} else {last};
+                nodes[i].max_end = max(nodes[i].end, max(left, right));
+            }
+            last_i = if (last_i >> k) & 1 == 1 {last_i - x} else {last_i + x};
+            if last_i < n && nodes[last_i].max_end > last {last = nodes[last_i].max_end};
+            k += 1;
+        }
+        ChromTree {nodes, max_level: k - 1}
+    }
+
+    /// Call the visitor on each node overlapping the [start, end) region
+    fn visit<F: FnMut(&Node)>(&self, start: u64, end: u64, mut visitor: F) {
+        let n = self.nodes.len();
+        if n == 0 || start >= end {return};
+        // (node position, level, whether the left subtree has been visited)
+        let mut stack: Vec<(usize, usize, bool)> = vec![((1 << self.max_level) - 1, self.max_level, false)];
+        while let Some((x, k, visited)) = stack.pop() {
+            if k <= 3 {
+                // small subtree; scan it linearly
+                let first = (x >> k) << k;
+                let last = min(first + (1 << (k + 1)) - 1, n);
+                for node in self.nodes.iter().take(last).skip(first) {
+                    if node.start >= end {break};
+                    if start < node.end {visitor(node)};
+                }
+            } else if !visited {
+                let left = x - (1 << (k - 1));
+                stack.push((x, k, true));
+                if left >= n || self.nodes[left].max_end > start {
+                    stack.push((left, k - 1, false));
+                }
+            } else if x < n && self.nodes[x].start < end {
+                if start < self.nodes[x].end {visitor(&self.nodes[x])};
+                stack.push((x + (1 << (k - 1)), k - 1, false));
+            }
+        }
+    }
+}
+
+/// Overlap queries shared by the interval index implementations
+pub trait IntervalQuery<T> {
+    /// Returns the positions of the records overlapping the [start, end) region in the source vector,
+    /// sorted by the records' start coordinates
+    fn find_indices(&self, chrom: &str, start: u64, end: u64) -> Vec<usize>;
+
+    /// Returns the number of records overlapping the [start, end) region
+    fn count(&self, chrom: &str, start: u64, end: u64) -> usize {
+        self.find_indices(chrom, start, end).len()
+    }
+
+    /// Returns the indexed records in their original order
+    fn records(&self) -> &[T];
+
+    /// Returns the records overlapping the [start, end) region, sorted by their start coordinates
+    fn find(&self, chrom: &str, start: u64, end: u64) -> Vec<&T> {
+        let records = self.records();
+        self.find_indices(chrom, start, end)
+            .into_iter()
+            .map(|i| &records[i])
+            .collect()
+    }
+
+    /// Returns the records containing the position
+    fn find_point(&self, chrom: &str, pos: u64) -> Vec<&T> {
+        self.find(chrom, pos, pos + 1)
+    }
+}
+
+/// Sort the hits by the records' coordinates and return the record positions
+fn sorted_hits(mut hits: Vec<(u64, u64, usize)>) -> Vec<usize> {
+    hits.sort_unstable();
+    hits.into_iter().map(|x| x.2).collect()
+}
+
+/// Static in-memory index over a collection of records, answering overlap queries in O(log n + k)
+///
+/// Records with undefined chromosome or coordinates are kept in the collection but never reported
+///
+/// # Usage
+/// ```
+/// use cubiculum::index::index::{IntervalIndex, IntervalQuery};
+/// use cubiculum::structs::structs::Interval;
+/// let index = IntervalIndex::new(vec![
+///     Interval::from(Some(String::from("chr1")), Some(100), Some(200), None),
+///     Interval::from(Some(String::from("chr1")), Some(150), Some(300), None),
+/// ]);
+/// assert_eq!(index.count("chr1", 180, 190), 2);
+/// assert_eq!(index.find("chr1", 250, 260).len(), 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct IntervalIndex<T> {
+    records: Vec<T>,
+    trees: FxHashMap<String, ChromTree>
+}
+
+impl<T: Coordinates> IntervalIndex<T> {
+    /// Build the index, taking ownership of the records
+    pub fn new(records: Vec<T>) -> IntervalIndex<T> {
+        let mut nodes: FxHashMap<String, Vec<Node>> = FxHashMap::default();
+        for (i, x) in records.iter().enumerate() {
+            if let (Some(chrom), Some(start), Some(end)) = (x.chrom(), x.start(), x.end()) {
+                let node = Node {start: *start, end: *end, max_end: *end, record: i};
+                match nodes.get_mut(chrom) {
+                    Some(chrom_nodes) => {chrom_nodes.push(node)},
+                    None => {nodes.insert(chrom.clone(), vec![node]);}
+                }
+            }
+        }
+        let trees = nodes.into_iter()
+            .map(|(chrom, chrom_nodes)| (chrom, ChromTree::new(chrom_nodes)))
+            .collect();
+        IntervalIndex {records, trees}
+    }
+
+    /// Consume the index, returning the records in their original order
+    pub fn into_records(self) -> Vec<T> {
+        self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl<T: Coordinates> IntervalQuery<T> for IntervalIndex<T> {
+    fn find_indices(&self, chrom: &str, start: u64, end: u64) -> Vec<usize> {
+        let tree = match self.trees.get(chrom) {
+            Some(x) => {x},
+            None => {return Vec::new()}
+        };
+        let mut hits: Vec<(u64, u64, usize)> = Vec::new();
+        tree.visit(start, end, |x| hits.push((x.start, x.end, x.record)));
+        sorted_hits(hits)
+    }
+
+    fn count(&self, chrom: &str, start: u64, end: u64) -> usize {
+        let mut count: usize = 0;
+        if let Some(tree) = self.trees.get(chrom) {
+            tree.visit(start, end, |_| count += 1);
+        }
+        count
+    }
+
+    fn records(&self) -> &[T] {
+        &self.records
+    }
+}
+
+/// A member of a nested containment list
+#[derive(Clone, Copy, Debug)]
+struct NestedNode {
+    start: u64,
+    end: u64,
+    record: usize,
+    /// range of the nodes contained in this one within the node storage
+    sublist: (usize, usize)
+}
+
+/// Static index storing the records as nested containment lists (Alekseyenko and Lee, 2007)
+///
+/// Records contained in another record are moved to the latter's sublist, so that both start
+/// and end coordinates are sorted within each list and every list is scanned contiguously.
+/// Performance relative to `IntervalIndex` depends on the nesting depth of the data;
+/// see `benches/index.rs` for a comparison
+///
+#[derive(Clone, Debug)]
+pub struct NestedContainmentList<T> {
+    records: Vec<T>,
+    /// per-chromosome node storage along with the range of the top-level list
+    lists: FxHashMap<String, (Vec<NestedNode>, (usize, usize))>
+}
+
+impl<T: Coordinates> NestedContainmentList<T> {
+    /// Build the index, taking ownership of the records
+    pub fn new(records: Vec<T>) -> NestedContainmentList<T> {
+        let mut nodes: FxHashMap<String, Vec<(u64, u64, usize)>> = FxHashMap::default();
+        for (i, x) in records.iter().enumerate() {
+            if let (Some(chrom), Some(start), Some(end)) = (x.chrom(), x.start(), x.end()) {
+                match nodes.get_mut(chrom) {
+                    Some(chrom_nodes) => {chrom_nodes.push((*start, *end, i))},
+                    None => {nodes.insert(chrom.clone(), vec![(*start, *end, i)]);}
+                }
+            }
+        }
+        let lists = nodes.into_iter()
+            .map(|(chrom, chrom_nodes)| (chrom, NestedContainmentList::<T>::build(chrom_nodes)))
+            .collect();
+        NestedContainmentList {records, lists}
+    }
+
+    fn build(mut nodes: Vec<(u64, u64, usize)>) -> (Vec<NestedNode>, (usize, usize)) {
+        // containing intervals precede the contained ones
+        nodes.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+        // children[0] is the top-level list; children[i + 1] holds the sublist of the i-th node
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes.len() + 1];
+        let mut parents: Vec<usize> = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            while let Some(parent) = parents.last() {
+                if nodes[*parent].1 >= node.1 {break};
+                parents.pop();
+            }
+            let list = match parents.last() {
+                Some(parent) => {parent + 1},
+                None => {0}
+            };
+            children[list].push(i);
+            parents.push(i);
+        }
+        // lay the lists out contiguously, breadth-first
+        let mut storage: Vec<NestedNode> = Vec::with_capacity(nodes.len());
+        let mut position: Vec<usize> = vec![0; nodes.len()];
+        let mut queue: std::collections::VecDeque<usize> = std::collections::VecDeque::from(vec![0]);
+        let mut top_level: (usize, usize) = (0, 0);
+        while let Some(list) = queue.pop_front() {
+            let first = storage.len();
+            for i in children[list].iter() {
+                position[*i] = storage.len();
+                let (start, end, record) = nodes[*i];
+                storage.push(NestedNode {start, end, record, sublist: (0, 0)});
+                queue.push_back(*i + 1);
+            }
+            let range = (first, storage.len());
+            if list == 0 {top_level = range} else {storage[position[list - 1]].sublist = range};
+        }
+        (storage, top_level)
+    }
+
+    /// Call the visitor on each node overlapping the [start, end) region
+    fn visit<F: FnMut(&NestedNode)>(&self, chrom: &str, start: u64, end: u64, mut visitor: F) {
+        let (storage, top_level) = match self.lists.get(chrom) {
+            Some(x) => {x},
+            None => {return}
+        };
+        if start >= end {return};
+        let mut stack: Vec<(usize, usize)> = vec![*top_level];
+        while let Some((first, last)) = stack.pop() {
+            let list = &storage[first..last];
+            let from = list.partition_point(|x| x.end <= start);
+            for node in list[from..].iter() {
+                if node.start >= end {break};
+                visitor(node);
+                if node.sublist.0 < node.sublist.1 {stack.push(node.sublist)};
+            }
+        }
+    }
+
+    /// Consume the index, returning the records in their original order
+    pub fn into_records(self) -> Vec<T> {
+        self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl<T: Coordinates> IntervalQuery<T> for NestedContainmentList<T> {
+    fn find_indices(&self, chrom: &str, start: u64, end: u64) -> Vec<usize> {
+        let mut hits: Vec<(u64, u64, usize)> = Vec::new();
+        self.visit(chrom, start, end, |x| hits.push((x.start, x.end, x.record)));
+        sorted_hits(hits)
+    }
+
+    fn count(&self, chrom: &str, start: u64, end: u64) -> usize {
+        let mut count: usize = 0;
+        self.visit(chrom, start, end, |_| count += 1);
+        count
+    }
+
+    fn records(&self) -> &[T] {
+        &self.records
+    }
+}
+
+/// Static index of records by name, answering lookups of all records sharing a name in O(1)
+///
+/// Useful for annotations split into several records per feature, e.g., transcript blocks reported
+/// as separate BED6 records. Unnamed records are kept in the collection but never reported
+///
+/// # Usage
+/// ```
+/// use cubiculum::index::index::NameIndex;
+/// use cubiculum::structs::structs::Interval;
+/// let block = |start: u64, name: &str| Interval::from(Some(String::from("chr1")), Some(start), Some(start + 50), Some(name.to_string()));
+/// let index = NameIndex::new(vec![block(100, "A"), block(300, "B"), block(500, "A")]);
+/// assert_eq!(index.indices("A"), &[0, 2]);
+/// assert_eq!(index.get("B").len(), 1);
+/// assert!(index.get("C").is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct NameIndex<T> {
+    records: Vec<T>,
+    names: FxHashMap<String, Vec<usize>>
+}
+
+impl<T: Named> NameIndex<T> {
+    /// Build the index, taking ownership of the records
+    pub fn new(records: Vec<T>) -> NameIndex<T> {
+        let mut names: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+        for (i, x) in records.iter().enumerate() {
+            if let Some(name) = x.name() {
+                match names.get_mut(name) {
+                    Some(positions) => {positions.push(i)},
+                    None => {names.insert(name.to_string(), vec![i]);}
+                }
+            }
+        }
+        NameIndex {records, names}
+    }
+
+    /// Returns the positions of the records with the given name in the source vector, in ascending order
+    pub fn indices(&self, name: &str) -> &[usize] {
+        self.names.get(name).map_or(&[], |x| x.as_slice())
+    }
+
+    /// Returns the records with the given name in their original order
+    pub fn get(&self, name: &str) -> Vec<&T> {
+        self.indices(name).iter().map(|i| &self.records[*i]).collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Returns the distinct record names, in arbitrary order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(|x| x.as_str())
+    }
+
+    /// Returns the indexed records in their original order
+    pub fn records(&self) -> &[T] {
+        &self.records
+    }
+
+    /// Consume the index, returning the records in their original order
+    pub fn into_records(self) -> Vec<T> {
+        self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+/// Bin offsets of the standard UCSC binning scheme, from the smallest (128 kb) to the largest (512 Mb) bins
+const BIN_OFFSETS: [u32; 5] = [512 + 64 + 8 + 1, 64 + 8 + 1, 8 + 1, 1, 0];
+/// Bin offsets of the extended UCSC binning scheme used for records ending past 512 Mb
+const BIN_OFFSETS_EXTENDED: [u32; 6] = [4096 + 512 + 64 + 8 + 1, 512 + 64 + 8 + 1, 64 + 8 + 1, 8 + 1, 1, 0];
+/// First bin number of the extended scheme
+const BIN_OFFSET_OLD_TO_EXTENDED: u32 = 4681;
+const BIN_FIRST_SHIFT: u32 = 17;
+const BIN_NEXT_SHIFT: u32 = 3;
+/// Largest end coordinate covered by the standard binning scheme
+const BIN_MAX_END_STANDARD: u64 = 1 << 29;
+/// Largest end coordinate covered by the extended binning scheme
+const BIN_MAX_END_EXTENDED: u64 = 1 << 32;
+
+/// Returns the UCSC hierarchical bin for the [start, end) interval
+///
+/// The standard scheme is used for intervals ending within the first 512 Mb of the chromosome,
+/// and the extended one otherwise, as in the UCSC Genome Browser tables. Returns an InputError
+/// if the interval ends past the 4 Gb limit of the extended scheme
+///
+pub fn ucsc_bin(start: u64, end: u64) -> Result<u32, CubiculumError> {
+    let end = binned_end(start, end)?;
+    let bin = if end <= BIN_MAX_END_STANDARD {
+        bin_from_range(start, end, &BIN_OFFSETS)
+    } else {
+        bin_from_range(start, end, &BIN_OFFSETS_EXTENDED).map(|x| BIN_OFFSET_OLD_TO_EXTENDED + x)
+    };
+    bin.ok_or_else(|| CubiculumError::InputError(
+        format!("Interval {}-{} cannot be assigned to a UCSC bin", start, end)
+    ))
+}
+
+/// End coordinate of the [start, end) interval as seen by the binning scheme, with empty intervals
+/// treated as single-base ones
+fn binned_end(start: u64, end: u64) -> Result<u64, CubiculumError> {
+    let end = max(end, start.saturating_add(1));
+    if end > BIN_MAX_END_EXTENDED {
+        return Err(CubiculumError::InputError(
+            format!(
+                "Interval {}-{} exceeds the maximum coordinate supported by the UCSC binning scheme",
+                start, end
+            )
+        ))
+    }
+    Ok(end)
+}
+
+fn bin_from_range(start: u64, end: u64, offsets: &[u32]) -> Option<u32> {
+    let mut start_bin = start >> BIN_FIRST_SHIFT;
+    let mut end_bin = (end - 1) >> BIN_FIRST_SHIFT;
+    for offset in offsets {
+        if start_bin == end_bin {return Some(*offset + start_bin as u32)};
+        start_bin >>= BIN_NEXT_SHIFT;
+        end_bin >>= BIN_NEXT_SHIFT;
+    }
+    None
+}
+
+/// Returns the UCSC bins which can hold records overlapping the [start, end) region
+///
+/// Bins are reported in increasing order. The top bin of the extended scheme, which holds records
+/// crossing the 512 Mb boundary, is always included; the rest of the extended scheme bins are included
+/// only if the region extends past 512 Mb. Returns an InputError if the region ends
+/// past the 4 Gb limit of the extended scheme
+///
+pub fn ucsc_overlapping_bins(start: u64, end: u64) -> Result<Vec<u32>, CubiculumError> {
+    let end = binned_end(start, end)?;
+    let mut bins: Vec<u32> = Vec::new();
+    if start < BIN_MAX_END_STANDARD {
+        bins_in_range(start, min(end, BIN_MAX_END_STANDARD), &BIN_OFFSETS, 0, &mut bins);
+    }
+    if end > BIN_MAX_END_STANDARD {
+        bins_in_range(start, end, &BIN_OFFSETS_EXTENDED, BIN_OFFSET_OLD_TO_EXTENDED, &mut bins);
+    } else {
+        bins.push(BIN_OFFSET_OLD_TO_EXTENDED);
+    }
+    bins.sort_unstable();
+    Ok(bins)
+}
+
+fn bins_in_range(start: u64, end: u64, offsets: &[u32], base: u32, bins: &mut Vec<u32>) {
+    let mut start_bin = start >> BIN_FIRST_SHIFT;
+    let mut end_bin = (end - 1) >> BIN_FIRST_SHIFT;
+    for offset in offsets {
+        bins.extend((start_bin..=end_bin).map(|x| base + offset + x as u32));
+        start_bin >>= BIN_NEXT_SHIFT;
+        end_bin >>= BIN_NEXT_SHIFT;
+    }
+}
+
+#[cfg(test)]
+mod test_index {
+    use super::*;
+    use crate::structs::structs::Interval;
+
+    fn interval(chrom: &str, start: u64, end: u64) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), None)
+    }
+
+    /// Deterministic pseudorandom intervals of varying lengths
+    fn random_intervals(n: usize) -> Vec<Interval> {
+        let mut state: u64 = 17;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 33
+        };
+        (0..n)
+            .map(|_| {
+                let start = next() % 100_000;
+                let length = if next() % 10 == 0 {next() % 20_000} else {next() % 500} + 1;
+                interval(if next() % 2 == 0 {"chr1"} else {"chr2"}, start, start + length)
+            })
+            .collect()
+    }
+
+    fn check_queries<I: IntervalQuery<Interval>>(index: &I) {
+        assert_eq!(index.find_indices("chr1", 190, 310), vec![1, 2, 0]);
+        assert_eq!(index.count("chr1", 200, 300), 1);
+        assert_eq!(index.count("chr1", 1000, 2000), 0);
+        assert_eq!(index.count("chr3", 0, 2000), 0);
+        assert_eq!(index.find_point("chr2", 199).len(), 1);
+        assert_eq!(index.find_point("chr2", 200).len(), 0);
+        assert_eq!(index.records().len(), 5);
+    }
+
+    #[test]
+    fn index_queries() {
+        let records = vec![
+            interval("chr1", 300, 400),
+            interval("chr1", 100, 200),
+            interval("chr1", 150, 1000),
+            interval("chr2", 100, 200),
+            interval("chr1", 350, 360),
+        ];
+        check_queries(&IntervalIndex::new(records.clone()));
+        check_queries(&NestedContainmentList::new(records));
+    }
+
+    #[test]
+    fn index_matches_linear_scan() {
+        let records = random_intervals(3000);
+        let index = IntervalIndex::new(records.clone());
+        let nclist = NestedContainmentList::new(records.clone());
+        for (chrom, start, end) in [("chr1", 0, 10), ("chr1", 5_000, 5_300), ("chr2", 50_000, 52_000), ("chr2", 99_990, 130_000)] {
+            let expected: Vec<usize> = {
+                let mut hits: Vec<(u64, u64, usize)> = records.iter()
+                    .enumerate()
+                    .filter(|(_, x)| {
+                        x.chrom().unwrap() == chrom && *x.start().unwrap() < end && *x.end().unwrap() > start
+                    })
+                    .map(|(i, x)| (*x.start().unwrap(), *x.end().unwrap(), i))
+                    .collect();
+                hits.sort_unstable();
+                hits.into_iter().map(|x| x.2).collect()
+            };
+            assert_eq!(index.find_indices(chrom, start, end), expected);
+            assert_eq!(index.count(chrom, start, end), expected.len());
+            assert_eq!(nclist.find_indices(chrom, start, end), expected);
+            assert_eq!(nclist.count(chrom, start, end), expected.len());
+        }
+    }
+
+    #[test]
+    fn name_lookups() {
+        let named = |start: u64, name: Option<&str>| {
+            Interval::from(Some(String::from("chr1")), Some(start), Some(start + 10), name.map(|x| x.to_string()))
+        };
+        let index = NameIndex::new(vec![named(0, Some("A")), named(20, None), named(40, Some("B")), named(60, Some("A"))]);
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.indices("A"), &[0, 3]);
+        assert_eq!(index.get("A").iter().map(|x| *x.start().unwrap()).collect::<Vec<u64>>(), vec![0, 60]);
+        assert!(index.contains("B") && !index.contains("C"));
+        let mut names: Vec<&str> = index.names().collect();
+        names.sort_unstable();
+        assert_eq!(names, vec!["A", "B"]);
+        assert_eq!(index.into_records().len(), 4);
+    }
+
+    #[test]
+    fn ucsc_bins() {
+        assert_eq!(ucsc_bin(0, 1).unwrap(), 585);
+        assert_eq!(ucsc_bin(0, 131_072).unwrap(), 585);
+        assert_eq!(ucsc_bin(131_071, 131_073).unwrap(), 73);
+        assert_eq!(ucsc_bin(0, 1 << 29).unwrap(), 0);
+        assert_eq!(ucsc_bin(1 << 29, (1 << 29) + 1).unwrap(), 4681 + 4681 + 4096);
+        let bins = ucsc_overlapping_bins(100_000, 200_000).unwrap();
+        assert_eq!(bins, vec![0, 1, 9, 73, 585, 586, 4681]);
+        for (start, end) in [(150_000, 150_100), (0, 1_000_000), (131_000, 132_000)] {
+            assert!(bins.contains(&ucsc_bin(start, end).unwrap()));
+        }
+        assert!(ucsc_overlapping_bins(1 << 29, (1 << 29) + 10).unwrap().iter().all(|x| *x >= 4681));
+        // a record crossing the 512 Mb boundary lands in the top extended bin,
+        // which must be queried even for regions ending below the boundary
+        let crossing = ucsc_bin(530_000_000, 540_000_000).unwrap();
+        assert_eq!(crossing, 4681);
+        assert!(ucsc_overlapping_bins(531_000_000, 531_500_000).unwrap().contains(&crossing));
+        assert!(matches!(ucsc_bin(0, (1 << 32) + 1), Err(CubiculumError::InputError(_))));
+        assert!(matches!(ucsc_bin(u64::MAX, u64::MAX), Err(CubiculumError::InputError(_))));
+        assert!(matches!(ucsc_overlapping_bins(u64::MAX, 0), Err(CubiculumError::InputError(_))));
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for overlap queries between sets of annotation records
+*/
+
+pub mod intersect;
@@ -0,0 +1,1350 @@
+//! # cubiculum::intersect
+//! 
+//! Overlap queries between two sets of coordinate-sorted records
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::cmp::{max, min, Ordering};
+use std::collections::VecDeque;
+use std::iter::Peekable;
+
+use fxhash::FxHashMap;
+
+use crate::error::error::CubiculumError;
+use crate::merge::merge::complement;
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates, Interval, Stranded};
+
+/// Check whether the two records overlap by at least one base
+pub fn overlaps<A, B>(a: &A, b: &B) -> bool
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    match (a.start(), a.end(), b.start(), b.end()) {
+        (Some(s1), Some(e1), Some(s2), Some(e2)) => {
+            a.chrom() == b.chrom() && s1 < e2 && s2 < e1
+        },
+        _ => {false}
+    }
+}
+
+/// Returns the region shared by the two overlapping records as an unnamed interval
+/// on their chromosome; None if the records do not overlap
+pub fn overlap_region<A, B>(a: &A, b: &B) -> Option<Interval>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    if !overlaps(a, b) {return None};
+    let start = max(*a.start().unwrap(), *b.start().unwrap());
+    let end = min(*a.end().unwrap(), *b.end().unwrap());
+    Some(Interval::from(a.chrom().cloned(), Some(start), Some(end), None))
+}
+
+/// Returns the number of bases shared by the two records
+pub fn overlap_size<A, B>(a: &A, b: &B) -> u64
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    if !overlaps(a, b) {return 0};
+    min(*a.end().unwrap(), *b.end().unwrap()) - max(*a.start().unwrap(), *b.start().unwrap())
+}
+
+/// Returns the number of bases shared by the blocks (exons) of the two entries;
+/// intronic-only overlaps contribute nothing
+pub fn block_overlap_size(a: &BedEntry, b: &BedEntry) -> u64 {
+    if a.chrom() != b.chrom() {return 0};
+    let mut blocks_b = b.exons().peekable();
+    let mut shared: u64 = 0;
+    for (start_a, end_a) in a.exons() {
+        while let Some((start_b, end_b)) = blocks_b.peek() {
+            if *end_b <= start_a {
+                blocks_b.next();
+                continue
+            }
+            if *start_b >= end_a {break};
+            shared += min(end_a, *end_b) - max(start_a, *start_b);
+            // the current B block can still overlap the next A block
+            if *end_b > end_a {break};
+            blocks_b.next();
+        }
+    }
+    shared
+}
+
+/// Returns the total length of the entry's blocks
+fn block_length(entry: &BedEntry) -> u64 {
+    entry.exons().map(|(start, end)| end - start).sum()
+}
+
+/// Minimum overlap requirements for a pair of records,
+/// following the semantics of bedtools' -f, -F, -r and -e options
+///
+/// The default value accepts any overlap of at least one base
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OverlapCriteria {
+    /// Minimum overlap as a fraction of the first (query) record's length
+    pub fraction_a: Option<f64>,
+    /// Minimum overlap as a fraction of the second (target) record's length
+    pub fraction_b: Option<f64>,
+    /// If set, `fraction_a` is required for the second record as well
+    pub reciprocal: bool,
+    /// If set, satisfying either of the two fractions is sufficient
+    pub either: bool
+}
+
+impl OverlapCriteria {
+    pub fn new() -> OverlapCriteria {
+        OverlapCriteria::default()
+    }
+
+    pub fn min_fraction_a(mut self, fraction: f64) -> OverlapCriteria {
+        self.fraction_a = Some(fraction);
+        self
+    }
+
+    pub fn min_fraction_b(mut self, fraction: f64) -> OverlapCriteria {
+        self.fraction_b = Some(fraction);
+        self
+    }
+
+    pub fn reciprocal(mut self) -> OverlapCriteria {
+        self.reciprocal = true;
+        self
+    }
+
+    pub fn either(mut self) -> OverlapCriteria {
+        self.either = true;
+        self
+    }
+
+    /// Check whether the records overlap to the required extent
+    pub fn passes<A, B>(&self, a: &A, b: &B) -> bool
+    where
+        A: Coordinates,
+        B: Coordinates
+    {
+        self.passes_sizes(overlap_size(a, b), a.length(), b.length())
+    }
+
+    /// Check whether the entries' blocks overlap to the required extent;
+    /// fractions are computed against the summed block lengths
+    pub fn passes_split(&self, a: &BedEntry, b: &BedEntry) -> bool {
+        self.passes_sizes(block_overlap_size(a, b), Some(block_length(a)), Some(block_length(b)))
+    }
+
+    fn passes_sizes(&self, shared: u64, length_a: Option<u64>, length_b: Option<u64>) -> bool {
+        if shared == 0 {return false};
+        let fraction_b = if self.reciprocal {self.fraction_a.or(self.fraction_b)} else {self.fraction_b};
+        let passes_a = fraction_passes(shared, length_a, self.fraction_a);
+        let passes_b = fraction_passes(shared, length_b, fraction_b);
+        if self.either && (self.fraction_a.is_some() || fraction_b.is_some()) {
+            (self.fraction_a.is_some() && passes_a) || (fraction_b.is_some() && passes_b)
+        } else {
+            passes_a && passes_b
+        }
+    }
+}
+
+/// Check whether the overlap covers the minimum fraction of the record's length
+fn fraction_passes(shared: u64, length: Option<u64>, fraction: Option<f64>) -> bool {
+    match (fraction, length) {
+        (None, _) => {true},
+        (Some(_), None) | (Some(_), Some(0)) => {false},
+        (Some(f), Some(len)) => {shared as f64 >= f * len as f64}
+    }
+}
+
+/// Streaming sweep-line intersection of two sorted record streams,
+/// created with `intersect()`
+pub struct Intersect<IA, IB>
+where
+    IA: Iterator,
+    IB: Iterator
+{
+    a: IA,
+    b: Peekable<IB>,
+    window: VecDeque<IB::Item>,
+    pending: VecDeque<(IA::Item, IB::Item)>,
+    last_a: Option<(Option<String>, Option<u64>)>,
+    last_b: Option<(Option<String>, Option<u64>)>,
+    criteria: OverlapCriteria,
+    matcher: fn(&OverlapCriteria, &IA::Item, &IB::Item) -> bool,
+    failed: bool
+}
+
+/// Intersect two coordinate-sorted streams of records
+///
+/// Both inputs must be sorted by chromosome name (lexicographically) and then by start coordinate,
+/// e.g. with `sort -k1,1 -k2,2n`. Records from `b` are kept in memory only while they can
+/// still overlap upcoming records from `a`, so the memory footprint is bounded by the overlap depth.
+///
+/// # Arguments
+/// `a`: the query stream;
+/// `b`: the target stream
+///
+/// # Returns
+/// An iterator over the overlapping (a, b) pairs, ordered by the `a` records;
+/// an unsorted record in either stream yields an UnsortedInput error, after which the iteration stops
+///
+/// # Usage
+/// ```
+/// use cubiculum::intersect::intersect::intersect;
+/// use cubiculum::structs::structs::Interval;
+/// let a = vec![Interval::from(Some(String::from("chr1")), Some(100), Some(200), None)];
+/// let b = vec![
+///     Interval::from(Some(String::from("chr1")), Some(150), Some(160), None),
+///     Interval::from(Some(String::from("chr1")), Some(200), Some(300), None),
+/// ];
+/// assert_eq!(intersect(a, b).count(), 1);
+/// ```
+pub fn intersect<A, B, IA, IB>(a: IA, b: IB) -> Intersect<IA::IntoIter, IB::IntoIter>
+where
+    A: Coordinates + Clone,
+    B: Coordinates + Clone,
+    IA: IntoIterator<Item = A>,
+    IB: IntoIterator<Item = B>
+{
+    Intersect {
+        a: a.into_iter(),
+        b: b.into_iter().peekable(),
+        window: VecDeque::new(),
+        pending: VecDeque::new(),
+        last_a: None,
+        last_b: None,
+        criteria: OverlapCriteria::default(),
+        matcher: OverlapCriteria::passes::<A, B>,
+        failed: false
+    }
+}
+
+/// Return an UnsortedInput error if the record precedes the previously seen one
+fn check_order<T: Coordinates>(
+    last: &mut Option<(Option<String>, Option<u64>)>, item: &T, stream: &str
+) -> Result<(), CubiculumError> {
+    let key = (item.chrom().cloned(), item.start().copied());
+    if let Some(prev) = last {
+        if (prev.0.as_ref(), prev.1) > (key.0.as_ref(), key.1) {
+            return Err(
+                CubiculumError::UnsortedInput(
+                    format!(
+                        "Stream {} is not coordinate-sorted: {:?}:{:?} follows {:?}:{:?}",
+                        stream, key.0, key.1, prev.0, prev.1
+                    )
+                )
+            )
+        }
+    }
+    *last = Some(key);
+    Ok(())
+}
+
+impl<A, B, IA, IB> Intersect<IA, IB>
+where
+    A: Coordinates + Clone,
+    B: Coordinates + Clone,
+    IA: Iterator<Item = A>,
+    IB: Iterator<Item = B>
+{
+    /// Report only the pairs satisfying the provided overlap criteria
+    pub fn with_criteria(mut self, criteria: OverlapCriteria) -> Self {
+        self.criteria = criteria;
+        self
+    }
+
+    /// Map the overlapping pairs to the regions shared by their members; errors are passed through
+    pub fn regions(self) -> impl Iterator<Item = Result<Interval, CubiculumError>> {
+        self.filter_map(|x| match x {
+            Ok((a, b)) => {overlap_region(&a, &b).map(Ok)},
+            Err(e) => {Some(Err(e))}
+        })
+    }
+
+    /// Find all window members overlapping the query, pulling new records from `b` when needed
+    fn process(&mut self, query: A) -> Result<(), CubiculumError> {
+        let (query_start, query_end) = match (query.start(), query.end()) {
+            (Some(x), Some(y)) => {(*x, *y)},
+            _ => {return Ok(())}
+        };
+        // drop the records which cannot overlap this or any of the subsequent queries
+        self.window.retain(|x| {
+            x.chrom() == query.chrom() && x.end().is_some_and(|e| *e > query_start)
+        });
+        // pull the records starting before the query's end
+        while let Some(next) = self.b.peek() {
+            match next.chrom().cmp(&query.chrom()) {
+                Ordering::Less => {},
+                Ordering::Greater => {break},
+                Ordering::Equal => {
+                    if next.start().is_some_and(|s| *s >= query_end) {break};
+                }
+            }
+            let next = match self.b.next() {
+                Some(x) => {x},
+                None => {break}
+            };
+            check_order(&mut self.last_b, &next, "b")?;
+            if next.chrom() == query.chrom() && next.end().is_some_and(|e| *e > query_start) {
+                self.window.push_back(next);
+            }
+        }
+        for target in self.window.iter() {
+            if (self.matcher)(&self.criteria, &query, target) {
+                self.pending.push_back((query.clone(), target.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<IA, IB> Intersect<IA, IB>
+where
+    IA: Iterator<Item = BedEntry>,
+    IB: Iterator<Item = BedEntry>
+{
+    /// Compare the entries at the block (exon) level rather than by their thin spans,
+    /// akin to bedtools' -split option; entries overlapping only by introns are not reported
+    pub fn split(mut self) -> Self {
+        self.matcher = OverlapCriteria::passes_split;
+        self
+    }
+}
+
+impl<A, B, IA, IB> Iterator for Intersect<IA, IB>
+where
+    A: Coordinates + Clone,
+    B: Coordinates + Clone,
+    IA: Iterator<Item = A>,
+    IB: Iterator<Item = B>
+{
+    type Item = Result<(A, B), CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(hit) = self.pending.pop_front() {return Some(Ok(hit))};
+            if self.failed {return None};
+            let query = self.a.next()?;
+            let checked = check_order(&mut self.last_a, &query, "a")
+                .and_then(|_| self.process(query));
+            if let Err(e) = checked {
+                self.failed = true;
+                return Some(Err(e))
+            }
+        }
+    }
+}
+
+/// A feature reported by `closest()` along with its signed distance to the query
+///
+/// Overlapping features have zero distance; otherwise, the distance equals the number of bases
+/// separating the features plus one, so that book-ended features are reported at distance 1.
+/// Features located upstream of the query have negative distances
+#[derive(Debug)]
+pub struct ClosestHit<'b, B> {
+    pub target: &'b B,
+    pub distance: i64
+}
+
+/// Settings for `closest()` and `closest_stranded()`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClosestOptions {
+    /// Overlapping features failing the criteria are not reported
+    pub criteria: OverlapCriteria,
+    /// If set, overlapping features are skipped altogether
+    pub ignore_overlaps: bool,
+    /// If set, all features tied with the k-th nearest one are reported as well
+    pub all_ties: bool
+}
+
+/// Per-chromosome index of the target features used by the proximity queries
+pub(crate) struct ChromIndex {
+    by_start: Vec<usize>,
+    by_end: Vec<usize>,
+    max_len: u64
+}
+
+impl ChromIndex {
+    /// Returns the indices of features overlapping the [start, end) region, sorted by their start coordinates
+    pub(crate) fn overlapping<'a, B: Coordinates>(&'a self, b: &'a [B], start: u64, end: u64) -> impl Iterator<Item = usize> + 'a {
+        let lowest_start = start.saturating_sub(self.max_len);
+        let first = self.by_start.partition_point(|i| *b[*i].start().unwrap() < lowest_start);
+        // empty regions overlap nothing
+        let last = if start < end {self.by_start.partition_point(|i| *b[*i].start().unwrap() < end)} else {first};
+        self.by_start[first..last]
+            .iter()
+            .copied()
+            .filter(move |i| *b[*i].end().unwrap() > start)
+    }
+}
+
+pub(crate) fn index_by_chrom<B: Coordinates>(b: &[B]) -> FxHashMap<&String, ChromIndex> {
+    let mut index: FxHashMap<&String, ChromIndex> = FxHashMap::default();
+    for (i, x) in b.iter().enumerate() {
+        let (chrom, start, end) = match (x.chrom(), x.start(), x.end()) {
+            (Some(c), Some(s), Some(e)) => {(c, *s, *e)},
+            _ => {continue}
+        };
+        let entry = index.entry(chrom).or_insert(
+            ChromIndex {by_start: Vec::new(), by_end: Vec::new(), max_len: 0}
+        );
+        entry.by_start.push(i);
+        entry.by_end.push(i);
+        entry.max_len = max(entry.max_len, end.saturating_sub(start));
+    }
+    for entry in index.values_mut() {
+        entry.by_start.sort_by_key(|i| (*b[*i].start().unwrap(), *b[*i].end().unwrap()));
+        entry.by_end.sort_by_key(|i| (*b[*i].end().unwrap(), *b[*i].start().unwrap()));
+    }
+    index
+}
+
+/// Find the k nearest `b` features for each of the `a` records
+///
+/// # Arguments
+/// `a`: query records;
+/// `b`: target features, in any order;
+/// `k`: number of nearest features to report per query;
+/// `options`: overlap and tie handling settings
+///
+/// # Returns
+/// A vector of hits for each query record, in the order of `a`, sorted by absolute distance.
+/// Only features located on the query's chromosome are considered;
+/// distances are signed with respect to the reference strand
+///
+pub fn closest<'b, A, B>(a: &[A], b: &'b [B], k: usize, options: &ClosestOptions) -> Vec<Vec<ClosestHit<'b, B>>>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    closest_core(a, b, k, options, |_| false)
+}
+
+/// Find the k nearest `b` features for each of the `a` records with strand-aware distances
+///
+/// Same as `closest()`, except that for queries located on the negative strand
+/// the upstream direction points towards larger coordinates; unstranded queries are treated as positive strand ones
+///
+pub fn closest_stranded<'b, A, B>(a: &[A], b: &'b [B], k: usize, options: &ClosestOptions) -> Vec<Vec<ClosestHit<'b, B>>>
+where
+    A: Coordinates + Stranded,
+    B: Coordinates
+{
+    closest_core(a, b, k, options, |x| x.defined_strand() == Some(false))
+}
+
+fn closest_core<'b, A, B, F>(
+    a: &[A], b: &'b [B], k: usize, options: &ClosestOptions, flip: F
+) -> Vec<Vec<ClosestHit<'b, B>>>
+where
+    A: Coordinates,
+    B: Coordinates,
+    F: Fn(&A) -> bool
+{
+    let index = index_by_chrom(b);
+    let mut output: Vec<Vec<ClosestHit<'b, B>>> = Vec::with_capacity(a.len());
+    for query in a {
+        let mut hits: Vec<ClosestHit<'b, B>> = Vec::new();
+        let (chrom_index, start, end) = match (query.chrom(), query.start(), query.end()) {
+            (Some(c), Some(s), Some(e)) if k > 0 => {
+                match index.get(c) {
+                    Some(x) => {(x, *s, *e)},
+                    None => {output.push(hits); continue}
+                }
+            },
+            _ => {output.push(hits); continue}
+        };
+        let by_start = &chrom_index.by_start;
+        let by_end = &chrom_index.by_end;
+        let first_right = by_start.partition_point(|i| *b[*i].start().unwrap() < end);
+        let mut overlapping: Vec<usize> = Vec::new();
+        if !options.ignore_overlaps {
+            let lowest_start = start.saturating_sub(chrom_index.max_len);
+            let first_overlap = by_start.partition_point(|i| *b[*i].start().unwrap() < lowest_start);
+            overlapping = by_start[first_overlap..first_right]
+                .iter()
+                .filter(|i| options.criteria.passes(query, &b[**i]))
+                .copied()
+                .collect();
+        }
+        let mut overlapping = overlapping.into_iter();
+        let mut left = by_end[..by_end.partition_point(|i| *b[*i].end().unwrap() <= start)]
+            .iter()
+            .rev()
+            .map(|i| (*i, start - *b[*i].end().unwrap() + 1, true))
+            .peekable();
+        let mut right = by_start[first_right..]
+            .iter()
+            .map(|i| (*i, *b[*i].start().unwrap() - end + 1, false))
+            .peekable();
+        let flipped = flip(query);
+        loop {
+            let candidate = match overlapping.next() {
+                Some(i) => {Some((i, 0, false))},
+                None => {
+                    match (left.peek(), right.peek()) {
+                        (Some(l), Some(r)) => {
+                            if l.1 <= r.1 {left.next()} else {right.next()}
+                        },
+                        (Some(_), None) => {left.next()},
+                        (None, _) => {right.next()}
+                    }
+                }
+            };
+            let (i, distance, upstream) = match candidate {
+                Some(x) => {x},
+                None => {break}
+            };
+            if hits.len() >= k {
+                let last = hits.last().unwrap().distance.unsigned_abs();
+                if !(options.all_ties && distance == last) {break};
+            }
+            let distance = distance as i64;
+            let distance = if upstream != flipped {-distance} else {distance};
+            hits.push(ClosestHit {target: &b[i], distance});
+        }
+        output.push(hits);
+    }
+    output
+}
+
+/// Find the `b` features located within a window around each of the `a` records
+///
+/// # Arguments
+/// `a`: query records;
+/// `b`: target features, in any order;
+/// `upstream`: number of bases to extend the query's window towards smaller coordinates;
+/// `downstream`: number of bases to extend the query's window towards larger coordinates
+///
+/// # Returns
+/// A vector of features overlapping the extended window for each query record,
+/// in the order of `a`; features are sorted by their start coordinates
+///
+pub fn window<'b, A, B>(a: &[A], b: &'b [B], upstream: u64, downstream: u64) -> Vec<Vec<&'b B>>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    window_core(a, b, upstream, downstream, |_| false)
+}
+
+/// Find the `b` features located within a strand-aware window around each of the `a` records
+///
+/// Same as `window()`, except that for queries located on the negative strand
+/// the upstream extension is applied to the query's end and the downstream one to its start;
+/// unstranded queries are treated as positive strand ones
+///
+pub fn window_stranded<'b, A, B>(a: &[A], b: &'b [B], upstream: u64, downstream: u64) -> Vec<Vec<&'b B>>
+where
+    A: Coordinates + Stranded,
+    B: Coordinates
+{
+    window_core(a, b, upstream, downstream, |x| x.defined_strand() == Some(false))
+}
+
+fn window_core<'b, A, B, F>(
+    a: &[A], b: &'b [B], upstream: u64, downstream: u64, flip: F
+) -> Vec<Vec<&'b B>>
+where
+    A: Coordinates,
+    B: Coordinates,
+    F: Fn(&A) -> bool
+{
+    let index = index_by_chrom(b);
+    let mut output: Vec<Vec<&'b B>> = Vec::with_capacity(a.len());
+    for query in a {
+        let (chrom_index, start, end) = match (query.chrom(), query.start(), query.end()) {
+            (Some(c), Some(s), Some(e)) => {
+                match index.get(c) {
+                    Some(x) => {(x, *s, *e)},
+                    None => {output.push(Vec::new()); continue}
+                }
+            },
+            _ => {output.push(Vec::new()); continue}
+        };
+        let (left, right) = if flip(query) {(downstream, upstream)} else {(upstream, downstream)};
+        let window_start = start.saturating_sub(left);
+        let window_end = end.saturating_add(right);
+        let hits: Vec<&'b B> = chrom_index.overlapping(b, window_start, window_end)
+            .map(|i| &b[i])
+            .collect();
+        output.push(hits);
+    }
+    output
+}
+
+/// Coverage of a single query record by the target features, as reported by `coverage()`
+#[derive(Debug)]
+pub struct Coverage<'a, A> {
+    pub query: &'a A,
+    /// Number of target features overlapping the query
+    pub count: usize,
+    /// Number of query bases covered by at least one target feature
+    pub covered: u64,
+    /// Query length
+    pub length: u64,
+    /// Fraction of query bases covered by at least one target feature
+    pub fraction: f64,
+    /// Number of query bases covered at each depth, starting from zero
+    pub histogram: Option<Vec<u64>>
+}
+
+/// Compute the coverage of each of the `a` records by the `b` features
+///
+/// # Arguments
+/// `a`: query records;
+/// `b`: target features, in any order;
+/// `histogram`: if set, the per-depth histogram of covered bases is computed for each query
+///
+/// # Returns
+/// A vector of coverage records, in the order of `a`
+///
+pub fn coverage<'a, A, B>(a: &'a [A], b: &[B], histogram: bool) -> Vec<Coverage<'a, A>>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    let index = index_by_chrom(b);
+    a.iter().map(|x| query_coverage(x, b, &index, histogram)).collect()
+}
+
+/// Compute the coverage of a single query record by the indexed `b` features
+pub(crate) fn query_coverage<'a, A, B>(
+    query: &'a A, b: &[B], index: &FxHashMap<&String, ChromIndex>, histogram: bool
+) -> Coverage<'a, A>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    let (start, end) = match (query.start(), query.end()) {
+        (Some(s), Some(e)) => {(*s, max(*s, *e))},
+        _ => {(0, 0)}
+    };
+    let length = end - start;
+    // depth changes within the query, as (position, increment) pairs
+    let mut events: Vec<(u64, i64)> = Vec::new();
+    if let Some(chrom_index) = query.chrom().and_then(|c| index.get(c)) {
+        for i in chrom_index.overlapping(b, start, end) {
+            events.push((max(*b[i].start().unwrap(), start), 1));
+            events.push((min(*b[i].end().unwrap(), end), -1));
+        }
+    }
+    let count = events.len() / 2;
+    events.sort_unstable();
+    let mut depth_histogram: Vec<u64> = vec![0];
+    let mut depth: usize = 0;
+    let mut prev = start;
+    for (pos, change) in events {
+        if depth >= depth_histogram.len() {depth_histogram.resize(depth + 1, 0)};
+        depth_histogram[depth] += pos - prev;
+        depth = (depth as i64 + change) as usize;
+        prev = pos;
+    }
+    depth_histogram[0] += end - prev;
+    let covered = length - depth_histogram[0];
+    let fraction = if length > 0 {covered as f64 / length as f64} else {0.0};
+    Coverage {
+        query,
+        count,
+        covered,
+        length,
+        fraction,
+        histogram: if histogram {Some(depth_histogram)} else {None}
+    }
+}
+
+/// Base-level overlap enrichment test results, as reported by `fisher()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FisherResult {
+    /// Contingency table of genome bases: [[in A and B, in A only], [in B only, in neither]]
+    pub table: [[u64; 2]; 2],
+    /// Odds ratio of the contingency table
+    pub ratio: f64,
+    /// P-value for depletion of the overlap
+    pub left: f64,
+    /// P-value for enrichment of the overlap
+    pub right: f64,
+    /// Two-sided p-value
+    pub two_sided: f64
+}
+
+/// Assess whether the two sets of records overlap more or less than expected by chance
+///
+/// # Arguments
+/// `a`: the first set of records;
+/// `b`: the second set of records;
+/// `chrom_sizes`: chromosome sizes defining the genome space; bases outside of it are ignored
+///
+/// # Returns
+/// The 2x2 contingency table of bases covered by either set along with the Fisher exact test p-values;
+/// a CoordinateError if any of the records has undefined coordinates
+///
+pub fn fisher<A, B>(a: &[A], b: &[B], chrom_sizes: &ChromSizes) -> Result<FisherResult, CubiculumError>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    let mut a_blocks = to_intervals(a);
+    let mut b_blocks = to_intervals(b);
+    let a_bases = covered_bases(&mut a_blocks, chrom_sizes)?;
+    let b_bases = covered_bases(&mut b_blocks, chrom_sizes)?;
+    a_blocks.append(&mut b_blocks);
+    let union = covered_bases(&mut a_blocks, chrom_sizes)?;
+    let total = chrom_sizes.total_length();
+    let both = a_bases + b_bases - union;
+    let table = [[both, a_bases - both], [b_bases - both, total - union]];
+    let ratio = (table[0][0] as f64 * table[1][1] as f64) / (table[0][1] as f64 * table[1][0] as f64);
+    let test = Hypergeometric {total, successes: b_bases, draws: a_bases};
+    let (left, right, two_sided) = test.p_values(both);
+    Ok(FisherResult {table, ratio, left, right, two_sided})
+}
+
+fn to_intervals<T: Coordinates>(records: &[T]) -> Vec<Interval> {
+    records.iter()
+        .map(|x| Interval::from(x.chrom().cloned(), x.start().copied(), x.end().copied(), None))
+        .collect()
+}
+
+/// Returns the number of genome bases covered by the intervals
+fn covered_bases(intervals: &mut [Interval], chrom_sizes: &ChromSizes) -> Result<u64, CubiculumError> {
+    let uncovered: u64 = complement(intervals, chrom_sizes)?
+        .iter()
+        .map(|x| x.length().unwrap_or(0))
+        .sum();
+    Ok(chrom_sizes.total_length() - uncovered)
+}
+
+/// Hypergeometric distribution of the number of successes among the draws
+struct Hypergeometric {
+    total: u64,
+    successes: u64,
+    draws: u64
+}
+
+impl Hypergeometric {
+    fn support(&self) -> (u64, u64) {
+        (
+            (self.draws + self.successes).saturating_sub(self.total),
+            min(self.draws, self.successes)
+        )
+    }
+
+    fn mode(&self) -> u64 {
+        let (lo, hi) = self.support();
+        let mode = ((self.draws as f64 + 1.0) * (self.successes as f64 + 1.0) / (self.total as f64 + 2.0)).floor() as u64;
+        mode.clamp(lo, hi)
+    }
+
+    fn ln_pmf(&self, k: u64) -> f64 {
+        ln_choose(self.successes, k)
+            + ln_choose(self.total - self.successes, self.draws - k)
+            - ln_choose(self.total, self.draws)
+    }
+
+    /// Sum the probabilities from `k` towards the distribution's edge, moving away from the mode
+    fn tail(&self, k: u64, upwards: bool) -> f64 {
+        let (lo, hi) = self.support();
+        if k < lo || k > hi {return 0.0};
+        let mut sum = 0.0;
+        let mut i = k;
+        loop {
+            let term = self.ln_pmf(i).exp();
+            sum += term;
+            if term <= sum * 1e-17 {break};
+            if upwards {
+                if i == hi {break};
+                i += 1;
+            } else {
+                if i == lo {break};
+                i -= 1;
+            }
+        }
+        sum
+    }
+
+    /// Returns the left, right and two-sided p-values for the observed number of successes
+    fn p_values(&self, observed: u64) -> (f64, f64, f64) {
+        let (lo, hi) = self.support();
+        if lo == hi {return (1.0, 1.0, 1.0)};
+        let mode = self.mode();
+        let left = if observed <= mode {
+            self.tail(observed, false)
+        } else {
+            1.0 - self.tail(observed + 1, true)
+        };
+        let right = if observed >= mode {
+            self.tail(observed, true)
+        } else if observed == 0 {
+            1.0
+        } else {
+            1.0 - self.tail(observed - 1, false)
+        };
+        // tables as or less probable than the observed one on the opposite side of the mode
+        let threshold = self.ln_pmf(observed) + 1e-7;
+        let two_sided = if observed <= mode {
+            let k = partition(mode, hi + 1, |x| self.ln_pmf(x) > threshold);
+            self.tail(observed, false) + if k <= hi {self.tail(k, true)} else {0.0}
+        } else {
+            let k = partition(lo, mode + 1, |x| self.ln_pmf(x) <= threshold);
+            self.tail(observed, true) + if k > lo {self.tail(k - 1, false)} else {0.0}
+        };
+        (left.clamp(0.0, 1.0), right.clamp(0.0, 1.0), two_sided.min(1.0))
+    }
+}
+
+/// Returns the first value in [lo, hi) for which `pred` stops holding, given that it is monotone
+fn partition<F: Fn(u64) -> bool>(mut lo: u64, mut hi: u64, pred: F) -> u64 {
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if pred(mid) {lo = mid + 1} else {hi = mid}
+    }
+    lo
+}
+
+fn ln_choose(n: u64, k: u64) -> f64 {
+    ln_gamma(n as f64 + 1.0) - ln_gamma(k as f64 + 1.0) - ln_gamma((n - k) as f64 + 1.0)
+}
+
+/// Lanczos approximation of the gamma function's logarithm for positive arguments
+fn ln_gamma(x: f64) -> f64 {
+    const COEFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7
+    ];
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let mut series = COEFS[0];
+    for (i, coef) in COEFS.iter().enumerate().skip(1) {
+        series += coef / (x + i as f64);
+    }
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// A bin of the relative distance distribution reported by `reldist()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RelDistBin {
+    /// Lower bound of the bin's relative distance values
+    pub reldist: f64,
+    /// Number of `a` records falling into the bin
+    pub count: usize,
+    /// Total number of `a` records with defined relative distances
+    pub total: usize,
+    /// Fraction of `a` records falling into the bin
+    pub fraction: f64
+}
+
+/// Compute the relative distances from the `a` midpoints to the flanking `b` midpoints
+///
+/// For each `a` record, the distance to the closest of the two flanking `b` feature midpoints is divided
+/// by the distance between the two `b` midpoints, resulting in a value between 0 and 0.5
+/// (Favorov et al., 2012); records lacking either of the flanking features are skipped
+///
+/// # Returns
+/// A vector of relative distances, in the order of `a`
+///
+pub fn relative_distances<A, B>(a: &[A], b: &[B]) -> Vec<f64>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    relative_distance_ratios(a, b)
+        .into_iter()
+        .map(|(dist, span)| dist as f64 / span as f64)
+        .collect()
+}
+
+/// Returns the (distance to the closest flanking midpoint, distance between flanking midpoints) pairs
+fn relative_distance_ratios<A, B>(a: &[A], b: &[B]) -> Vec<(u64, u64)>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    let mut midpoints: FxHashMap<&String, Vec<u64>> = FxHashMap::default();
+    for x in b {
+        if let (Some(c), Some(s), Some(e)) = (x.chrom(), x.start(), x.end()) {
+            midpoints.entry(c).or_default().push((s + e) / 2);
+        }
+    }
+    for chrom_midpoints in midpoints.values_mut() {
+        chrom_midpoints.sort_unstable();
+    }
+    let mut ratios: Vec<(u64, u64)> = Vec::new();
+    for x in a {
+        let (chrom_midpoints, mid) = match (x.chrom(), x.start(), x.end()) {
+            (Some(c), Some(s), Some(e)) => {
+                match midpoints.get(c) {
+                    Some(m) => {(m, (s + e) / 2)},
+                    None => {continue}
+                }
+            },
+            _ => {continue}
+        };
+        let right = chrom_midpoints.partition_point(|m| *m <= mid);
+        if right == 0 || right == chrom_midpoints.len() {continue};
+        let (left_mid, right_mid) = (chrom_midpoints[right - 1], chrom_midpoints[right]);
+        ratios.push((min(mid - left_mid, right_mid - mid), right_mid - left_mid));
+    }
+    ratios
+}
+
+/// Compute the relative distance distribution between the two sets of records
+///
+/// # Returns
+/// Fifty bins of 0.01 width spanning relative distances from 0 to 0.5;
+/// a uniform distribution indicates no spatial correlation between the two sets,
+/// while an excess of small distances points to their colocalization
+///
+pub fn reldist<A, B>(a: &[A], b: &[B]) -> Vec<RelDistBin>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    const BIN_NUM: usize = 50;
+    let ratios = relative_distance_ratios(a, b);
+    let total = ratios.len();
+    let mut counts: Vec<usize> = vec![0; BIN_NUM];
+    for (dist, span) in ratios {
+        let bin = min((dist * 100 / span) as usize, BIN_NUM - 1);
+        counts[bin] += 1;
+    }
+    counts.into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            RelDistBin {
+                reldist: i as f64 / 100.0,
+                count,
+                total,
+                fraction: if total > 0 {count as f64 / total as f64} else {0.0}
+            }
+        })
+        .collect()
+}
+
+/// Genome segments labeled by the input sets covering them, as reported by `multiinter()`
+#[derive(Clone, Debug, Default)]
+pub struct MultiIntersection {
+    /// Segments covered by at least one of the sets, sorted by chromosome and coordinates
+    pub segments: Vec<Interval>,
+    /// Presence matrix; `presence[i][j]` is set if the `i`-th segment is covered by the `j`-th set
+    pub presence: Vec<Vec<bool>>
+}
+
+impl MultiIntersection {
+    /// Returns the number of sets covering the `i`-th segment
+    pub fn count(&self, i: usize) -> usize {
+        self.presence[i].iter().filter(|x| **x).count()
+    }
+}
+
+/// Partition the genome into segments labeled by which of the input sets cover them
+///
+/// # Arguments
+/// `sets`: record sets to compare; records within each set may overlap and come in any order
+///
+/// # Returns
+/// Segments covered by at least one set along with their presence matrix;
+/// segments are split wherever the combination of covering sets changes
+///
+pub fn multiinter<T: Coordinates>(sets: &[&[T]]) -> MultiIntersection {
+    let mut events: FxHashMap<&String, Vec<(u64, usize, i64)>> = FxHashMap::default();
+    for (set, records) in sets.iter().enumerate() {
+        for x in records.iter() {
+            if let (Some(c), Some(s), Some(e)) = (x.chrom(), x.start(), x.end()) {
+                if s >= e {continue};
+                let chrom_events = events.entry(c).or_default();
+                chrom_events.push((*s, set, 1));
+                chrom_events.push((*e, set, -1));
+            }
+        }
+    }
+    let mut chroms: Vec<&String> = events.keys().copied().collect();
+    chroms.sort();
+    let mut output = MultiIntersection::default();
+    for chrom in chroms {
+        let mut chrom_events = events.remove(chrom).unwrap();
+        chrom_events.sort_unstable();
+        let mut depths: Vec<i64> = vec![0; sets.len()];
+        let mut prev: u64 = 0;
+        for (pos, set, change) in chrom_events {
+            if pos > prev && depths.iter().any(|x| *x > 0) {
+                let presence: Vec<bool> = depths.iter().map(|x| *x > 0).collect();
+                let last = output.segments.len();
+                let extends = last > 0
+                    && output.presence[last - 1] == presence
+                    && output.segments[last - 1].chrom() == Some(chrom)
+                    && output.segments[last - 1].end() == Some(&prev);
+                if extends {
+                    output.segments[last - 1].update_end(pos);
+                } else {
+                    output.segments.push(Interval::from(Some(chrom.clone()), Some(prev), Some(pos), None));
+                    output.presence.push(presence);
+                }
+            }
+            depths[set] += change;
+            prev = pos;
+        }
+    }
+    output
+}
+
+/// Overlap summary of a single query record against several annotation sets, as reported by `annotate()`
+#[derive(Debug)]
+pub struct Annotation<'a, A> {
+    pub query: &'a A,
+    /// Number of overlapping features in each annotation set
+    pub counts: Vec<usize>,
+    /// Fraction of query bases covered by each annotation set
+    pub fractions: Vec<f64>
+}
+
+impl<A> Annotation<'_, A> {
+    /// Format the counts and fractions as tab-separated columns, two per annotation set,
+    /// to be appended to the query's line
+    pub fn columns(&self) -> String {
+        self.counts.iter()
+            .zip(self.fractions.iter())
+            .map(|(count, fraction)| format!("{}\t{}", count, fraction))
+            .collect::<Vec<String>>()
+            .join("\t")
+    }
+}
+
+/// Annotate the query records with their overlaps against several annotation sets
+///
+/// # Arguments
+/// `query`: records to annotate;
+/// `annotations`: annotation sets, records within each set may come in any order;
+/// `criteria`: overlapping features failing the criteria are ignored
+///
+/// # Returns
+/// A vector of per-set overlap counts and covered fractions for each of the query records,
+/// in the order of `query`
+///
+pub fn annotate<'a, A, B>(query: &'a [A], annotations: &[&[B]], criteria: &OverlapCriteria) -> Vec<Annotation<'a, A>>
+where
+    A: Coordinates,
+    B: Coordinates
+{
+    let indices: Vec<FxHashMap<&String, ChromIndex>> = annotations.iter()
+        .map(|x| index_by_chrom(x))
+        .collect();
+    let mut output: Vec<Annotation<'a, A>> = Vec::with_capacity(query.len());
+    for x in query {
+        let mut counts: Vec<usize> = vec![0; annotations.len()];
+        let mut fractions: Vec<f64> = vec![0.0; annotations.len()];
+        if let (Some(chrom), Some(start), Some(end)) = (x.chrom(), x.start(), x.end()) {
+            let (start, end) = (*start, *end);
+            for (i, (set, index)) in annotations.iter().zip(indices.iter()).enumerate() {
+                let chrom_index = match index.get(chrom) {
+                    Some(c) => {c},
+                    None => {continue}
+                };
+                let mut covered: u64 = 0;
+                let mut covered_until = start;
+                // overlapping features come sorted by their start coordinates
+                for j in chrom_index.overlapping(set, start, end) {
+                    if !criteria.passes(x, &set[j]) {continue};
+                    counts[i] += 1;
+                    let block_start = max(*set[j].start().unwrap(), covered_until);
+                    let block_end = min(*set[j].end().unwrap(), end);
+                    if block_end > block_start {
+                        covered += block_end - block_start;
+                        covered_until = block_end;
+                    }
+                }
+                if end > start {fractions[i] = covered as f64 / (end - start) as f64};
+            }
+        }
+        output.push(Annotation {query: x, counts, fractions});
+    }
+    output
+}
+
+#[cfg(test)]
+mod test_intersect {
+    use super::*;
+
+    fn interval(chrom: &str, start: u64, end: u64, name: &str) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), Some(name.to_string()))
+    }
+
+    #[test]
+    fn sweep_pairs() {
+        use crate::structs::structs::Named;
+        let a = vec![
+            interval("chr1", 100, 200, "a1"),
+            interval("chr1", 150, 400, "a2"),
+            interval("chr2", 0, 50, "a3"),
+        ];
+        let b = vec![
+            interval("chr1", 0, 100, "b1"),
+            interval("chr1", 120, 160, "b2"),
+            interval("chr1", 300, 310, "b3"),
+            interval("chr1", 500, 600, "b4"),
+            interval("chr2", 10, 20, "b5"),
+        ];
+        let pairs: Vec<(String, String)> = intersect(a, b)
+            .map(|x| x.unwrap())
+            .map(|(x, y)| (x.name().unwrap().to_string(), y.name().unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (String::from("a1"), String::from("b2")),
+                (String::from("a2"), String::from("b2")),
+                (String::from("a2"), String::from("b3")),
+                (String::from("a3"), String::from("b5")),
+            ]
+        );
+    }
+
+    #[test]
+    fn sweep_regions() {
+        let a = vec![interval("chr1", 100, 200, "a")];
+        let b = vec![interval("chr1", 50, 120, "b1"), interval("chr1", 180, 250, "b2")];
+        let regions: Vec<(u64, u64)> = intersect(a, b)
+            .regions()
+            .map(|x| x.unwrap())
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap()))
+            .collect();
+        assert_eq!(regions, vec![(100, 120), (180, 200)]);
+    }
+
+    #[test]
+    fn overlap_fractions() {
+        let a = interval("chr1", 100, 200, "a");
+        let b = interval("chr1", 150, 1000, "b");
+        assert!(OverlapCriteria::new().passes(&a, &b));
+        assert!(OverlapCriteria::new().min_fraction_a(0.5).passes(&a, &b));
+        assert!(!OverlapCriteria::new().min_fraction_a(0.6).passes(&a, &b));
+        assert!(!OverlapCriteria::new().min_fraction_b(0.1).passes(&a, &b));
+        assert!(!OverlapCriteria::new().min_fraction_a(0.5).reciprocal().passes(&a, &b));
+        assert!(
+            OverlapCriteria::new().min_fraction_a(0.5).min_fraction_b(0.1).either().passes(&a, &b)
+        );
+        assert!(!OverlapCriteria::new().passes(&a, &interval("chr1", 200, 300, "c")));
+    }
+
+    #[test]
+    fn sweep_with_criteria() {
+        let a = vec![interval("chr1", 100, 200, "a")];
+        let b = vec![interval("chr1", 50, 120, "b1"), interval("chr1", 110, 190, "b2")];
+        let hits = intersect(a, b)
+            .with_criteria(OverlapCriteria::new().min_fraction_a(0.5))
+            .count();
+        assert_eq!(hits, 1);
+    }
+
+    #[test]
+    fn split_blocks() {
+        let bed = |line: &str| crate::extract::extract::try_parse_bed(line, 12).unwrap();
+        // exons at 100-200 and 500-600
+        let a = vec![bed("chr1\t100\t600\ta\t0\t+\t100\t600\t0\t2\t100,100,\t0,400,")];
+        let b = vec![
+            bed("chr1\t150\t550\tboth\t0\t+\t150\t550\t0\t2\t100,50,\t0,350,"),
+            bed("chr1\t250\t450\tintronic\t0\t+\t250\t450\t0\t1\t200,\t0,"),
+        ];
+        assert_eq!(block_overlap_size(&a[0], &b[1]), 0);
+        assert_eq!(block_overlap_size(&a[0], &b[0]), 100);
+        assert_eq!(intersect(a.clone(), b.clone()).count(), 2);
+        let names: Vec<String> = intersect(a.clone(), b.clone())
+            .split()
+            .map(|x| x.unwrap())
+            .map(|(_, y)| y.name().unwrap().to_string())
+            .collect();
+        assert_eq!(names, vec![String::from("both")]);
+        let strict = intersect(a, b)
+            .split()
+            .with_criteria(OverlapCriteria::new().min_fraction_b(0.7))
+            .count();
+        assert_eq!(strict, 0);
+    }
+
+    #[test]
+    fn closest_features() {
+        use crate::structs::structs::Named;
+        let a = vec![interval("chr1", 100, 200, "a1"), interval("chr2", 0, 10, "a2")];
+        let b = vec![
+            interval("chr1", 350, 400, "right"),
+            interval("chr1", 0, 50, "far_left"),
+            interval("chr1", 60, 100, "bookended"),
+            interval("chr1", 199, 250, "overlap"),
+            interval("chr1", 250, 260, "tie"),
+        ];
+        let summary = |hits: &Vec<ClosestHit<'_, Interval>>| -> Vec<(String, i64)> {
+            hits.iter().map(|x| (x.target.name().unwrap().to_string(), x.distance)).collect()
+        };
+        let hits = closest(&a, &b, 2, &ClosestOptions::default());
+        assert_eq!(
+            summary(&hits[0]),
+            vec![(String::from("overlap"), 0), (String::from("bookended"), -1)]
+        );
+        assert!(hits[1].is_empty());
+        let options = ClosestOptions {ignore_overlaps: true, all_ties: true, ..Default::default()};
+        let hits = closest(&a, &b, 2, &options);
+        assert_eq!(
+            summary(&hits[0]),
+            vec![(String::from("bookended"), -1), (String::from("far_left"), -51), (String::from("tie"), 51)]
+        );
+    }
+
+    #[test]
+    fn closest_on_minus_strand() {
+        let a = vec![crate::extract::extract::try_parse_bed("chr1\t100\t200\ta\t0\t-", 6).unwrap()];
+        let b = vec![interval("chr1", 0, 50, "b1"), interval("chr1", 300, 400, "b2")];
+        let distances: Vec<i64> = closest_stranded(&a, &b, 2, &ClosestOptions::default())[0]
+            .iter()
+            .map(|x| x.distance)
+            .collect();
+        assert_eq!(distances, vec![51, -101]);
+    }
+
+    #[test]
+    fn window_around_features() {
+        use crate::structs::structs::Named;
+        let b = vec![
+            interval("chr1", 0, 50, "far_left"),
+            interval("chr1", 60, 80, "left"),
+            interval("chr1", 150, 160, "inside"),
+            interval("chr1", 240, 300, "right"),
+            interval("chr2", 100, 200, "other"),
+        ];
+        let names = |hits: &Vec<&Interval>| -> Vec<String> {
+            hits.iter().map(|x| x.name().unwrap().to_string()).collect()
+        };
+        let a = vec![interval("chr1", 100, 200, "a")];
+        assert_eq!(names(&window(&a, &b, 30, 50)[0]), vec!["left", "inside", "right"]);
+        assert_eq!(names(&window(&a, &b, 0, 10)[0]), vec!["inside"]);
+        let empty = vec![interval("chr1", 155, 155, "e")];
+        assert!(window(&empty, &b, 0, 0)[0].is_empty());
+        let minus = vec![crate::extract::extract::try_parse_bed("chr1\t100\t200\ta\t0\t-", 6).unwrap()];
+        assert_eq!(names(&window_stranded(&minus, &b, 50, 0)[0]), vec!["inside", "right"]);
+        let unstranded = vec![crate::extract::extract::try_parse_bed("chr1\t100\t200\ta", 4).unwrap()];
+        assert_eq!(names(&window_stranded(&unstranded, &b, 50, 0)[0]), vec!["left", "inside"]);
+        assert_eq!(closest_stranded(&unstranded, &b, 1, &ClosestOptions::default())[0].len(), 1);
+    }
+
+    #[test]
+    fn query_coverage() {
+        let a = vec![interval("chr1", 100, 200, "a1"), interval("chr2", 0, 10, "a2")];
+        let b = vec![
+            interval("chr1", 150, 300, "b1"),
+            interval("chr1", 50, 120, "b2"),
+            interval("chr1", 160, 170, "b3"),
+            interval("chr1", 200, 210, "b4"),
+        ];
+        let result = coverage(&a, &b, true);
+        assert_eq!(result[0].count, 3);
+        assert_eq!(result[0].covered, 70);
+        assert_eq!(result[0].length, 100);
+        assert!((result[0].fraction - 0.7).abs() < 1e-9);
+        assert_eq!(result[0].histogram, Some(vec![30, 60, 10]));
+        assert_eq!(result[1].count, 0);
+        assert_eq!(result[1].histogram, Some(vec![10]));
+        assert_eq!(coverage(&a, &b, false)[0].histogram, None);
+    }
+
+    #[test]
+    fn fisher_test() {
+        let mut sizes = ChromSizes::new();
+        sizes.insert(String::from("chr1"), 8);
+        let a = vec![interval("chr1", 0, 4, "a")];
+        let b = vec![interval("chr1", 1, 5, "b"), interval("chr2", 0, 100, "ignored")];
+        let result = fisher(&a, &b, &sizes).unwrap();
+        assert_eq!(result.table, [[3, 1], [1, 3]]);
+        assert!((result.ratio - 9.0).abs() < 1e-9);
+        assert!((result.right - 17.0 / 70.0).abs() < 1e-9);
+        assert!((result.left - 69.0 / 70.0).abs() < 1e-9);
+        assert!((result.two_sided - 34.0 / 70.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn relative_distance() {
+        let b = vec![
+            interval("chr1", 0, 20, "b1"),
+            interval("chr1", 100, 120, "b2"),
+            interval("chr1", 300, 320, "b3"),
+        ];
+        let a = vec![
+            interval("chr1", 0, 5, "before_b1"),
+            interval("chr1", 30, 40, "a1"),
+            interval("chr1", 105, 115, "at_b2"),
+            interval("chr1", 200, 220, "a2"),
+            interval("chr1", 400, 410, "after_b3"),
+            interval("chr2", 400, 410, "other"),
+        ];
+        assert_eq!(relative_distances(&a, &b), vec![0.25, 0.0, 0.5]);
+        let bins = reldist(&a, &b);
+        assert_eq!(bins.len(), 50);
+        assert_eq!((bins[0].count, bins[25].count, bins[49].count), (1, 1, 1));
+        assert_eq!(bins[0].total, 3);
+    }
+
+    #[test]
+    fn multiple_set_intersection() {
+        let first = vec![interval("chr1", 0, 100, "x"), interval("chr2", 0, 10, "x")];
+        let second = vec![interval("chr1", 50, 150, "y")];
+        let third = vec![interval("chr1", 60, 70, "z"), interval("chr1", 70, 80, "z")];
+        let result = multiinter(&[first.as_slice(), second.as_slice(), third.as_slice()]);
+        let segments: Vec<(String, u64, u64)> = result.segments
+            .iter()
+            .map(|x| (x.chrom().unwrap().clone(), *x.start().unwrap(), *x.end().unwrap()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (String::from("chr1"), 0, 50),
+                (String::from("chr1"), 50, 60),
+                (String::from("chr1"), 60, 80),
+                (String::from("chr1"), 80, 100),
+                (String::from("chr1"), 100, 150),
+                (String::from("chr2"), 0, 10),
+            ]
+        );
+        assert_eq!(result.presence[2], vec![true, true, true]);
+        assert_eq!(result.presence[4], vec![false, true, false]);
+        assert_eq!(result.count(1), 2);
+    }
+
+    #[test]
+    fn multiple_set_annotation() {
+        let query = vec![interval("chr1", 100, 200, "q1"), interval("chr2", 0, 100, "q2")];
+        let exons = vec![
+            interval("chr1", 150, 170, "e1"),
+            interval("chr1", 90, 120, "e2"),
+            interval("chr1", 160, 180, "e3"),
+        ];
+        let repeats = vec![interval("chr2", 50, 150, "r1"), interval("chr1", 0, 1000, "r2")];
+        let result = annotate(&query, &[exons.as_slice(), repeats.as_slice()], &OverlapCriteria::default());
+        assert_eq!(result[0].counts, vec![3, 1]);
+        assert_eq!(result[0].fractions, vec![0.5, 1.0]);
+        assert_eq!(result[1].counts, vec![0, 1]);
+        assert_eq!(result[1].columns(), "0\t0\t1\t0.5");
+        let strict = annotate(&query, &[exons.as_slice()], &OverlapCriteria::new().min_fraction_b(1.0));
+        assert_eq!(strict[0].counts, vec![2]);
+        assert_eq!(strict[0].fractions, vec![0.3]);
+    }
+
+    #[test]
+    fn unsorted_input() {
+        let a = vec![interval("chr1", 300, 400, "a1"), interval("chr1", 100, 200, "a2")];
+        let b = vec![interval("chr1", 0, 1000, "b")];
+        let mut stream = intersect(a.clone(), b.clone());
+        assert!(stream.next().unwrap().is_ok());
+        assert!(matches!(stream.next(), Some(Err(CubiculumError::UnsortedInput(_)))));
+        assert!(stream.next().is_none());
+        let b = vec![interval("chr1", 350, 1000, "b1"), interval("chr1", 0, 1000, "b2")];
+        let mut stream = intersect(a, b);
+        assert!(matches!(stream.next(), Some(Err(CubiculumError::UnsortedInput(_)))));
+        assert!(stream.next().is_none());
+    }
+}
@@ -1,13 +1,84 @@
 //! A stub for a Rust crate for BED file manipulation
 //! 
+//! With the default features disabled (`--no-default-features`), the library has no file system
+//! or threading dependencies and can be built for `wasm32-unknown-unknown`
+//! 
 
 #![warn(rust_2021_compatibility)]
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod collection;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod coverage;
+pub mod error;
 pub mod extract;
+#[cfg(feature = "fasta")]
+pub mod fasta;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+#[cfg(feature = "polars")]
+pub mod frame;
+pub mod gap;
+pub mod gene;
+pub mod index;
+pub mod intersect;
+pub mod liftover;
+pub mod maf;
 pub mod merge;
+#[cfg(feature = "noodles")]
+pub mod noodles;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod options;
+pub mod pipeline;
+pub mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod sample;
+pub mod sort;
+pub mod stats;
 pub mod structs;
+pub mod track;
+pub mod transform;
 
+#[cfg(feature = "async")]
+pub use crate::async_io::*;
+pub use crate::collection::*;
+#[cfg(feature = "arrow")]
+pub use crate::columnar::*;
+pub use crate::coverage::*;
+pub use crate::error::*;
 pub use crate::extract::*;
+#[cfg(feature = "fasta")]
+pub use crate::fasta::*;
+#[cfg(feature = "ffi")]
+pub use crate::ffi::*;
+pub use crate::filter::*;
+#[cfg(feature = "polars")]
+pub use crate::frame::*;
+pub use crate::gap::*;
+pub use crate::gene::*;
+pub use crate::index::*;
+pub use crate::intersect::*;
+pub use crate::liftover::*;
+pub use crate::maf::*;
 pub use crate::merge::*;
-pub use crate::structs::*;
\ No newline at end of file
+#[cfg(feature = "noodles")]
+pub use crate::noodles::*;
+#[cfg(feature = "parallel")]
+pub use crate::parallel::*;
+pub use crate::options::*;
+pub use crate::pipeline::*;
+pub use crate::progress::*;
+#[cfg(feature = "python")]
+pub use crate::python::*;
+pub use crate::sample::*;
+pub use crate::sort::*;
+pub use crate::stats::*;
+pub use crate::structs::*;
+pub use crate::track::*;
+pub use crate::transform::*;
\ No newline at end of file
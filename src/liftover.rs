@@ -0,0 +1,5 @@
+/*!
+Module for coordinate conversion between genome assemblies with UCSC chain files
+*/
+
+pub mod liftover;
@@ -0,0 +1,435 @@
+//! # cubiculum::liftover
+//!
+//! Coordinate conversion between genome assemblies with UCSC chain files
+//!
+//! As in UCSC liftOver, each record is mapped through a single chain: a record is mapped if a chain
+//! covers at least the requested fraction of its bases, and is reported as unmapped
+//! with an explicit reason otherwise
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::cmp::{max, min};
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::{BufRead, Write};
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{is_bed_header_line, is_track_line, to_line, try_parse_bed};
+use crate::structs::structs::{BedEntry, Coordinates, Interval, Named};
+
+/// An ungapped aligned block of a chain
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainBlock {
+    /// Block start in the source assembly
+    pub source_start: u64,
+    /// Block start in the target assembly, counted on the target strand of the chain
+    pub target_start: u64,
+    pub size: u64
+}
+
+/// A single chain alignment between the source (reference) and the target (query) assembly
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chain {
+    pub id: String,
+    pub score: f64,
+    pub source_chrom: String,
+    pub source_size: u64,
+    pub source_start: u64,
+    pub source_end: u64,
+    pub target_chrom: String,
+    pub target_size: u64,
+    /// False if the source maps to the reverse strand of the target
+    pub target_strand: bool,
+    /// Aligned blocks in ascending source coordinate order
+    pub blocks: Vec<ChainBlock>
+}
+
+impl Chain {
+    /// Map the source range through the chain
+    ///
+    /// # Returns
+    /// Start and end of the mapped range on the target forward strand and the number of aligned bases;
+    /// None if no base of the range is aligned
+    fn map_range(&self, start: u64, end: u64) -> Option<(u64, u64, u64)> {
+        let first = self.blocks.partition_point(|x| x.source_start + x.size <= start);
+        let mut covered: u64 = 0;
+        let mut mapped_start: Option<u64> = None;
+        let mut mapped_end: u64 = 0;
+        for block in &self.blocks[first..] {
+            if block.source_start >= end {break};
+            let overlap_start = max(start, block.source_start);
+            let overlap_end = min(end, block.source_start + block.size);
+            if overlap_start >= overlap_end {continue};
+            covered += overlap_end - overlap_start;
+            if mapped_start.is_none() {
+                mapped_start = Some(block.target_start + overlap_start - block.source_start);
+            }
+            mapped_end = block.target_start + overlap_end - block.source_start;
+        }
+        let mapped_start = mapped_start?;
+        if self.target_strand {
+            Some((mapped_start, mapped_end, covered))
+        } else {
+            Some((self.target_size - mapped_end, self.target_size - mapped_start, covered))
+        }
+    }
+}
+
+/// Reasons for a record to remain unmapped
+#[derive(Clone, Debug)]
+pub enum Unmapped {
+    /// No chain aligns any base of the record
+    Deleted,
+    /// The best chain aligns less than the requested fraction of bases; holds the aligned fraction
+    PartiallyDeleted(f64),
+    /// The requested fraction is aligned only by several chains combined; holds the mapped pieces
+    Split(Vec<Interval>),
+    /// Several chains align the requested fraction each; holds all the mappings
+    Duplicated(Vec<Interval>),
+    /// Some blocks of a BED12 entry are not aligned or overlap each other in the target assembly
+    BrokenBlocks
+}
+
+impl fmt::Display for Unmapped {
+    /// Reason line in the UCSC liftOver unmapped file style
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unmapped::Deleted => {write!(f, "Deleted in new")},
+            Unmapped::PartiallyDeleted(_) => {write!(f, "Partially deleted in new")},
+            Unmapped::Split(_) => {write!(f, "Split in new")},
+            Unmapped::Duplicated(_) => {write!(f, "Duplicated in new")},
+            Unmapped::BrokenBlocks => {write!(f, "Blocks not mapped in new")}
+        }
+    }
+}
+
+/// Result of a coordinate conversion
+#[derive(Clone, Debug)]
+pub enum Lifted<T> {
+    Mapped(T),
+    Unmapped(Unmapped)
+}
+
+/// Chains indexed by source chromosome
+#[derive(Clone, Debug, Default)]
+pub struct ChainMap {
+    chains: FxHashMap<String, Vec<Chain>>
+}
+
+fn parse_field<T: std::str::FromStr>(value: &str, line: &str) -> Result<T, CubiculumError> {
+    value.parse::<T>().map_err(|_| CubiculumError::ParseError(format!("Invalid chain line: {}", line)))
+}
+
+impl ChainMap {
+    /// Parse the chains from a UCSC chain file stream
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<ChainMap, CubiculumError> {
+        let mut map = ChainMap::default();
+        let mut current: Option<(Chain, u64, u64)> = None;
+        for line in reader.lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {continue};
+            let data: Vec<&str> = trimmed.split_whitespace().collect();
+            if data[0] == "chain" {
+                if current.is_some() {
+                    return Err(CubiculumError::ParseError(format!("Chain is missing its last block line before: {}", line)))
+                }
+                if data.len() < 13 {
+                    return Err(CubiculumError::ParseError(format!("Invalid chain header: {}", line)))
+                }
+                if data[4] != "+" {
+                    return Err(CubiculumError::ParseError(format!("Source strand must be '+': {}", line)))
+                }
+                let chain = Chain {
+                    id: data[12].to_string(),
+                    score: parse_field(data[1], &line)?,
+                    source_chrom: data[2].to_string(),
+                    source_size: parse_field(data[3], &line)?,
+                    source_start: parse_field(data[5], &line)?,
+                    source_end: parse_field(data[6], &line)?,
+                    target_chrom: data[7].to_string(),
+                    target_size: parse_field(data[8], &line)?,
+                    target_strand: data[9] != "-",
+                    blocks: Vec::new()
+                };
+                let target_start: u64 = parse_field(data[10], &line)?;
+                current = Some((chain.clone(), chain.source_start, target_start));
+                continue
+            }
+            let (chain, source_pos, target_pos) = current.as_mut().ok_or(
+                CubiculumError::ParseError(format!("Alignment line outside of a chain: {}", line))
+            )?;
+            let size: u64 = parse_field(data[0], &line)?;
+            chain.blocks.push(ChainBlock {source_start: *source_pos, target_start: *target_pos, size});
+            match data.len() {
+                1 => {
+                    // the last block of the chain
+                    let (chain, source_pos, _) = current.take().unwrap();
+                    if source_pos + size != chain.source_end {
+                        return Err(
+                            CubiculumError::ParseError(format!("Blocks of chain {} do not sum up to its end", chain.id))
+                        )
+                    }
+                    map.chains.entry(chain.source_chrom.clone()).or_default().push(chain);
+                },
+                3 => {
+                    *source_pos += size + parse_field::<u64>(data[1], &line)?;
+                    *target_pos += size + parse_field::<u64>(data[2], &line)?;
+                },
+                _ => {return Err(CubiculumError::ParseError(format!("Invalid chain line: {}", line)))}
+            }
+        }
+        if let Some((chain, _, _)) = current {
+            return Err(CubiculumError::ParseError(format!("Chain {} is missing its last block line", chain.id)))
+        }
+        for chains in map.chains.values_mut() {
+            chains.sort_by_key(|x| (x.source_start, x.source_end));
+        }
+        Ok(map)
+    }
+
+    /// Parse the chains from a UCSC chain file
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ChainMap, CubiculumError> {
+        let file = File::open(path.as_ref()).map_err(
+            |e| CubiculumError::IoError(format!("Cannot open {:?}: {}", path.as_ref(), e))
+        )?;
+        ChainMap::from_reader(BufReader::new(file))
+    }
+
+    /// Total number of chains
+    pub fn len(&self) -> usize {
+        self.chains.values().map(|x| x.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Find the single chain mapping the requested fraction of the source range
+    fn select(&self, chrom: &str, start: u64, end: u64, min_match: f64) -> Result<(&Chain, u64, u64), Unmapped> {
+        let length = end.saturating_sub(start).max(1) as f64;
+        let mapped: Vec<(&Chain, (u64, u64, u64))> = match self.chains.get(chrom) {
+            Some(chains) => {
+                chains.iter()
+                    .filter(|x| x.source_start < end && start < x.source_end)
+                    .filter_map(|x| x.map_range(start, end).map(|y| (x, y)))
+                    .collect()
+            },
+            None => {Vec::new()}
+        };
+        if mapped.is_empty() {return Err(Unmapped::Deleted)};
+        let piece = |chain: &Chain, coords: (u64, u64, u64)| {
+            Interval::from(Some(chain.target_chrom.clone()), Some(coords.0), Some(coords.1), None)
+        };
+        let passing: Vec<&(&Chain, (u64, u64, u64))> = mapped.iter()
+            .filter(|x| x.1.2 as f64 / length >= min_match)
+            .collect();
+        match passing.len() {
+            1 => {Ok((passing[0].0, passing[0].1.0, passing[0].1.1))},
+            0 => {
+                let total: u64 = mapped.iter().map(|x| x.1.2).sum();
+                if mapped.len() > 1 && total as f64 / length >= min_match {
+                    Err(Unmapped::Split(mapped.iter().map(|x| piece(x.0, x.1)).collect()))
+                } else {
+                    let best = mapped.iter().map(|x| x.1.2).max().unwrap();
+                    Err(Unmapped::PartiallyDeleted(best as f64 / length))
+                }
+            },
+            _ => {Err(Unmapped::Duplicated(passing.iter().map(|x| piece(x.0, x.1)).collect()))}
+        }
+    }
+
+    /// Map an interval to the target assembly; the name is preserved
+    ///
+    /// # Arguments
+    /// `interval`: interval to map;
+    /// `min_match`: minimal fraction of the interval bases a chain must align (0.95 in UCSC liftOver)
+    ///
+    pub fn lift_interval<T: Coordinates + Named>(&self, interval: &T, min_match: f64) -> Lifted<Interval> {
+        let (chrom, start, end) = match (interval.chrom(), interval.start(), interval.end()) {
+            (Some(x), Some(y), Some(z)) => {(x, *y, *z)},
+            _ => {return Lifted::Unmapped(Unmapped::Deleted)}
+        };
+        match self.select(chrom, start, end, min_match) {
+            Ok((chain, new_start, new_end)) => {
+                Lifted::Mapped(
+                    Interval::from(
+                        Some(chain.target_chrom.clone()),
+                        Some(new_start),
+                        Some(new_end),
+                        interval.name().map(|x| x.to_string())
+                    )
+                )
+            },
+            Err(reason) => {Lifted::Unmapped(reason)}
+        }
+    }
+
+    /// Map a BED entry to the target assembly
+    ///
+    /// The entry span is mapped with `min_match` as in `lift_interval()`; for BED12 entries, each block
+    /// is then mapped through the same chain, and the coding boundaries are mapped along with the blocks.
+    /// Entries mapped to the reverse strand of the target have their strand flipped
+    ///
+    pub fn lift_entry(&self, entry: &BedEntry, min_match: f64) -> Lifted<BedEntry> {
+        let (chrom, start, end) = match (entry.chrom(), entry.start(), entry.end()) {
+            (Some(x), Some(y), Some(z)) => {(x, *y, *z)},
+            _ => {return Lifted::Unmapped(Unmapped::Deleted)}
+        };
+        let (chain, new_start, new_end) = match self.select(chrom, start, end, min_match) {
+            Ok(x) => {x},
+            Err(reason) => {return Lifted::Unmapped(reason)}
+        };
+        let mut blocks: Vec<(u64, u64)> = if entry.format() == 12 && entry.exon_sizes().is_some() {
+            let mut blocks: Vec<(u64, u64)> = Vec::with_capacity(entry.exons().len());
+            for (block_start, block_end) in entry.exons() {
+                match chain.map_range(block_start, block_end) {
+                    Some((x, y, _)) => {blocks.push((x, y))},
+                    None => {return Lifted::Unmapped(Unmapped::BrokenBlocks)}
+                }
+            }
+            blocks
+        } else {
+            vec![(new_start, new_end)]
+        };
+        blocks.sort_unstable();
+        if blocks.windows(2).any(|x| x[0].1 > x[1].0) {
+            return Lifted::Unmapped(Unmapped::BrokenBlocks)
+        }
+        let thick: (u64, u64) = match (entry.thick_start(), entry.thick_end()) {
+            (Some(x), Some(y)) if x < y => {
+                chain.map_range(x, y).map_or((blocks[0].0, blocks[0].0), |z| (z.0, z.1))
+            },
+            _ => {(blocks[0].0, blocks[0].0)}
+        };
+        let strand = entry.strand().map(|x| x == chain.target_strand);
+        Lifted::Mapped(entry.relocated(chain.target_chrom.clone(), blocks, thick, strand))
+    }
+}
+
+/// Map all BED records from the stream to the target assembly
+///
+/// Mapped records are written to `mapped` in the input format; unmapped ones are written to `unmapped`,
+/// each preceded by a `#reason` comment line as in UCSC liftOver. Track/browser lines are copied to both outputs,
+/// while blank lines and comments are skipped
+///
+/// # Returns
+/// The numbers of mapped and unmapped records; an error if a line cannot be parsed or the output cannot be written
+///
+pub fn liftover_bed<R, W, U>(
+    map: &ChainMap, reader: R, format: usize, mapped: &mut W, unmapped: &mut U, min_match: f64
+) -> Result<(usize, usize), CubiculumError>
+where
+    R: BufRead,
+    W: Write,
+    U: Write
+{
+    let (mut mapped_num, mut unmapped_num): (usize, usize) = (0, 0);
+    for line in reader.lines() {
+        let line = line?;
+        let trimmed = line.trim();
+        if is_bed_header_line(trimmed) {
+            if is_track_line(trimmed) {
+                writeln!(mapped, "{}", trimmed)?;
+                writeln!(unmapped, "{}", trimmed)?;
+            }
+            continue
+        }
+        let entry = try_parse_bed(&line, format)?;
+        match map.lift_entry(&entry, min_match) {
+            Lifted::Mapped(x) => {
+                writeln!(mapped, "{}", to_line(&x, format as u8)?)?;
+                mapped_num += 1;
+            },
+            Lifted::Unmapped(reason) => {
+                writeln!(unmapped, "#{}\n{}", reason, trimmed)?;
+                unmapped_num += 1;
+            }
+        }
+    }
+    Ok((mapped_num, unmapped_num))
+}
+
+#[cfg(test)]
+mod test_liftover {
+    use super::*;
+
+    const CHAINS: &str = "chain 1000 chr1 1000 + 100 400 chrA 2000 + 1100 1420 1
+100 50 70
+150
+
+chain 500 chr1 1000 + 600 700 chrB 500 - 0 100 2
+100
+";
+
+    fn interval(chrom: &str, start: u64, end: u64) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), Some(String::from("x")))
+    }
+
+    fn coords(lifted: Lifted<Interval>) -> (String, u64, u64) {
+        match lifted {
+            Lifted::Mapped(x) => {(x.chrom().unwrap().clone(), *x.start().unwrap(), *x.end().unwrap())},
+            Lifted::Unmapped(reason) => {panic!("Interval unexpectedly unmapped: {}", reason)}
+        }
+    }
+
+    #[test]
+    fn chain_parsing() {
+        let map = ChainMap::from_reader(CHAINS.as_bytes()).unwrap();
+        assert_eq!(map.len(), 2);
+        let chain = &map.chains["chr1"][0];
+        assert_eq!(chain.blocks[1], ChainBlock {source_start: 250, target_start: 1270, size: 150});
+        assert!(ChainMap::from_reader("chain 1 chr1 10 + 0 10 chrA 10 + 0 10 1\n5 0 0\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn interval_mapping() {
+        let map = ChainMap::from_reader(CHAINS.as_bytes()).unwrap();
+        assert_eq!(coords(map.lift_interval(&interval("chr1", 120, 180), 0.95)), (String::from("chrA"), 1120, 1180));
+        // reverse strand chain
+        assert_eq!(coords(map.lift_interval(&interval("chr1", 610, 650), 0.95)), (String::from("chrB"), 450, 490));
+        // two thirds of the interval fall into the aligned blocks
+        assert_eq!(coords(map.lift_interval(&interval("chr1", 150, 300), 0.5)), (String::from("chrA"), 1150, 1320));
+        match map.lift_interval(&interval("chr1", 150, 300), 0.95) {
+            Lifted::Unmapped(Unmapped::PartiallyDeleted(x)) => {assert!((x - 2.0 / 3.0).abs() < 1e-9)},
+            _ => {panic!("Expected a partially deleted interval")}
+        }
+        assert!(matches!(map.lift_interval(&interval("chr1", 450, 550), 0.95), Lifted::Unmapped(Unmapped::Deleted)));
+        match map.lift_interval(&interval("chr1", 350, 650), 0.3) {
+            Lifted::Unmapped(Unmapped::Split(pieces)) => {assert_eq!(pieces.len(), 2)},
+            _ => {panic!("Expected a split interval")}
+        }
+    }
+
+    #[test]
+    fn entry_mapping() {
+        let map = ChainMap::from_reader(CHAINS.as_bytes()).unwrap();
+        let bed = "track name=test\n\
+            chr1\t100\t400\tA\t0\t+\t120\t300\t0\t2\t50,140,\t0,160,\n\
+            chr1\t600\t700\tB\t0\t+\t620\t680\t0\t1\t100,\t0,\n\
+            chr1\t100\t400\tC\t0\t+\t100\t400\t0\t2\t50,30,\t0,120,\n";
+        let mut mapped: Vec<u8> = Vec::new();
+        let mut unmapped: Vec<u8> = Vec::new();
+        let counts = liftover_bed(&map, bed.as_bytes(), 12, &mut mapped, &mut unmapped, 0.5).unwrap();
+        assert_eq!(counts, (2, 1));
+        assert_eq!(
+            String::from_utf8(mapped).unwrap(),
+            "track name=test\n\
+            chrA\t1100\t1420\tA\t0\t+\t1120\t1320\t0\t2\t50,140,\t0,180,\n\
+            chrB\t400\t500\tB\t0\t-\t420\t480\t0\t1\t100,\t0,\n"
+        );
+        assert_eq!(
+            String::from_utf8(unmapped).unwrap(),
+            "track name=test\n#Blocks not mapped in new\nchr1\t100\t400\tC\t0\t+\t100\t400\t0\t2\t50,30,\t0,120,\n"
+        );
+    }
+}
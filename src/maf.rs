@@ -0,0 +1,5 @@
+/*!
+Module for conversion of MAF multiple alignment blocks into BED records
+*/
+
+pub mod maf;
@@ -0,0 +1,298 @@
+//! # cubiculum::maf
+//!
+//! Conversion of MAF multiple alignment blocks into per-species BED records
+//!
+//! Each aligned sequence ('s' line) of a block is reported on the forward strand of its source
+//! chromosome, either as a single BED6 interval or as a BED12 record with the segments aligned to the block's
+//! reference (first) sequence as blocks.
+//! Source names follow the UCSC 'species.chromosome' convention
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::io::{BufRead, Lines, Write};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::to_line;
+use crate::structs::structs::BedEntry;
+
+/// A single aligned sequence of a MAF block
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MafComponent {
+    /// Source name as recorded in the file
+    pub src: String,
+    /// Start of the aligned region, counted on the aligned strand
+    pub start: u64,
+    /// Number of aligned (non-gap) bases
+    pub size: u64,
+    pub strand: bool,
+    /// Length of the source sequence
+    pub src_size: u64,
+    /// Alignment row, with gaps as '-'
+    pub text: String
+}
+
+impl MafComponent {
+    /// Species part of the source name; the whole name if it contains no dot
+    pub fn species(&self) -> &str {
+        self.src.split_once('.').map_or(&self.src, |x| x.0)
+    }
+
+    /// Chromosome part of the source name; the whole name if it contains no dot
+    pub fn chrom(&self) -> &str {
+        self.src.split_once('.').map_or(&self.src, |x| x.1)
+    }
+
+    /// Aligned region coordinates on the forward strand of the source
+    pub fn forward_coords(&self) -> (u64, u64) {
+        if self.strand {
+            (self.start, self.start + self.size)
+        } else {
+            (self.src_size - self.start - self.size, self.src_size - self.start)
+        }
+    }
+
+    /// Segments of the aligned region whose bases are aligned to bases of the `reference` row,
+    /// in ascending forward strand coordinates; segments adjacent in the source are merged
+    pub fn aligned_segments(&self, reference: &MafComponent) -> Vec<(u64, u64)> {
+        let mut segments: Vec<(u64, u64)> = Vec::new();
+        let mut pos = self.start;
+        let mut run_start: Option<u64> = None;
+        for (base, ref_base) in self.text.bytes().zip(reference.text.bytes()) {
+            if base != b'-' && ref_base != b'-' {
+                if run_start.is_none() {run_start = Some(pos)};
+            } else if let Some(x) = run_start.take() {
+                push_segment(&mut segments, x, pos);
+            }
+            if base != b'-' {pos += 1};
+        }
+        if let Some(x) = run_start {push_segment(&mut segments, x, pos)};
+        if !self.strand {
+            segments = segments.into_iter()
+                .rev()
+                .map(|(x, y)| (self.src_size - y, self.src_size - x))
+                .collect();
+        }
+        segments
+    }
+
+    /// BED6 record spanning the aligned region, with the species as name
+    pub fn to_bed(&self, score: &str) -> BedEntry {
+        let (start, end) = self.forward_coords();
+        BedEntry::bed6(
+            self.chrom().to_string(), start, end, self.species().to_string(), score.to_string(), self.strand
+        )
+    }
+
+    /// BED12 record with the segments aligned to the `reference` row as blocks;
+    /// None if no base is aligned to the reference
+    pub fn to_bed12(&self, reference: &MafComponent, score: &str) -> Option<BedEntry> {
+        let segments = self.aligned_segments(reference);
+        if segments.is_empty() {return None};
+        let start = segments[0].0;
+        let end = segments[segments.len() - 1].1;
+        Some(BedEntry::bed12(
+            self.chrom().to_string(), start, end, self.species().to_string(), score.to_string(), self.strand,
+            start, start, String::from("0"), segments.len() as u16,
+            segments.iter().map(|x| x.1 - x.0).collect(),
+            segments.iter().map(|x| x.0 - start).collect()
+        ))
+    }
+}
+
+fn push_segment(segments: &mut Vec<(u64, u64)>, start: u64, end: u64) {
+    match segments.last_mut() {
+        Some(last) if last.1 == start => {last.1 = end},
+        _ => {segments.push((start, end))}
+    }
+}
+
+/// A MAF alignment block
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MafBlock {
+    /// Block score from the 'a' line, if any
+    pub score: Option<String>,
+    pub components: Vec<MafComponent>
+}
+
+/// Iterator over the alignment blocks of a MAF stream
+pub struct MafReader<R> {
+    lines: Lines<R>,
+    line_num: usize
+}
+
+impl<R: BufRead> MafReader<R> {
+    pub fn new(reader: R) -> MafReader<R> {
+        MafReader {lines: reader.lines(), line_num: 0}
+    }
+}
+
+fn parse_component(line: &str, line_num: usize) -> Result<MafComponent, CubiculumError> {
+    let data: Vec<&str> = line.split_whitespace().collect();
+    let err = || CubiculumError::ParseError(format!("Invalid MAF sequence line at line {}: {}", line_num, line));
+    if data.len() != 7 {return Err(err())};
+    let component = MafComponent {
+        src: data[1].to_string(),
+        start: data[2].parse::<u64>().map_err(|_| err())?,
+        size: data[3].parse::<u64>().map_err(|_| err())?,
+        strand: match data[4] {
+            "+" => {true},
+            "-" => {false},
+            _ => {return Err(err())}
+        },
+        src_size: data[5].parse::<u64>().map_err(|_| err())?,
+        text: data[6].to_string()
+    };
+    if component.start + component.size > component.src_size {return Err(err())};
+    if component.text.bytes().filter(|x| *x != b'-').count() as u64 != component.size {return Err(err())};
+    Ok(component)
+}
+
+impl<R: BufRead> Iterator for MafReader<R> {
+    type Item = Result<MafBlock, CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut block: Option<MafBlock> = None;
+        for line in self.lines.by_ref() {
+            self.line_num += 1;
+            let line = match line {
+                Ok(x) => {x},
+                Err(e) => {return Some(Err(e.into()))}
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                if block.is_some() {break};
+                continue
+            }
+            if trimmed.starts_with('#') {continue};
+            match trimmed.as_bytes()[0] {
+                b'a' => {
+                    let score = trimmed.split_whitespace()
+                        .find_map(|x| x.strip_prefix("score="))
+                        .map(|x| x.to_string());
+                    block = Some(MafBlock {score, components: Vec::new()});
+                },
+                b's' => {
+                    let current = match block.as_mut() {
+                        Some(x) => {x},
+                        None => {
+                            return Some(Err(CubiculumError::ParseError(
+                                format!("Sequence line outside of an alignment block at line {}", self.line_num)
+                            )))
+                        }
+                    };
+                    match parse_component(trimmed, self.line_num) {
+                        Ok(x) => {
+                            if current.components.first().is_some_and(|y| y.text.len() != x.text.len()) {
+                                return Some(Err(CubiculumError::ParseError(
+                                    format!("Alignment row length differs from the block's at line {}", self.line_num)
+                                )))
+                            }
+                            current.components.push(x)
+                        },
+                        Err(e) => {return Some(Err(e))}
+                    }
+                },
+                // 'i', 'e' and 'q' lines carry no aligned coordinates
+                _ => {}
+            }
+        }
+        block.map(Ok)
+    }
+}
+
+/// Convert the aligned sequences of a MAF stream into BED records
+///
+/// # Arguments
+/// `reader`: MAF stream;
+/// `writer`: output stream;
+/// `species`: species to report; all species are reported if empty;
+/// `split`: if true, BED12 records with the segments aligned to the block's first sequence as blocks
+/// are reported instead of BED6 spans; sequences with no base aligned to the first one are skipped
+///
+/// # Returns
+/// The number of records written
+///
+pub fn maf_to_bed<R: BufRead, W: Write>(
+    reader: R, writer: &mut W, species: &[&str], split: bool
+) -> Result<usize, CubiculumError> {
+    let mut written: usize = 0;
+    for block in MafReader::new(reader) {
+        let block = block?;
+        let score = block.score.as_deref().unwrap_or("0");
+        for component in block.components.iter() {
+            if !species.is_empty() && !species.contains(&component.species()) {continue};
+            let line = if split {
+                match component.to_bed12(&block.components[0], score) {
+                    Some(x) => {to_line(&x, 12)?},
+                    None => {continue}
+                }
+            } else {
+                to_line(&component.to_bed(score), 6)?
+            };
+            writeln!(writer, "{}", line)?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test_maf {
+    use super::*;
+
+    const MAF: &str = "##maf version=1 scoring=tba.v8
+# comment
+
+a score=23262.0
+s hg18.chr7    27578828 38 + 158545518 AAA-GGGAATGTTAACCAAATGA---ATTGTCTCTTACGGTG
+s panTro1.chr6 28741140 38 + 161576975 AAA-GGGAATGTTAACCAAATGA---ATTGTCTCTTACGGTG
+i panTro1.chr6 N 0 C 0
+s mm4.chr6     53215344 36 - 151104725 -AATGGGAATGTTAAGCAAACGA---ATTGTCTCTCAGTG--
+
+a score=5062.0
+s hg18.chr7 27699739 6 + 158545518 TAAAGA
+e mm4.chr6 53310102 13 + 151104725 I
+";
+
+    #[test]
+    fn maf_parsing() {
+        let blocks: Vec<MafBlock> = MafReader::new(MAF.as_bytes()).collect::<Result<_, _>>().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].score.as_deref(), Some("23262.0"));
+        assert_eq!(blocks[0].components.len(), 3);
+        let mouse = &blocks[0].components[2];
+        assert_eq!((mouse.species(), mouse.chrom()), ("mm4", "chr6"));
+        assert_eq!(mouse.forward_coords(), (151104725 - 53215344 - 36, 151104725 - 53215344));
+        assert_eq!(blocks[1].components.len(), 1);
+        assert!(MafReader::new("a score=1\ns hg18.chr7 0 5 + 10 AC-G\n".as_bytes()).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn aligned_segments() {
+        let blocks: Vec<MafBlock> = MafReader::new(MAF.as_bytes()).collect::<Result<_, _>>().unwrap();
+        let human = &blocks[0].components[0];
+        assert_eq!(human.aligned_segments(human), vec![(27578828, 27578866)]);
+        let mouse = &blocks[0].components[2];
+        let end = 151104725 - 53215344;
+        assert_eq!(mouse.aligned_segments(human), vec![(end - 36, end - 3), (end - 2, end)]);
+        assert_eq!(human.aligned_segments(mouse), vec![(27578829, 27578864)]);
+    }
+
+    #[test]
+    fn maf_conversion() {
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(maf_to_bed(MAF.as_bytes(), &mut output, &["hg18"], false).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr7\t27578828\t27578866\thg18\t23262.0\t+\nchr7\t27699739\t27699745\thg18\t5062.0\t+\n"
+        );
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(maf_to_bed(MAF.as_bytes(), &mut output, &["mm4"], true).unwrap(), 1);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "chr6\t97889345\t97889381\tmm4\t23262.0\t-\t97889345\t97889345\t0\t2\t33,2,\t0,34,\n"
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use fxhash::FxHashMap;
 use num_traits::CheckedSub;
 use std::cmp::{Ord, PartialOrd, min, max};
+use fxhash::FxHashSet;
+use std::collections::BTreeMap;
 use std::ops::Sub;
 
-use crate::structs::structs::{Coordinates,  Interval, Named};
+use crate::error::error::CubiculumError;
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates,  Interval, Named, Scored, Stranded};
 
 /// Assess intersection between the two numeric intervals
 /// 
@@ -14,18 +17,18 @@ use crate::structs::structs::{Coordinates,  Interval, Named};
 /// `end2` - start coordinate of the first interval;
 /// 
 /// # Returns
-/// Numeric value of the intersection size if the intervals intersect, zero otherwise
+/// Numeric value of the intersection size if the intervals intersect (zero for book-ended intervals), None otherwise
 /// 
 /// # Usage
 /// 
 /// ```
-/// use cubiculum;
+/// use cubiculum::merge::merge::intersection;
 /// let s1: u8 = 10;
 /// let e1: u8 = 20;
 /// let s2: u8 = 15;
 /// let e2: u8 = 23;
-/// let inter_size: u8 = intersection(s1, e1, s2, e2);
-/// assert_eq!(inter_size, 5);
+/// let inter_size: Option<u8> = intersection(s1, e1, s2, e2);
+/// assert_eq!(inter_size, Some(5));
 /// ```
 pub fn intersection<T>(
     start1: T, end1: T, start2: T, end2: T
@@ -46,265 +49,1023 @@ where T: Ord + PartialOrd + Sub<Output = T> + CheckedSub<Output = T>//<T: cmp::P
 /// `inter2` - the second Cordinates object
 /// 
 /// # Returns
-/// An Option containing the merged interval if the objects overlap, None otherwise
+/// An Option containing the merged interval if the objects overlap, None otherwise;
+/// a CoordinateError if either of the objects has undefined coordinates
 /// 
 /// # Usage
 /// ```
-/// use cubiculum::merge::merge;
-/// let inter1 = Interval::from(String::from("chr1"), 100, 200, String::from("inter1"));
-/// let inter2 = Interval::from(String::from("chr1"), 170, 300, String::from("inter1"));
-/// let merged = merge(inter1, inter2);
-/// assert_eq!(merged, Interval::from(None, 100, 300, None));
+/// use cubiculum::merge::merge::merge;
+/// use cubiculum::structs::structs::{Coordinates, Interval};
+/// let inter1 = Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("inter1")));
+/// let inter2 = Interval::from(Some(String::from("chr1")), Some(170), Some(300), Some(String::from("inter2")));
+/// let merged = merge(inter1, inter2).unwrap().unwrap();
+/// assert_eq!((merged.start(), merged.end()), (Some(&100), Some(&300)));
 /// ```
-pub fn merge<T>(inter1: T, inter2: T) -> Option<Interval> 
+pub fn merge<T>(inter1: T, inter2: T) -> Result<Option<Interval>, CubiculumError>
 where
     T: Coordinates
 {
-    let s1 = *inter1.start().expect("Cannot merge intervals with undefined coordinates");
-    let e1 = *inter1.end().expect("Cannot merge intervals with undefined coordinates");
-    let s2 = *inter2.start().expect("Cannot merge intervals with undefined coordinates");
-    let e2 = *inter2.end().expect("Cannot merge intervals with undefined coordinates");
+    let (s1, e1) = defined_bounds(&inter1, "merge")?;
+    let (s2, e2) = defined_bounds(&inter2, "merge")?;
     match intersection(s1, e1, s2, e2) {
-        None => {return None},
+        None => {Ok(None)},
         Some(_) => {
             let mut merged: Interval = Interval::new();
             let merged_start = min(s1, s2);
             merged.update_start(merged_start);
             let merged_end = max(e1, e2);
             merged.update_end(merged_end);
-            return Some(merged);
+            Ok(Some(merged))
         }
-    };
+    }
 }
 
+/// Union of the (start, end) blocks, sorted by coordinates; overlapping and book-ended blocks are merged
+pub(crate) fn block_union<I: IntoIterator<Item = (u64, u64)>>(blocks: I) -> Vec<(u64, u64)> {
+    let mut blocks: Vec<(u64, u64)> = blocks.into_iter().collect();
+    blocks.sort_unstable();
+    let mut union: Vec<(u64, u64)> = Vec::with_capacity(blocks.len());
+    for (start, end) in blocks {
+        match union.last_mut() {
+            Some(last) if start <= last.1 => {last.1 = last.1.max(end)},
+            _ => {union.push((start, end))}
+        }
+    }
+    union
+}
 
-// merge all the overlapping intervals in the vector
-pub fn merge_multiple<T>(intervals: &mut Vec<T>) -> Vec<Interval> 
-where 
+/// Start and end coordinates of the interval; a CoordinateError naming the operation if either is undefined
+pub(crate) fn defined_bounds<T: Coordinates>(interval: &T, operation: &str) -> Result<(u64, u64), CubiculumError> {
+    match (interval.start(), interval.end()) {
+        (Some(start), Some(end)) => {Ok((*start, *end))},
+        _ => {
+            Err(CubiculumError::CoordinateError(format!("Cannot {} intervals with undefined coordinates", operation)))
+        }
+    }
+}
+
+
+/// Merge all the overlapping or book-ended intervals in the vector
+///
+/// # Arguments
+/// `intervals`: Vec collection containing the intervals; sorted in place by chromosome, start and end coordinates
+///
+/// # Returns
+/// A vector of unnamed merged intervals sorted by chromosome and start coordinate;
+/// intervals located on different chromosomes are never merged.
+/// A CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn merge_multiple<T>(intervals: &mut [T]) -> Result<Vec<Interval>, CubiculumError>
+where
     T: Coordinates
 {
-    let mut out_vec: Vec<Interval> = Vec::new();
-    if intervals.len() == 0 {return out_vec}
-    let mut prev_start: u64 = 0;
-    let mut prev_end: u64 = 0;
-    for el in intervals {
-        let curr_start = *el.start().unwrap();
-        let curr_end = *el.end().unwrap();
-        match intersection(prev_start, prev_end, curr_start, curr_end) {
-            Some(_) => {
-                // current item intersects the last interval in the output vector;
-                // create a single intersecting item out of them 
-                let _ = out_vec.pop();
-                prev_start = min(prev_start, curr_start);
-                prev_end = max(prev_end, curr_end);
-                let mut merged: Interval = Interval::new();
-                merged.update_chrom(el.chrom().unwrap().clone());
-                merged.update_start(prev_start);
-                merged.update_end(prev_end);
-                out_vec.push(merged);
-            },
-            None => {
-                // no intersection to the previous item; create a new interval, add it to the output vector
-                prev_start = curr_start;
-                prev_end = curr_end;
-                // since the output value is the vector of Intervals, create an Interval decoy for this element
-                let mut out_interval = Interval::new();
-                out_interval.update_chrom(el.chrom().unwrap().clone());
-                out_interval.update_start(prev_start);
-                out_interval.update_end(prev_end);
-                out_vec.push(out_interval);
+    sort_by_coordinates(intervals);
+    Ok(
+        merge_sorted(intervals, |_| None)?
+            .into_iter()
+            .map(|(x, _, _)| x)
+            .collect()
+    )
+}
+
+/// Merge all the overlapping or book-ended intervals in the vector as `merge_multiple()` does,
+/// recording the names of the merged members as a comma-separated list in the name field
+/// of the respective merged interval
+///
+pub fn merge_multiple_named<T>(intervals: &mut [T]) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    sort_by_coordinates(intervals);
+    Ok(
+        merge_sorted(intervals, |_| None)?
+            .into_iter()
+            .map(|(mut x, _, members)| {
+                name_merged(intervals, &mut x, &members);
+                x
+            })
+            .collect()
+    )
+}
+
+fn sort_by_coordinates<T: Coordinates>(intervals: &mut [T]) {
+    intervals.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom())
+            .then(a.start().cmp(&b.start()))
+            .then(a.end().cmp(&b.end()))
+    );
+}
+
+/// Merge overlapping or book-ended intervals located on the same strand
+///
+/// # Arguments
+/// `intervals`: Vec collection containing the intervals; sorted in place by chromosome, strand, start and end coordinates;
+/// `keep_names`: if set, names of the merged members are recorded as a comma-separated list
+/// in the name field of the respective merged interval
+///
+/// # Returns
+/// A vector of merged intervals paired with their strand, sorted by chromosome and start coordinate;
+/// intervals located on different chromosomes or strands are never merged. Unstranded intervals
+/// are merged with each other only, and their strand is reported as None.
+/// A CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn merge_stranded<T>(intervals: &mut [T], keep_names: bool) -> Result<Vec<(Interval, Option<bool>)>, CubiculumError>
+where
+    T: Coordinates + Named + Stranded
+{
+    intervals.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom())
+            .then(a.defined_strand().cmp(&b.defined_strand()))
+            .then(a.start().cmp(&b.start()))
+            .then(a.end().cmp(&b.end()))
+    );
+    let mut out_vec: Vec<(Interval, Option<bool>)> = merge_sorted(intervals, |x| x.defined_strand())?
+        .into_iter()
+        .map(|(mut x, strand, members)| {
+            if keep_names {name_merged(intervals, &mut x, &members)};
+            (x, strand)
+        })
+        .collect();
+    out_vec.sort_by(
+        |a, b| a.0.chrom().cmp(&b.0.chrom())
+            .then(a.0.start().cmp(&b.0.start()))
+            .then(a.0.end().cmp(&b.0.end()))
+            .then(a.1.cmp(&b.1))
+    );
+    Ok(out_vec)
+}
+
+/// Lazy merge of a coordinate-sorted record stream, created with `merge_stream()`
+pub struct MergeStream<I> {
+    records: I,
+    keep_names: bool,
+    curr: Option<(Interval, Vec<String>)>,
+    last: Option<(Option<String>, u64)>,
+    failed: bool
+}
+
+/// Merge the overlapping or book-ended records of a coordinate-sorted stream
+///
+/// Records are consumed one at a time, and only the interval currently being merged is kept in memory,
+/// so arbitrarily large sorted files can be merged without collecting them first
+///
+/// # Arguments
+/// `records`: records sorted by chromosome name (lexicographically) and start coordinate;
+/// `keep_names`: if set, names of the merged members are recorded as a comma-separated list
+/// in the name field of the respective merged interval
+///
+/// # Returns
+/// An iterator over the merged intervals, equivalent to the output of merge_multiple();
+/// a record with undefined coordinates yields a CoordinateError, and an unsorted record an UnsortedInput error,
+/// after which the iteration stops
+///
+pub fn merge_stream<T, I>(records: I, keep_names: bool) -> MergeStream<I::IntoIter>
+where
+    T: Coordinates + Named,
+    I: IntoIterator<Item = T>
+{
+    MergeStream {records: records.into_iter(), keep_names, curr: None, last: None, failed: false}
+}
+
+impl<T, I> Iterator for MergeStream<I>
+where
+    T: Coordinates + Named,
+    I: Iterator<Item = T>
+{
+    type Item = Result<Interval, CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {return None};
+        loop {
+            let el = match self.records.next() {
+                Some(x) => {x},
+                None => {return self.curr.take().map(|(x, names)| Ok(self.finalize(x, names)))}
+            };
+            let (el_start, el_end) = match defined_bounds(&el, "merge") {
+                Ok(x) => {x},
+                Err(e) => {return Some(Err(self.fail(e)))}
+            };
+            let key = (el.chrom().cloned(), el_start);
+            if let Some(prev) = &self.last {
+                if (prev.0.as_ref(), prev.1) > (key.0.as_ref(), key.1) {
+                    let e = CubiculumError::UnsortedInput(
+                        format!("Input stream is not coordinate-sorted: {:?}:{} follows {:?}:{}", key.0, key.1, prev.0, prev.1)
+                    );
+                    return Some(Err(self.fail(e)))
+                }
             }
-        };
+            self.last = Some(key);
+            let name = if self.keep_names {el.name().map(|x| x.to_string())} else {None};
+            if let Some((curr, names)) = self.curr.as_mut() {
+                if curr.chrom() == el.chrom() && el_start <= *curr.end().unwrap() {
+                    // current item intersects the last interval; extend the latter
+                    if el_end > *curr.end().unwrap() {curr.update_end(el_end)};
+                    names.extend(name);
+                    continue
+                }
+            }
+            let next = (
+                Interval::from(el.chrom().cloned(), Some(el_start), Some(el_end), None),
+                name.into_iter().collect()
+            );
+            if let Some((x, names)) = self.curr.replace(next) {
+                return Some(Ok(self.finalize(x, names)))
+            }
+        }
     }
-    out_vec.sort_by(
-        |a, b| if a.start().unwrap() == b.start().unwrap() {
-            a.end().unwrap().cmp(&b.end().unwrap())
+}
+
+impl<I> MergeStream<I> {
+    fn finalize(&self, mut interval: Interval, names: Vec<String>) -> Interval {
+        if self.keep_names {interval.update_name(names.join(","))};
+        interval
+    }
+
+    /// Stop the iteration, discarding the interval being merged
+    fn fail(&mut self, error: CubiculumError) -> CubiculumError {
+        self.failed = true;
+        self.curr = None;
+        error
+    }
+}
+
+/// Score aggregation modes for merge_aggregate()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoreAggregation {
+    Sum,
+    Mean,
+    Min,
+    Max
+}
+
+/// A merged interval accompanied by the data on its members, produced by merge_aggregate()
+#[derive(Clone, Debug)]
+pub struct MergedRecord {
+    pub interval: Interval,
+    /// Names of the merged members in the order of their coordinates; unnamed members are skipped
+    pub names: Vec<String>,
+    /// Number of merged members
+    pub count: usize,
+    /// Aggregated score of the members; None if aggregation was not requested or no member has a numeric score
+    pub score: Option<f64>
+}
+
+impl MergedRecord {
+    /// Returns the member names as a single comma-separated string
+    pub fn joined_names(&self) -> String {
+        self.names.join(",")
+    }
+}
+
+/// Merge overlapping or book-ended intervals, recording names, count and an aggregated score of the merged members
+///
+/// # Arguments
+/// `intervals`: Vec collection containing the intervals; sorted in place by chromosome, start and end coordinates;
+/// `score_mode`: how member scores should be aggregated; scores are not assessed if set to None
+///
+/// # Returns
+/// A vector of MergedRecord objects sorted by chromosome and start coordinate;
+/// a CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn merge_aggregate<T>(
+    intervals: &mut [T], score_mode: Option<ScoreAggregation>
+) -> Result<Vec<MergedRecord>, CubiculumError>
+where
+    T: Coordinates + Named + Scored
+{
+    sort_by_coordinates(intervals);
+    let merged = merge_sorted(intervals, |_| None)?
+        .into_iter()
+        .map(|(interval, _, members)| {
+            let names: Vec<String> = members
+                .iter()
+                .filter_map(|i| intervals[*i].name().map(|x| x.to_string()))
+                .collect();
+            let scores: Vec<f64> = members
+                .iter()
+                .filter_map(|i| intervals[*i].score_value())
+                .collect();
+            let score: Option<f64> = match score_mode {
+                _ if scores.is_empty() => {None},
+                Some(ScoreAggregation::Sum) => {Some(scores.iter().sum())},
+                Some(ScoreAggregation::Mean) => {Some(scores.iter().sum::<f64>() / scores.len() as f64)},
+                Some(ScoreAggregation::Min) => {scores.iter().cloned().reduce(f64::min)},
+                Some(ScoreAggregation::Max) => {scores.iter().cloned().reduce(f64::max)},
+                None => {None}
+            };
+            MergedRecord {interval, names, count: members.len(), score}
+        })
+        .collect();
+    Ok(merged)
+}
+
+/// A merged interval along with its strand and the indices of the merged members
+pub(crate) type MergedGroup = (Interval, Option<bool>, Vec<usize>);
+
+/// Sweep over the sorted intervals, merging the overlapping ones within the same chromosome and strand group
+///
+/// Returns the merged unnamed intervals along with their strand and indices of the merged members
+pub(crate) fn merge_sorted<T, F>(
+    intervals: &[T], strand_of: F
+) -> Result<Vec<MergedGroup>, CubiculumError>
+where
+    T: Coordinates,
+    F: Fn(&T) -> Option<bool>
+{
+    let mut out_vec: Vec<MergedGroup> = Vec::new();
+    if intervals.is_empty() {return Ok(out_vec)}
+    let mut curr_chrom: Option<&String> = None;
+    let mut curr_strand: Option<bool> = None;
+    let mut curr_start: u64 = 0;
+    let mut curr_end: u64 = 0;
+    let mut curr_members: Vec<usize> = Vec::new();
+    for (i, el) in intervals.iter().enumerate() {
+        let (el_start, el_end) = defined_bounds(el, "merge")?;
+        let el_strand = strand_of(el);
+        if i > 0 && el.chrom() == curr_chrom && el_strand == curr_strand && el_start <= curr_end {
+            // current item intersects the last interval; extend the latter
+            curr_end = max(curr_end, el_end);
         } else {
-            a.start().unwrap().cmp(&b.start().unwrap())
+            // no intersection to the previous item; record the latter and start a new interval
+            if i > 0 {
+                out_vec.push(
+                    (
+                        Interval::from(curr_chrom.cloned(), Some(curr_start), Some(curr_end), None),
+                        curr_strand,
+                        std::mem::take(&mut curr_members)
+                    )
+                );
+            }
+            curr_chrom = el.chrom();
+            curr_strand = el_strand;
+            curr_start = el_start;
+            curr_end = el_end;
         }
+        curr_members.push(i);
+    }
+    out_vec.push(
+        (
+            Interval::from(curr_chrom.cloned(), Some(curr_start), Some(curr_end), None),
+            curr_strand,
+            curr_members
+        )
     );
-    out_vec
+    Ok(out_vec)
+}
+
+/// Assign cluster IDs to intervals, grouping the overlapping or nearby ones
+///
+/// # Arguments
+/// `intervals`: a slice of intervals, in any order; the slice itself is left intact;
+/// `distance`: maximum gap between the neighbouring intervals within the same cluster;
+/// zero groups overlapping and book-ended intervals only
+///
+/// # Returns
+/// A vector of cluster IDs for each interval, in the order of the input slice;
+/// IDs are zero-based and numbered in the chromosome and start coordinate order.
+/// A CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn cluster<T>(intervals: &[T], distance: u64) -> Result<Vec<usize>, CubiculumError>
+where
+    T: Coordinates
+{
+    cluster_core(intervals, distance, |_| None)
+}
+
+/// Assign cluster IDs to intervals, grouping the overlapping or nearby ones located on the same strand
+///
+/// Same as `cluster()`, except that intervals located on different strands are never clustered together;
+/// unstranded intervals are clustered with each other only. Within each chromosome, clusters of unstranded
+/// intervals are numbered first, followed by the negative and the positive strand ones
+///
+pub fn cluster_stranded<T>(intervals: &[T], distance: u64) -> Result<Vec<usize>, CubiculumError>
+where
+    T: Coordinates + Stranded
+{
+    cluster_core(intervals, distance, |x| x.defined_strand())
+}
+
+fn cluster_core<T, F>(intervals: &[T], distance: u64, strand_of: F) -> Result<Vec<usize>, CubiculumError>
+where
+    T: Coordinates,
+    F: Fn(&T) -> Option<bool>
+{
+    let mut order: Vec<usize> = (0..intervals.len()).collect();
+    order.sort_by(
+        |a, b| intervals[*a].chrom().cmp(&intervals[*b].chrom())
+            .then(strand_of(&intervals[*a]).cmp(&strand_of(&intervals[*b])))
+            .then(intervals[*a].start().cmp(&intervals[*b].start()))
+            .then(intervals[*a].end().cmp(&intervals[*b].end()))
+    );
+    let mut ids: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+    let mut curr_chrom: Option<&String> = None;
+    let mut curr_strand: Option<bool> = None;
+    let mut curr_end: u64 = 0;
+    let mut curr_id: usize = 0;
+    for (i, idx) in order.into_iter().enumerate() {
+        let el = &intervals[idx];
+        let (el_start, el_end) = defined_bounds(el, "cluster")?;
+        let el_strand = strand_of(el);
+        if i > 0 && el.chrom() == curr_chrom && el_strand == curr_strand && el_start <= curr_end.saturating_add(distance) {
+            curr_end = max(curr_end, el_end);
+        } else {
+            if i > 0 {curr_id += 1};
+            curr_chrom = el.chrom();
+            curr_strand = el_strand;
+            curr_end = el_end;
+        }
+        ids.push((idx, curr_id));
+    }
+    // restore the input order
+    ids.sort_unstable();
+    Ok(ids.into_iter().map(|(_, id)| id).collect())
+}
+
+/// Record comma-separated names of the merged members in the merged interval's name field
+pub(crate) fn name_merged<T>(intervals: &[T], merged: &mut Interval, members: &[usize])
+where
+    T: Named
+{
+    let names: Vec<&str> = members
+        .iter()
+        .filter_map(|i| intervals[*i].name())
+        .collect();
+    merged.update_name(names.join(","));
+}
+
+/// Compute the genomic regions not covered by any of the intervals
+///
+/// # Arguments
+/// `intervals`: Vec collection containing the intervals; sorted in place by chromosome, start and end coordinates;
+/// `chrom_sizes`: ChromSizes object defining the genome; intervals on chromosomes missing from it are ignored
+///
+/// # Returns
+/// A vector of uncovered intervals, following the chromosome order of `chrom_sizes`;
+/// chromosomes with no intervals are reported in full. A CoordinateError if any of the intervals
+/// has undefined coordinates
+///
+pub fn complement<T>(intervals: &mut [T], chrom_sizes: &ChromSizes) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates
+{
+    sort_by_coordinates(intervals);
+    let mut covered: FxHashMap<String, Vec<(u64, u64)>> = FxHashMap::default();
+    for (merged, _, _) in merge_sorted(intervals, |_| None)? {
+        let chrom = match merged.chrom() {
+            Some(x) => {x.clone()},
+            None => {continue}
+        };
+        covered.entry(chrom).or_default().push(defined_bounds(&merged, "merge")?);
+    }
+    let mut out_vec: Vec<Interval> = Vec::new();
+    for chrom in chrom_sizes.chroms() {
+        let size = chrom_sizes.get(chrom).unwrap_or(0);
+        let mut prev_end: u64 = 0;
+        if let Some(blocks) = covered.get(chrom) {
+            for (start, end) in blocks {
+                let start = min(*start, size);
+                if start > prev_end {
+                    out_vec.push(Interval::from(Some(chrom.clone()), Some(prev_end), Some(start), None));
+                }
+                prev_end = max(prev_end, min(*end, size));
+            }
+        }
+        if prev_end < size {
+            out_vec.push(Interval::from(Some(chrom.clone()), Some(prev_end), Some(size), None));
+        }
+    }
+    Ok(out_vec)
+}
+
+/// Partition the genome into alternating covered and uncovered segments
+///
+/// Covered segments are split wherever the set of overlapping intervals changes, as in discrete_interval_map(),
+/// and carry the names of the contributing intervals; uncovered segments carry no names
+///
+/// # Arguments
+/// `intervals`: a slice of named intervals;
+/// `chrom_sizes`: ChromSizes object defining the genome; intervals on chromosomes missing from it are ignored
+///
+/// # Returns
+/// A vector of segments tiling each chromosome from zero to its size, following the chromosome order of `chrom_sizes`,
+/// each paired with the names of the overlapping intervals in the input order;
+/// a CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn partition<'a, T>(intervals: &'a [T], chrom_sizes: &ChromSizes) -> Result<Vec<(Interval, Vec<&'a str>)>, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    let mut by_chrom: FxHashMap<String, Vec<Segment>> = FxHashMap::default();
+    for (segment, members) in discrete_intervals(intervals)? {
+        let chrom = match segment.chrom() {
+            Some(x) => {x.clone()},
+            None => {continue}
+        };
+        let (start, end) = defined_bounds(&segment, "partition")?;
+        by_chrom.entry(chrom).or_default().push((start, end, members));
+    }
+    let mut out_vec: Vec<(Interval, Vec<&'a str>)> = Vec::new();
+    for chrom in chrom_sizes.chroms() {
+        let size = chrom_sizes.get(chrom).unwrap_or(0);
+        let mut prev_end: u64 = 0;
+        if let Some(segments) = by_chrom.get(chrom) {
+            for (start, end, members) in segments {
+                let start = min(*start, size);
+                let end = min(*end, size);
+                if start >= end {continue};
+                if start > prev_end {
+                    out_vec.push((Interval::from(Some(chrom.clone()), Some(prev_end), Some(start), None), Vec::new()));
+                }
+                let names: Vec<&'a str> = members.iter().filter_map(|i| intervals[*i].name()).collect();
+                out_vec.push((Interval::from(Some(chrom.clone()), Some(start), Some(end), None), names));
+                prev_end = end;
+            }
+        }
+        if prev_end < size {
+            out_vec.push((Interval::from(Some(chrom.clone()), Some(prev_end), Some(size), None), Vec::new()));
+        }
+    }
+    Ok(out_vec)
 }
 
 /// create an interval spanning over all the Coordinates objects in the vector
 ///
 /// # Arguments
-/// `intervals`: Vec collection containing the intervals
+/// `intervals`: Vec collection containing the intervals; sorted in place by start and end coordinates
 /// 
 /// # Returns
-pub fn total_span<T>(intervals: &mut Vec<T>) -> Interval 
+/// An interval named as `{chrom}:{start}-{end}`, spanning from the smallest start to the largest end coordinate;
+/// an error if the collection is empty, contains undefined coordinates or intervals from different chromosomes
+/// 
+pub fn total_span<T>(intervals: &mut [T]) -> Result<Interval, CubiculumError>
 where 
     T: Coordinates
 {
     intervals.sort_by(
-        |a, b| if a.start().unwrap() == b.start().unwrap() {
-            a.end().unwrap().cmp(&b.end().unwrap())
-        } else {
-            a.start().unwrap().cmp(&b.start().unwrap())
+        |a, b| a.start().cmp(&b.start()).then(a.end().cmp(&b.end()))
+    );
+    let chrom: &String = match intervals.first() {
+        Some(x) => {
+            x.chrom().ok_or(
+                CubiculumError::MissingTraitError("Intervals for total span inference must have a defined chromosome".to_string())
+            )?
+        },
+        None => {return Err(CubiculumError::InputError("Cannot infer total span for an empty collection".to_string()))}
+    };
+    if let Some(x) = intervals.iter().find(|x| x.chrom() != Some(chrom)) {
+        return Err(
+            CubiculumError::InputError(
+                format!("Intervals for total span inference come from different chromosomes: {} and {:?}", chrom, x.chrom())
+            )
+        )
+    }
+    let (start, end) = span(intervals.iter())?;
+    let name: String = format!("{}:{}-{}", chrom, start, end);
+    Ok(Interval::from(Some(chrom.clone()), Some(start), Some(end), Some(name)))
+}
+
+/// Returns the smallest start and the largest end coordinate among the intervals
+fn span<'a, T, I>(intervals: I) -> Result<(u64, u64), CubiculumError>
+where
+    T: Coordinates + 'a,
+    I: Iterator<Item = &'a T>
+{
+    let mut bounds: Option<(u64, u64)> = None;
+    for x in intervals {
+        let (start, end) = match (x.start(), x.end()) {
+            (Some(s), Some(e)) => {(*s, *e)},
+            _ => {
+                return Err(
+                    CubiculumError::MissingTraitError("Intervals for total span inference must have defined coordinates".to_string())
+                )
+            }
+        };
+        bounds = match bounds {
+            Some((curr_start, curr_end)) => {Some((min(curr_start, start), max(curr_end, end)))},
+            None => {Some((start, end))}
+        };
+    }
+    bounds.ok_or(CubiculumError::InputError("Cannot infer total span for an empty collection".to_string()))
+}
+
+/// create an interval spanning over all the intervals for each chromosome
+///
+/// # Arguments
+/// `intervals`: a slice of intervals, in any order; intervals with undefined chromosome are skipped
+///
+/// # Returns
+/// A map from chromosome names to spanning intervals named as `{chrom}:{start}-{end}`;
+/// an error if any of the intervals has undefined coordinates
+///
+pub fn total_span_per_chrom<T>(intervals: &[T]) -> Result<FxHashMap<String, Interval>, CubiculumError>
+where
+    T: Coordinates
+{
+    let mut groups: FxHashMap<&String, Vec<&T>> = FxHashMap::default();
+    for x in intervals {
+        if let Some(chrom) = x.chrom() {
+            groups.entry(chrom).or_default().push(x);
         }
-        );
-    let chrom: String = intervals[0]
-        .chrom()
-        .expect("Intervals for total span inference must have a defined")
-        .clone();
-    let start: u64 = *intervals[0].start().unwrap();
-    let end: u64 = *intervals[intervals.len() - 1].end().unwrap();
-    let name: String = String::from(format!("{}:{}-{}", chrom, start, end));
-    Interval::from(Some(chrom), Some(start), Some(end), Some(name))
+    }
+    let mut out_map: FxHashMap<String, Interval> = FxHashMap::default();
+    for (chrom, members) in groups {
+        let (start, end) = span(members.into_iter())?;
+        let name: String = format!("{}:{}-{}", chrom, start, end);
+        out_map.insert(chrom.clone(), Interval::from(Some(chrom.clone()), Some(start), Some(end), Some(name)));
+    }
+    Ok(out_map)
+}
+
+/// create an interval spanning over all the intervals sharing the same name, e.g. gene spans from exon records
+///
+/// # Arguments
+/// `intervals`: a slice of intervals, in any order; unnamed intervals are skipped
+///
+/// # Returns
+/// A map from names to the spanning intervals bearing the same name;
+/// an error if any of the intervals has undefined coordinates, or if the same name is found on different chromosomes
+///
+pub fn span_per_name<T>(intervals: &[T]) -> Result<FxHashMap<String, Interval>, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    let mut groups: FxHashMap<&str, Vec<&T>> = FxHashMap::default();
+    for x in intervals {
+        if let Some(name) = x.name() {
+            groups.entry(name).or_default().push(x);
+        }
+    }
+    let mut out_map: FxHashMap<String, Interval> = FxHashMap::default();
+    for (name, members) in groups {
+        let chrom: Option<&String> = members[0].chrom();
+        if members.iter().any(|x| x.chrom() != chrom) {
+            return Err(CubiculumError::InputError(format!("Intervals named {} come from different chromosomes", name)))
+        }
+        let (start, end) = span(members.into_iter())?;
+        out_map.insert(name.to_string(), Interval::from(chrom.cloned(), Some(start), Some(end), Some(name.to_string())));
+    }
+    Ok(out_map)
 }
 
 /// split a vector of potentially overlapping intervals into discrete, non-overlapping ones,
 /// and map the resulting intervals to names of original items overlapping the respective interval
 /// 
+/// Discrete intervals are named with consecutive numbers, which serve as keys in the output map;
+/// names of the overlapping items are listed in the order of their first appearance among the
+/// coordinate-sorted input. The intervals are discretized in a single sweep over sorted boundaries,
+/// taking O(n log n) time plus the output size
 /// 
-pub fn discrete_interval_map<T>(intervals: &mut Vec<T>) -> (Vec<Interval>, FxHashMap<String, Vec<&str>>)
+/// # Returns
+/// The discrete intervals and the name map; a CoordinateError if any of the intervals has undefined coordinates,
+/// and a MissingTraitError if any of them is unnamed
+/// 
+pub fn discrete_interval_map<T>(intervals: &mut [T]) -> Result<DiscreteIntervalMap<'_>, CubiculumError>
 where 
     T: Coordinates + Named
 {
     let mut interval_vec: Vec<Interval> = Vec::new();
     let mut out_map: FxHashMap<String, Vec<&str>> = FxHashMap::default();
-    if intervals.len() == 0 {
-        return (interval_vec, out_map);
+    if intervals.is_empty() {
+        return Ok((interval_vec, out_map));
     }
     intervals.sort_by(
-        |a, b| if a.start().unwrap() == b.start().unwrap() {
-            a.end().unwrap().cmp(&b.end().unwrap())
-        } else {
-            a.start().unwrap().cmp(&b.start().unwrap())
-        }
+        |a, b| a.start().cmp(&b.start()).then(a.end().cmp(&b.end()))
     );
-    
-    let mut curr: usize = 0;
-    let mut next: usize = 1;
-
-    let mut start_points: Vec<u64> = Vec::new();
-    let mut start2trs: FxHashMap<u64, Vec<&str>> = FxHashMap::default();
-    let chrom: Option<String> = match intervals[0].chrom() {
-        Some(x) => {Some(x.clone())},
-        None => {None}
-    };
-    let mut curr_interval: u64 = 0;
-    // let end2trs: FxHashMap<u64, Vec<&str>> = FxHashMap::default();
-    while curr < intervals.len() {
-        let first_start: u64 = match intervals[curr].start() {
-            Some(x) => {*x},
-            None => {
-                panic!(
-                    "Cannot discretize intervals with undefined coordinates; found an undefined start coordinate for interval {}", curr
-                )
-            }
-        };
-        let first_end: u64 = match intervals[curr].end() {
-            Some(x) => {*x},
+    let chrom: Option<String> = intervals[0].chrom().cloned();
+    let (names, segments) = discretize_sorted(intervals)?;
+    for (curr_interval, (inter_start, inter_end, members)) in segments.into_iter().enumerate() {
+        let interval_name: String = curr_interval.to_string();
+        out_map.insert(interval_name.clone(), members.into_iter().map(|x| names[x]).collect());
+        interval_vec.push(
+            Interval::from(chrom.clone(), Some(inter_start), Some(inter_end), Some(interval_name))
+        );
+    }
+    Ok((interval_vec, out_map))
+}
+
+/// Discrete intervals along with the names of the original items overlapping each of them,
+/// as reported by discrete_interval_map()
+pub type DiscreteIntervalMap<'a> = (Vec<Interval>, FxHashMap<String, Vec<&'a str>>);
+
+/// Chromosome-aware version of discrete_interval_map()
+///
+/// Intervals are grouped by chromosome, and each chromosome is discretized independently,
+/// so that segments never span across chromosomes
+///
+/// # Returns
+/// A map from chromosome names to the discrete intervals and their name maps,
+/// as reported by discrete_interval_map(); discrete interval numbering starts anew for each chromosome.
+/// A MissingTraitError if any of the intervals has undefined chromosome or name
+/// and a CoordinateError if any has undefined coordinates
+///
+pub fn discrete_interval_map_by_chrom<T>(
+    intervals: &mut [T]
+) -> Result<FxHashMap<String, DiscreteIntervalMap<'_>>, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    sort_by_coordinates(intervals);
+    let intervals: &[T] = intervals;
+    let mut out_map: FxHashMap<String, DiscreteIntervalMap<'_>> = FxHashMap::default();
+    for chunk in intervals.chunk_by(|a, b| a.chrom() == b.chrom()) {
+        let (chrom, discrete) = discretize_chrom(chunk)?;
+        out_map.insert(chrom, discrete);
+    }
+    Ok(out_map)
+}
+
+/// Discretize the sorted intervals of a single chromosome, naming the segments by their ordinal numbers
+///
+/// Returns the chromosome name along with the discrete intervals and their name map
+pub(crate) fn discretize_chrom<T>(chunk: &[T]) -> Result<(String, DiscreteIntervalMap<'_>), CubiculumError>
+where
+    T: Coordinates + Named
+{
+    let chrom: String = chunk.first()
+        .and_then(|x| x.chrom())
+        .ok_or(CubiculumError::MissingTraitError("Cannot discretize intervals with undefined chromosome".to_string()))?
+        .clone();
+    let (names, segments) = discretize_sorted(chunk)?;
+    let mut interval_vec: Vec<Interval> = Vec::with_capacity(segments.len());
+    let mut name_map: FxHashMap<String, Vec<&str>> = FxHashMap::default();
+    for (curr_interval, (inter_start, inter_end, members)) in segments.into_iter().enumerate() {
+        let interval_name: String = curr_interval.to_string();
+        name_map.insert(interval_name.clone(), members.into_iter().map(|x| names[x]).collect());
+        interval_vec.push(
+            Interval::from(Some(chrom.clone()), Some(inter_start), Some(inter_end), Some(interval_name))
+        );
+    }
+    Ok((chrom, (interval_vec, name_map)))
+}
+
+/// A discrete segment as (start, end, keys of the overlapping items)
+pub(crate) type Segment = (u64, u64, Vec<usize>);
+
+/// Sweep over the boundaries of the sorted intervals, splitting them into discrete segments
+///
+/// Returns the interned names of the intervals, ordered by their first appearance in the input,
+/// and the (start, end, name indices) tuples for each covered segment
+pub(crate) fn discretize_sorted<T>(intervals: &[T]) -> Result<(Vec<&str>, Vec<Segment>), CubiculumError>
+where
+    T: Coordinates + Named
+{
+    let mut names: Vec<&str> = Vec::new();
+    let mut name_ids: FxHashMap<&str, usize> = FxHashMap::default();
+    // boundary events as (position, is start, name index) tuples
+    let mut events: Vec<(u64, bool, usize)> = Vec::with_capacity(intervals.len() * 2);
+    for x in intervals.iter() {
+        let (start, end) = defined_bounds(x, "discretize")?;
+        let name: &str = x.name().ok_or(CubiculumError::MissingTraitError("Cannot discretize unnamed intervals".to_string()))?;
+        let name_id = match name_ids.get(name) {
+            Some(id) => {*id},
             None => {
-                panic!(
-                    "Cannot discretize intervals with undefined coordinates; found an undefined end coordinate for interval {}", curr
-                )
+                names.push(name);
+                name_ids.insert(name, names.len() - 1);
+                names.len() - 1
             }
         };
-        if let None = intervals[curr].name() {
-            panic!("Cannot discretize unnamed intervals");
+        if start < end {
+            events.push((start, true, name_id));
+            events.push((end, false, name_id));
         }
-        let mut curr_end = first_end;
-        start_points.push(first_start);
-        start_points.push(first_end);
-        start2trs.entry(first_start).or_insert(Vec::new()).push(intervals[curr].name().unwrap());
-
-        while next < intervals.len() {
-            let next_start: u64 = match intervals[next].start() {
-                Some(x) => {*x},
-                None => {
-                    panic!(
-                        "Cannot discretize intervals with undefined coordinates; found an undefined start coordinate for interval {}", next
-                    )
-                }
-            };
-            let next_end: u64 = match intervals[next].end() {
-                Some(x) => {*x},
-                None => {
-                    panic!(
-                        "Cannot discretize intervals with undefined coordinates; found an undefined end coordinate for interval {}", next
-                    )
-                }
-            };
+    }
+    Ok((names, sweep_segments(events)))
+}
 
-            if let None = intervals[next].name() {
-                panic!("Cannot discretize unnamed intervals");
-            }
+/// Split the covered range into segments at each of the boundary events
+///
+/// Events are (position, is start, key) tuples; each segment lists the keys active over it in ascending order
+fn sweep_segments(mut events: Vec<(u64, bool, usize)>) -> Vec<Segment> {
+    // ends precede starts at the same position
+    events.sort_unstable();
+    let mut segments: Vec<Segment> = Vec::new();
+    // number of active intervals per key
+    let mut active: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut prev: u64 = 0;
+    for (pos, is_start, key) in events {
+        if pos > prev && !active.is_empty() {
+            segments.push((prev, pos, active.keys().copied().collect()));
+        }
+        if is_start {
+            *active.entry(key).or_insert(0) += 1;
+        } else if let Some(count) = active.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {active.remove(&key);}
+        }
+        prev = pos;
+    }
+    segments
+}
 
-            if next_start > curr_end {
-                break
+/// Split the intervals into discrete, non-overlapping segments, each mapped to the overlapping input items
+///
+/// Unlike discrete_interval_map(), the input is left intact and neither names nor
+/// synthetic string keys are involved
+///
+/// # Returns
+/// A vector of unnamed segments, sorted by chromosome and coordinates,
+/// each paired with the ascending indices of the overlapping items in the input slice;
+/// a CoordinateError if any of the intervals has undefined coordinates
+///
+pub fn discrete_intervals<T>(intervals: &[T]) -> Result<Vec<(Interval, Vec<usize>)>, CubiculumError>
+where
+    T: Coordinates
+{
+    let mut order: Vec<usize> = (0..intervals.len()).collect();
+    order.sort_by(|a, b| intervals[*a].chrom().cmp(&intervals[*b].chrom()));
+    let mut out_vec: Vec<(Interval, Vec<usize>)> = Vec::new();
+    for chunk in order.chunk_by(|a, b| intervals[*a].chrom() == intervals[*b].chrom()) {
+        let chrom: Option<&String> = intervals[chunk[0]].chrom();
+        let mut events: Vec<(u64, bool, usize)> = Vec::with_capacity(chunk.len() * 2);
+        for i in chunk {
+            let (start, end) = defined_bounds(&intervals[*i], "discretize")?;
+            if start < end {
+                events.push((start, true, *i));
+                events.push((end, false, *i));
             }
+        }
+        for (start, end, members) in sweep_segments(events) {
+            out_vec.push((Interval::from(chrom.cloned(), Some(start), Some(end), None), members));
+        }
+    }
+    Ok(out_vec)
+}
 
-            curr_end = max(curr_end, next_end);
-            if !start_points.contains(&next_start) {start_points.push(next_start)};
-            if !start_points.contains(&next_end) {start_points.push(next_end)};
-            for i in &start_points {
-                if *i < next_start {continue};
-                if *i >= next_end {continue};
-                start2trs
-                    .entry(*i)
-                    .and_modify(|x| 
-                        if x.contains(&intervals[next].name().unwrap()) {} else {x.push(intervals[next].name().unwrap())}
-                    )
-                    .or_insert(vec![intervals[next].name().unwrap()]);
-            }
+/// Remove the entries identical to an earlier entry in all the fields
+///
+/// # Returns
+/// A vector of unique entries in the order of their first occurrence
+///
+pub fn dedup_exact(entries: Vec<BedEntry>) -> Vec<BedEntry> {
+    let mut seen: FxHashSet<BedEntry> = FxHashSet::default();
+    let mut out_vec: Vec<BedEntry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        if seen.contains(&entry) {continue};
+        seen.insert(entry.clone());
+        out_vec.push(entry);
+    }
+    out_vec
+}
 
-            // assess whether any of the previous intervals cover terminal coordinates for the current interval
-            for i in curr..next+1 {
-                // every interval that does not end before this point is attributed to this discrete interval
-                let i_end: u64 = *intervals[i].end().unwrap();
-                // println!("i_end={}, next_start={}, next_end={}", i_end, next_start, next_end);
-                if i_end > next_start {
-                    start2trs
-                        .entry(next_start)
-                        .and_modify(|x| 
-                            if x.contains(&intervals[i].name().unwrap()) {} else {x.push(intervals[i].name().unwrap())}
-                        )
-                        .or_insert(vec![intervals[i].name().unwrap()]);
-                };
+/// Defines which entry is retained by dedup_by_coordinates() out of the group of duplicates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DedupKeep {
+    /// The entry with the longest coding sequence (thickEnd - thickStart)
+    Longest,
+    /// The entry with the highest numeric score; unparseable scores rank below any number
+    HighestScore,
+    /// The first entry of the group
+    First
+}
 
-                if i_end > next_end {
-                    start2trs
-                        .entry(next_end)
-                        .and_modify(|x| 
-                            if x.contains(&intervals[i].name().unwrap()) {} else {x.push(intervals[i].name().unwrap())}
-                        )
-                        .or_insert(vec![intervals[i].name().unwrap()]);
+fn cds_length(entry: &BedEntry) -> u64 {
+    match (entry.thick_start(), entry.thick_end()) {
+        (Some(x), Some(y)) => {y.saturating_sub(x)},
+        _ => {0}
+    }
+}
+
+/// Remove the entries sharing chromosome, strand and exon coordinates with another entry,
+/// retaining one entry per group of duplicates
+///
+/// Names, scores, colors and coding boundaries are ignored when comparing entries;
+/// ties are resolved in favour of the first entry of the group
+///
+/// # Returns
+/// A vector of retained entries in the order each group first occurs
+///
+pub fn dedup_by_coordinates(entries: Vec<BedEntry>, keep: DedupKeep) -> Vec<BedEntry> {
+    type Key = (Option<String>, Option<bool>, Vec<(u64, u64)>);
+    let mut groups: FxHashMap<Key, usize> = FxHashMap::default();
+    let mut out_vec: Vec<BedEntry> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let key: Key = (entry.chrom().cloned(), entry.strand(), entry.exons().collect());
+        match groups.get(&key) {
+            Some(i) => {
+                let replace = match keep {
+                    DedupKeep::Longest => {cds_length(&entry) > cds_length(&out_vec[*i])},
+                    DedupKeep::HighestScore => {
+                        match (entry.score_value(), out_vec[*i].score_value()) {
+                            (Some(x), Some(y)) => {x > y},
+                            (Some(_), None) => {true},
+                            _ => {false}
+                        }
+                    },
+                    DedupKeep::First => {false}
                 };
+                if replace {out_vec[*i] = entry};
+            },
+            None => {
+                groups.insert(key, out_vec.len());
+                out_vec.push(entry);
             }
-            next += 1;
         }
-        // discretize the overlapping interval
-        start_points.sort();
-        for i in 1..start_points.len() {
-            // define interval boundaries
-            let inter_start: u64 = start_points[i-1];
-            let inter_end: u64 = start_points[i];
-            // define which transcripts correspond to this interval
-            let tr_names: &Vec<&str>  = start2trs.get(&inter_start).unwrap_or_else(||
-                {
-                    println!("{:#?}", start2trs);
-                    println!("{:#?}", start_points);
-                    panic!("No transcripts overlapping this value: {}!", inter_start);
+    }
+    out_vec
+}
+
+/// Defines which of the overlapping entries takes precedence in resolve_overlaps()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverlapPriority {
+    /// Longer entries go first
+    Longest,
+    /// Entries with higher numeric scores go first; entries with no valid score go last
+    HighestScore,
+    /// Entries go in the lexicographic order of their names; unnamed entries go last
+    FirstByName
+}
+
+/// Defines how resolve_overlaps() treats an entry overlapping an entry of higher priority
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ResolvePolicy {
+    pub priority: OverlapPriority,
+    /// If true, overlapping entries are trimmed to their non-overlapping parts instead of being removed
+    pub trim: bool
+}
+
+impl ResolvePolicy {
+    /// Remove the entries overlapping an entry of higher priority
+    pub fn remove(priority: OverlapPriority) -> ResolvePolicy {
+        ResolvePolicy {priority, trim: false}
+    }
+
+    /// Trim the entries to the parts not covered by entries of higher priority
+    pub fn trim(priority: OverlapPriority) -> ResolvePolicy {
+        ResolvePolicy {priority, trim: true}
+    }
+}
+
+/// Produce a non-overlapping set of intervals out of potentially overlapping entries
+///
+/// Entries are processed in the order of priority, ties being resolved in favour of the earlier entry;
+/// each entry is either retained in full, dropped or (if `policy.trim` is set) reduced to the parts
+/// not claimed by entries processed earlier, which may split it into several intervals
+///
+/// # Returns
+/// A vector of non-overlapping intervals named after the source entries,
+/// sorted by chromosome and coordinates; a CoordinateError if any of the entries has undefined chromosome
+/// or coordinates
+///
+pub fn resolve_overlaps<T>(entries: &[T], policy: ResolvePolicy) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates + Named + Scored
+{
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    match policy.priority {
+        OverlapPriority::Longest => {
+            order.sort_by_key(|i| std::cmp::Reverse(entries[*i].length().unwrap_or(0)));
+        },
+        OverlapPriority::HighestScore => {
+            order.sort_by(|a, b| {
+                match (entries[*a].score_value(), entries[*b].score_value()) {
+                    (Some(x), Some(y)) => {y.partial_cmp(&x).unwrap_or(std::cmp::Ordering::Equal)},
+                    (x, y) => {y.is_some().cmp(&x.is_some())}
                 }
+            });
+        },
+        OverlapPriority::FirstByName => {
+            order.sort_by(|a, b| {
+                match (entries[*a].name(), entries[*b].name()) {
+                    (Some(x), Some(y)) => {x.cmp(y)},
+                    (x, y) => {y.is_some().cmp(&x.is_some())}
+                }
+            });
+        }
+    }
+    let mut claimed: FxHashMap<&String, BTreeMap<u64, u64>> = FxHashMap::default();
+    let mut out_vec: Vec<Interval> = Vec::new();
+    for i in order {
+        let entry = &entries[i];
+        let chrom = entry.chrom().ok_or(
+            CubiculumError::CoordinateError("Cannot resolve overlaps for intervals with undefined coordinates".to_string())
+        )?;
+        let (start, end) = defined_bounds(entry, "resolve overlaps for")?;
+        if start >= end {continue};
+        let regions = claimed.entry(chrom).or_default();
+        let mut blocking: Vec<(u64, u64)> = Vec::new();
+        if let Some((s, e)) = regions.range(..start).next_back() {
+            if *e > start {blocking.push((*s, *e))};
+        }
+        blocking.extend(regions.range(start..end).map(|(s, e)| (*s, *e)));
+        if !blocking.is_empty() && !policy.trim {continue};
+        let mut fragments: Vec<(u64, u64)> = Vec::new();
+        let mut cursor = start;
+        for (s, e) in blocking {
+            if s > cursor {fragments.push((cursor, s))};
+            cursor = max(cursor, e);
+        }
+        if cursor < end {fragments.push((cursor, end))};
+        for (s, e) in fragments {
+            regions.insert(s, e);
+            out_vec.push(
+                Interval::from(Some(chrom.clone()), Some(s), Some(e), entry.name().map(|x| x.to_string()))
             );
-            // create an interval object and add the resulting values to the output collections
-            let interval_name: String = curr_interval.to_string();
-            out_map.insert(interval_name.clone(), tr_names.clone());
-            let discrete_interval: Interval = Interval::from(
-                chrom.clone(), Some(inter_start), Some(inter_end), Some(interval_name)
-            );
-            interval_vec.push(discrete_interval); 
-            curr_interval += 1;
         }
-        // clear the storage structures
-        start_points.clear();
-        start2trs.clear();
-        // next iteration starts from the break point
-        curr = next;
     }
-    (interval_vec, out_map)
+    out_vec.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom()).then(a.start().cmp(&b.start()))
+    );
+    Ok(out_vec)
 }
 
 #[cfg(test)]
@@ -317,7 +1078,7 @@ mod discretizer_test{
             Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
             Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("two")))
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
@@ -328,7 +1089,7 @@ mod discretizer_test{
             Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
             Interval::from(Some(String::from("chr1")), Some(150), Some(220), Some(String::from("two")))
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
@@ -339,7 +1100,7 @@ mod discretizer_test{
             Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
             Interval::from(Some(String::from("chr1")), Some(150), Some(180), Some(String::from("two")))
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
@@ -350,7 +1111,7 @@ mod discretizer_test{
             Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
             Interval::from(Some(String::from("chr1")), Some(100), Some(220), Some(String::from("two")))
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
@@ -362,11 +1123,79 @@ mod discretizer_test{
             Interval::from(Some(String::from("chr1")), Some(100), Some(220), Some(String::from("two"))),
             Interval::from(Some(String::from("chr1")), Some(230), Some(250), Some(String::from("three")))
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
 
+    #[test]
+    fn discretizer_segments(){
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(250), Some(260), Some(String::from("three"))),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(300), Some(String::from("one"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(200), Some(String::from("two"))),
+            Interval::from(Some(String::from("chr1")), Some(300), Some(320), Some(String::from("four"))),
+            Interval::from(Some(String::from("chr1")), Some(400), Some(500), Some(String::from("five")))
+        ];
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
+        let segments: Vec<(u64, u64, Vec<&str>)> = vec
+            .iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap(), map[Named::name(x).unwrap()].clone()))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (100, 150, vec!["one"]),
+                (150, 200, vec!["one", "two"]),
+                (200, 250, vec!["one"]),
+                (250, 260, vec!["one", "three"]),
+                (260, 300, vec!["one"]),
+                (300, 320, vec!["four"]),
+                (400, 500, vec!["five"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn discretizer_by_chrom(){
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr2")), Some(150), Some(250), Some(String::from("three"))),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(220), Some(String::from("two")))
+        ];
+        let result = discrete_interval_map_by_chrom(&mut input).unwrap();
+        assert_eq!(result.len(), 2);
+        let (chr1, chr1_map) = &result["chr1"];
+        assert_eq!(chr1.len(), 3);
+        assert_eq!(chr1_map["1"], vec!["one", "two"]);
+        let (chr2, chr2_map) = &result["chr2"];
+        assert_eq!(chr2.len(), 1);
+        assert_eq!(chr2[0].chrom(), Some(&String::from("chr2")));
+        assert_eq!(chr2_map["0"], vec!["three"]);
+    }
+
+    #[test]
+    fn discretizer_indices(){
+        let input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr2")), Some(150), Some(250), None),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), None),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(220), None)
+        ];
+        let segments: Vec<(String, u64, u64, Vec<usize>)> = discrete_intervals(&input).unwrap()
+            .into_iter()
+            .map(|(x, members)| (x.chrom().unwrap().clone(), *x.start().unwrap(), *x.end().unwrap(), members))
+            .collect();
+        assert_eq!(
+            segments,
+            vec![
+                (String::from("chr1"), 100, 150, vec![1]),
+                (String::from("chr1"), 150, 200, vec![1, 2]),
+                (String::from("chr1"), 200, 220, vec![2]),
+                (String::from("chr2"), 150, 250, vec![0]),
+            ]
+        );
+    }
+
     #[test]
     fn real_life_test(){
         let mut input: Vec<Interval> = vec![
@@ -389,8 +1218,298 @@ mod discretizer_test{
             // Interval::from(Some(String::from("chr1")), Some(230), Some(250), Some(String::from("three"))),
             // Interval::from(Some(String::from("chr1")), Some(230), Some(250), Some(String::from("three"))),
         ];
-        let (vec, map) = discrete_interval_map(&mut input);
+        let (vec, map) = discrete_interval_map(&mut input).unwrap();
         println!("{:#?}", vec);
         println!("{:#?}", map);
     }
 }
+
+#[cfg(test)]
+mod merge_test {
+    use super::*;
+    use crate::structs::structs::BedEntry;
+
+    fn coords(intervals: &[Interval]) -> Vec<(String, u64, u64)> {
+        intervals
+            .iter()
+            .map(|x| (x.chrom().unwrap().clone(), *x.start().unwrap(), *x.end().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn merge_keeps_separate_intervals() {
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(300), Some(400), Some(String::from("three"))),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("one"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(250), Some(String::from("two"))),
+        ];
+        let merged = merge_multiple(&mut input).unwrap();
+        assert_eq!(
+            coords(&merged),
+            vec![(String::from("chr1"), 100, 250), (String::from("chr1"), 300, 400)]
+        );
+        assert!(merged[0].name().is_none());
+    }
+
+    #[test]
+    fn undefined_coordinates() {
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(250), Some(String::from("b"))),
+        ];
+        assert_eq!(coords(&merge_multiple(&mut input).unwrap()), vec![(String::from("chr1"), 100, 250)]);
+        input.push(Interval::from(Some(String::from("chr1")), None, Some(300), Some(String::from("c"))));
+        assert!(matches!(merge_multiple(&mut input), Err(CubiculumError::CoordinateError(_))));
+        assert!(matches!(cluster(&input, 0), Err(CubiculumError::CoordinateError(_))));
+        assert!(matches!(discrete_intervals(&input), Err(CubiculumError::CoordinateError(_))));
+        assert!(matches!(merge(input[0].clone(), input[1].clone()), Err(CubiculumError::CoordinateError(_))));
+        let mut unnamed: Vec<Interval> = vec![Interval::from(Some(String::from("chr1")), Some(100), Some(200), None)];
+        assert!(matches!(discrete_interval_map_by_chrom(&mut unnamed), Err(CubiculumError::MissingTraitError(_))));
+    }
+
+    #[test]
+    fn merge_is_chromosome_aware() {
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr2")), Some(100), Some(200), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(250), Some(String::from("b"))),
+            Interval::from(Some(String::from("chr1")), Some(250), Some(260), Some(String::from("c"))),
+        ];
+        let merged = merge_multiple_named(&mut input).unwrap();
+        assert_eq!(
+            coords(&merged),
+            vec![(String::from("chr1"), 150, 260), (String::from("chr2"), 100, 200)]
+        );
+        assert_eq!(merged[0].name(), Some("b,c"));
+        assert_eq!(merged[1].name(), Some("a"));
+    }
+
+    #[test]
+    fn merge_by_strand() {
+        let mut input: Vec<BedEntry> = vec![
+            BedEntry::bed6(String::from("chr1"), 100, 200, String::from("a"), String::from("0"), true),
+            BedEntry::bed6(String::from("chr1"), 150, 300, String::from("b"), String::from("0"), false),
+            BedEntry::bed6(String::from("chr1"), 180, 250, String::from("c"), String::from("0"), true),
+            BedEntry::bed6(String::from("chr1"), 290, 320, String::from("d"), String::from("0"), false),
+        ];
+        let merged = merge_stranded(&mut input, true).unwrap();
+        let result: Vec<(u64, u64, Option<bool>, &str)> = merged
+            .iter()
+            .map(|(x, s)| (*x.start().unwrap(), *x.end().unwrap(), *s, x.name().unwrap()))
+            .collect();
+        assert_eq!(result, vec![(100, 250, Some(true), "a,c"), (150, 320, Some(false), "b,d")]);
+
+        let mut unstranded: Vec<BedEntry> = vec![
+            BedEntry::bed4(String::from("chr1"), 100, 200, String::from("e")),
+            BedEntry::bed6(String::from("chr1"), 150, 300, String::from("f"), String::from("0"), true),
+            BedEntry::bed4(String::from("chr1"), 180, 250, String::from("g")),
+        ];
+        let merged = merge_stranded(&mut unstranded, true).unwrap();
+        let result: Vec<(u64, u64, Option<bool>, &str)> = merged
+            .iter()
+            .map(|(x, s)| (*x.start().unwrap(), *x.end().unwrap(), *s, x.name().unwrap()))
+            .collect();
+        assert_eq!(result, vec![(100, 250, None, "e,g"), (150, 300, Some(true), "f")]);
+        // merge_stranded sorts its input by strand in place, putting the unstranded e and g first
+        assert_eq!(cluster_stranded(&unstranded, 0).unwrap(), vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn merge_with_aggregation() {
+        let mut input: Vec<BedEntry> = vec![
+            BedEntry::bed5(String::from("chr1"), 100, 200, String::from("a"), String::from("5")),
+            BedEntry::bed5(String::from("chr1"), 150, 300, String::from("b"), String::from("1")),
+            BedEntry::bed5(String::from("chr1"), 180, 250, String::from("c"), String::from("3")),
+            BedEntry::bed5(String::from("chr1"), 400, 500, String::from("d"), String::from(".")),
+        ];
+        let merged = merge_aggregate(&mut input, Some(ScoreAggregation::Mean)).unwrap();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].count, 3);
+        assert_eq!(merged[0].joined_names(), "a,b,c");
+        assert_eq!(merged[0].score, Some(3.0));
+        assert_eq!(merged[1].names, vec![String::from("d")]);
+        assert!(merged[1].score.is_none());
+        let merged = merge_aggregate(&mut input, Some(ScoreAggregation::Max)).unwrap();
+        assert_eq!(merged[0].score, Some(5.0));
+        let merged = merge_aggregate(&mut input, None).unwrap();
+        assert!(merged[0].score.is_none());
+    }
+
+    #[test]
+    fn genome_complement() {
+        let chrom_sizes = ChromSizes::from_reader(
+            "chr1\t1000\nchr2\t500\n# comment\nchr3\t300\n".as_bytes()
+        ).unwrap();
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(0), Some(100), None),
+            Interval::from(Some(String::from("chr1")), Some(50), Some(200), None),
+            Interval::from(Some(String::from("chr1")), Some(900), Some(1000), None),
+            Interval::from(Some(String::from("chr2")), Some(100), Some(600), None),
+            Interval::from(Some(String::from("chrUn")), Some(0), Some(10), None),
+        ];
+        let gaps = complement(&mut input, &chrom_sizes).unwrap();
+        assert_eq!(
+            coords(&gaps),
+            vec![
+                (String::from("chr1"), 200, 900),
+                (String::from("chr2"), 0, 100),
+                (String::from("chr3"), 0, 300)
+            ]
+        );
+    }
+
+    #[test]
+    fn interval_clustering() {
+        let input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(300), Some(400), None),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), None),
+            Interval::from(Some(String::from("chr2")), Some(100), Some(200), None),
+            Interval::from(Some(String::from("chr1")), Some(200), Some(250), None),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(160), None),
+        ];
+        assert_eq!(cluster(&input, 0).unwrap(), vec![1, 0, 2, 0, 0]);
+        assert_eq!(cluster(&input, 50).unwrap(), vec![0, 0, 1, 0, 0]);
+        let stranded: Vec<BedEntry> = [
+            "chr1\t100\t200\ta\t0\t+",
+            "chr1\t150\t250\tb\t0\t-",
+            "chr1\t240\t300\tc\t0\t+",
+        ]
+            .iter()
+            .map(|x| crate::extract::extract::try_parse_bed(x, 6).unwrap())
+            .collect();
+        assert_eq!(cluster_stranded(&stranded, 0).unwrap(), vec![1, 0, 2]);
+        assert_eq!(cluster_stranded(&stranded, 40).unwrap(), vec![1, 0, 1]);
+    }
+
+    #[test]
+    fn interval_spans() {
+        let mut input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(100), Some(500), Some(String::from("gene1"))),
+            Interval::from(Some(String::from("chr1")), Some(300), Some(400), Some(String::from("gene1"))),
+            Interval::from(Some(String::from("chr1")), Some(50), Some(80), Some(String::from("gene2"))),
+        ];
+        let span = total_span(&mut input).unwrap();
+        assert_eq!((*span.start().unwrap(), *span.end().unwrap()), (50, 500));
+        assert_eq!(Named::name(&span), Some("chr1:50-500"));
+        input.push(Interval::from(Some(String::from("chr2")), Some(0), Some(10), Some(String::from("gene2"))));
+        assert!(total_span(&mut input).is_err());
+        assert!(total_span(&mut Vec::<Interval>::new()).is_err());
+        let per_chrom = total_span_per_chrom(&input).unwrap();
+        assert_eq!(coords(&[per_chrom["chr1"].clone(), per_chrom["chr2"].clone()]), vec![
+            (String::from("chr1"), 50, 500),
+            (String::from("chr2"), 0, 10)
+        ]);
+        assert!(span_per_name(&input).is_err());
+        input.retain(|x| x.chrom() == Some(&String::from("chr1")));
+        let per_name = span_per_name(&input).unwrap();
+        assert_eq!((*per_name["gene1"].start().unwrap(), *per_name["gene1"].end().unwrap()), (100, 500));
+        assert_eq!(Named::name(&per_name["gene2"]), Some("gene2"));
+    }
+
+    #[test]
+    fn streaming_merge() {
+        let input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(150), Some(180), Some(String::from("b"))),
+            Interval::from(Some(String::from("chr1")), Some(200), Some(250), Some(String::from("c"))),
+            Interval::from(Some(String::from("chr1")), Some(300), Some(400), Some(String::from("d"))),
+            Interval::from(Some(String::from("chr2")), Some(120), Some(130), Some(String::from("e"))),
+        ];
+        let merged: Vec<Interval> = merge_stream(input.clone(), true).collect::<Result<_, _>>().unwrap();
+        let mut copy = input.clone();
+        assert_eq!(coords(&merged), coords(&merge_multiple_named(&mut copy).unwrap()));
+        let names: Vec<&str> = merged.iter().map(|x| Named::name(x).unwrap()).collect();
+        assert_eq!(names, vec!["a,b,c", "d", "e"]);
+        assert!(merge_stream(Vec::<Interval>::new(), false).next().is_none());
+        assert!(Named::name(&merge_stream(input, false).next().unwrap().unwrap()).is_none());
+    }
+
+    #[test]
+    fn streaming_merge_unsorted() {
+        let input: Vec<Interval> = vec![
+            Interval::from(Some(String::from("chr1")), Some(300), Some(400), None),
+            Interval::from(Some(String::from("chr1")), Some(100), Some(200), None),
+            Interval::from(Some(String::from("chr1")), Some(500), Some(600), None),
+        ];
+        let mut stream = merge_stream(input, false);
+        assert!(matches!(stream.next(), Some(Err(CubiculumError::UnsortedInput(_)))));
+        assert!(stream.next().is_none());
+        let undefined = vec![Interval::from(Some(String::from("chr1")), None, None, None)];
+        assert!(matches!(merge_stream(undefined, false).next(), Some(Err(CubiculumError::CoordinateError(_)))));
+    }
+
+    #[test]
+    fn dedup_entries() {
+        let bed = |x: &str| crate::extract::extract::try_parse_bed(x, 12).unwrap();
+        let entries = vec![
+            bed("chr1\t100\t500\ta\t10\t+\t150\t450\t0\t2\t100,100,\t0,300,"),
+            bed("chr1\t100\t500\ta\t10\t+\t150\t450\t0\t2\t100,100,\t0,300,"),
+            bed("chr1\t100\t500\tb\t50\t+\t120\t480\t0\t2\t100,100,\t0,300,"),
+            bed("chr1\t100\t500\tc\t20\t-\t150\t450\t0\t2\t100,100,\t0,300,"),
+        ];
+        assert_eq!(dedup_exact(entries.clone()).len(), 3);
+        let first = dedup_by_coordinates(entries.clone(), DedupKeep::First);
+        assert_eq!(first.iter().map(|x| x.name().unwrap().as_str()).collect::<Vec<&str>>(), vec!["a", "c"]);
+        let longest = dedup_by_coordinates(entries.clone(), DedupKeep::Longest);
+        assert_eq!(longest[0].name().unwrap(), "b");
+        let scored = dedup_by_coordinates(entries, DedupKeep::HighestScore);
+        assert_eq!(scored[0].name().unwrap(), "b");
+        assert_eq!(scored[1].name().unwrap(), "c");
+    }
+
+    #[test]
+    fn genome_partition() {
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr2"), 50);
+        chrom_sizes.insert(String::from("chr1"), 100);
+        let input = vec![
+            Interval::from(Some(String::from("chr1")), Some(10), Some(30), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(20), Some(40), Some(String::from("b"))),
+            Interval::from(Some(String::from("chr1")), Some(90), Some(120), Some(String::from("c"))),
+            Interval::from(Some(String::from("chr3")), Some(0), Some(10), Some(String::from("d"))),
+        ];
+        let segments = partition(&input, &chrom_sizes).unwrap();
+        let bounds: Vec<(String, u64, u64)> = segments.iter().map(|x| coords(std::slice::from_ref(&x.0))[0].clone()).collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (String::from("chr2"), 0, 50),
+                (String::from("chr1"), 0, 10),
+                (String::from("chr1"), 10, 20),
+                (String::from("chr1"), 20, 30),
+                (String::from("chr1"), 30, 40),
+                (String::from("chr1"), 40, 90),
+                (String::from("chr1"), 90, 100),
+            ]
+        );
+        let names: Vec<Vec<&str>> = segments.into_iter().map(|x| x.1).collect();
+        assert_eq!(names, vec![vec![], vec![], vec!["a"], vec!["a", "b"], vec!["b"], vec![], vec!["c"]]);
+    }
+
+    #[test]
+    fn overlap_resolution() {
+        let input = vec![
+            Interval::from(Some(String::from("chr1")), Some(0), Some(100), Some(String::from("c"))),
+            Interval::from(Some(String::from("chr1")), Some(40), Some(60), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(90), Some(150), Some(String::from("b"))),
+            Interval::from(Some(String::from("chr2")), Some(90), Some(150), Some(String::from("d"))),
+        ];
+        let longest = resolve_overlaps(&input, ResolvePolicy::remove(OverlapPriority::Longest)).unwrap();
+        assert_eq!(
+            coords(&longest),
+            vec![(String::from("chr1"), 0, 100), (String::from("chr2"), 90, 150)]
+        );
+        let by_name = resolve_overlaps(&input, ResolvePolicy::trim(OverlapPriority::FirstByName)).unwrap();
+        assert_eq!(
+            coords(&by_name),
+            vec![
+                (String::from("chr1"), 0, 40),
+                (String::from("chr1"), 40, 60),
+                (String::from("chr1"), 60, 90),
+                (String::from("chr1"), 90, 150),
+                (String::from("chr2"), 90, 150),
+            ]
+        );
+        let names: Vec<&str> = by_name.iter().map(|x| Named::name(x).unwrap()).collect();
+        assert_eq!(names, vec!["c", "a", "c", "b", "d"]);
+    }
+}
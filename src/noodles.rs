@@ -0,0 +1,5 @@
+/*!
+Module for the conversions between BED records and noodles BED and GFF records; requires the `noodles` feature
+*/
+
+pub mod noodles;
@@ -0,0 +1,424 @@
+//! # cubiculum::noodles
+//!
+//! Conversions between BED records and the record types of the noodles-bed and noodles-gff crates
+//!
+//! noodles keeps 1-based, fully closed coordinates, which are converted to and from the 0-based,
+//! half-open BED ones. noodles-bed models the first six BED columns only; the thick coordinates,
+//! color and blocks of BED8, BED9 and BED12 records are kept in the other fields of a BED6 record.
+//! GFF exon and CDS features can be assembled into BED12 transcripts, and BED records unfolded
+//! into transcript, exon and CDS features
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use noodles_bed::feature::record::other_fields::Value as FieldValue;
+use noodles_bed::feature::record::Strand as BedStrand;
+use noodles_bed::feature::record_buf::{other_fields::Value, OtherFields};
+use noodles_bed::feature::{Record as BedRecord, RecordBuf as BedRecordBuf};
+use noodles_core::Position;
+use noodles_gff::feature::record::{Phase, Strand as GffStrand};
+use noodles_gff::feature::record_buf::attributes::field::{tag, Tag, Value as AttributeValue};
+use noodles_gff::feature::record_buf::Attributes;
+use noodles_gff::feature::RecordBuf as GffRecordBuf;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{to_line, try_parse_bed};
+use crate::structs::structs::{BedEntry, Coordinates};
+
+fn missing(field: &str) -> CubiculumError {
+    CubiculumError::MissingTraitError(format!("Undefined {} field", field))
+}
+
+/// 1-based position of a 0-based coordinate
+fn position(coord: u64) -> Result<Position, CubiculumError> {
+    usize::try_from(coord)
+        .ok()
+        .and_then(|x| x.checked_add(1))
+        .and_then(Position::new)
+        .ok_or_else(|| CubiculumError::CoordinateError(format!("Coordinate {} cannot be represented as a position", coord)))
+}
+
+/// 0-based coordinate of a 1-based position
+fn coordinate(position: Position) -> u64 {
+    usize::from(position) as u64 - 1
+}
+
+/// Chromosome, start and end of a record to be converted into a noodles-bed record with `n` standard fields
+fn standard_fields(entry: &BedEntry, n: u8) -> Result<(&String, Position, Option<Position>), CubiculumError> {
+    if entry.format() < n {
+        return Err(
+            CubiculumError::FormattingError(
+                format!("Cannot convert BED{} entry into a BED{} record", entry.format(), n)
+            )
+        )
+    }
+    let chrom = entry.chrom().ok_or_else(|| missing("chromosome"))?;
+    let start = position(entry.thin_start().ok_or_else(|| missing("thinStart"))?)?;
+    // BED end coordinate is the 1-based position of the last base
+    let end = match entry.thin_end().ok_or_else(|| missing("thinEnd"))? {
+        0 => {None},
+        x => {Some(position(x - 1)?)}
+    };
+    Ok((chrom, start, end))
+}
+
+fn bed_score(entry: &BedEntry) -> Result<u16, CubiculumError> {
+    let score = entry.score().ok_or_else(|| missing("score"))?;
+    score.parse::<u16>().map_err(|_| {
+        CubiculumError::FormattingError(format!("Score {} is not an integer in the 0-65535 range", score))
+    })
+}
+
+impl TryFrom<&BedEntry> for BedRecordBuf<3> {
+    type Error = CubiculumError;
+
+    fn try_from(entry: &BedEntry) -> Result<BedRecordBuf<3>, CubiculumError> {
+        let (chrom, start, end) = standard_fields(entry, 3)?;
+        let mut builder = BedRecordBuf::<3>::builder()
+            .set_reference_sequence_name(chrom.as_str())
+            .set_feature_start(start);
+        if let Some(x) = end {builder = builder.set_feature_end(x)};
+        Ok(builder.build())
+    }
+}
+
+impl TryFrom<&BedEntry> for BedRecordBuf<4> {
+    type Error = CubiculumError;
+
+    fn try_from(entry: &BedEntry) -> Result<BedRecordBuf<4>, CubiculumError> {
+        let (chrom, start, end) = standard_fields(entry, 4)?;
+        let mut builder = BedRecordBuf::<4>::builder()
+            .set_reference_sequence_name(chrom.as_str())
+            .set_feature_start(start)
+            .set_name(entry.name().ok_or_else(|| missing("name"))?.as_str());
+        if let Some(x) = end {builder = builder.set_feature_end(x)};
+        Ok(builder.build())
+    }
+}
+
+impl TryFrom<&BedEntry> for BedRecordBuf<5> {
+    type Error = CubiculumError;
+
+    fn try_from(entry: &BedEntry) -> Result<BedRecordBuf<5>, CubiculumError> {
+        let (chrom, start, end) = standard_fields(entry, 5)?;
+        let mut builder = BedRecordBuf::<5>::builder()
+            .set_reference_sequence_name(chrom.as_str())
+            .set_feature_start(start)
+            .set_name(entry.name().ok_or_else(|| missing("name"))?.as_str())
+            .set_score(bed_score(entry)?);
+        if let Some(x) = end {builder = builder.set_feature_end(x)};
+        Ok(builder.build())
+    }
+}
+
+/// Converts a BED6 or higher record; the columns past the sixth one are stored as other fields
+/// of the BED8, BED9 or BED12 layout
+impl TryFrom<&BedEntry> for BedRecordBuf<6> {
+    type Error = CubiculumError;
+
+    fn try_from(entry: &BedEntry) -> Result<BedRecordBuf<6>, CubiculumError> {
+        let (chrom, start, end) = standard_fields(entry, 6)?;
+        let strand = if entry.strand().ok_or_else(|| missing("strand"))? {BedStrand::Forward} else {BedStrand::Reverse};
+        let format = match entry.format() {
+            8 => {8},
+            9..=11 => {9},
+            12 => {12},
+            _ => {6}
+        };
+        let other_fields: Vec<Value> = if format > 6 {
+            to_line(entry, format)?.split('\t').skip(6).map(Value::from).collect()
+        } else {
+            Vec::new()
+        };
+        let mut builder = BedRecordBuf::<6>::builder()
+            .set_reference_sequence_name(chrom.as_str())
+            .set_feature_start(start)
+            .set_name(entry.name().ok_or_else(|| missing("name"))?.as_str())
+            .set_score(bed_score(entry)?)
+            .set_strand(strand)
+            .set_other_fields(OtherFields::from(other_fields));
+        if let Some(x) = end {builder = builder.set_feature_end(x)};
+        Ok(builder.build())
+    }
+}
+
+fn other_field(value: FieldValue<'_>) -> String {
+    match value {
+        FieldValue::Int64(x) => {x.to_string()},
+        FieldValue::UInt64(x) => {x.to_string()},
+        FieldValue::Float64(x) => {x.to_string()},
+        FieldValue::Character(x) => {char::from(x).to_string()},
+        FieldValue::String(x) => {x.to_string()}
+    }
+}
+
+/// Convert a noodles-bed record into a BED record
+///
+/// # Arguments
+/// `record`: a noodles-bed record with `N` standard fields, either a parsed `noodles_bed::Record`
+/// or a `RecordBuf`; for BED6 records, two, three or six other fields are read as the columns
+/// of BED8, BED9 or BED12, respectively, and any other number of them is ignored
+///
+/// # Returns
+/// A BED record of format N or the format defined by the other fields; a ParseError if the record
+/// cannot be decoded, and a MissingTraitError if a BED6 record has an undefined strand
+///
+pub fn from_bed_record<const N: usize, R: BedRecord<N>>(record: &R) -> Result<BedEntry, CubiculumError> {
+    let io_error = |e: std::io::Error| CubiculumError::ParseError(e.to_string());
+    let mut fields: Vec<String> = vec![
+        record.reference_sequence_name().to_string(),
+        coordinate(record.feature_start().map_err(io_error)?).to_string(),
+        record.feature_end().transpose().map_err(io_error)?.map_or(0, usize::from).to_string()
+    ];
+    if let Some(name) = record.name() {
+        fields.push(name.map_or(String::from("."), |x| x.to_string()));
+    }
+    if let Some(score) = record.score() {
+        fields.push(score.map_err(io_error)?.to_string());
+    }
+    if let Some(strand) = record.strand() {
+        match strand.map_err(io_error)? {
+            Some(BedStrand::Forward) => {fields.push(String::from("+"))},
+            Some(BedStrand::Reverse) => {fields.push(String::from("-"))},
+            None => {return Err(missing("strand"))}
+        }
+        let other_fields = record.other_fields();
+        if matches!(other_fields.len(), 2 | 3 | 6) {
+            fields.extend(other_fields.iter().map(other_field));
+        }
+    }
+    try_parse_bed(&fields.join("\t"), fields.len())
+}
+
+/// Converts a noodles-bed record; see `from_bed_record()`
+impl<const N: usize> TryFrom<&BedRecordBuf<N>> for BedEntry
+where
+    BedRecordBuf<N>: BedRecord<N>
+{
+    type Error = CubiculumError;
+
+    fn try_from(record: &BedRecordBuf<N>) -> Result<BedEntry, CubiculumError> {
+        from_bed_record(record)
+    }
+}
+
+/// First value of a GFF attribute
+fn attribute(feature: &GffRecordBuf, tag: &str) -> Option<String> {
+    feature.attributes().get(tag.as_bytes()).and_then(|x| x.iter().next()).map(|x| x.to_string())
+}
+
+/// BED record of a GFF feature; features with no or unknown strand become BED5 records
+fn gff_entry(feature: &GffRecordBuf, name: String) -> BedEntry {
+    let chrom = feature.reference_sequence_name().to_string();
+    let start = coordinate(feature.start());
+    let end = usize::from(feature.end()) as u64;
+    let score = feature.score().map_or(String::from("0"), |x| x.to_string());
+    match feature.strand() {
+        GffStrand::Forward => {BedEntry::bed6(chrom, start, end, name, score, true)},
+        GffStrand::Reverse => {BedEntry::bed6(chrom, start, end, name, score, false)},
+        _ => {BedEntry::bed5(chrom, start, end, name, score)}
+    }
+}
+
+/// Converts a GFF feature into a BED6 record named after its ID, Name or type, in this order of preference;
+/// features with no or unknown strand are converted into BED5 records
+impl From<&GffRecordBuf> for BedEntry {
+    fn from(feature: &GffRecordBuf) -> BedEntry {
+        let name = attribute(feature, tag::ID)
+            .or_else(|| attribute(feature, tag::NAME))
+            .unwrap_or_else(|| feature.ty().to_string());
+        gff_entry(feature, name)
+    }
+}
+
+#[derive(Default)]
+struct TranscriptFeatures {
+    exons: Vec<BedEntry>,
+    cds: Vec<BedEntry>
+}
+
+/// Assemble BED12 transcripts out of GFF exon and CDS features
+///
+/// Features are grouped by their Parent attribute, with features of multiple parents added to each of them.
+/// The coding sequence spans from the first to the last CDS base; transcripts with no exon features
+/// are assembled out of their CDS features. Features of other types and features without a parent are ignored
+///
+/// # Returns
+/// Transcripts named after their parents, in the order of the parents' first appearance;
+/// an error if a transcript's features lie on different chromosomes or strands or have undefined strand
+///
+/// # Examples
+/// ```
+/// use cubiculum::noodles::noodles::{gff_transcripts, to_gff_features};
+/// use cubiculum::extract::extract::try_parse_bed;
+///
+/// let entry = try_parse_bed("chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,", 12).unwrap();
+/// let features = to_gff_features(&entry, "cubiculum").unwrap();
+/// assert_eq!(features.len(), 5);
+/// assert_eq!(gff_transcripts(&features).unwrap(), vec![entry]);
+/// ```
+pub fn gff_transcripts(features: &[GffRecordBuf]) -> Result<Vec<BedEntry>, CubiculumError> {
+    let mut order: Vec<String> = Vec::new();
+    let mut transcripts: FxHashMap<String, TranscriptFeatures> = FxHashMap::default();
+    for feature in features {
+        let is_cds = match feature.ty().to_string().as_str() {
+            "exon" => {false},
+            "CDS" => {true},
+            _ => {continue}
+        };
+        let parents = match feature.attributes().get(tag::PARENT.as_bytes()) {
+            Some(x) => {x},
+            None => {continue}
+        };
+        for parent in parents.iter() {
+            let parent = parent.to_string();
+            let block = gff_entry(feature, parent.clone());
+            let transcript = transcripts.entry(parent.clone()).or_insert_with(|| {
+                order.push(parent);
+                TranscriptFeatures::default()
+            });
+            if is_cds {transcript.cds.push(block)} else {transcript.exons.push(block)};
+        }
+    }
+    order.iter()
+        .map(|parent| {
+            let transcript = &transcripts[parent];
+            let thick = if transcript.cds.is_empty() {
+                None
+            } else {
+                let start = transcript.cds.iter().filter_map(|x| x.thin_start()).min().unwrap_or(0);
+                let end = transcript.cds.iter().filter_map(|x| x.thin_end()).max().unwrap_or(0);
+                Some((start, end))
+            };
+            let blocks = if transcript.exons.is_empty() {&transcript.cds} else {&transcript.exons};
+            BedEntry::from_exons(blocks, thick)
+        })
+        .collect()
+}
+
+/// Unfold a BED record into GFF features
+///
+/// # Arguments
+/// `entry`: a BED6 or higher record; BED6 through BED9 records are considered single-exon transcripts;
+/// `source`: value of the source column
+///
+/// # Returns
+/// A transcript feature with the record's name as its ID, followed by its exon and CDS features
+/// in ascending coordinate order; the CDS phases follow the direction of transcription.
+/// An error if the record lacks name, strand or coordinates
+///
+pub fn to_gff_features(entry: &BedEntry, source: &str) -> Result<Vec<GffRecordBuf>, CubiculumError> {
+    let chrom = entry.chrom().ok_or_else(|| missing("chromosome"))?;
+    let name = entry.name().ok_or_else(|| missing("name"))?;
+    let plus = entry.strand().ok_or_else(|| missing("strand"))?;
+    let strand = if plus {GffStrand::Forward} else {GffStrand::Reverse};
+    let feature = |ty: &str, tag: &str, start: u64, end: u64| -> Result<_, CubiculumError> {
+        if start >= end {
+            return Err(
+                CubiculumError::CoordinateError(format!("Cannot convert an empty {} of {} into a GFF feature", ty, name))
+            )
+        }
+        let attributes: Attributes = [(Tag::from(tag), AttributeValue::from(name.as_str()))].into_iter().collect();
+        Ok(
+            GffRecordBuf::builder()
+                .set_reference_sequence_name(chrom.as_str())
+                .set_source(source)
+                .set_type(ty)
+                .set_start(position(start)?)
+                .set_end(position(end - 1)?)
+                .set_strand(strand)
+                .set_attributes(attributes)
+        )
+    };
+    let start = entry.thin_start().ok_or_else(|| missing("thinStart"))?;
+    let end = entry.thin_end().ok_or_else(|| missing("thinEnd"))?;
+    let mut features: Vec<GffRecordBuf> = vec![feature("transcript", tag::ID, start, end)?.build()];
+    for (exon_start, exon_end) in entry.exons() {
+        features.push(feature("exon", tag::PARENT, exon_start, exon_end)?.build());
+    }
+    if let Some(blocks) = entry.cds_blocks() {
+        let mut phases: Vec<Phase> = vec![Phase::Zero; blocks.len()];
+        let indices: Vec<usize> = if plus {(0..blocks.len()).collect()} else {(0..blocks.len()).rev().collect()};
+        let mut coding: u64 = 0;
+        for i in indices {
+            phases[i] = match (3 - coding % 3) % 3 {
+                0 => {Phase::Zero},
+                1 => {Phase::One},
+                _ => {Phase::Two}
+            };
+            coding += blocks[i].1 - blocks[i].0;
+        }
+        for ((cds_start, cds_end), phase) in blocks.into_iter().zip(phases) {
+            features.push(feature("CDS", tag::PARENT, cds_start, cds_end)?.set_phase(phase).build());
+        }
+    }
+    Ok(features)
+}
+
+#[cfg(test)]
+mod test_noodles {
+    use super::*;
+
+    const BED12: &str = "chr1\t100\t600\tA\t0\t-\t150\t550\t255,0,0\t3\t100,50,100,\t0,200,400,";
+
+    #[test]
+    fn bed_records() {
+        let entry = try_parse_bed(BED12, 12).unwrap();
+        let record = BedRecordBuf::<6>::try_from(&entry).unwrap();
+        assert_eq!(usize::from(record.feature_start()), 101);
+        assert_eq!(record.feature_end().map(usize::from), Some(600));
+        assert_eq!(record.strand(), Some(BedStrand::Reverse));
+        assert_eq!(BedEntry::try_from(&record).unwrap(), entry);
+
+        let mut writer = noodles_bed::io::Writer::<6, Vec<u8>>::new(Vec::new());
+        writer.write_feature_record(&record).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap(), format!("{}\n", BED12));
+
+        let bed3 = BedRecordBuf::<3>::try_from(&entry).unwrap();
+        assert_eq!(BedEntry::try_from(&bed3).unwrap(), try_parse_bed("chr1\t100\t600", 3).unwrap());
+        let bed5 = BedRecordBuf::<5>::try_from(&entry).unwrap();
+        assert_eq!(BedEntry::try_from(&bed5).unwrap().format(), 5);
+        let bed3_entry = BedEntry::bed3(String::from("chr1"), 0, 10);
+        assert!(matches!(BedRecordBuf::<4>::try_from(&bed3_entry), Err(CubiculumError::FormattingError(_))));
+        let scored = try_parse_bed("chr1\t0\t10\tB\t0.5", 5).unwrap();
+        assert!(matches!(BedRecordBuf::<5>::try_from(&scored), Err(CubiculumError::FormattingError(_))));
+    }
+
+    #[test]
+    fn parsed_records() {
+        let line = format!("{}\n", BED12);
+        let mut reader = noodles_bed::io::Reader::<6, _>::new(line.as_bytes());
+        let mut record = noodles_bed::Record::<6>::default();
+        reader.read_record(&mut record).unwrap();
+        assert_eq!(from_bed_record(&record).unwrap(), try_parse_bed(BED12, 12).unwrap());
+
+        let mut reader = noodles_bed::io::Reader::<6, _>::new("chr1\t0\t10\tB\t0\t.\n".as_bytes());
+        reader.read_record(&mut record).unwrap();
+        assert!(matches!(from_bed_record(&record), Err(CubiculumError::MissingTraitError(_))));
+    }
+
+    #[test]
+    fn gff_features() {
+        let entry = try_parse_bed(BED12, 12).unwrap();
+        let features = to_gff_features(&entry, "test").unwrap();
+        let types: Vec<String> = features.iter().map(|x| x.ty().to_string()).collect();
+        assert_eq!(types, vec!["transcript", "exon", "exon", "exon", "CDS", "CDS", "CDS"]);
+        // minus strand: the last CDS block (500-550) is the first one translated
+        let phases: Vec<Option<Phase>> = features[4..].iter().map(|x| x.phase()).collect();
+        assert_eq!(phases, vec![Some(Phase::Two), Some(Phase::One), Some(Phase::Zero)]);
+        assert_eq!(usize::from(features[4].start()), 151);
+
+        let transcripts = gff_transcripts(&features).unwrap();
+        assert_eq!(transcripts.len(), 1);
+        assert_eq!(transcripts[0].exons().collect::<Vec<(u64, u64)>>(), entry.exons().collect::<Vec<(u64, u64)>>());
+        assert_eq!((transcripts[0].thick_start(), transcripts[0].thick_end()), (Some(150), Some(550)));
+        assert_eq!(transcripts[0].name().unwrap(), "A");
+
+        let transcript = BedEntry::from(&features[0]);
+        assert_eq!(transcript, BedEntry::bed6(String::from("chr1"), 100, 600, String::from("A"), String::from("0"), false));
+        let bed4 = try_parse_bed("chr1\t0\t10\tB", 4).unwrap();
+        assert!(matches!(to_gff_features(&bed4, "test"), Err(CubiculumError::MissingTraitError(_))));
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for strict and lenient processing settings
+*/
+
+pub mod options;
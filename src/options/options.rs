@@ -0,0 +1,173 @@
+//! # cubiculum::options
+//!
+//! Handling of anomalous input shared by the parsing and editing operations
+//!
+//! `ProcessingOptions` decides what happens when an operation encounters a recoverable anomaly,
+//! such as a thickStart lying before thinStart or a clipping range missing the entry entirely.
+//! In strict mode, the anomaly is returned as an error; in lenient mode, the operation fixes it
+//! and reports the fix to the warning handler, or logs it with `log::warn!()` if no handler is set.
+//! Input that cannot be fixed, such as non-numeric coordinates, is an error in both modes.
+//!
+//! The options are accepted by `parse_bed_with_options()`, `bed_to_fraction_with_options()`,
+//! `BedEntry::clip_by_with_options()` and `BedEntry::graft_with_options()`
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::error::error::CubiculumError;
+
+/// Reaction to recoverable input anomalies
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strictness {
+    /// Anomalies are reported as errors
+    #[default]
+    Strict,
+    /// Anomalies are fixed and passed to the warning handler
+    Lenient
+}
+
+/// Callback receiving the anomalies fixed in lenient mode
+pub type WarningHandler = Arc<dyn Fn(&CubiculumError) + Send + Sync>;
+
+/// Anomaly handling settings; strict by default, with lenient-mode anomalies sent to the `log` crate
+/// unless a warning handler is set
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use cubiculum::extract::extract::parse_bed_with_options;
+/// use cubiculum::options::options::ProcessingOptions;
+///
+/// let line = "chr1\t100\t200\tA\t0\t+\t50\t150";
+/// assert!(parse_bed_with_options(line, 8, &ProcessingOptions::strict()).is_err());
+///
+/// let warnings = Arc::new(Mutex::new(Vec::new()));
+/// let sink = warnings.clone();
+/// let options = ProcessingOptions::lenient().warning_handler(move |x| sink.lock().unwrap().push(x.to_string()));
+/// let entry = parse_bed_with_options(line, 8, &options).unwrap();
+/// assert_eq!(entry.thick_start(), Some(100));
+/// assert_eq!(warnings.lock().unwrap().len(), 1);
+/// ```
+#[derive(Clone, Default)]
+pub struct ProcessingOptions {
+    pub strictness: Strictness,
+    handler: Option<WarningHandler>
+}
+
+impl fmt::Debug for ProcessingOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessingOptions")
+            .field("strictness", &self.strictness)
+            .field("handler", &self.handler.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+impl ProcessingOptions {
+    pub fn new() -> ProcessingOptions {
+        ProcessingOptions::default()
+    }
+
+    pub fn strict() -> ProcessingOptions {
+        ProcessingOptions::default()
+    }
+
+    pub fn lenient() -> ProcessingOptions {
+        ProcessingOptions::default().strictness(Strictness::Lenient)
+    }
+
+    pub fn strictness(mut self, strictness: Strictness) -> ProcessingOptions {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Set the callback receiving the anomalies fixed in lenient mode
+    pub fn warning_handler<F>(mut self, handler: F) -> ProcessingOptions
+    where
+        F: Fn(&CubiculumError) + Send + Sync + 'static
+    {
+        self.handler = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strictness == Strictness::Strict
+    }
+
+    /// Pass the warning to the handler; without a handler, the warning is logged at the warn level
+    pub fn warn(&self, warning: &CubiculumError) {
+        match &self.handler {
+            Some(handler) => {handler(warning)},
+            None => {log::warn!("{}", warning)}
+        }
+    }
+
+    /// Register a recoverable anomaly
+    ///
+    /// # Returns
+    /// The anomaly as an error in strict mode; in lenient mode, the anomaly is passed
+    /// to `warn()`, and the caller is expected to fix it
+    ///
+    pub fn anomaly(&self, anomaly: CubiculumError) -> Result<(), CubiculumError> {
+        if self.is_strict() {return Err(anomaly)};
+        self.warn(&anomaly);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_options {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::extract::extract::{bed_to_fraction_with_options, parse_bed_with_options, to_line};
+    use crate::structs::structs::{GraftOptions, Interval};
+
+    fn collecting() -> (ProcessingOptions, Arc<Mutex<Vec<String>>>) {
+        let warnings: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let sink = warnings.clone();
+        (ProcessingOptions::lenient().warning_handler(move |x| sink.lock().unwrap().push(x.to_string())), warnings)
+    }
+
+    #[test]
+    fn lenient_parsing() {
+        let (options, warnings) = collecting();
+        let line = "chr1\t600\t100\tA\t0\t+\t50\t700\t0\t3\t100,100,\t0,200,400,";
+        assert!(matches!(parse_bed_with_options(line, 12, &ProcessingOptions::strict()), Err(CubiculumError::CoordinateError(_))));
+        let entry = parse_bed_with_options(line, 12, &options).unwrap();
+        assert_eq!(to_line(&entry, 12).unwrap(), "chr1\t100\t600\tA\t0\t+\t100\t600\t0\t2\t100,100,\t0,200,");
+        assert_eq!(warnings.lock().unwrap().len(), 4);
+        assert!(parse_bed_with_options("chr1\tx\t100", 3, &options).is_err());
+    }
+
+    #[test]
+    fn lenient_editing() {
+        let (options, warnings) = collecting();
+        let line = "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,";
+        let mut entry = parse_bed_with_options(line, 12, &options).unwrap();
+        assert!(entry.clip_by_with_options(Some(210), Some(290), false, &ProcessingOptions::strict()).is_err());
+        assert!(entry.clip_by_with_options(Some(210), Some(290), false, &options).unwrap().is_none());
+        let clipped = entry.clip_by_with_options(Some(150), None, false, &options).unwrap().unwrap();
+        assert_eq!(clipped.thin_start(), Some(150));
+
+        let graft = Interval::from(Some(String::from("chr1")), Some(550), Some(700), None);
+        let graft_options = GraftOptions::new();
+        assert!(entry.graft_with_options(graft.clone(), &graft_options, &ProcessingOptions::strict(), false).is_err());
+        let grafted = entry.graft_with_options(graft, &graft_options, &options, false).unwrap().unwrap();
+        assert_eq!(grafted.thin_end(), Some(700));
+        assert_eq!(warnings.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn lenient_fraction() {
+        let (options, warnings) = collecting();
+        let line = "chr1\t100\t600\tA\t0\t+\t150\t650\t0\t3\t100,100,100,\t0,200,400,";
+        assert!(bed_to_fraction_with_options(line.to_string(), "cds", false, None, &ProcessingOptions::strict()).is_err());
+        let cds = bed_to_fraction_with_options(line.to_string(), "cds", false, None, &options).unwrap().unwrap();
+        assert_eq!(cds, "chr1\t150\t600\tA\t0\t+\t150\t600\t0\t3\t50,100,100,\t0,150,350,");
+        assert_eq!(warnings.lock().unwrap().len(), 1);
+        assert!(bed_to_fraction_with_options(line.to_string(), "exon", false, None, &options).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for multithreaded per-chromosome execution; requires the `parallel` feature
+*/
+
+pub mod parallel;
@@ -0,0 +1,310 @@
+//! # cubiculum::parallel
+//! 
+//! Per-chromosome execution of the interval operations on a thread pool
+//!
+//! All functions accept an optional thread pool; if none is provided, the global rayon pool is used.
+//! The results are identical to those of the respective single-threaded functions
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use fxhash::FxHashMap;
+use std::io::{BufRead, BufWriter, Write};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{check_fraction_mode, fraction_record, write_fraction, Bed6Naming, FractionBuffer};
+use crate::intersect::intersect::{index_by_chrom, query_coverage, Coverage, OverlapCriteria};
+use crate::merge::merge::{discretize_chrom, merge_sorted, name_merged, DiscreteIntervalMap};
+use crate::structs::structs::{Coordinates, Interval, Named};
+
+/// Create a thread pool with the given number of threads
+pub fn thread_pool(threads: usize) -> Result<ThreadPool, ThreadPoolBuildError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+}
+
+/// Run the operation in the provided pool, or in the global one
+fn install<R, F>(pool: Option<&ThreadPool>, op: F) -> R
+where
+    R: Send,
+    F: FnOnce() -> R + Send
+{
+    match pool {
+        Some(x) => {x.install(op)},
+        None => {op()}
+    }
+}
+
+/// Run the function for each chromosome in parallel
+///
+/// # Arguments
+/// `records`: input records; records with undefined chromosome are skipped;
+/// `pool`: thread pool to use;
+/// `f`: function accepting the chromosome name and the indices of the chromosome's records
+/// in `records`, in their input order
+///
+/// # Returns
+/// A vector of (chromosome, result) pairs, sorted by chromosome name
+///
+pub fn per_chrom<T, R, F>(records: &[T], pool: Option<&ThreadPool>, f: F) -> Vec<(String, R)>
+where
+    T: Coordinates + Sync,
+    R: Send,
+    F: Fn(&str, &[usize]) -> R + Sync
+{
+    let mut groups: FxHashMap<&String, Vec<usize>> = FxHashMap::default();
+    for (i, x) in records.iter().enumerate() {
+        if let Some(chrom) = x.chrom() {
+            groups.entry(chrom).or_default().push(i);
+        }
+    }
+    let mut groups: Vec<(&String, Vec<usize>)> = groups.into_iter().collect();
+    groups.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    install(pool, || {
+        groups.par_iter()
+            .map(|(chrom, indices)| (chrom.to_string(), f(chrom, indices)))
+            .collect()
+    })
+}
+
+/// Sort the intervals by chromosome and coordinates and split them into per-chromosome chunks
+fn sorted_chunks<T: Coordinates>(intervals: &mut [T]) -> Vec<&[T]> {
+    intervals.sort_by(
+        |a, b| a.chrom().cmp(&b.chrom())
+            .then(a.start().cmp(&b.start()))
+            .then(a.end().cmp(&b.end()))
+    );
+    let intervals: &[T] = intervals;
+    intervals.chunk_by(|a, b| a.chrom() == b.chrom()).collect()
+}
+
+/// Parallel version of merge_multiple()
+pub fn par_merge_multiple<T>(intervals: &mut [T], pool: Option<&ThreadPool>) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates + Sync
+{
+    let chunks = sorted_chunks(intervals);
+    let merged: Vec<Vec<Interval>> = install(pool, || {
+        chunks.par_iter()
+            .map(|chunk| Ok(merge_sorted(chunk, |_| None)?.into_iter().map(|(x, _, _)| x).collect::<Vec<Interval>>()))
+            .collect::<Result<Vec<Vec<Interval>>, CubiculumError>>()
+    })?;
+    Ok(merged.into_iter().flatten().collect())
+}
+
+/// Parallel version of merge_multiple_named()
+pub fn par_merge_multiple_named<T>(intervals: &mut [T], pool: Option<&ThreadPool>) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates + Named + Sync
+{
+    let chunks = sorted_chunks(intervals);
+    let merged: Vec<Vec<Interval>> = install(pool, || {
+        chunks.par_iter()
+            .map(|chunk| {
+                let named = merge_sorted(chunk, |_| None)?
+                    .into_iter()
+                    .map(|(mut x, _, members)| {
+                        name_merged(chunk, &mut x, &members);
+                        x
+                    })
+                    .collect::<Vec<Interval>>();
+                Ok(named)
+            })
+            .collect::<Result<Vec<Vec<Interval>>, CubiculumError>>()
+    })?;
+    Ok(merged.into_iter().flatten().collect())
+}
+
+/// Parallel version of discrete_interval_map_by_chrom()
+pub fn par_discrete_interval_map_by_chrom<'a, T>(
+    intervals: &'a mut [T], pool: Option<&ThreadPool>
+) -> Result<FxHashMap<String, DiscreteIntervalMap<'a>>, CubiculumError>
+where
+    T: Coordinates + Named + Sync
+{
+    let chunks = sorted_chunks(intervals);
+    install(pool, || {
+        chunks.par_iter()
+            .map(|chunk| discretize_chrom(chunk))
+            .collect()
+    })
+}
+
+/// Parallel version of coverage()
+pub fn par_coverage<'a, A, B>(a: &'a [A], b: &[B], histogram: bool, pool: Option<&ThreadPool>) -> Vec<Coverage<'a, A>>
+where
+    A: Coordinates + Sync,
+    B: Coordinates + Sync
+{
+    let index = index_by_chrom(b);
+    install(pool, || {
+        a.par_iter()
+            .map(|x| query_coverage(x, b, &index, histogram))
+            .collect()
+    })
+}
+
+/// Find all overlapping pairs between the two sets of records in parallel
+///
+/// Unlike intersect(), neither of the inputs needs to be sorted
+///
+/// # Returns
+/// A vector of (index in `a`, index in `b`) pairs for the records satisfying the overlap criteria,
+/// sorted by the `a` index and then by the `b` record coordinates
+///
+pub fn par_intersect<A, B>(a: &[A], b: &[B], criteria: &OverlapCriteria, pool: Option<&ThreadPool>) -> Vec<(usize, usize)>
+where
+    A: Coordinates + Sync,
+    B: Coordinates + Sync
+{
+    let index = index_by_chrom(b);
+    install(pool, || {
+        a.par_iter()
+            .enumerate()
+            .flat_map_iter(|(i, x)| {
+                let hits: Vec<(usize, usize)> = match (x.chrom().and_then(|c| index.get(c)), x.start(), x.end()) {
+                    (Some(chrom_index), Some(start), Some(end)) => {
+                        chrom_index.overlapping(b, *start, *end)
+                            .filter(|j| criteria.passes(x, &b[*j]))
+                            .map(|j| (i, j))
+                            .collect()
+                    },
+                    _ => {Vec::new()}
+                };
+                hits
+            })
+            .collect()
+    })
+}
+
+/// Parallel version of fraction_stream_named()
+///
+/// The stream is read in chunks of `chunk_size` lines, each chunk being processed in parallel;
+/// records are written in their input order
+pub fn par_fraction_stream<R, W>(
+    reader: R,
+    writer: &mut W,
+    mode: &str,
+    intron: bool,
+    naming: Option<&Bed6Naming>,
+    chunk_size: usize,
+    pool: Option<&ThreadPool>
+) -> Result<usize, CubiculumError>
+where
+    R: BufRead,
+    W: Write
+{
+    check_fraction_mode(mode)?;
+    let chunk_size = chunk_size.max(1);
+    let mut output = BufWriter::new(writer);
+    let mut written: usize = 0;
+    let mut lines = reader.lines();
+    loop {
+        let chunk: Vec<String> = lines.by_ref()
+            .take(chunk_size)
+            .collect::<Result<Vec<String>, _>>()?;
+        if chunk.is_empty() {break};
+        let fractions: Vec<Option<String>> = install(pool, || {
+            chunk.into_par_iter()
+                .map_init(
+                    FractionBuffer::new,
+                    |buffer, line| fraction_record(buffer, &line, mode, intron, naming).map(|x| x.map(String::from))
+                )
+                .collect::<Result<Vec<Option<String>>, CubiculumError>>()
+        })?;
+        for fraction in fractions.iter().flatten() {
+            written += write_fraction(&mut output, fraction)?;
+        }
+    }
+    output.flush()?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod test_parallel {
+    use super::*;
+    use crate::extract::extract::fraction_stream_named;
+    use crate::intersect::intersect::coverage;
+    use crate::merge::merge::{discrete_interval_map_by_chrom, merge_multiple, merge_multiple_named};
+
+    fn intervals() -> Vec<Interval> {
+        let mut state: u64 = 5;
+        let mut next = move || {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            state >> 33
+        };
+        (0..2000)
+            .map(|i| {
+                let start = next() % 50_000;
+                let chrom = format!("chr{}", next() % 5);
+                Interval::from(Some(chrom), Some(start), Some(start + next() % 300 + 1), Some(format!("x{}", i)))
+            })
+            .collect()
+    }
+
+    fn coords(intervals: &[Interval]) -> Vec<(String, u64, u64, String)> {
+        intervals
+            .iter()
+            .map(|x| (x.chrom().unwrap().clone(), *x.start().unwrap(), *x.end().unwrap(), Named::name(x).unwrap_or("").to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let pool = thread_pool(4).unwrap();
+        let mut input = intervals();
+        let mut copy = input.clone();
+        assert_eq!(
+            coords(&par_merge_multiple_named(&mut input, Some(&pool)).unwrap()),
+            coords(&merge_multiple_named(&mut copy).unwrap())
+        );
+        assert_eq!(coords(&par_merge_multiple(&mut input, None).unwrap()), coords(&merge_multiple(&mut copy).unwrap()));
+        let queries = intervals();
+        let expected: Vec<(usize, u64)> = coverage(&queries, &input, false).iter().map(|x| (x.count, x.covered)).collect();
+        let observed: Vec<(usize, u64)> = par_coverage(&queries, &input, false, None).iter().map(|x| (x.count, x.covered)).collect();
+        assert_eq!(observed, expected);
+        let pairs = par_intersect(&queries, &input, &OverlapCriteria::default(), Some(&pool));
+        assert_eq!(pairs.len(), expected.iter().map(|x| x.0).sum::<usize>());
+        let mut copy = input.clone();
+        let sequential = discrete_interval_map_by_chrom(&mut copy).unwrap();
+        let mut copy = input.clone();
+        let parallel = par_discrete_interval_map_by_chrom(&mut copy, Some(&pool)).unwrap();
+        assert_eq!(parallel.len(), sequential.len());
+        for (chrom, (segments, names)) in sequential.iter() {
+            assert_eq!(coords(&parallel[chrom].0), coords(segments));
+            assert_eq!(&parallel[chrom].1, names);
+        }
+        let counts = per_chrom(&input, Some(&pool), |_, indices| indices.len());
+        assert_eq!(counts.iter().map(|x| x.1).sum::<usize>(), input.len());
+        assert!(counts.windows(2).all(|x| x[0].0 < x[1].0));
+    }
+
+    #[test]
+    fn parallel_fraction_stream() {
+        let bed: String = (0..500u64)
+            .map(|i| {
+                let start = i * 1000;
+                format!(
+                    "chr1\t{}\t{}\tT{}\t0\t{}\t{}\t{}\t0\t3\t100,100,100,\t0,200,400,\n",
+                    start, start + 500, i, if i % 2 == 0 {'+'} else {'-'}, start + 50 + i % 40, start + 450
+                )
+            })
+            .collect();
+        let pool = thread_pool(4).unwrap();
+        let naming = Bed6Naming::new().ordinal_in_name("exon");
+        for naming in [None, Some(&naming)] {
+            let mut sequential: Vec<u8> = Vec::new();
+            let expected = fraction_stream_named(bed.as_bytes(), &mut sequential, "cds", false, naming).unwrap();
+            let mut parallel: Vec<u8> = Vec::new();
+            let written = par_fraction_stream(bed.as_bytes(), &mut parallel, "cds", false, naming, 64, Some(&pool)).unwrap();
+            assert_eq!(written, expected);
+            assert_eq!(parallel, sequential);
+        }
+        let mut output: Vec<u8> = Vec::new();
+        assert!(par_fraction_stream(bed.as_bytes(), &mut output, "exon", false, None, 64, None).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for single-pass BED processing pipelines
+*/
+
+pub mod pipeline;
@@ -0,0 +1,358 @@
+//! # cubiculum::pipeline
+//!
+//! Chained record operations executed in a single pass over a record stream
+//!
+//! A `Pipeline` is assembled from filter, clip, fraction and map stages, optionally followed
+//! by a merge of the resulting records. Each record passes through all stages before the next one is read,
+//! so no intermediate collections are created between the steps
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, extract_fraction, try_parse_bed, BedFractionMode};
+use crate::filter::filter::Filter;
+use crate::merge::merge::merge_stream;
+use crate::options::options::ProcessingOptions;
+use crate::structs::structs::{BedEntry, Interval, Named, ToBedLine};
+
+type FilterFn = Box<dyn Fn(&BedEntry) -> bool + Send + Sync>;
+type MapFn = Box<dyn Fn(BedEntry) -> Option<BedEntry> + Send + Sync>;
+
+/// A single pipeline operation
+enum Stage {
+    Filter(FilterFn),
+    Clip(Option<u64>, Option<u64>),
+    Fraction(BedFractionMode, bool),
+    Map(MapFn)
+}
+
+impl Stage {
+    fn apply(&self, mut entry: BedEntry) -> Result<Option<BedEntry>, CubiculumError> {
+        match self {
+            Stage::Filter(f) => {Ok(if f(&entry) {Some(entry)} else {None})},
+            Stage::Clip(start, end) => {entry.clip_by_with_options(*start, *end, false, &ProcessingOptions::lenient())},
+            Stage::Fraction(mode, intron) => {extract_fraction(&entry, *mode, *intron)},
+            Stage::Map(f) => {Ok(f(entry))}
+        }
+    }
+}
+
+impl fmt::Debug for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stage::Filter(_) => {write!(f, "Filter")},
+            Stage::Clip(start, end) => {write!(f, "Clip({:?}, {:?})", start, end)},
+            Stage::Fraction(mode, intron) => {write!(f, "Fraction({:?}, {})", mode, intron)},
+            Stage::Map(_) => {write!(f, "Map")}
+        }
+    }
+}
+
+/// Chain of record operations
+///
+/// Stages are applied in the order they were added; a record discarded by a stage
+/// is not passed to the following ones
+///
+/// ```
+/// use cubiculum::extract::extract::BedFractionMode;
+/// use cubiculum::pipeline::pipeline::Pipeline;
+///
+/// let pipeline = Pipeline::new()
+///     .filter(|x| x.strand() == Some(true))
+///     .fraction(BedFractionMode::Cds, false)
+///     .merge(false);
+/// let bed = "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,\n\
+///     chr1\t300\t700\tB\t0\t+\t320\t500\t0\t2\t100,200,\t0,200,\n\
+///     chr1\t400\t900\tC\t0\t-\t400\t900\t0\t1\t500,\t0,\n\
+///     chr1\t800\t1000\tD\t0\t+\t850\t950\t0\t1\t200,\t0,\n";
+/// let mut output: Vec<u8> = Vec::new();
+/// assert_eq!(pipeline.run_stream(bed.as_bytes(), 12, &mut output).unwrap(), 2);
+/// assert_eq!(String::from_utf8(output).unwrap(), "chr1\t150\t500\nchr1\t850\t950\n");
+/// ```
+#[derive(Debug, Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+    merge: Option<bool>
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Pipeline::default()
+    }
+
+    /// Keep the records for which the predicate returns true
+    pub fn filter<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&BedEntry) -> bool + Send + Sync + 'static
+    {
+        self.stages.push(Stage::Filter(Box::new(f)));
+        self
+    }
+
+    /// Keep the records passing the filter
+    pub fn filter_by(self, filter: Filter) -> Self {
+        self.filter(move |x| filter.matches(x))
+    }
+
+    /// Clip the records to the [start, end) range; see `BedEntry::clip_by()`.
+    /// Records lying outside of the range are discarded, while records with undefined chromosome
+    /// or coordinates result in an error
+    pub fn clip(mut self, start: Option<u64>, end: Option<u64>) -> Self {
+        self.stages.push(Stage::Clip(start, end));
+        self
+    }
+
+    /// Replace the records with their fraction; see `extract_fraction()`.
+    /// Records with an empty fraction are discarded
+    pub fn fraction(mut self, mode: BedFractionMode, intron: bool) -> Self {
+        self.stages.push(Stage::Fraction(mode, intron));
+        self
+    }
+
+    /// Apply an arbitrary transformation; records for which it returns None are discarded
+    pub fn map<F>(mut self, f: F) -> Self
+    where
+        F: Fn(BedEntry) -> Option<BedEntry> + Send + Sync + 'static
+    {
+        self.stages.push(Stage::Map(Box::new(f)));
+        self
+    }
+
+    /// Merge the overlapping and book-ended records produced by the preceding stages; see `merge_stream()`.
+    /// Merging is always the last step, and requires the stage output to be coordinate-sorted.
+    /// It is applied by `write_all()` and `run_stream()` only
+    pub fn merge(mut self, keep_names: bool) -> Self {
+        self.merge = Some(keep_names);
+        self
+    }
+
+    /// Number of per-record stages
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Pass a single record through all stages
+    ///
+    /// # Returns
+    /// The processed record; None if it was discarded at any of the stages
+    ///
+    pub fn apply(&self, entry: BedEntry) -> Result<Option<BedEntry>, CubiculumError> {
+        let mut entry = entry;
+        for stage in self.stages.iter() {
+            entry = match stage.apply(entry)? {
+                Some(x) => {x},
+                None => {return Ok(None)}
+            };
+        }
+        Ok(Some(entry))
+    }
+
+    /// Lazily pass the records through all stages, except for the merge
+    pub fn process<I>(&self, entries: I) -> Processed<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = BedEntry>
+    {
+        Processed {pipeline: self, entries: entries.into_iter()}
+    }
+
+    /// Run the pipeline over the records, writing the results to the output stream
+    ///
+    /// # Arguments
+    /// `entries`: input records;
+    /// `writer`: output stream;
+    /// `format`: output BED format; ignored if the pipeline ends with a merge,
+    /// in which case merged intervals are written as BED3 (BED4 if the merged names are kept)
+    ///
+    /// # Returns
+    /// The number of records written; an error if a stage fails, the output cannot be written,
+    /// or the records to be merged are not coordinate-sorted
+    ///
+    pub fn write_all<I, W>(&self, entries: I, writer: &mut W, format: u8) -> Result<usize, CubiculumError>
+    where
+        I: IntoIterator<Item = BedEntry>,
+        W: Write
+    {
+        let mut written: usize = 0;
+        let keep_names = match self.merge {
+            Some(x) => {x},
+            None => {
+                let mut line = String::new();
+                for entry in self.process(entries) {
+                    line.clear();
+                    entry?.write_line(format, &mut line)?;
+                    write_line(writer, &line)?;
+                    written += 1;
+                }
+                return Ok(written)
+            }
+        };
+        // the stream is cut short at the first stage error;
+        // merge_stream() reports unsorted records and undefined coordinates itself
+        let mut error: Option<CubiculumError> = None;
+        let processed = self.process(entries).map_while(|x| match x {
+            Ok(entry) => {Some(entry)},
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        });
+        for interval in merge_stream(processed, keep_names) {
+            write_line(writer, &interval_line(&interval?)?)?;
+            written += 1;
+        }
+        match error {
+            Some(e) => {Err(e)},
+            None => {Ok(written)}
+        }
+    }
+
+    /// Run the pipeline over a BED stream; records are written in the input format
+    ///
+    /// Blank lines, comments and track/browser lines are skipped
+    ///
+    /// # Returns
+    /// The number of records written; see `write_all()`
+    ///
+    pub fn run_stream<R, W>(&self, reader: R, format: usize, writer: &mut W) -> Result<usize, CubiculumError>
+    where
+        R: BufRead,
+        W: Write
+    {
+        let mut error: Option<CubiculumError> = None;
+        let entries = bed_lines(reader)
+            .map_while(|line| match line.and_then(|x| try_parse_bed(&x, format)) {
+                Ok(x) => {Some(x)},
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            });
+        let written = self.write_all(entries, writer, format as u8);
+        match error {
+            Some(e) => {Err(e)},
+            None => {written}
+        }
+    }
+}
+
+/// Iterator over the records processed by a `Pipeline`, created with `Pipeline::process()`
+pub struct Processed<'a, I> {
+    pipeline: &'a Pipeline,
+    entries: I
+}
+
+impl<I> Iterator for Processed<'_, I>
+where
+    I: Iterator<Item = BedEntry>
+{
+    type Item = Result<BedEntry, CubiculumError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entry in self.entries.by_ref() {
+            match self.pipeline.apply(entry) {
+                Ok(Some(x)) => {return Some(Ok(x))},
+                Ok(None) => {continue},
+                Err(e) => {return Some(Err(e))}
+            }
+        }
+        None
+    }
+}
+
+fn write_line<W: Write>(writer: &mut W, line: &str) -> Result<(), CubiculumError> {
+    writeln!(writer, "{}", line).map_err(CubiculumError::from)
+}
+
+/// BED4 line for named intervals, BED3 otherwise
+fn interval_line(interval: &Interval) -> Result<String, CubiculumError> {
+    interval.to_bed_line(if interval.name().is_some() {4} else {3})
+}
+
+#[cfg(test)]
+mod test_pipeline {
+    use super::*;
+    use crate::structs::structs::Coordinates;
+    use crate::extract::extract::{to_line, try_parse_bed};
+
+    fn entries() -> Vec<BedEntry> {
+        [
+            "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,",
+            "chr1\t300\t900\tB\t0\t+\t320\t880\t0\t2\t100,100,\t0,500,",
+            "chr2\t0\t100\tC\t0\t-\t0\t0\t0\t1\t100,\t0,"
+        ]
+            .iter()
+            .map(|x| try_parse_bed(x, 12).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn chained_stages() {
+        let pipeline = Pipeline::new()
+            .filter(|x| x.chrom().map(|c| c.as_str()) == Some("chr1"))
+            .clip(Some(320), Some(850))
+            .fraction(BedFractionMode::Cds, false)
+            .map(|mut x| {
+                let name = Named::name(&x).unwrap().to_lowercase();
+                x.update_name(&name);
+                Some(x)
+            });
+        assert_eq!(pipeline.len(), 4);
+        let lines: Vec<String> = pipeline.process(entries())
+            .map(|x| to_line(&x.unwrap(), 12).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "chr1\t320\t350\ta\t0\t+\t320\t350\tNA\t1\t30,\t0,",
+                "chr1\t320\t850\tb\t0\t+\t320\t850\tNA\t2\t80,50,\t0,480,"
+            ]
+        );
+    }
+
+    #[test]
+    fn clip_stage() {
+        let bed = "chr1\t100\t200\nchr1\t300\t400\nchr1\t120\t500\n";
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(Pipeline::new().clip(Some(150), Some(350)).run_stream(bed.as_bytes(), 3, &mut output).unwrap(), 3);
+        assert_eq!(String::from_utf8(output).unwrap(), "chr1\t150\t200\nchr1\t300\t350\nchr1\t150\t350\n");
+
+        let missed = Pipeline::new().clip(Some(1000), None).process(entries()).count();
+        assert_eq!(missed, 0);
+        let undefined = Pipeline::new().clip(Some(150), None).process(vec![BedEntry::empty()]).next().unwrap();
+        assert!(matches!(undefined, Err(CubiculumError::MissingTraitError(_))));
+    }
+
+    #[test]
+    fn filter_stage() {
+        let pipeline = Pipeline::new().filter_by(Filter::new().coding_only().name_matches("?"));
+        let names: Vec<String> = pipeline.process(entries())
+            .map(|x| x.unwrap().name().unwrap().clone())
+            .collect();
+        assert_eq!(names, vec!["A", "B"]);
+    }
+
+    #[test]
+    fn merged_output() {
+        let pipeline = Pipeline::new().fraction(BedFractionMode::All, false).merge(true);
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(pipeline.write_all(entries(), &mut output, 12).unwrap(), 2);
+        assert_eq!(String::from_utf8(output).unwrap(), "chr1\t100\t900\tA,B\nchr2\t0\t100\tC\n");
+
+        let mut reversed = entries();
+        reversed.reverse();
+        let mut output: Vec<u8> = Vec::new();
+        assert!(matches!(
+            Pipeline::new().merge(false).write_all(reversed, &mut output, 3),
+            Err(CubiculumError::UnsortedInput(_))
+        ));
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for progress reporting on long-running stream operations
+*/
+
+pub mod progress;
@@ -0,0 +1,235 @@
+//! # cubiculum::progress
+//!
+//! Progress hooks for stream operations
+//!
+//! Stream functions do not report progress themselves; instead, their input is wrapped
+//! in a `ProgressReader` (for BED streams) or a `ProgressIter` (for record iterators),
+//! which invokes the hook as the data is consumed. Any function accepting a `BufRead` or an iterator
+//! can thus report progress, e.g. `fraction_stream()`, `Pipeline::run_stream()` or `merge_stream()`
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::io::{self, BufRead, Read};
+
+/// Default number of lines (records) between two consecutive progress reports
+pub const DEFAULT_REPORT_INTERVAL: u64 = 100_000;
+
+/// Amount of input consumed so far
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of lines (for readers) or records (for iterators) consumed
+    pub records: u64,
+    /// Number of bytes consumed; zero for iterators
+    pub bytes: u64,
+    /// True for the last report issued when the input is exhausted
+    pub finished: bool
+}
+
+/// Reader counting the consumed lines and bytes and reporting them every `interval` lines
+/// and once at the end of the stream
+///
+/// ```
+/// use cubiculum::extract::extract::fraction_stream;
+/// use cubiculum::progress::progress::ProgressReader;
+///
+/// let bed = "chr1\t100\t600\tA\t0\t+\t150\t350\t0\t3\t100,100,100,\t0,200,400,\n".repeat(10);
+/// let mut reports = Vec::new();
+/// let reader = ProgressReader::new(bed.as_bytes(), |x| reports.push(*x)).every(4);
+/// let mut output: Vec<u8> = Vec::new();
+/// fraction_stream(reader, &mut output, "cds", false, false).unwrap();
+/// assert_eq!(reports.iter().map(|x| x.records).collect::<Vec<u64>>(), vec![4, 8, 10]);
+/// assert!(reports.last().unwrap().finished);
+/// ```
+pub struct ProgressReader<R, F>
+where
+    F: FnMut(&Progress)
+{
+    inner: R,
+    hook: F,
+    progress: Progress,
+    interval: u64,
+    next_report: u64
+}
+
+impl<R, F> ProgressReader<R, F>
+where
+    F: FnMut(&Progress)
+{
+    pub fn new(inner: R, hook: F) -> Self {
+        ProgressReader {
+            inner,
+            hook,
+            progress: Progress::default(),
+            interval: DEFAULT_REPORT_INTERVAL,
+            next_report: DEFAULT_REPORT_INTERVAL
+        }
+    }
+
+    /// Set the number of lines between two consecutive reports
+    pub fn every(mut self, interval: u64) -> Self {
+        self.interval = interval.max(1);
+        self.next_report = self.progress.records + self.interval;
+        self
+    }
+
+    /// Progress made so far
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn count(&mut self, bytes: usize, lines: usize) {
+        self.progress.bytes += bytes as u64;
+        for _ in 0..lines {
+            self.progress.records += 1;
+            if self.progress.records >= self.next_report {
+                (self.hook)(&self.progress);
+                self.next_report += self.interval;
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.progress.finished {return};
+        self.progress.finished = true;
+        (self.hook)(&self.progress);
+    }
+}
+
+impl<R, F> Read for ProgressReader<R, F>
+where
+    R: Read,
+    F: FnMut(&Progress)
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            self.finish();
+        } else {
+            let lines = buf[..n].iter().filter(|x| **x == b'\n').count();
+            self.count(n, lines);
+        }
+        Ok(n)
+    }
+}
+
+impl<R, F> BufRead for ProgressReader<R, F>
+where
+    R: BufRead,
+    F: FnMut(&Progress)
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.inner.fill_buf()?.is_empty() {self.finish()};
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        // the consumed bytes are still in the inner buffer, so peeking at them does not trigger a read
+        let (bytes, lines) = match self.inner.fill_buf() {
+            Ok(x) => {
+                let data = &x[..amt.min(x.len())];
+                (data.len(), data.iter().filter(|x| **x == b'\n').count())
+            },
+            Err(_) => {(amt, 0)}
+        };
+        self.count(bytes, lines);
+        self.inner.consume(amt);
+    }
+}
+
+/// Iterator counting the consumed records, created with `with_progress()`
+pub struct ProgressIter<I, F>
+where
+    F: FnMut(&Progress)
+{
+    inner: I,
+    hook: F,
+    progress: Progress,
+    interval: u64
+}
+
+/// Report the number of records consumed from the iterator every `interval` records
+/// and once it is exhausted
+pub fn with_progress<I, F>(records: I, interval: u64, hook: F) -> ProgressIter<I::IntoIter, F>
+where
+    I: IntoIterator,
+    F: FnMut(&Progress)
+{
+    ProgressIter {inner: records.into_iter(), hook, progress: Progress::default(), interval: interval.max(1)}
+}
+
+impl<I, F> Iterator for ProgressIter<I, F>
+where
+    I: Iterator,
+    F: FnMut(&Progress)
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.next() {
+            Some(x) => {
+                self.progress.records += 1;
+                if self.progress.records.is_multiple_of(self.interval) {(self.hook)(&self.progress)};
+                Some(x)
+            },
+            None => {
+                if !self.progress.finished {
+                    self.progress.finished = true;
+                    (self.hook)(&self.progress);
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_progress {
+    use super::*;
+    use std::io::BufReader;
+    use crate::merge::merge::merge_stream;
+    use crate::structs::structs::Interval;
+
+    #[test]
+    fn reader_progress() {
+        let data = "a\nbb\nccc\n";
+        let mut reports: Vec<Progress> = Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+        {
+            let reader = ProgressReader::new(data.as_bytes(), |x| reports.push(*x)).every(2);
+            for line in reader.lines() {lines.push(line.unwrap())};
+        }
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            reports,
+            vec![
+                Progress {records: 2, bytes: 5, finished: false},
+                Progress {records: 3, bytes: 9, finished: true}
+            ]
+        );
+
+        let mut last = Progress::default();
+        let mut reader = BufReader::with_capacity(3, ProgressReader::new(data.as_bytes(), |x| last = *x));
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        drop(reader);
+        assert_eq!(content, data);
+        assert_eq!(last, Progress {records: 3, bytes: 9, finished: true});
+    }
+
+    #[test]
+    fn iterator_progress() {
+        let intervals: Vec<Interval> = (0..5u64)
+            .map(|i| Interval::from(Some(String::from("chr1")), Some(i * 10), Some(i * 10 + 15), None))
+            .collect();
+        let mut reports: Vec<u64> = Vec::new();
+        let merged: Vec<Interval> = merge_stream(with_progress(intervals, 2, |x| reports.push(x.records)), false).collect::<Result<_, _>>().unwrap();
+        assert_eq!(merged.len(), 1);
+        assert_eq!(reports, vec![2, 4, 5]);
+    }
+}
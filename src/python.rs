@@ -0,0 +1,5 @@
+/*!
+Module for the Python bindings; requires the `python` feature
+*/
+
+pub mod python;
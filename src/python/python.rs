@@ -0,0 +1,237 @@
+//! # cubiculum::python
+//!
+//! Python bindings exposing BED parsing, fraction extraction, merging and intersection
+//!
+//! The bindings form the `cubiculum` Python extension module with a read-only `BedEntry` class and the
+//! `parse_bed()`, `bed_to_fraction()`, `merge()` and `intersect()` functions; library errors are raised
+//! as ValueError. Like the C interface, the extension is not part of the default rlib build; it is built with
+//! `PYO3_BUILD_EXTENSION_MODULE=1 cargo rustc --release --lib --features python --crate-type cdylib`,
+//! after which `libcubiculum.so` is copied as `cubiculum.so` (`cubiculum.pyd` on Windows) into the Python path
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_to_fraction, to_line, try_parse_bed};
+use crate::intersect::intersect::intersect;
+use crate::merge::merge::{merge_multiple, merge_multiple_named};
+use crate::sort::sort::{sort_records, ChromOrder};
+use crate::structs::structs::{BedEntry, Coordinates, Interval, Named};
+
+fn value_error(e: CubiculumError) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// BED record exposed to Python; the fields are read-only and undefined fields are reported as None
+#[pyclass(name = "BedEntry", module = "cubiculum", eq, frozen, from_py_object)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PyBedEntry {
+    entry: BedEntry
+}
+
+impl PyBedEntry {
+    pub fn entry(&self) -> &BedEntry {
+        &self.entry
+    }
+}
+
+impl From<BedEntry> for PyBedEntry {
+    fn from(entry: BedEntry) -> PyBedEntry {
+        PyBedEntry {entry}
+    }
+}
+
+#[pymethods]
+impl PyBedEntry {
+    #[getter]
+    fn format(&self) -> u8 {
+        self.entry.format()
+    }
+
+    #[getter]
+    fn chrom(&self) -> Option<String> {
+        self.entry.chrom().cloned()
+    }
+
+    #[getter]
+    fn start(&self) -> Option<u64> {
+        self.entry.thin_start()
+    }
+
+    #[getter]
+    fn end(&self) -> Option<u64> {
+        self.entry.thin_end()
+    }
+
+    #[getter]
+    fn name(&self) -> Option<String> {
+        self.entry.name().cloned()
+    }
+
+    #[getter]
+    fn score(&self) -> Option<String> {
+        self.entry.score().cloned()
+    }
+
+    /// "+" or "-"; None for records without the strand column
+    #[getter]
+    fn strand(&self) -> Option<&'static str> {
+        self.entry.strand().map(|x| if x {"+"} else {"-"})
+    }
+
+    #[getter]
+    fn thick_start(&self) -> Option<u64> {
+        self.entry.thick_start()
+    }
+
+    #[getter]
+    fn thick_end(&self) -> Option<u64> {
+        self.entry.thick_end()
+    }
+
+    #[getter]
+    fn item_rgb(&self) -> Option<String> {
+        self.entry.rgb().cloned()
+    }
+
+    #[getter]
+    fn block_count(&self) -> Option<u16> {
+        self.entry.exon_num()
+    }
+
+    #[getter]
+    fn block_sizes(&self) -> Option<Vec<u64>> {
+        self.entry.exon_sizes().cloned()
+    }
+
+    #[getter]
+    fn block_starts(&self) -> Option<Vec<u64>> {
+        self.entry.exon_starts().cloned()
+    }
+
+    /// Genomic coordinates of the record's blocks
+    fn exons(&self) -> Vec<(u64, u64)> {
+        self.entry.exons().collect()
+    }
+
+    /// BED line of the given format; the record's own format by default
+    #[pyo3(signature = (format = None))]
+    fn to_line(&self, format: Option<u8>) -> PyResult<String> {
+        to_line(&self.entry, format.unwrap_or(self.entry.format())).map_err(value_error)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BedEntry({}:{}-{}, name={})",
+            self.entry.chrom().map_or(".", |x| x.as_str()),
+            self.entry.thin_start().map_or(String::from("."), |x| x.to_string()),
+            self.entry.thin_end().map_or(String::from("."), |x| x.to_string()),
+            self.entry.name().map_or(".", |x| x.as_str())
+        )
+    }
+}
+
+/// Parse a BED line of the given format (3 to 9 or 12); raises ValueError for malformed lines
+#[pyfunction(name = "parse_bed")]
+#[pyo3(signature = (line, format = 12))]
+fn py_parse_bed(line: &str, format: usize) -> PyResult<PyBedEntry> {
+    try_parse_bed(line, format).map(PyBedEntry::from).map_err(value_error)
+}
+
+/// Extract the fraction of a BED12 line; see `bed_to_fraction()`. Returns None for empty fractions
+#[pyfunction(name = "bed_to_fraction")]
+#[pyo3(signature = (line, mode, intron = false, bed6 = false))]
+fn py_bed_to_fraction(line: String, mode: &str, intron: bool, bed6: bool) -> PyResult<Option<String>> {
+    bed_to_fraction(line, mode, intron, bed6).map_err(value_error)
+}
+
+fn merged_entry(interval: Interval) -> Option<BedEntry> {
+    match interval.name() {
+        Some(name) => {
+            let name = name.to_string();
+            Some(BedEntry::bed4(interval.chrom()?.clone(), *interval.start()?, *interval.end()?, name))
+        },
+        None => {BedEntry::from_interval(interval)}
+    }
+}
+
+/// Merge overlapping and book-ended records into BED3 records, or BED4 records listing
+/// the merged records' names if `keep_names` is set; the result is sorted by chromosome and coordinates
+#[pyfunction(name = "merge")]
+#[pyo3(signature = (entries, keep_names = false))]
+fn py_merge(py: Python<'_>, entries: Vec<PyBedEntry>, keep_names: bool) -> PyResult<Vec<PyBedEntry>> {
+    let mut entries: Vec<BedEntry> = entries.into_iter().map(|x| x.entry).collect();
+    let merged = py.detach(|| {
+        if keep_names {merge_multiple_named(&mut entries)} else {merge_multiple(&mut entries)}
+    }).map_err(value_error)?;
+    Ok(merged.into_iter().filter_map(merged_entry).map(PyBedEntry::from).collect())
+}
+
+/// Find the overlapping record pairs of the two lists; the lists need not be sorted.
+/// Pairs are ordered by the chromosome and coordinates of the first record
+#[pyfunction(name = "intersect")]
+fn py_intersect(py: Python<'_>, a: Vec<PyBedEntry>, b: Vec<PyBedEntry>) -> PyResult<Vec<(PyBedEntry, PyBedEntry)>> {
+    let mut a: Vec<BedEntry> = a.into_iter().map(|x| x.entry).collect();
+    let mut b: Vec<BedEntry> = b.into_iter().map(|x| x.entry).collect();
+    let pairs = py.detach(|| {
+        sort_records(&mut a, &ChromOrder::Lexicographic);
+        sort_records(&mut b, &ChromOrder::Lexicographic);
+        intersect(a, b).collect::<Result<Vec<(BedEntry, BedEntry)>, CubiculumError>>()
+    }).map_err(value_error)?;
+    Ok(pairs.into_iter().map(|(x, y)| (PyBedEntry::from(x), PyBedEntry::from(y))).collect())
+}
+
+/// Fast BED annotation processing
+#[pymodule(name = "cubiculum")]
+fn cubiculum_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyBedEntry>()?;
+    m.add_function(wrap_pyfunction!(py_parse_bed, m)?)?;
+    m.add_function(wrap_pyfunction!(py_bed_to_fraction, m)?)?;
+    m.add_function(wrap_pyfunction!(py_merge, m)?)?;
+    m.add_function(wrap_pyfunction!(py_intersect, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_python {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    const SCRIPT: &str = r#"
+a = cubiculum.parse_bed("chr1\t100\t600\tA\t0\t+\t150\t550\t0\t2\t100,100,\t0,400,")
+assert (a.chrom, a.start, a.end, a.strand) == ("chr1", 100, 600, "+")
+assert a.block_sizes == [100, 100] and a.exons() == [(100, 200), (500, 600)]
+assert a.to_line(6) == "chr1\t100\t600\tA\t0\t+"
+b = cubiculum.parse_bed("chr1\t150\t300\tB", 4)
+assert b.strand is None and b.format == 4
+c = cubiculum.parse_bed("chr1\t0\t50\tC", 4)
+assert cubiculum.bed_to_fraction(a.to_line(), "cds") == "chr1\t150\t550\tA\t0\t+\t150\t550\t0\t2\t50,50,\t0,350,"
+assert cubiculum.bed_to_fraction(a.to_line(), "cds", bed6=True).count("\n") == 1
+merged = cubiculum.merge([b, a, c], keep_names=True)
+assert [(x.start, x.end, x.name) for x in merged] == [(0, 50, "C"), (100, 600, "A,B")]
+pairs = cubiculum.intersect([b, c], [a])
+assert len(pairs) == 1 and pairs[0] == (b, a)
+try:
+    cubiculum.parse_bed("chr1\t100", 3)
+    raise AssertionError("malformed line parsed")
+except ValueError:
+    pass
+"#;
+
+    #[test]
+    fn python_module() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "cubiculum").unwrap();
+            cubiculum_module(&module).unwrap();
+            let globals = PyDict::new(py);
+            globals.set_item("cubiculum", module).unwrap();
+            let script = std::ffi::CString::new(SCRIPT).unwrap();
+            py.run(&script, Some(&globals), None).unwrap();
+        });
+    }
+}
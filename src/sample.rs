@@ -0,0 +1,5 @@
+/*!
+Module for random sampling of annotation records
+*/
+
+pub mod sample;
@@ -0,0 +1,126 @@
+//! # cubiculum::sample
+//! 
+//! Reproducible random sampling of annotation record streams
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::io::BufRead;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, try_parse_bed};
+use crate::structs::structs::BedEntry;
+
+/// SplitMix64 pseudorandom number generator;
+/// kept in-house so that the samples stay reproducible across dependency updates
+struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 {state: seed}
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a uniformly distributed number in [0, bound)
+    fn below(&mut self, bound: u64) -> u64 {
+        // rejection sampling to avoid the modulo bias
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let x = self.next_u64();
+            if x < zone {return x % bound};
+        }
+    }
+}
+
+/// Draw a uniform random sample of records from a stream of arbitrary length
+///
+/// # Arguments
+/// `records`: an iterable over the records, consumed in a single pass;
+/// `n`: sample size;
+/// `seed`: random seed; the same seed and input always produce the same sample
+///
+/// # Returns
+/// A vector of at most `n` records, in the order of their appearance in the stream;
+/// only the sampled records are kept in memory
+///
+pub fn sample<T, I>(records: I, n: usize, seed: u64) -> Vec<T>
+where
+    I: IntoIterator<Item = T>
+{
+    let mut rng = SplitMix64::new(seed);
+    if n == 0 {return Vec::new()};
+    // the stream may turn out shorter than the sample size, so the reservoir grows as needed past the first records
+    let mut reservoir: Vec<(usize, T)> = Vec::with_capacity(n.min(1024));
+    for (i, record) in records.into_iter().enumerate() {
+        if i < n {
+            reservoir.push((i, record));
+            continue
+        }
+        let j = rng.below(i as u64 + 1) as usize;
+        if j < n {reservoir[j] = (i, record)};
+    }
+    reservoir.sort_by_key(|x| x.0);
+    reservoir.into_iter().map(|x| x.1).collect()
+}
+
+/// Draw a uniform random sample of entries from a BED stream
+///
+/// Lines are sampled before parsing, so only the sampled entries are parsed;
+/// empty lines along with comment, track and browser lines are skipped
+///
+/// # Arguments
+/// `reader`: a BED stream;
+/// `format`: BED format of the input (3 to 12);
+/// `n`: sample size;
+/// `seed`: random seed
+///
+pub fn sample_bed<R: BufRead>(reader: R, format: usize, n: usize, seed: u64) -> Result<Vec<BedEntry>, CubiculumError> {
+    let mut error: Option<CubiculumError> = None;
+    let records = bed_lines(reader)
+        .map_while(|x| match x {
+            Ok(line) => {Some(line)},
+            Err(e) => {error = Some(e); None}
+        });
+    let lines: Vec<String> = sample(records, n, seed);
+    if let Some(e) = error {return Err(e)};
+    lines.into_iter()
+        .map(|x| {
+            try_parse_bed(&x, format)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test_sample {
+    use super::*;
+
+    #[test]
+    fn reservoir_sampling() {
+        let first = sample(0..1000, 10, 42);
+        assert_eq!(first.len(), 10);
+        assert!(first.windows(2).all(|x| x[0] < x[1]));
+        assert_eq!(first, sample(0..1000, 10, 42));
+        assert_ne!(first, sample(0..1000, 10, 7));
+        assert_eq!(sample(0..5, 10, 42), vec![0, 1, 2, 3, 4]);
+        assert!(sample(0..5, 0, 42).is_empty());
+        assert_eq!(sample(0..3, usize::MAX, 42), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn bed_sampling() {
+        let input = "track name=test\nchr1\t0\t10\nchr1\t20\t30\n\nchr2\t5\t15\n";
+        let entries = sample_bed(input.as_bytes(), 3, 2, 1).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(sample_bed(input.as_bytes(), 3, 5, 1).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,5 @@
+/*!
+Module for chromosome-aware sorting of annotation records
+*/
+
+pub mod sort;
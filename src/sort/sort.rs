@@ -0,0 +1,244 @@
+//! # cubiculum::sort
+//! 
+//! Sorting of annotation records by natural or user-defined chromosome order
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::cmp::Ordering;
+#[cfg(feature = "fs")]
+use std::fs::File;
+#[cfg(feature = "fs")]
+use std::io::{BufRead, BufReader};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use fxhash::FxHashMap;
+
+#[cfg(feature = "fs")]
+use crate::error::error::CubiculumError;
+use crate::structs::structs::Coordinates;
+
+/// Defines the order in which chromosomes are arranged when sorting
+#[derive(Clone, Debug, Default)]
+pub enum ChromOrder {
+    /// Plain string comparison (chr1 < chr10 < chr2 < chrM < chrX), as in `sort -k1,1`
+    #[default]
+    Lexicographic,
+    /// Natural order: numbered chromosomes by number, then X, Y and the mitochondrial chromosome,
+    /// then the remaining contigs (chr1 < chr2 < chr10 < chrX < chrY < chrM < chrUn_...)
+    Natural,
+    /// User-defined order; chromosomes missing from the list follow the listed ones in natural order
+    Custom(FxHashMap<String, usize>)
+}
+
+impl ChromOrder {
+    /// Create a custom order from a sequence of chromosome names
+    pub fn from_names<S, I>(names: I) -> ChromOrder
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>
+    {
+        let mut ranks: FxHashMap<String, usize> = FxHashMap::default();
+        for name in names {
+            let rank = ranks.len();
+            ranks.entry(name.as_ref().to_string()).or_insert(rank);
+        }
+        ChromOrder::Custom(ranks)
+    }
+
+    /// Create a custom order from the first column of a FASTA index (.fai)
+    /// or a chromosome sizes file, preserving the order of lines
+    #[cfg(feature = "fs")]
+    pub fn from_fai<P: AsRef<Path>>(path: P) -> Result<ChromOrder, CubiculumError> {
+        let file = File::open(path.as_ref()).map_err(
+            |e| CubiculumError::IoError(format!("Cannot open {:?}: {}", path.as_ref(), e))
+        )?;
+        let mut names: Vec<String> = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {continue}
+            if let Some(name) = line.split('\t').next() {
+                names.push(name.to_string());
+            }
+        }
+        Ok(ChromOrder::from_names(names))
+    }
+
+    /// Compare two chromosome names according to the order
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            ChromOrder::Lexicographic => {a.cmp(b)},
+            ChromOrder::Natural => {natural_chrom_cmp(a, b)},
+            ChromOrder::Custom(ranks) => {
+                match (ranks.get(a), ranks.get(b)) {
+                    (Some(x), Some(y)) => {x.cmp(y)},
+                    (Some(_), None) => {Ordering::Less},
+                    (None, Some(_)) => {Ordering::Greater},
+                    (None, None) => {natural_chrom_cmp(a, b)}
+                }
+            }
+        }
+    }
+
+    /// Compare two records by chromosome (according to the order), start and end coordinates;
+    /// records with undefined chromosomes go first
+    pub fn compare_records<A: Coordinates, B: Coordinates>(&self, a: &A, b: &B) -> Ordering {
+        let by_chrom = match (a.chrom(), b.chrom()) {
+            (Some(x), Some(y)) => {self.compare(x, y)},
+            (x, y) => {x.is_some().cmp(&y.is_some())}
+        };
+        by_chrom
+            .then(a.start().cmp(&b.start()))
+            .then(a.end().cmp(&b.end()))
+    }
+}
+
+/// Rank of the chromosome class in natural order: autosomes, X, Y, mitochondrial, other contigs
+fn chrom_class(name: &str) -> (u8, &str) {
+    let stripped = match name.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("chr") => {&name[3..]},
+        _ => {name}
+    };
+    if !stripped.is_empty() && stripped.bytes().all(|x| x.is_ascii_digit()) {
+        return (0, stripped)
+    }
+    match stripped {
+        "X" | "x" => (1, stripped),
+        "Y" | "y" => (2, stripped),
+        "M" | "m" | "MT" | "Mt" | "mt" => (3, stripped),
+        _ => (4, stripped)
+    }
+}
+
+/// Compare two strings treating runs of digits as numbers (chr2_random < chr10_random)
+fn natural_str_cmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            let i_end = i + a[i..].iter().take_while(|x| x.is_ascii_digit()).count();
+            let j_end = j + b[j..].iter().take_while(|x| x.is_ascii_digit()).count();
+            // compare the digit runs numerically without parsing, ignoring leading zeros
+            let x = trim_zeros(&a[i..i_end]);
+            let y = trim_zeros(&b[j..j_end]);
+            let ord = x.len().cmp(&y.len()).then(x.cmp(y));
+            if ord != Ordering::Equal {return ord}
+            i = i_end;
+            j = j_end;
+        } else {
+            let ord = a[i].cmp(&b[j]);
+            if ord != Ordering::Equal {return ord}
+            i += 1;
+            j += 1;
+        }
+    }
+    (a.len() - i).cmp(&(b.len() - j)).then(a.cmp(b))
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let n = digits.iter().take_while(|x| **x == b'0').count();
+    &digits[n..]
+}
+
+/// Compare two chromosome names in natural order (chr1 < chr2 < chr10 < chrX < chrY < chrM < other contigs)
+pub fn natural_chrom_cmp(a: &str, b: &str) -> Ordering {
+    let (a_class, a_rest) = chrom_class(a);
+    let (b_class, b_rest) = chrom_class(b);
+    a_class.cmp(&b_class)
+        .then_with(|| natural_str_cmp(a_rest, b_rest))
+        .then_with(|| a.cmp(b))
+}
+
+/// Sort the records in place by chromosome (according to the order), start and end coordinates
+pub fn sort_records<T: Coordinates>(records: &mut [T], order: &ChromOrder) {
+    records.sort_by(|a, b| order.compare_records(a, b));
+}
+
+/// Check whether the records are sorted according to the chromosome order
+pub fn is_sorted_by<T: Coordinates>(records: &[T], order: &ChromOrder) -> bool {
+    records.windows(2).all(|x| order.compare_records(&x[0], &x[1]) != Ordering::Greater)
+}
+
+/// Sort a stream of records by chromosome order, start and end coordinates
+///
+/// Records are bucketed by chromosome as they arrive and each chromosome is sorted only once
+/// the stream is exhausted, so the whole stream is held in memory;
+/// records with undefined chromosomes are emitted first
+pub fn sort_stream<T, I>(records: I, order: &ChromOrder) -> impl Iterator<Item = T>
+where
+    T: Coordinates,
+    I: IntoIterator<Item = T>
+{
+    let mut unplaced: Vec<T> = Vec::new();
+    let mut buckets: FxHashMap<String, Vec<T>> = FxHashMap::default();
+    for record in records {
+        match record.chrom() {
+            Some(chrom) => {
+                let chrom = chrom.clone();
+                buckets.entry(chrom).or_default().push(record);
+            },
+            None => {unplaced.push(record)}
+        }
+    }
+    let mut chroms: Vec<String> = buckets.keys().cloned().collect();
+    chroms.sort_by(|a, b| order.compare(a, b));
+    sort_records(&mut unplaced, order);
+    let sorted = chroms.into_iter()
+        .flat_map(move |chrom| {
+            let mut bucket = buckets.remove(&chrom).unwrap();
+            bucket.sort_by(|a, b| a.start().cmp(&b.start()).then(a.end().cmp(&b.end())));
+            bucket
+        });
+    unplaced.into_iter().chain(sorted)
+}
+
+#[cfg(test)]
+mod test_sort {
+    use super::*;
+    use crate::structs::structs::Interval;
+
+    fn interval(chrom: &str, start: u64, end: u64) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), None)
+    }
+
+    fn chroms(records: &[Interval]) -> Vec<&str> {
+        records.iter().map(|x| x.chrom().unwrap().as_str()).collect()
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec!["chrM", "chr10", "chrUn_gl000220", "chrX", "chr2", "chr1", "chrY", "chr1_gl000191_random"];
+        names.sort_by(|a, b| natural_chrom_cmp(a, b));
+        assert_eq!(
+            names,
+            vec!["chr1", "chr2", "chr10", "chrX", "chrY", "chrM", "chr1_gl000191_random", "chrUn_gl000220"]
+        );
+        assert_eq!(natural_str_cmp("scaffold_9", "scaffold_10"), Ordering::Less);
+    }
+
+    #[test]
+    fn custom_order() {
+        let order = ChromOrder::from_names(["chrX", "chr2", "chr1"]);
+        let mut records = vec![
+            interval("chr1", 10, 20), interval("chr3", 0, 5), interval("chr2", 5, 10),
+            interval("chrX", 100, 200), interval("chr1", 5, 10)
+        ];
+        sort_records(&mut records, &order);
+        assert_eq!(chroms(&records), vec!["chrX", "chr2", "chr1", "chr1", "chr3"]);
+        assert_eq!(records[2].start(), Some(&5));
+        assert!(is_sorted_by(&records, &order));
+        assert!(!is_sorted_by(&records, &ChromOrder::Natural));
+    }
+
+    #[test]
+    fn streaming_sort() {
+        let records = vec![
+            interval("chr10", 0, 5), interval("chr2", 30, 40), interval("chrM", 0, 100), interval("chr2", 10, 20)
+        ];
+        let sorted: Vec<Interval> = sort_stream(records, &ChromOrder::Natural).collect();
+        assert_eq!(chroms(&sorted), vec!["chr2", "chr2", "chr10", "chrM"]);
+        assert_eq!(sorted[0].start(), Some(&10));
+    }
+}
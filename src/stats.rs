@@ -0,0 +1,5 @@
+/*!
+Module for summary statistics over BED records
+*/
+
+pub mod stats;
@@ -0,0 +1,294 @@
+//! # cubiculum::stats
+//!
+//! Summary statistics over BED records: record counts, length distribution, N50,
+//! format composition and strand balance, as well as per-chromosome breakdowns
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::BufRead;
+
+use crate::coverage::coverage::GenomeMask;
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{bed_lines, try_parse_bed};
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates};
+
+/// Summary statistics report; lengths refer to the records' spans
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SummaryStats {
+    pub intervals: usize,
+    pub total_length: u64,
+    pub mean_length: f64,
+    pub median_length: f64,
+    /// Length of the shortest record among the longest ones covering at least a half of the total length
+    pub n50: u64,
+    /// Number of records per BED format
+    pub format_counts: BTreeMap<u8, usize>,
+    pub plus: usize,
+    pub minus: usize,
+    pub unstranded: usize
+}
+
+impl SummaryStats {
+    /// Compute the statistics for the records, e.g., `SummaryStats::from_entries(collection.iter_sorted())`
+    pub fn from_entries<'a, I: IntoIterator<Item = &'a BedEntry>>(entries: I) -> SummaryStats {
+        let mut stats = SummaryStats::default();
+        let mut lengths: Vec<u64> = Vec::new();
+        for entry in entries {
+            lengths.push(entry.thin_end().unwrap_or(0).saturating_sub(entry.thin_start().unwrap_or(0)));
+            *stats.format_counts.entry(entry.format()).or_default() += 1;
+            match entry.strand() {
+                Some(true) => {stats.plus += 1},
+                Some(false) => {stats.minus += 1},
+                None => {stats.unstranded += 1}
+            }
+        }
+        stats.intervals = lengths.len();
+        if lengths.is_empty() {return stats};
+        lengths.sort_unstable();
+        let n = lengths.len();
+        stats.total_length = lengths.iter().sum();
+        stats.mean_length = stats.total_length as f64 / n as f64;
+        stats.median_length = if n % 2 == 1 {
+            lengths[n / 2] as f64
+        } else {
+            (lengths[n / 2 - 1] + lengths[n / 2]) as f64 / 2.0
+        };
+        let mut cumulative: u64 = 0;
+        for length in lengths.iter().rev() {
+            cumulative += length;
+            if cumulative * 2 >= stats.total_length {
+                stats.n50 = *length;
+                break
+            }
+        }
+        stats
+    }
+
+    /// Fraction of the stranded records located on the plus strand; None if no record is stranded
+    pub fn strand_balance(&self) -> Option<f64> {
+        let stranded = self.plus + self.minus;
+        if stranded == 0 {return None};
+        Some(self.plus as f64 / stranded as f64)
+    }
+
+    /// Serialize the report as a single-line JSON object
+    pub fn to_json(&self) -> String {
+        let formats: Vec<String> = self.format_counts.iter()
+            .map(|(format, count)| format!("\"{}\":{}", format, count))
+            .collect();
+        let balance = match self.strand_balance() {
+            Some(x) => {x.to_string()},
+            None => {String::from("null")}
+        };
+        format!(
+            "{{\"intervals\":{},\"total_length\":{},\"mean_length\":{},\"median_length\":{},\"n50\":{},\
+            \"format_counts\":{{{}}},\"plus\":{},\"minus\":{},\"unstranded\":{},\"strand_balance\":{}}}",
+            self.intervals, self.total_length, self.mean_length, self.median_length, self.n50,
+            formats.join(","), self.plus, self.minus, self.unstranded, balance
+        )
+    }
+}
+
+impl fmt::Display for SummaryStats {
+    /// Tab-separated key-value lines, one per statistic
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "intervals\t{}", self.intervals)?;
+        writeln!(f, "total_length\t{}", self.total_length)?;
+        writeln!(f, "mean_length\t{:.2}", self.mean_length)?;
+        writeln!(f, "median_length\t{:.1}", self.median_length)?;
+        writeln!(f, "n50\t{}", self.n50)?;
+        for (format, count) in self.format_counts.iter() {
+            writeln!(f, "bed{}\t{}", format, count)?;
+        }
+        writeln!(f, "plus\t{}", self.plus)?;
+        writeln!(f, "minus\t{}", self.minus)?;
+        writeln!(f, "unstranded\t{}", self.unstranded)?;
+        match self.strand_balance() {
+            Some(x) => {write!(f, "strand_balance\t{:.4}", x)},
+            None => {write!(f, "strand_balance\tNA")}
+        }
+    }
+}
+
+/// Per-chromosome report row
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChromStats {
+    pub chrom: String,
+    pub entries: usize,
+    /// Number of bases covered by the records' spans after merging the overlapping ones
+    pub covered_bases: u64,
+    /// Chromosome length; None if chromosome sizes were not provided or lack the chromosome
+    pub length: Option<u64>,
+}
+
+impl ChromStats {
+    /// Header line matching the Display output
+    pub const HEADER: &'static str = "chrom\tentries\tcovered_bases\tlength\tentries_per_mb\tcovered_fraction";
+
+    /// Number of records per megabase of the chromosome length
+    pub fn density_per_mb(&self) -> Option<f64> {
+        match self.length {
+            Some(x) if x > 0 => {Some(self.entries as f64 * 1_000_000.0 / x as f64)},
+            _ => {None}
+        }
+    }
+
+    /// Fraction of the chromosome covered by the records
+    pub fn covered_fraction(&self) -> Option<f64> {
+        match self.length {
+            Some(x) if x > 0 => {Some(self.covered_bases as f64 / x as f64)},
+            _ => {None}
+        }
+    }
+}
+
+impl fmt::Display for ChromStats {
+    /// Tab-separated line; undefined values are reported as NA
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}\t", self.chrom, self.entries, self.covered_bases)?;
+        match self.length {
+            Some(x) => {write!(f, "{}\t", x)?},
+            None => {write!(f, "NA\t")?}
+        }
+        match (self.density_per_mb(), self.covered_fraction()) {
+            (Some(x), Some(y)) => {write!(f, "{:.4}\t{:.4}", x, y)},
+            _ => {write!(f, "NA\tNA")}
+        }
+    }
+}
+
+/// Compute the per-chromosome record counts and coverage
+///
+/// If chromosome sizes are provided, every chromosome listed there is reported in the sizes order,
+/// including the ones without records, followed by the chromosomes missing from the sizes in lexicographic order;
+/// otherwise, only the chromosomes with records are reported in lexicographic order.
+/// Records with undefined coordinates are ignored
+///
+pub fn chrom_report<T: Coordinates>(entries: &[T], chrom_sizes: Option<&ChromSizes>) -> Vec<ChromStats> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in entries {
+        if let (Some(c), Some(_), Some(_)) = (entry.chrom(), entry.start(), entry.end()) {
+            *counts.entry(c.as_str()).or_default() += 1;
+        }
+    }
+    let mask = GenomeMask::from_intervals(entries);
+    let row = |chrom: &str| {
+        ChromStats {
+            chrom: chrom.to_string(),
+            entries: counts.get(chrom).copied().unwrap_or(0),
+            covered_bases: mask.runs(chrom).map_or(0, |x| x.iter().map(|(s, e)| e - s).sum()),
+            length: chrom_sizes.and_then(|x| x.get(chrom))
+        }
+    };
+    let mut report: Vec<ChromStats> = Vec::new();
+    if let Some(sizes) = chrom_sizes {
+        report.extend(sizes.chroms().map(|c| row(c)));
+    }
+    report.extend(
+        counts.keys()
+            .filter(|c| chrom_sizes.and_then(|x| x.get(c)).is_none())
+            .map(|c| row(c))
+    );
+    report
+}
+
+/// BED format of a line inferred from its column number: twelve and more columns are read as BED12,
+/// nine to eleven as BED9, and seven as BED6
+fn line_format(line: &str) -> Result<usize, CubiculumError> {
+    match line.split('\t').count() {
+        x if x >= 12 => {Ok(12)},
+        x if x >= 9 => {Ok(9)},
+        7 => {Ok(6)},
+        x if x >= 3 => {Ok(x)},
+        _ => {Err(CubiculumError::ParseError(format!("BED line has less than three columns: {}", line)))}
+    }
+}
+
+/// Compute the statistics for all BED records in the stream
+///
+/// The format of each line is inferred from its column number, so files mixing formats are accepted.
+/// Blank lines, comments and track/browser lines are skipped
+///
+/// # Returns
+/// The report; an error if a line cannot be parsed
+///
+pub fn summary_stream<R: BufRead>(reader: R) -> Result<SummaryStats, CubiculumError> {
+    Ok(SummaryStats::from_entries(read_entries(reader)?.iter()))
+}
+
+/// Compute the per-chromosome report for all BED records in the stream; see `chrom_report()`
+/// for the row order and `summary_stream()` for the accepted input
+pub fn chrom_report_stream<R: BufRead>(
+    reader: R, chrom_sizes: Option<&ChromSizes>
+) -> Result<Vec<ChromStats>, CubiculumError> {
+    Ok(chrom_report(&read_entries(reader)?, chrom_sizes))
+}
+
+/// Parse all BED records in the stream, inferring the format of each line
+fn read_entries<R: BufRead>(reader: R) -> Result<Vec<BedEntry>, CubiculumError> {
+    let mut entries: Vec<BedEntry> = Vec::new();
+    for line in bed_lines(reader) {
+        let line = line?;
+        let trimmed = line.trim();
+        entries.push(try_parse_bed(trimmed, line_format(trimmed)?)?);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test_stats {
+    use super::*;
+
+    #[test]
+    fn length_statistics() {
+        let bed = "track name=test\nchr1\t0\t100\nchr1\t0\t200\tA\t0\t+\n\nchr2\t0\t300\tB\t0\t-\n\
+            chr2\t0\t400\tC\t0\t+\t0\t400\t0\t1\t400,\t0,\n";
+        let stats = summary_stream(bed.as_bytes()).unwrap();
+        assert_eq!(stats.intervals, 4);
+        assert_eq!(stats.total_length, 1000);
+        assert_eq!(stats.mean_length, 250.0);
+        assert_eq!(stats.median_length, 250.0);
+        // 400 + 300 >= 1000 / 2
+        assert_eq!(stats.n50, 300);
+        assert_eq!(stats.format_counts.into_iter().collect::<Vec<(u8, usize)>>(), vec![(3, 1), (6, 2), (12, 1)]);
+        assert_eq!((stats.plus, stats.minus, stats.unstranded), (2, 1, 1));
+        assert!(summary_stream("chr1\t100\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn report_serialization() {
+        let empty = SummaryStats::from_entries(&[]);
+        assert_eq!(empty.strand_balance(), None);
+        assert_eq!(
+            empty.to_json(),
+            "{\"intervals\":0,\"total_length\":0,\"mean_length\":0,\"median_length\":0,\"n50\":0,\
+            \"format_counts\":{},\"plus\":0,\"minus\":0,\"unstranded\":0,\"strand_balance\":null}"
+        );
+        let stats = summary_stream("chr1\t0\t10\tA\t0\t+\nchr1\t5\t10\tB\t0\t+\nchr1\t0\t3\tC\t0\t-\n".as_bytes()).unwrap();
+        assert_eq!(stats.median_length, 5.0);
+        assert!(stats.to_json().contains("\"format_counts\":{\"6\":3}"));
+        assert!(stats.to_string().ends_with("strand_balance\t0.6667"));
+    }
+
+    #[test]
+    fn chrom_breakdown() {
+        let bed = "chr2\t0\t100\tA\nchr2\t50\t150\tB\nchr1\t10\t20\tC\nchrUn\t0\t5\tD\n";
+        let report = chrom_report_stream(bed.as_bytes(), None).unwrap();
+        let rows: Vec<(&str, usize, u64)> = report.iter().map(|x| (x.chrom.as_str(), x.entries, x.covered_bases)).collect();
+        assert_eq!(rows, vec![("chr1", 1, 10), ("chr2", 2, 150), ("chrUn", 1, 5)]);
+        assert_eq!(report[0].to_string(), "chr1\t1\t10\tNA\tNA\tNA");
+
+        let sizes = ChromSizes::from_reader("chr1\t1000\nchr2\t2000000\nchr3\t500\n".as_bytes()).unwrap();
+        let report = chrom_report_stream(bed.as_bytes(), Some(&sizes)).unwrap();
+        let chroms: Vec<&str> = report.iter().map(|x| x.chrom.as_str()).collect();
+        assert_eq!(chroms, vec!["chr1", "chr2", "chr3", "chrUn"]);
+        assert_eq!(report[1].density_per_mb(), Some(1.0));
+        assert_eq!(report[2].to_string(), "chr3\t0\t0\t500\t0.0000\t0.0000");
+        assert_eq!(report[3].length, None);
+        assert_eq!(ChromStats::HEADER.split('\t').count(), report[0].to_string().split('\t').count());
+    }
+}
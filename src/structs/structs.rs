@@ -1,7 +1,22 @@
+use fxhash::FxHashMap;
 use std::cmp::{min, max};
-
-use crate::extract::extract::{parse_bed, to_line};
-use crate::merge::merge::{intersection, merge_multiple};
+use std::fmt;
+#[cfg(feature = "fs")]
+use std::fs::File;
+use std::io::BufRead;
+#[cfg(feature = "fs")]
+use std::io::BufReader;
+use std::ops::{Bound, RangeBounds};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{extract_fraction, extract_fraction_blocks, BedFractionMode};
+#[cfg(test)]
+use crate::extract::extract::{to_line, try_parse_bed};
+use crate::intersect::intersect::{block_overlap_size, intersect, Intersect};
+use crate::merge::merge::{block_union, intersection, merge_multiple, merge_stream, MergeStream};
+use crate::options::options::ProcessingOptions;
 
 /// Contains data on storage structures for annotation manipulations in Cubiculum and associated packages
 
@@ -38,7 +53,7 @@ impl Interval {
         self.end = Some(end);
     }
 }
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct BedEntry{
     format: Option<u8>,
     chrom: Option<String>,
@@ -232,8 +247,7 @@ impl BedEntry{
     }
 
     pub fn format(&self) -> u8 {
-        if let None = self.format {return 0};
-        self.format.unwrap()
+        self.format.unwrap_or(0)
     }
 
     pub fn thin_start(&self) -> Option<u64> {
@@ -287,6 +301,122 @@ impl BedEntry{
         self.thin_end = Some(thin_end)
     }
 
+    /// Set the itemRgb value; entries in formats below BED9 are promoted to BED9,
+    /// with missing coding boundaries spanning the whole entry
+    pub fn update_rgb(&mut self, rgb: String) {
+        if self.format() < 9 {
+            self.format = Some(9);
+            if self.thick_start.is_none() || self.thick_end.is_none() {
+                self.thick_start = self.thin_start;
+                self.thick_end = self.thin_end;
+            }
+        }
+        self.rgb = Some(rgb)
+    }
+
+    /// Write the entry into a caller-provided buffer as a tab-separated BED line, without the trailing newline
+    ///
+    /// This is the allocation-free counterpart of `to_line()`: a single buffer can be cleared and reused
+    /// for every record in a loop
+    ///
+    /// # Arguments
+    /// `format`: number of columns in the output line, three through nine or twelve;
+    /// `out`: buffer the line is appended to
+    ///
+    /// # Returns
+    /// An error if the format is not supported or exceeds the entry's own one, or if a field required
+    /// by the format is undefined; in the latter case, `out` may contain a partially written line
+    ///
+    pub fn write_line(&self, format: u8, out: &mut impl fmt::Write) -> Result<(), CubiculumError> {
+        let entry_format = match self.format() {
+            0 => {return Err(CubiculumError::MissingTraitError("Undefined BED format for the entry".to_string()))}
+            x  => {x},
+        };
+        if entry_format < format {
+            return Err(
+                CubiculumError::FormattingError(
+                    format!("Cannot format BED{} entry into a BED{} line", format, entry_format)
+                )
+            );
+        }
+        if format < 3 || format == 7 || (format > 9 && format < 12) || format > 12 {
+            return Err(
+                CubiculumError::FormattingError(
+                    format!("Provided format BED{} is not supported. Accepted formats are : BED3,4,5,6,8,9,12", format)
+                )
+            );
+        }
+        let written = |e: fmt::Error| CubiculumError::FormattingError(e.to_string());
+        let chrom = match self.chrom() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined chromosome field".to_string()))}
+        };
+        let thin_start = match self.thin_start() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thinStart field".to_string()))}
+        };
+        let thin_end = match self.thin_end() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thinEnd field".to_string()))}
+        };
+        write!(out, "{}\t{}\t{}", chrom, thin_start, thin_end).map_err(written)?;
+        if format == 3 {return Ok(())};
+        let name = match self.name() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined name field".to_string()))}
+        };
+        write!(out, "\t{}", name).map_err(written)?;
+        if format == 4 {return Ok(())};
+        let score = match self.score() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined score field".to_string()))}
+        };
+        write!(out, "\t{}", score).map_err(written)?;
+        if format == 5 {return Ok(())};
+        let strand = match self.strand() {
+            Some(x) => {
+                match x {
+                    true => {'+'},
+                    false => {'-'}
+                }
+            },
+            None => {return Err(CubiculumError::MissingTraitError("Undefined strand field".to_string()))}
+        };
+        write!(out, "\t{}", strand).map_err(written)?;
+        if format == 6 {return Ok(())};
+        let thick_start = match self.thick_start() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thickStart field".to_string()))}
+        };
+        let thick_end = match self.thick_end() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thickEnd field".to_string()))}
+        };
+        write!(out, "\t{}\t{}", thick_start, thick_end).map_err(written)?;
+        if format == 8 {return Ok(())};
+        let rgb = match self.rgb() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined Rgb field".to_string()))}
+        };
+        write!(out, "\t{}", rgb).map_err(written)?;
+        if format == 9 {return Ok(())};
+        let (exon_num, exon_sizes, exon_starts) = match (self.exon_num(), self.exon_sizes(), self.exon_starts()) {
+            (Some(x), Some(y), Some(z)) => {(x, y, z)},
+            (None, _, _) => {return Err(CubiculumError::MissingTraitError("Undefined exonNumber field".to_string()))},
+            (_, None, _) => {return Err(CubiculumError::MissingTraitError("Undefined exonSizes field".to_string()))},
+            (_, _, None) => {return Err(CubiculumError::MissingTraitError("Undefined exonStarts field".to_string()))}
+        };
+        write!(out, "\t{}\t", exon_num).map_err(written)?;
+        for size in exon_sizes {
+            write!(out, "{},", size).map_err(written)?;
+        }
+        out.write_char('\t').map_err(written)?;
+        for start in exon_starts {
+            write!(out, "{},", start).map_err(written)?;
+        }
+        Ok(())
+    }
+
     /// Returns the length sum for all the blocks
     /// 
     pub fn block_length(&self) -> u64 {
@@ -296,11 +426,10 @@ impl BedEntry{
                 _ => {0}
             };
         }
-        let mut length_sum: u64 = 0;
-        for x in self.exon_sizes().unwrap() {
-            length_sum += *x
+        match self.exon_sizes() {
+            Some(x) => {x.iter().sum()},
+            None => {0}
         }
-        return length_sum;
     }
 
     pub fn to_interval(&mut self) -> Interval {
@@ -313,7 +442,7 @@ impl BedEntry{
     }
 
     pub fn to_blocks(&self) -> Option<Vec<BedEntry>> {
-        if self.format.unwrap() != 12 {
+        if self.format() != 12 {
             return None;
         }
         let chrom: &str = match &self.chrom {
@@ -340,10 +469,14 @@ impl BedEntry{
             Some(x) => {x},
             None => {return None}
         };
+        let (exon_sizes, exon_starts) = match (&self.exon_sizes, &self.exon_starts) {
+            (Some(x), Some(y)) if x.len() >= ex_num && y.len() >= ex_num => {(x, y)},
+            _ => {return None}
+        };
         let mut blocks: Vec<BedEntry> = Vec::with_capacity(ex_num);
         for i in 0..ex_num {
-            let start: u64 = thin_start + self.exon_starts.as_ref().unwrap()[i];
-            let end: u64 = start + self.exon_sizes.as_ref().unwrap()[i];
+            let start: u64 = thin_start + exon_starts[i];
+            let end: u64 = start + exon_sizes[i];
             blocks.push(
                 BedEntry::bed6(
                     chrom.to_string(),
@@ -357,19 +490,109 @@ impl BedEntry{
         Some(blocks)
     }
 
-    pub fn clip_by(&mut self, start: Option<u64>, end: Option<u64>, inplace: bool) -> Option<BedEntry> {
-        let chrom: &str = match &self.chrom {
-            Some(x) => {x},
-            None => {return None}
+    /// Extracts the requested fraction of the entry as a new BED12 record
+    ///
+    /// # Arguments
+    /// `mode` - transcript fraction to extract (all blocks, CDS, UTRs, or 5'/3' UTR only)
+    /// `intron` - if true, report the introns lying within the requested fraction instead of exons
+    ///
+    /// # Returns
+    /// A new BedEntry covering the requested fraction, or None if the entry has no such fraction
+    /// or lacks the data (blocks, thick coordinates, strand) needed to compute it
+    ///
+    pub fn fraction(&self, mode: BedFractionMode, intron: bool) -> Option<BedEntry> {
+        extract_fraction(self, mode, intron).ok().flatten()
+    }
+
+    /// Extracts the requested fraction of the entry and splits it into individual BED6 blocks
+    ///
+    /// Same as BedEntry::fraction() but returns each exon (or intron) of the fraction as
+    /// a separate record, in ascending coordinate order, with the block's ordinal number
+    /// in the score field
+    ///
+    pub fn fraction_blocks(&self, mode: BedFractionMode, intron: bool) -> Option<Vec<BedEntry>> {
+        extract_fraction_blocks(self, mode, intron).ok().flatten()
+    }
+
+    /// Returns the exon with the given ordinal number as a BED6 entry
+    ///
+    /// Exons are numbered from 1 starting from the transcript's 5'-end, i.e., the first exon
+    /// of a minus-strand transcript is its rightmost block. A missing score is reported as 0
+    ///
+    /// # Returns
+    /// None if the entry is not in BED12 format, has no defined strand, or has fewer than `number` exons
+    ///
+    pub fn exon(&self, number: usize) -> Option<BedEntry> {
+        let (start, end) = self.exon_coords(number..=number)?[0];
+        let mut exon = self.clone();
+        exon.set_blocks(vec![(start, end)]);
+        exon.thick_start = None;
+        exon.thick_end = None;
+        exon.rgb = None;
+        exon.exon_num = None;
+        exon.exon_sizes = None;
+        exon.exon_starts = None;
+        exon.score.get_or_insert_with(|| String::from("0"));
+        exon.format = Some(6);
+        Some(exon)
+    }
+
+    /// Returns a BED12 entry containing only the exons within the given range of ordinal numbers
+    ///
+    /// Exons are numbered from 1 starting from the transcript's 5'-end; both `2..5` and `2..=4`
+    /// select the second, third and fourth exons. Coding boundaries are clipped to the selected exons
+    ///
+    /// # Returns
+    /// None if the entry is not in BED12 format, has no defined strand,
+    /// or the range is empty or exceeds the number of exons
+    ///
+    pub fn exons_range<R: RangeBounds<usize>>(&self, range: R) -> Option<BedEntry> {
+        let blocks = self.exon_coords(range)?;
+        let mut output = self.clone();
+        output.set_blocks(blocks);
+        Some(output)
+    }
+
+    /// Genomic coordinates of the exons within the range of strand-aware ordinal numbers,
+    /// in ascending coordinate order
+    fn exon_coords<R: RangeBounds<usize>>(&self, range: R) -> Option<Vec<(u64, u64)>> {
+        if self.format() != 12 || self.exon_sizes.is_none() || self.exon_starts.is_none() {
+            return None
+        }
+        let strand = self.strand?;
+        let blocks: Vec<(u64, u64)> = self.exons().collect();
+        let first = match range.start_bound() {
+            Bound::Included(x) => {*x},
+            Bound::Excluded(x) => {x + 1},
+            Bound::Unbounded => {1}
         };
-        let thin_start: u64 = match self.thin_start {
-            Some(x) => {x},
-            None => {return None}
+        let last = match range.end_bound() {
+            Bound::Included(x) => {*x},
+            Bound::Excluded(x) => {x.checked_sub(1)?},
+            Bound::Unbounded => {blocks.len()}
         };
-        let name: &str = match &self.name {
-            Some(x) => {x},
-            None => {return None}
+        if first == 0 || first > last || last > blocks.len() {
+            return None
+        }
+        let (from, to) = if strand {
+            (first - 1, last)
+        } else {
+            (blocks.len() - last, blocks.len() - first + 1)
         };
+        Some(blocks[from..to].to_vec())
+    }
+
+    /// Clip the entry to the [start, end) range, trimming or discarding the blocks outside of it;
+    /// None leaves the respective side unclipped
+    ///
+    /// # Returns
+    /// The clipped copy of the entry; None if `inplace` is set (the entry itself is updated then)
+    /// or if the entry has undefined chromosome or coordinates
+    ///
+    pub fn clip_by(&mut self, start: Option<u64>, end: Option<u64>, inplace: bool) -> Option<BedEntry> {
+        if self.chrom.is_none() || self.thin_start.is_none() || self.thin_end.is_none() {
+            return None
+        }
         let mut new_thin_start: u64 = match start {
             Some(x) => {max(self.thin_start.unwrap(), x)},
             None => self.thin_start.unwrap()
@@ -472,11 +695,161 @@ impl BedEntry{
 
     }
     
+    /// Clip the entry as `clip_by()` does, snapping the clip boundaries falling within the coding sequence
+    /// to the nearest codon boundaries so that the clipped CDS stays in frame
+    ///
+    /// # Arguments
+    /// `start`, `end`, `inplace`: see `clip_by()`;
+    /// `snap`: CodonSnap::Inward moves the boundaries into the retained region (discarding the partial codons),
+    /// CodonSnap::Outward moves them away from it (retaining the partial codons in full)
+    ///
+    /// Boundaries outside of the coding sequence and entries with undefined strand or no CDS are clipped as is
+    ///
+    pub fn clip_by_codons(
+        &mut self, start: Option<u64>, end: Option<u64>, snap: CodonSnap, inplace: bool
+    ) -> Option<BedEntry> {
+        let (start, end) = match (self.cds_blocks(), self.strand) {
+            (Some(cds), Some(strand)) => {
+                let cds_len: u64 = cds.iter().map(|x| x.1 - x.0).sum();
+                // codon boundaries are counted from the 5'-end of the CDS
+                let anchor = if strand {0} else {cds_len % 3};
+                let round_down = |x: u64| if x < anchor {0} else {x - (x - anchor) % 3};
+                let round_up = |x: u64| {
+                    if x < anchor {return anchor};
+                    match (x - anchor) % 3 {
+                        0 => {x},
+                        r => {min(cds_len, x + 3 - r)}
+                    }
+                };
+                let new_start = start.map(|pos| match coding_offset(&cds, pos) {
+                    Some(o) => {
+                        let o = match snap {CodonSnap::Inward => {round_up(o)}, CodonSnap::Outward => {round_down(o)}};
+                        coding_position(&cds, o).unwrap_or(pos)
+                    },
+                    None => {pos}
+                });
+                let new_end = end.map(|pos| match coding_offset(&cds, pos) {
+                    Some(o) => {
+                        let o = match snap {CodonSnap::Inward => {round_down(o)}, CodonSnap::Outward => {round_up(o)}};
+                        if o == 0 {cds[0].0} else {coding_position(&cds, o - 1).map(|x| x + 1).unwrap_or(pos)}
+                    },
+                    None => {pos}
+                });
+                (new_start, new_end)
+            },
+            _ => {(start, end)}
+        };
+        self.clip_by(start, end, inplace)
+    }
+
+    /// Shorten the entry by `n` transcript bases from its 5'-end, following the strand
+    ///
+    /// Blocks, thin and thick boundaries are updated in place; the coding sequence is clipped
+    /// if the trimmed bases overlap it. Returns an error if the strand, chromosome or coordinates are undefined,
+    /// or the entry is not longer than `n` bases
+    ///
+    pub fn trim_five_prime(&mut self, n: u64) -> Result<(), CubiculumError> {
+        self.trim_end(n, true)
+    }
+
+    /// Shorten the entry by `n` transcript bases from its 3'-end, following the strand;
+    /// see `trim_five_prime()`
+    ///
+    pub fn trim_three_prime(&mut self, n: u64) -> Result<(), CubiculumError> {
+        self.trim_end(n, false)
+    }
+
+    fn trim_end(&mut self, n: u64, five_prime: bool) -> Result<(), CubiculumError> {
+        let strand = self.strand.ok_or(
+            CubiculumError::MissingTraitError("Cannot trim an entry with undefined strand".to_string())
+        )?;
+        if n == 0 {return Ok(())};
+        let length: u64 = self.exons().map(|x| x.1 - x.0).sum();
+        if n >= length {
+            return Err(
+                CubiculumError::InputError(format!("Cannot trim {} bases from {:?} of length {}", n, self.name, length))
+            )
+        }
+        // the first retained base counting from the trimmed end
+        let offset = if five_prime {n} else {length - n - 1};
+        let pos = self.transcript_to_genomic(offset).ok_or(
+            CubiculumError::CoordinateError(format!("Transcript offset {} is out of range for {:?}", offset, self.name))
+        )?;
+        let clipped = if five_prime == strand {
+            self.clip_by(Some(pos), None, false)
+        } else {
+            self.clip_by(None, Some(pos + 1), false)
+        };
+        *self = clipped.ok_or(
+            CubiculumError::MissingTraitError(
+                "Cannot trim an entry with undefined chromosome or coordinates".to_string()
+            )
+        )?;
+        Ok(())
+    }
+
+    /// Clip the entry as `clip_by()` does, additionally reporting the changes introduced
+    ///
+    /// # Returns
+    /// The result of `clip_by()` paired with an EditReport comparing the entry before and after clipping
+    ///
+    pub fn clip_by_reported(
+        &mut self, start: Option<u64>, end: Option<u64>, inplace: bool
+    ) -> (Option<BedEntry>, EditReport) {
+        let before = self.clone();
+        let result = self.clip_by(start, end, inplace);
+        let report = EditReport::between(&before, result.as_ref().unwrap_or(self));
+        (result, report)
+    }
+
+    /// Clip the entry as `clip_by()` does, with the given anomaly handling
+    ///
+    /// A clipping range that misses all of the entry's blocks is an anomaly; in lenient mode,
+    /// it is reported to the warning handler, and the entry is discarded (None is returned, and the entry
+    /// is left intact if `inplace` is set). Entries without chromosome or coordinates cannot be clipped
+    /// in either mode
+    ///
+    /// # Returns
+    /// The result of `clip_by()`; an error if the entry cannot be clipped or, in strict mode,
+    /// if the range misses the entry
+    ///
+    pub fn clip_by_with_options(
+        &mut self, start: Option<u64>, end: Option<u64>, inplace: bool, options: &ProcessingOptions
+    ) -> Result<Option<BedEntry>, CubiculumError> {
+        if self.chrom.is_none() || self.thin_start.is_none() || self.thin_end.is_none() {
+            return Err(
+                CubiculumError::MissingTraitError(
+                    String::from("Clipping requires the entry chromosome and coordinates to be defined")
+                )
+            )
+        }
+        let range_start = start.unwrap_or(0);
+        let range_end = end.unwrap_or(u64::MAX);
+        if !self.exons().any(|(x, y)| x < range_end && y > range_start && range_start < range_end) {
+            options.anomaly(
+                CubiculumError::CoordinateError(
+                    format!(
+                        "Clipping range {:?}-{:?} does not overlap any block of entry {}",
+                        start, end, self.name.as_ref().map_or("", |x| x.as_str())
+                    )
+                )
+            )?;
+            return Ok(None)
+        }
+        Ok(self.clip_by(start, end, inplace))
+    }
+
     pub fn to_cds(&mut self, inplace: bool)  -> Option<BedEntry> {
-        if self.format.unwrap() < 8 {return None};
+        if self.format() < 8 {return None};
         self.clip_by(self.thick_start, self.thick_end, inplace)
     }
 
+    /// Attach an interval (graft) to the entry; positional-flag counterpart of `graft_with()`
+    ///
+    /// `append_upstream` and `append_downstream` correspond to GraftSide::Upstream and GraftSide::Downstream,
+    /// respectively; if neither is set, the graft is merged at its own position. Setting both results in an error
+    ///
+    #[deprecated(note = "use `graft_with()` with GraftOptions instead")]
     pub fn graft<T>(
         &mut self, graft: T, inplace: bool,
         chrom_compatible: bool,
@@ -484,67 +857,110 @@ impl BedEntry{
         coding: bool,
         append_upstream: bool, 
         append_downstream: bool,
-    ) -> Option<BedEntry> 
+    ) -> Result<Option<BedEntry>, CubiculumError> 
     where
         T: Coordinates + Clone
     {
-        if append_upstream && append_downstream {
-            panic!("Cannot append from both up- and downstream sides");
-        }
+        let side = match (append_upstream, append_downstream) {
+            (true, true) => {
+                return Err(CubiculumError::InputError("Cannot append from both up- and downstream sides".to_string()))
+            },
+            (true, false) => {GraftSide::Upstream},
+            (false, true) => {GraftSide::Downstream},
+            (false, false) => {GraftSide::Merge}
+        };
+        let options = GraftOptions {side, coding, allow_overlaps, chrom_check: chrom_compatible};
+        self.graft_with(graft, &options, inplace)
+    }
+
+    /// Attach an interval (graft) to the entry, extending the adjacent block or adding a new one
+    ///
+    /// # Arguments
+    /// `graft`: the interval to attach;
+    /// `options`: GraftOptions object defining the side to attach the graft to and the compatibility checks;
+    /// strand-relative sides (GraftSide::FivePrime and GraftSide::ThreePrime) are resolved from the entry's strand;
+    /// `inplace`: if true, the entry itself is updated
+    ///
+    /// # Returns
+    /// Ok(Some) with the grafted copy if `inplace` is false, Ok(None) if the entry was updated in place;
+    /// an error if the graft cannot be attached, in which case the entry is left intact
+    ///
+    pub fn graft_with<T>(
+        &mut self, graft: T, options: &GraftOptions, inplace: bool
+    ) -> Result<Option<BedEntry>, CubiculumError> 
+    where
+        T: Coordinates + Clone
+    {
+        let chrom_compatible = options.chrom_check;
+        let allow_overlaps = options.allow_overlaps;
+        let coding = options.coding;
+        let side = options.side.genomic(self.strand)?;
+        let append_upstream = side == GraftSide::Upstream;
+        let append_downstream = side == GraftSide::Downstream;
         if self.format() != 12 {
-            panic!("Cannot graft to a non-BED12 object");
+            return Err(CubiculumError::FormattingError("Cannot graft to a non-BED12 object".to_string()));
         }
         if chrom_compatible {
             match (self.chrom(), graft.chrom()) {
                 (Some(x), Some(y)) => {
                     if x != y {
-                        panic!("BED12 and graft are located on different chromosomes ({} and {})", x, y)
+                        return Err(
+                            CubiculumError::IncompatibleChrom(
+                                format!("BED12 and graft are located on different chromosomes ({} and {})", x, y)
+                            )
+                        )
                     }
                 },
-                _ => {panic!("Undefined chromosome for either BED12 or graft when `chrom_compatible` was set")}
+                _ => {
+                    return Err(
+                        CubiculumError::MissingTraitError(
+                            "Undefined chromosome for either BED12 or graft when `chrom_compatible` was set".to_string()
+                        )
+                    )
+                }
             }
         }
 
         let mut thin_start = match self.thin_start {
             Some(x) => {x},
-            None => {panic!("Undefined thinStart value for BED12")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thinStart value for BED12".to_string()))}
         };
         let mut thick_start = match self.thick_start {
             Some(x) => {x},
-            None => {panic!("CRITICAL: Undefined thickStart value for BED12")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thickStart value for BED12".to_string()))}
         };
         let mut thin_end = match self.thin_end {
             Some(x) => {x},
-            None => {panic!("CRITICAL: Undefined thinEnd value for BED12")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thinEnd value for BED12".to_string()))}
         };
         let mut thick_end = match self.thick_end {
             Some(x) => {x},
-            None => {panic!("CRITICAL: Undefined thickEnd value for BED12")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined thickEnd value for BED12".to_string()))}
         };
         
         let mut exon_num = match self.exon_num {
             Some(x) => {x},
-            None => {panic!("CRITICAL: Exon number is not defined for the BED12 object")}
+            None => {return Err(CubiculumError::MissingTraitError("Exon number is not defined for the BED12 object".to_string()))}
         };
 
         let mut exon_sizes = match &mut self.exon_sizes {
             Some(x) => {x.clone()},
-            None => {panic!("CRITICAL: Exon sizes are not defined for the BED12 object")}
+            None => {return Err(CubiculumError::MissingTraitError("Exon sizes are not defined for the BED12 object".to_string()))}
         };
         let mut exon_starts = match &mut self.exon_starts {
             Some(x) => {x.clone()},
-            None => {panic!("CRITICAL: Exon starts are not defined for the BED12 object")}
+            None => {return Err(CubiculumError::MissingTraitError("Exon starts are not defined for the BED12 object".to_string()))}
         };
 
         let graft_start = match graft.start() {
             Some(x) => {*x},
-            None => {panic!("CRITICAL: Undefined start coordinate for a grafted interval")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined start coordinate for a grafted interval".to_string()))}
         };
         let graft_end = match graft.end() {
             Some(x) => {*x},
-            None => {panic!("CRITICAL: Undefined end coordinate for a grafted interval")}
+            None => {return Err(CubiculumError::MissingTraitError("Undefined end coordinate for a grafted interval".to_string()))}
         };
-        let mut graft_len = graft.length().unwrap();
+        let mut graft_len = graft_end.saturating_sub(graft_start);
 
         // keep track on whether the final block should be merged
         let mut to_merge = false;
@@ -552,11 +968,16 @@ impl BedEntry{
         // for appending upstream, only the start coordinate actually matters
         if append_upstream {
             if coding && thin_start != thick_start {
-                panic!("CRITICAL: Attempting to graft a coding block to a sequence with non-coding upstream fraction")
+                return Err(
+                    CubiculumError::NonCodingFlank(
+                        "Attempting to graft a coding block to a sequence with non-coding upstream fraction".to_string()
+                    )
+                )
             }
             if !coding && graft_start > thick_start {
-                println!("WARNING: Graft start coordinate lies within the coding sequence");
-                return None;
+                return Err(
+                    CubiculumError::CodingOverlap("Graft start coordinate lies within the coding sequence".to_string())
+                )
             };
             // update the start coordinate(s)
             let updated_start: bool = graft_start < thin_start;
@@ -572,7 +993,7 @@ impl BedEntry{
                 if !to_merge {
                     let inter_ = intersection(exon_start, exon_end, graft_start, graft_end);
                     if let Some(inter) = inter_ {
-                        if inter > 0 {if allow_overlaps {to_merge = true} else {return None}}
+                        if inter > 0 {if allow_overlaps {to_merge = true} else {return Err(overlap_rejected())}}
                     } 
                 }
                 // if exon_start <= graft_start && graft_start <= exon_end {
@@ -594,7 +1015,7 @@ impl BedEntry{
                             let new_exon_start = min(graft_start, thick_start);
                             // safeguard against marginal cases: 
                             if new_exon_start < thin_start {
-                                if to_merge {exon_starts[i] = 0} else {return None}
+                                if to_merge {exon_starts[i] = 0} else {return Err(overlap_rejected())}
                             } else {
                                 exon_starts[i] = new_exon_start - thin_start
                             }
@@ -616,11 +1037,16 @@ impl BedEntry{
         } else if append_downstream {
         // the reverse is true for downstream appending
             if coding && thin_end != thick_end {
-                panic!("CRITICAL: Attempting to graft a coding block to a sequence with non-coding downstream fraction")
+                return Err(
+                    CubiculumError::NonCodingFlank(
+                        "Attempting to graft a coding block to a sequence with non-coding downstream fraction".to_string()
+                    )
+                )
             }
             if !coding && graft_end < thick_end {
-                println!("WARNING: Graft end coordinate lies within the coding sequence");
-                return None;
+                return Err(
+                    CubiculumError::CodingOverlap("Graft end coordinate lies within the coding sequence".to_string())
+                )
             };
             // update the start coordinate(s)
             if coding {thick_end = graft_end};
@@ -633,7 +1059,7 @@ impl BedEntry{
                 if !to_merge {
                     let inter_ = intersection(exon_start, exon_end, graft_start, graft_end);
                     if let Some(inter) = inter_ {
-                        if inter > 0 {if allow_overlaps {to_merge = true} else {return None}}
+                        if inter > 0 {if allow_overlaps {to_merge = true} else {return Err(overlap_rejected())}}
                     } 
                 }
                 if exon_start < thick_end {
@@ -665,8 +1091,12 @@ impl BedEntry{
             //     println!("Graft end coordinate lies within the coding sequence");
             //     return None;
             // };
-            let mut blocks = self.to_blocks().unwrap();
-            let mut graft_interval = BedEntry::from_interval(graft).unwrap();
+            let mut blocks = self.to_blocks().ok_or(
+                CubiculumError::MissingTraitError("Cannot split the BED12 object into blocks".to_string())
+            )?;
+            let mut graft_interval = BedEntry::from_interval(graft).ok_or(
+                CubiculumError::MissingTraitError("Undefined coordinates for a grafted interval".to_string())
+            )?;
             if append_upstream {graft_interval.update_thin_end(thick_start)}
             if append_downstream {graft_interval.update_thin_start(thick_end)}
             blocks.push(graft_interval);
@@ -678,10 +1108,9 @@ impl BedEntry{
                     a.start().unwrap().cmp(&b.start().unwrap())
                 }
             );
-            let merged_blocks = merge_multiple(&mut blocks);
-            if merged_blocks.len() < unmerged_block_num as usize && !allow_overlaps {
-                println!("Grafted interval overlaps some of the existing blocks. Consider setting allow overlap to allow merging blocks");
-                return None;
+            let merged_blocks = merge_multiple(&mut blocks)?;
+            if merged_blocks.len() < unmerged_block_num && !allow_overlaps {
+                return Err(overlap_rejected());
             }
             // println!("merged_blocks={:#?},\nmerged_blocks.len()={}", merged_blocks, merged_blocks.len());
             // println!("blocks.len()={}, merged_blocks.len()={}", blocks.len(), merged_blocks.len());
@@ -703,37 +1132,1334 @@ impl BedEntry{
             exon_num = merged_blocks.len() as u16;
         }
 
-        if inplace{
-            self.thin_start = Some(thin_start);
-            self.thin_end = Some(thin_end);
-            self.thick_start = Some(thick_start);
-            self.thick_start = Some(thick_start);
-            self.exon_num = Some(exon_num as u16);
-            self.exon_sizes = Some(exon_sizes);
-            self.exon_starts = Some(exon_starts);
-            return None;
-        }
-        let mut grafted_bed = BedEntry::empty();
-        grafted_bed.format = Some(12);
-        grafted_bed.chrom = self.chrom.clone();
-        grafted_bed.thin_start = Some(thin_start);
-        grafted_bed.thin_end = Some(thin_end);
-        grafted_bed.name = self.name.clone();
-        grafted_bed.score = self.score.clone();
-        grafted_bed.strand = self.strand;
-        grafted_bed.thick_start = Some(thick_start);
-        grafted_bed.thick_end = Some(thick_end);
-        grafted_bed.rgb = self.rgb.clone();
-        grafted_bed.exon_num = Some(exon_num);
-        grafted_bed.exon_sizes = Some(exon_sizes);
-        grafted_bed.exon_starts = Some(exon_starts);
-        Some(grafted_bed)
+        if inplace{
+            self.thin_start = Some(thin_start);
+            self.thin_end = Some(thin_end);
+            self.thick_start = Some(thick_start);
+            self.thick_end = Some(thick_end);
+            self.exon_num = Some(exon_num);
+            self.exon_sizes = Some(exon_sizes);
+            self.exon_starts = Some(exon_starts);
+            return Ok(None);
+        }
+        let mut grafted_bed = BedEntry::empty();
+        grafted_bed.format = Some(12);
+        grafted_bed.chrom = self.chrom.clone();
+        grafted_bed.thin_start = Some(thin_start);
+        grafted_bed.thin_end = Some(thin_end);
+        grafted_bed.name = self.name.clone();
+        grafted_bed.score = self.score.clone();
+        grafted_bed.strand = self.strand;
+        grafted_bed.thick_start = Some(thick_start);
+        grafted_bed.thick_end = Some(thick_end);
+        grafted_bed.rgb = self.rgb.clone();
+        grafted_bed.exon_num = Some(exon_num);
+        grafted_bed.exon_sizes = Some(exon_sizes);
+        grafted_bed.exon_starts = Some(exon_starts);
+        Ok(Some(grafted_bed))
+    }
+
+    /// Attach the graft as `graft_with()` does, with the given anomaly handling
+    ///
+    /// A graft rejected for overlapping the existing blocks is an anomaly; in lenient mode,
+    /// it is reported to the warning handler, and the graft is merged with the blocks it overlaps.
+    /// All other grafting errors are returned in both modes
+    ///
+    pub fn graft_with_options<T>(
+        &mut self, graft: T, graft_options: &GraftOptions, options: &ProcessingOptions, inplace: bool
+    ) -> Result<Option<BedEntry>, CubiculumError>
+    where
+        T: Coordinates + Clone
+    {
+        match self.graft_with(graft.clone(), graft_options, inplace) {
+            Err(CubiculumError::OverlapRejected(x)) => {
+                options.anomaly(CubiculumError::OverlapRejected(x))?;
+                self.graft_with(graft, &graft_options.allow_overlaps(true), inplace)
+            },
+            result => {result}
+        }
+    }
+
+    /// Attach the graft as `graft_with()` does, additionally reporting the changes introduced
+    ///
+    /// # Returns
+    /// The result of `graft_with()` paired with an EditReport comparing the entry before and after grafting;
+    /// the report also points to the block the graft was absorbed into
+    ///
+    pub fn graft_with_reported<T>(
+        &mut self, graft: T, options: &GraftOptions, inplace: bool
+    ) -> Result<(Option<BedEntry>, EditReport), CubiculumError>
+    where
+        T: Coordinates + Clone
+    {
+        let before = self.clone();
+        let bounds = (graft.start().copied(), graft.end().copied());
+        let result = self.graft_with(graft, options, inplace)?;
+        let after = result.as_ref().unwrap_or(self);
+        let mut report = EditReport::between(&before, after);
+        if let (Some(start), Some(end)) = bounds {
+            report.absorbing_block = after.exons().position(|x| x.0 < end && start < x.1);
+        }
+        Ok((result, report))
+    }
+
+    /// Returns an iterator over the genomic coordinates of the entry's blocks
+    ///
+    /// For entries without the block structure (BED3 through BED9),
+    /// the whole thin interval is reported as a single block
+    ///
+    pub fn exons(&self) -> Exons<'_> {
+        let thin_start = self.thin_start.unwrap_or(0);
+        let thin_end = self.thin_end.unwrap_or(thin_start);
+        match (&self.exon_sizes, &self.exon_starts) {
+            (Some(sizes), Some(starts)) => {
+                Exons {
+                    thin_start,
+                    thin_end,
+                    sizes: Some(sizes),
+                    starts: Some(starts),
+                    front: 0,
+                    back: min(sizes.len(), starts.len())
+                }
+            },
+            _ => {
+                Exons {
+                    thin_start,
+                    thin_end,
+                    sizes: None,
+                    starts: None,
+                    front: 0,
+                    back: if self.thin_start.is_some() && self.thin_end.is_some() {1} else {0}
+                }
+            }
+        }
+    }
+
+    /// Returns the coding portions of the entry's blocks in ascending coordinate order
+    ///
+    /// Returns None if the entry has no defined or zero-length coding sequence
+    ///
+    pub fn cds_blocks(&self) -> Option<Vec<(u64, u64)>> {
+        let (thick_start, thick_end) = match (self.thick_start, self.thick_end) {
+            (Some(x), Some(y)) => {(x, y)},
+            _ => {return None}
+        };
+        if thick_start >= thick_end {return None};
+        let blocks: Vec<(u64, u64)> = self.exons()
+            .filter_map(|(start, end)| {
+                let block_start = max(start, thick_start);
+                let block_end = min(end, thick_end);
+                if block_start < block_end {Some((block_start, block_end))} else {None}
+            })
+            .collect();
+        if blocks.is_empty() {return None};
+        Some(blocks)
+    }
+
+    /// Returns the untranslated blocks of the entry annotated with their side and adjacency to the CDS
+    ///
+    /// A UTR block is adjacent to the CDS if it belongs to the same exon as the first or the last coding base,
+    /// and separated from the CDS by an intron otherwise. Blocks are reported in ascending coordinate order;
+    /// the vector is empty for non-coding entries and entries with no UTRs.
+    /// Returns None if the entry has undefined strand or lacks thick coordinates
+    ///
+    pub fn utr_blocks(&self) -> Option<Vec<UtrBlock>> {
+        let strand = self.strand?;
+        let (thick_start, thick_end) = (self.thick_start?, self.thick_end?);
+        if thick_start >= thick_end {return Some(Vec::new())};
+        let (upstream, downstream) = if strand {
+            (UtrSide::FivePrime, UtrSide::ThreePrime)
+        } else {
+            (UtrSide::ThreePrime, UtrSide::FivePrime)
+        };
+        let mut blocks: Vec<UtrBlock> = Vec::new();
+        let mut push = |start: u64, end: u64, side: &UtrSide, adjacent: bool| {
+            blocks.push(
+                UtrBlock {
+                    chrom: self.chrom.clone(),
+                    start: Some(start),
+                    end: Some(end),
+                    name: self.name.clone(),
+                    strand: Some(strand),
+                    side: Some(side.clone()),
+                    adjacent: Some(adjacent)
+                }
+            )
+        };
+        for (start, end) in self.exons() {
+            if start < thick_start {
+                push(start, min(end, thick_start), &upstream, end > thick_start);
+            }
+            if end > thick_end {
+                push(max(start, thick_end), end, &downstream, start < thick_end);
+            }
+        }
+        Some(blocks)
+    }
+
+    /// Returns the reading frame of each block in genePred `exonFrames` convention
+    ///
+    /// Frames are reported in ascending coordinate order; each value is the number of coding bases preceding
+    /// the block in the direction of transcription modulo three, or -1 for blocks with no coding bases.
+    /// Returns None if the entry has undefined strand or lacks block or thick coordinates
+    ///
+    pub fn exon_frames(&self) -> Option<Vec<i8>> {
+        let strand = self.strand?;
+        let (thick_start, thick_end) = (self.thick_start?, self.thick_end?);
+        if self.exon_sizes.is_none() || self.exon_starts.is_none() {return None};
+        let coding: Vec<u64> = self.exons()
+            .map(|(start, end)| min(end, thick_end).saturating_sub(max(start, thick_start)))
+            .collect();
+        let mut frames: Vec<i8> = vec![-1; coding.len()];
+        let mut preceding: u64 = 0;
+        let order: Vec<usize> = if strand {(0..coding.len()).collect()} else {(0..coding.len()).rev().collect()};
+        for i in order {
+            if coding[i] == 0 {continue};
+            frames[i] = (preceding % 3) as i8;
+            preceding += coding[i];
+        }
+        Some(frames)
+    }
+
+    /// Returns the genomic interval(s) occupied by the start codon
+    ///
+    /// The codon is inferred from the thickStart/thickEnd values and the strand of the entry,
+    /// and can be split by an intron into up to three intervals reported in ascending coordinate order.
+    /// Returns None if the entry is non-coding, has undefined strand, or its CDS is shorter than a single codon
+    ///
+    pub fn start_codon(&self) -> Option<Vec<Interval>> {
+        let strand = self.strand?;
+        self.terminal_codon(!strand)
+    }
+
+    /// Returns the genomic interval(s) occupied by the stop codon
+    ///
+    /// Follows the same conventions as `start_codon()`; the stop codon is expected
+    /// to lie within the thick interval, as is the case for most annotation sources
+    ///
+    pub fn stop_codon(&self) -> Option<Vec<Interval>> {
+        let strand = self.strand?;
+        self.terminal_codon(strand)
+    }
+
+    /// Returns the single-base interval of the transcription start site,
+    /// i.e. the first base for plus-strand entries and the last base for minus-strand ones
+    ///
+    /// Returns None if the strand or the coordinates are undefined
+    ///
+    pub fn tss(&self) -> Option<Interval> {
+        let strand = self.strand?;
+        self.terminal_base(!strand)
+    }
+
+    /// Returns the single-base interval of the transcription end site;
+    /// follows the same conventions as `tss()`
+    ///
+    pub fn tes(&self) -> Option<Interval> {
+        let strand = self.strand?;
+        self.terminal_base(strand)
+    }
+
+    fn terminal_base(&self, from_end: bool) -> Option<Interval> {
+        let (start, end) = (self.thin_start?, self.thin_end?);
+        if start >= end {return None};
+        let pos = if from_end {end - 1} else {start};
+        Some(Interval::from(self.chrom.clone(), Some(pos), Some(pos + 1), self.name.clone()))
+    }
+
+    /// Collects the first (`from_end` = false) or the last (`from_end` = true)
+    /// three coding bases of the entry
+    fn terminal_codon(&self, from_end: bool) -> Option<Vec<Interval>> {
+        let cds = self.cds_blocks()?;
+        let mut remaining: u64 = 3;
+        let mut codon: Vec<Interval> = Vec::with_capacity(3);
+        let ordered: Box<dyn Iterator<Item = &(u64, u64)>> = if from_end {
+            Box::new(cds.iter().rev())
+        } else {
+            Box::new(cds.iter())
+        };
+        for (start, end) in ordered {
+            if remaining == 0 {break};
+            let taken = min(remaining, end - start);
+            let (codon_start, codon_end) = if from_end {
+                (end - taken, *end)
+            } else {
+                (*start, start + taken)
+            };
+            codon.push(
+                Interval::from(self.chrom.clone(), Some(codon_start), Some(codon_end), self.name.clone())
+            );
+            remaining -= taken;
+        }
+        if remaining > 0 {return None};
+        if from_end {codon.reverse()};
+        Some(codon)
+    }
+
+    /// Projects a genomic position onto the transcript (mRNA) coordinate space
+    ///
+    /// # Arguments
+    /// `pos`: a zero-based genomic coordinate of a single base
+    ///
+    /// # Returns
+    /// A zero-based offset from the 5'-end of the spliced transcript,
+    /// or None if the position lies outside the blocks (e.g., in an intron) or the strand is undefined
+    ///
+    pub fn genomic_to_transcript(&self, pos: u64) -> Option<u64> {
+        let strand = self.strand?;
+        let mut offset: u64 = 0;
+        if strand {
+            for (start, end) in self.exons() {
+                if pos >= start && pos < end {return Some(offset + pos - start)};
+                offset += end - start;
+            }
+        } else {
+            for (start, end) in self.exons().rev() {
+                if pos >= start && pos < end {return Some(offset + end - 1 - pos)};
+                offset += end - start;
+            }
+        }
+        None
+    }
+
+    /// Projects a transcript (mRNA) coordinate back onto the genome
+    ///
+    /// # Arguments
+    /// `offset`: a zero-based offset from the 5'-end of the spliced transcript
+    ///
+    /// # Returns
+    /// A zero-based genomic coordinate of the respective base,
+    /// or None if the offset exceeds the transcript length or the strand is undefined
+    ///
+    pub fn transcript_to_genomic(&self, offset: u64) -> Option<u64> {
+        let strand = self.strand?;
+        let mut remaining: u64 = offset;
+        if strand {
+            for (start, end) in self.exons() {
+                if remaining < end - start {return Some(start + remaining)};
+                remaining -= end - start;
+            }
+        } else {
+            for (start, end) in self.exons().rev() {
+                if remaining < end - start {return Some(end - 1 - remaining)};
+                remaining -= end - start;
+            }
+        }
+        None
+    }
+
+    /// Maps a codon of the encoded protein onto the genome
+    ///
+    /// # Arguments
+    /// `aa_index`: a zero-based index of the amino acid in the protein sequence
+    ///
+    /// # Returns
+    /// One to three genomic intervals covering the codon in ascending coordinate order,
+    /// or None if the entry is non-coding or the CDS does not contain a complete codon at this index
+    ///
+    pub fn codon_to_genomic(&self, aa_index: u64) -> Option<Vec<Interval>> {
+        let strand = self.strand?;
+        let mut cds = self.cds_blocks()?;
+        if !strand {cds.reverse()};
+        let codon_start: u64 = aa_index.checked_mul(3)?;
+        let codon_end: u64 = codon_start.checked_add(3)?;
+        let mut offset: u64 = 0;
+        let mut codon: Vec<Interval> = Vec::with_capacity(3);
+        for (start, end) in cds {
+            let block_len = end - start;
+            let overlap_start = max(offset, codon_start);
+            let overlap_end = min(offset + block_len, codon_end);
+            if overlap_start < overlap_end {
+                let (genomic_start, genomic_end) = if strand {
+                    (start + overlap_start - offset, start + overlap_end - offset)
+                } else {
+                    (end - (overlap_end - offset), end - (overlap_start - offset))
+                };
+                codon.push(
+                    Interval::from(self.chrom.clone(), Some(genomic_start), Some(genomic_end), self.name.clone())
+                );
+            }
+            offset += block_len;
+            if offset >= codon_end {break};
+        }
+        if offset < codon_end {return None};
+        if !strand {codon.reverse()};
+        Some(codon)
+    }
+
+    /// Returns the genomic coordinates of the introns in ascending coordinate order
+    ///
+    pub fn introns(&self) -> Vec<(u64, u64)> {
+        let blocks: Vec<(u64, u64)> = self.exons().collect();
+        blocks
+            .windows(2)
+            .filter(|x| x[0].1 < x[1].0)
+            .map(|x| (x[0].1, x[1].0))
+            .collect()
+    }
+
+    /// Returns a hashable key describing the intron chain of the entry
+    ///
+    /// Entries sharing the chromosome, strand and all intron coordinates produce equal keys.
+    /// Returns None for single-block entries since those have no intron chain to compare
+    ///
+    pub fn intron_chain_key(&self) -> Option<IntronChain> {
+        let introns = self.introns();
+        if introns.is_empty() {return None};
+        Some(
+            IntronChain {chrom: self.chrom.clone(), strand: self.strand, introns}
+        )
+    }
+
+    /// Checks whether the two entries have identical intron chains
+    ///
+    pub fn same_intron_chain(&self, other: &BedEntry) -> bool {
+        match (self.intron_chain_key(), other.intron_chain_key()) {
+            (Some(x), Some(y)) => {x == y},
+            _ => {false}
+        }
+    }
+
+    /// Remove the block with the given index (counted in ascending coordinate order)
+    ///
+    /// The thin and thick boundaries, block sizes, starts and count are updated accordingly;
+    /// a thick boundary within the removed block moves to the nearest remaining block edge inside the coding region.
+    /// Returns an error if the entry is not in BED12 format, the index is out of range,
+    /// or the entry contains a single block
+    ///
+    pub fn remove_exon(&mut self, index: usize) -> Result<(), CubiculumError> {
+        let mut blocks = self.editable_blocks()?;
+        if index >= blocks.len() {
+            return Err(CubiculumError::InputError(format!("Block index {} is out of range for {:?}", index, self.name)))
+        }
+        if blocks.len() == 1 {
+            return Err(CubiculumError::InputError(format!("Cannot remove the only block of {:?}", self.name)))
+        }
+        blocks.remove(index);
+        self.set_blocks(blocks);
+        Ok(())
+    }
+
+    /// Insert a new block at the given genomic coordinates
+    ///
+    /// Returns an error if the entry is not in BED12 format, the block is empty,
+    /// or it overlaps any of the existing blocks
+    ///
+    pub fn insert_exon(&mut self, start: u64, end: u64) -> Result<(), CubiculumError> {
+        let mut blocks = self.editable_blocks()?;
+        self.validate_block(&blocks, start, end, None)?;
+        let index = blocks.partition_point(|x| x.0 < start);
+        blocks.insert(index, (start, end));
+        self.set_blocks(blocks);
+        Ok(())
+    }
+
+    /// Replace the block with the given index (counted in ascending coordinate order)
+    /// with a block at the new genomic coordinates
+    ///
+    /// Returns an error if the entry is not in BED12 format, the index is out of range, the new block is empty,
+    /// or it overlaps any of the remaining blocks
+    ///
+    pub fn replace_exon(&mut self, index: usize, start: u64, end: u64) -> Result<(), CubiculumError> {
+        let mut blocks = self.editable_blocks()?;
+        if index >= blocks.len() {
+            return Err(CubiculumError::InputError(format!("Block index {} is out of range for {:?}", index, self.name)))
+        }
+        self.validate_block(&blocks, start, end, Some(index))?;
+        blocks.remove(index);
+        let index = blocks.partition_point(|x| x.0 < start);
+        blocks.insert(index, (start, end));
+        self.set_blocks(blocks);
+        Ok(())
+    }
+
+    fn editable_blocks(&self) -> Result<Vec<(u64, u64)>, CubiculumError> {
+        if self.format() != 12 || self.exon_sizes.is_none() || self.exon_starts.is_none() {
+            return Err(CubiculumError::FormattingError("Blocks can be edited in BED12 entries only".to_string()))
+        }
+        Ok(self.exons().collect())
+    }
+
+    fn validate_block(&self, blocks: &[(u64, u64)], start: u64, end: u64, skip: Option<usize>) -> Result<(), CubiculumError> {
+        if start >= end {
+            return Err(CubiculumError::InputError(format!("Block {}-{} is empty", start, end)))
+        }
+        let overlapping = blocks.iter()
+            .enumerate()
+            .any(|(i, x)| Some(i) != skip && x.0 < end && start < x.1);
+        if overlapping {
+            return Err(
+                CubiculumError::InputError(format!("Block {}-{} overlaps other blocks of {:?}", start, end, self.name))
+            )
+        }
+        Ok(())
+    }
+
+    /// Rebuild the block-related fields from the ascending genomic block coordinates
+    ///
+    /// Thick boundaries left outside the blocks are moved inwards to the nearest block edge,
+    /// so that the coding sequence starts and ends within exons; if no block is left within
+    /// the coding region, the entry becomes non-coding with both boundaries at chromStart.
+    /// Non-coding entries are merely clipped to the new thin boundaries
+    fn set_blocks(&mut self, blocks: Vec<(u64, u64)>) {
+        let start = blocks[0].0;
+        let end = blocks[blocks.len() - 1].1;
+        self.thin_start = Some(start);
+        self.thin_end = Some(end);
+        match (self.thick_start, self.thick_end) {
+            (Some(thick_start), Some(thick_end)) if thick_start < thick_end => {
+                let new_start = blocks.iter().find(|x| x.1 > thick_start).map(|x| max(x.0, thick_start));
+                let new_end = blocks.iter().rev().find(|x| x.0 < thick_end).map(|x| min(x.1, thick_end));
+                let (new_start, new_end) = match (new_start, new_end) {
+                    (Some(x), Some(y)) if x < y => {(x, y)},
+                    _ => {(start, start)}
+                };
+                self.thick_start = Some(new_start);
+                self.thick_end = Some(new_end);
+            },
+            _ => {
+                self.thick_start = self.thick_start.map(|x| x.clamp(start, end));
+                self.thick_end = self.thick_end.map(|x| x.clamp(start, end));
+            }
+        }
+        self.exon_num = Some(blocks.len() as u16);
+        self.exon_sizes = Some(blocks.iter().map(|x| x.1 - x.0).collect());
+        self.exon_starts = Some(blocks.iter().map(|x| x.0 - start).collect());
+    }
+
+    /// Copy of the entry placed at new coordinates, e.g., after conversion between assemblies
+    ///
+    /// `blocks` are the new ascending block coordinates (a single span for non-BED12 entries);
+    /// `thick` replaces the coding boundaries if the entry has them
+    pub(crate) fn relocated(
+        &self, chrom: String, blocks: Vec<(u64, u64)>, thick: (u64, u64), strand: Option<bool>
+    ) -> BedEntry {
+        let mut output = self.clone();
+        output.chrom = Some(chrom);
+        if self.strand.is_some() {output.strand = strand};
+        if self.format() == 12 && self.exon_sizes.is_some() {
+            output.set_blocks(blocks);
+        } else {
+            output.thin_start = Some(blocks[0].0);
+            output.thin_end = Some(blocks[blocks.len() - 1].1);
+        }
+        if self.thick_start.is_some() && self.thick_end.is_some() {
+            output.thick_start = Some(thick.0);
+            output.thick_end = Some(thick.1);
+        }
+        output
+    }
+
+    /// Assemble a BED12 entry out of single-block records (e.g., BED6 exons)
+    ///
+    /// Blocks are sorted by coordinates, with overlapping and book-ended blocks merged.
+    /// Name, score and strand are inherited from the first block
+    ///
+    /// # Arguments
+    /// `blocks`: single-block records sharing chromosome and strand;
+    /// `thick`: coding sequence boundaries; if None, the entry is considered non-coding
+    /// and both thickStart and thickEnd are set to chromStart
+    ///
+    /// # Returns
+    /// A BED12 entry; an error if the collection is empty, the blocks lie on different chromosomes or strands,
+    /// or the coding boundaries fall outside of the assembled entry
+    ///
+    pub fn from_exons(blocks: &[BedEntry], thick: Option<(u64, u64)>) -> Result<BedEntry, CubiculumError> {
+        let first = blocks.first().ok_or(
+            CubiculumError::InputError("Cannot assemble an entry from an empty block collection".to_string())
+        )?;
+        let chrom = first.chrom.clone().ok_or(
+            CubiculumError::MissingTraitError("Blocks must have a defined chromosome".to_string())
+        )?;
+        let strand = first.strand.ok_or(
+            CubiculumError::MissingTraitError("Blocks must have a defined strand".to_string())
+        )?;
+        let mut coords: Vec<(u64, u64)> = Vec::with_capacity(blocks.len());
+        for block in blocks {
+            if block.chrom.as_ref() != Some(&chrom) || block.strand != Some(strand) {
+                return Err(
+                    CubiculumError::InputError(
+                        format!("Blocks of {:?} lie on different chromosomes or strands", first.name)
+                    )
+                )
+            }
+            match (block.thin_start, block.thin_end) {
+                (Some(x), Some(y)) if x < y => {coords.push((x, y))},
+                _ => {
+                    return Err(
+                        CubiculumError::MissingTraitError(format!("Block of {:?} has invalid coordinates", first.name))
+                    )
+                }
+            }
+        }
+        let merged: Vec<(u64, u64)> = block_union(coords);
+        let start = merged[0].0;
+        let end = merged[merged.len() - 1].1;
+        let (thick_start, thick_end) = thick.unwrap_or((start, start));
+        if thick_start > thick_end || thick_start < start || thick_end > end {
+            return Err(
+                CubiculumError::InputError(
+                    format!("Coding boundaries {}-{} do not fit into {:?} ({}-{})", thick_start, thick_end, first.name, start, end)
+                )
+            )
+        }
+        Ok(
+            BedEntry::bed12(
+                chrom, start, end,
+                first.name.clone().unwrap_or(String::from(".")),
+                first.score.clone().unwrap_or(String::from("0")),
+                strand, thick_start, thick_end, String::from("0"),
+                merged.len() as u16,
+                merged.iter().map(|x| x.1 - x.0).collect(),
+                merged.iter().map(|x| x.0 - start).collect()
+            )
+        )
+    }
+
+    /// Returns the number of bases shared by the exons of the two entries;
+    /// intronic overlaps are not counted, and the strand is disregarded
+    ///
+    pub fn exonic_overlap(&self, other: &BedEntry) -> u64 {
+        block_overlap_size(self, other)
+    }
+
+    /// Returns the fraction of the entry's exonic bases shared with the exons of the other entry;
+    /// returns None if the entry has no exonic bases
+    ///
+    pub fn exonic_overlap_fraction(&self, other: &BedEntry) -> Option<f64> {
+        let length = self.block_length();
+        if length == 0 {return None};
+        Some(self.exonic_overlap(other) as f64 / length as f64)
+    }
+}
+
+/// Summary of the changes introduced to an entry by an editing operation
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct EditReport {
+    /// Number of the block bases present after the edit only
+    pub bases_added: u64,
+    /// Number of the block bases present before the edit only
+    pub bases_removed: u64,
+    pub blocks_before: usize,
+    pub blocks_after: usize,
+    /// Number of the original blocks fused with other original blocks
+    pub blocks_merged: usize,
+    /// Whether thickStart or thickEnd has changed
+    pub thick_moved: bool,
+    /// Index of the resulting block containing the graft, for the grafting operations
+    pub absorbing_block: Option<usize>
+}
+
+impl EditReport {
+    /// Compare the entry states before and after an edit
+    pub fn between(before: &BedEntry, after: &BedEntry) -> EditReport {
+        let old: Vec<(u64, u64)> = before.exons().collect();
+        let new: Vec<(u64, u64)> = after.exons().collect();
+        let blocks_merged = new.iter()
+            .map(|x| old.iter().filter(|y| y.0 < x.1 && x.0 < y.1).count())
+            .filter(|x| *x > 1)
+            .sum();
+        EditReport {
+            bases_added: uncovered_bases(&new, &old),
+            bases_removed: uncovered_bases(&old, &new),
+            blocks_before: old.len(),
+            blocks_after: new.len(),
+            blocks_merged,
+            thick_moved: (before.thick_start, before.thick_end) != (after.thick_start, after.thick_end),
+            absorbing_block: None
+        }
+    }
+}
+
+/// Number of bases in the sorted non-overlapping blocks `a` not covered by the sorted non-overlapping blocks `b`
+fn uncovered_bases(a: &[(u64, u64)], b: &[(u64, u64)]) -> u64 {
+    let mut total: u64 = 0;
+    let mut j: usize = 0;
+    for (start, end) in a {
+        let mut cursor = *start;
+        while j < b.len() && b[j].1 <= cursor {j += 1};
+        let mut k = j;
+        while k < b.len() && b[k].0 < *end {
+            if b[k].0 > cursor {total += b[k].0 - cursor};
+            cursor = max(cursor, b[k].1);
+            k += 1;
+        }
+        if cursor < *end {total += end - cursor};
+    }
+    total
+}
+
+/// Defines where `BedEntry::graft_with()` attaches the graft
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GraftSide {
+    /// Extend the entry from its start coordinate
+    Upstream,
+    /// Extend the entry from its end coordinate
+    Downstream,
+    /// Extend the entry from its 5'-end, i.e. upstream for plus-strand and downstream for minus-strand entries
+    FivePrime,
+    /// Extend the entry from its 3'-end, i.e. downstream for plus-strand and upstream for minus-strand entries
+    ThreePrime,
+    /// Merge the graft into the block structure at its own position
+    #[default]
+    Merge
+}
+
+impl GraftSide {
+    /// Resolve the strand-relative sides into the genomic ones
+    fn genomic(self, strand: Option<bool>) -> Result<GraftSide, CubiculumError> {
+        let strand = match self {
+            GraftSide::FivePrime | GraftSide::ThreePrime => {
+                strand.ok_or(
+                    CubiculumError::MissingTraitError(
+                        "Cannot resolve a strand-relative graft side for an entry with undefined strand".to_string()
+                    )
+                )?
+            },
+            x => {return Ok(x)}
+        };
+        match (self, strand) {
+            (GraftSide::FivePrime, true) | (GraftSide::ThreePrime, false) => {Ok(GraftSide::Upstream)},
+            _ => {Ok(GraftSide::Downstream)}
+        }
+    }
+}
+
+/// Grafting parameters for `BedEntry::graft_with()`
+///
+/// The default value merges a non-coding graft at its own position, rejecting overlaps with the existing blocks
+/// and grafts from other chromosomes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GraftOptions {
+    pub side: GraftSide,
+    /// If true, the graft extends the coding sequence as well
+    pub coding: bool,
+    /// If true, the graft is merged with the blocks it overlaps
+    pub allow_overlaps: bool,
+    /// If true, the entry and the graft must lie on the same chromosome
+    pub chrom_check: bool
+}
+
+impl Default for GraftOptions {
+    fn default() -> GraftOptions {
+        GraftOptions {side: GraftSide::Merge, coding: false, allow_overlaps: false, chrom_check: true}
+    }
+}
+
+impl GraftOptions {
+    pub fn new() -> GraftOptions {
+        GraftOptions::default()
+    }
+
+    pub fn side(mut self, side: GraftSide) -> GraftOptions {
+        self.side = side;
+        self
+    }
+
+    pub fn coding(mut self, coding: bool) -> GraftOptions {
+        self.coding = coding;
+        self
+    }
+
+    pub fn allow_overlaps(mut self, allow_overlaps: bool) -> GraftOptions {
+        self.allow_overlaps = allow_overlaps;
+        self
+    }
+
+    pub fn chrom_check(mut self, chrom_check: bool) -> GraftOptions {
+        self.chrom_check = chrom_check;
+        self
+    }
+}
+
+fn overlap_rejected() -> CubiculumError {
+    CubiculumError::OverlapRejected(
+        "Grafted interval overlaps some of the existing blocks; set `allow_overlaps` to merge them".to_string()
+    )
+}
+
+/// Defines the direction in which `BedEntry::clip_by_codons()` moves the clip boundaries
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CodonSnap {
+    Inward,
+    Outward
+}
+
+/// Number of coding bases preceding the genomic position, provided the position lies strictly within the CDS
+fn coding_offset(cds: &[(u64, u64)], pos: u64) -> Option<u64> {
+    if pos <= cds[0].0 || pos >= cds[cds.len() - 1].1 {return None};
+    let mut offset: u64 = 0;
+    for (start, end) in cds {
+        if pos < *start {return Some(offset)};
+        if pos < *end {return Some(offset + pos - start)};
+        offset += end - start;
+    }
+    None
+}
+
+/// Genomic position of the coding base with the given ascending offset
+fn coding_position(cds: &[(u64, u64)], offset: u64) -> Option<u64> {
+    let mut remaining = offset;
+    for (start, end) in cds {
+        if remaining < end - start {return Some(start + remaining)};
+        remaining -= end - start;
+    }
+    None
+}
+
+/// Hashable representation of a transcript's intron chain,
+/// created with `BedEntry::intron_chain_key()`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct IntronChain {
+    chrom: Option<String>,
+    strand: Option<bool>,
+    introns: Vec<(u64, u64)>
+}
+
+impl IntronChain {
+    pub fn introns(&self) -> &Vec<(u64, u64)> {
+        &self.introns
+    }
+}
+
+/// Iterator over the genomic block coordinates of a BedEntry object,
+/// created with `BedEntry::exons()`
+#[derive(Clone, Debug)]
+pub struct Exons<'a> {
+    thin_start: u64,
+    thin_end: u64,
+    sizes: Option<&'a Vec<u64>>,
+    starts: Option<&'a Vec<u64>>,
+    front: usize,
+    back: usize
+}
+
+impl Exons<'_> {
+    fn block(&self, i: usize) -> (u64, u64) {
+        match (self.sizes, self.starts) {
+            (Some(sizes), Some(starts)) => {
+                let start = self.thin_start + starts[i];
+                (start, start + sizes[i])
+            },
+            _ => {(self.thin_start, self.thin_end)}
+        }
+    }
+}
+
+impl Iterator for Exons<'_> {
+    type Item = (u64, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {return None};
+        let block = self.block(self.front);
+        self.front += 1;
+        Some(block)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl DoubleEndedIterator for Exons<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front >= self.back {return None};
+        self.back -= 1;
+        Some(self.block(self.back))
+    }
+}
+
+impl ExactSizeIterator for Exons<'_> {}
+
+#[cfg(test)]
+mod test_codons {
+    use super::*;
+
+    #[test]
+    fn start_stop_forward() {
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t+\t110\t290\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        let start = input.start_codon().unwrap();
+        assert_eq!(start.len(), 1);
+        assert_eq!((*start[0].start().unwrap(), *start[0].end().unwrap()), (110, 113));
+        let stop = input.stop_codon().unwrap();
+        assert_eq!(stop.len(), 1);
+        assert_eq!((*stop[0].start().unwrap(), *stop[0].end().unwrap()), (287, 290));
+    }
+
+    #[test]
+    fn exon_frames() {
+        let plus = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t150\t550\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        // 50 coding bases in the first block, 100 in the second one
+        assert_eq!(plus.exon_frames().unwrap(), vec![0, 2, 0]);
+        let minus = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t-\t150\t350\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        assert_eq!(minus.exon_frames().unwrap(), vec![2, 0, -1]);
+        let noncoding = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t100\t100\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        assert_eq!(noncoding.exon_frames().unwrap(), vec![-1, -1, -1]);
+    }
+
+    #[test]
+    fn codon_split_by_intron() {
+        // the start codon on the minus strand is split between the last two blocks
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t-\t100\t202\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        let start = input.start_codon().unwrap();
+        let coords: Vec<(u64, u64)> = start
+            .iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap()))
+            .collect();
+        assert_eq!(coords, vec![(149, 150), (200, 202)]);
+        let stop = input.stop_codon().unwrap();
+        assert_eq!((*stop[0].start().unwrap(), *stop[0].end().unwrap()), (100, 103));
+    }
+
+    #[test]
+    fn noncoding_has_no_codons() {
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t+\t300\t300\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        assert!(input.start_codon().is_none());
+        assert!(input.stop_codon().is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_projection {
+    use super::*;
+
+    #[test]
+    fn forward_projection() {
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t+\t110\t290\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        assert_eq!(input.genomic_to_transcript(100), Some(0));
+        assert_eq!(input.genomic_to_transcript(149), Some(49));
+        assert_eq!(input.genomic_to_transcript(160), None);
+        assert_eq!(input.genomic_to_transcript(200), Some(50));
+        assert_eq!(input.transcript_to_genomic(50), Some(200));
+        assert_eq!(input.transcript_to_genomic(149), Some(299));
+        assert_eq!(input.transcript_to_genomic(150), None);
+    }
+
+    #[test]
+    fn reverse_projection() {
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t-\t110\t290\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        assert_eq!(input.genomic_to_transcript(299), Some(0));
+        assert_eq!(input.genomic_to_transcript(200), Some(99));
+        assert_eq!(input.genomic_to_transcript(149), Some(100));
+        for offset in 0..150 {
+            let pos = input.transcript_to_genomic(offset).unwrap();
+            assert_eq!(input.genomic_to_transcript(pos), Some(offset));
+        }
+    }
+
+    #[test]
+    fn codon_projection() {
+        // CDS spans bases 140..150 and 200..211 on the minus strand, seven codons in total
+        let input = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t-\t140\t211\t0\t2\t50,100,\t0,100,",
+            12
+        ).unwrap();
+        let first = input.codon_to_genomic(0).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!((*first[0].start().unwrap(), *first[0].end().unwrap()), (208, 211));
+        // the fourth codon covers CDS bases 9..12 and is split by the intron
+        let split = input.codon_to_genomic(3).unwrap();
+        let coords: Vec<(u64, u64)> = split
+            .iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap()))
+            .collect();
+        assert_eq!(coords, vec![(149, 150), (200, 202)]);
+        let last = input.codon_to_genomic(6).unwrap();
+        assert_eq!((*last[0].start().unwrap(), *last[0].end().unwrap()), (140, 143));
+        assert!(input.codon_to_genomic(7).is_none());
+        assert!(input.codon_to_genomic(u64::MAX / 3).is_none());
+        assert!(input.codon_to_genomic(u64::MAX).is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_intron_chain {
+    use super::*;
+    use fxhash::FxHashSet;
+
+    #[test]
+    fn identical_chains_with_different_utrs() {
+        let first = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t+\t110\t290\t0\t3\t50,50,50,\t0,80,150,",
+            12
+        ).unwrap();
+        let second = try_parse_bed(
+            "chr1\t120\t320\tB\t0\t+\t130\t290\t0\t3\t30,50,70,\t0,60,130,",
+            12
+        ).unwrap();
+        assert_eq!(first.introns(), vec![(150, 180), (230, 250)]);
+        assert!(first.same_intron_chain(&second));
+        let mut keys: FxHashSet<IntronChain> = FxHashSet::default();
+        keys.insert(first.intron_chain_key().unwrap());
+        assert!(!keys.insert(second.intron_chain_key().unwrap()));
+    }
+
+    #[test]
+    fn different_strand_or_single_exon() {
+        let plus = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t+\t110\t290\t0\t2\t50,50,\t0,150,",
+            12
+        ).unwrap();
+        let minus = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t-\t110\t290\t0\t2\t50,50,\t0,150,",
+            12
+        ).unwrap();
+        let single = try_parse_bed(
+            "chr1\t100\t300\tA\t0\t-\t110\t290\t0\t1\t200,\t0,",
+            12
+        ).unwrap();
+        assert!(!plus.same_intron_chain(&minus));
+        assert!(!single.same_intron_chain(&single));
+        assert!(single.intron_chain_key().is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_write_line {
+    use super::*;
+
+    #[test]
+    fn reused_buffer() {
+        let line = "chr1\t100\t600\tA\t0\t-\t150\t350\t0\t3\t100,100,100,\t0,200,400,";
+        let entry = try_parse_bed(&String::from(line), 12).unwrap();
+        let mut out = String::new();
+        for format in [3, 4, 5, 6, 8, 9, 12] {
+            out.clear();
+            entry.write_line(format, &mut out).unwrap();
+            assert_eq!(out, to_line(&entry, format).unwrap());
+        }
+        assert_eq!(out, line);
+        out.clear();
+        assert!(entry.write_line(7, &mut out).is_err());
+        let bed3 = BedEntry::bed3(String::from("chr1"), 0, 10);
+        assert!(bed3.write_line(6, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod test_bed_line {
+    use super::*;
+
+    #[test]
+    fn interval_lines() {
+        let named = Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("A")));
+        assert_eq!(named.to_bed_line(3).unwrap(), "chr1\t100\t200");
+        assert_eq!(named.to_bed_line(4).unwrap(), "chr1\t100\t200\tA");
+        assert_eq!(ToBedLine::to_bed_line(&&named, 6).unwrap(), "chr1\t100\t200\tA\t0\t.");
+        assert!(named.to_bed_line(5).is_err());
+        let unnamed = Interval::from(Some(String::from("chr1")), Some(100), Some(200), None);
+        assert!(unnamed.to_bed_line(4).is_err());
+        assert!(Interval::new().to_bed_line(3).is_err());
+    }
+
+    #[test]
+    fn utr_block_lines() {
+        let entry = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t-\t150\t350\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        let utrs = entry.utr_blocks().unwrap();
+        let mut out = String::new();
+        for utr in utrs.iter() {
+            utr.write_bed_line(6, &mut out).unwrap();
+            out.push('\n');
+        }
+        assert_eq!(out, "chr1\t100\t150\tA\t0\t-\nchr1\t350\t400\tA\t0\t-\nchr1\t500\t600\tA\t0\t-\n");
+    }
+}
+
+#[cfg(test)]
+mod test_fraction {
+    use super::*;
+
+    fn entry() -> BedEntry {
+        try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t150\t550\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap()
+    }
+
+    #[test]
+    fn cds_fraction() {
+        let cds = entry().fraction(BedFractionMode::Cds, false).unwrap();
+        assert_eq!(
+            to_line(&cds, 12).unwrap(),
+            "chr1\t150\t550\tA\t0\t+\t150\t550\t0\t3\t50,100,50,\t0,150,350,"
+        );
+        let blocks = entry().fraction_blocks(BedFractionMode::Cds, false).unwrap();
+        let coords: Vec<(u64, u64)> = blocks
+            .iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap()))
+            .collect();
+        assert_eq!(coords, vec![(150, 200), (300, 400), (500, 550)]);
+    }
+
+    #[test]
+    fn utr_and_intron_fractions() {
+        let utr5 = entry().fraction_blocks(BedFractionMode::Utr5, false).unwrap();
+        assert_eq!(utr5.len(), 1);
+        assert_eq!((*utr5[0].start().unwrap(), *utr5[0].end().unwrap()), (100, 150));
+        let introns = entry().fraction_blocks(BedFractionMode::All, true).unwrap();
+        assert_eq!(introns.len(), 2);
+        assert_eq!((*introns[1].start().unwrap(), *introns[1].end().unwrap()), (400, 500));
+    }
+
+    #[test]
+    fn missing_fraction() {
+        let no_utr = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t100\t600\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        assert!(no_utr.fraction(BedFractionMode::Utr, false).is_none());
+        let single = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t150\t550\t0\t1\t500,\t0,",
+            12
+        ).unwrap();
+        assert!(single.fraction(BedFractionMode::All, true).is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_exon_range {
+    use super::*;
+
+    fn entry(strand: &str) -> BedEntry {
+        try_parse_bed(
+            &format!("chr1\t100\t900\tA\t5\t{}\t150\t850\t0\t4\t100,100,100,100,\t0,200,400,700,", strand),
+            12
+        ).unwrap()
+    }
+
+    #[test]
+    fn single_exon() {
+        let plus = entry("+").exon(2).unwrap();
+        assert_eq!(to_line(&plus, 6).unwrap(), "chr1\t300\t400\tA\t5\t+");
+        let minus = entry("-").exon(1).unwrap();
+        assert_eq!(to_line(&minus, 6).unwrap(), "chr1\t800\t900\tA\t5\t-");
+        assert!(entry("+").exon(0).is_none());
+        assert!(entry("+").exon(5).is_none());
+        // a transcript assembled from BED4 records has no score of its own
+        let mut unscored = entry("+");
+        unscored.score = None;
+        assert_eq!(to_line(&unscored.exon(2).unwrap(), 6).unwrap(), "chr1\t300\t400\tA\t0\t+");
+    }
+
+    #[test]
+    fn exon_ranges() {
+        let plus = entry("+").exons_range(2..=4).unwrap();
+        assert_eq!(
+            to_line(&plus, 12).unwrap(),
+            "chr1\t300\t900\tA\t5\t+\t300\t850\t0\t3\t100,100,100,\t0,200,500,"
+        );
+        let minus = entry("-").exons_range(1..3).unwrap();
+        assert_eq!(
+            to_line(&minus, 12).unwrap(),
+            "chr1\t500\t900\tA\t5\t-\t500\t850\t0\t2\t100,100,\t0,300,"
+        );
+        assert_eq!(entry("-").exons_range(..).unwrap(), entry("-"));
+        assert!(entry("+").exons_range(3..3).is_none());
+        assert!(entry("+").exons_range(3..=5).is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_utr_blocks {
+    use super::*;
+
+    #[test]
+    fn annotated_utrs() {
+        // 5'-UTR split between the first coding exon and a separate exon; 3'-UTR merged with the last coding exon
+        let minus = try_parse_bed(
+            "chr1\t100\t900\tA\t0\t-\t150\t550\t0\t3\t100,100,200,\t0,400,600,",
+            12
+        ).unwrap();
+        let utrs = minus.utr_blocks().unwrap();
+        let lines: Vec<String> = utrs.iter().map(|x| x.to_line().unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "chr1\t100\t150\tA\t0\t-\t3UTR\tadjacent",
+                "chr1\t550\t600\tA\t0\t-\t5UTR\tadjacent",
+                "chr1\t700\t900\tA\t0\t-\t5UTR\tseparated",
+            ]
+        );
+        let plus = try_parse_bed(
+            "chr1\t100\t900\tA\t0\t+\t500\t900\t0\t3\t100,100,400,\t0,200,400,",
+            12
+        ).unwrap();
+        let utrs = plus.utr_blocks().unwrap();
+        assert_eq!(utrs.len(), 2);
+        assert!(utrs.iter().all(|x| x.side() == Some(&UtrSide::FivePrime)));
+        assert!(utrs.iter().all(|x| x.is_adjacent() == Some(false)));
+    }
+
+    #[test]
+    fn noncoding_has_no_utrs() {
+        let noncoding = try_parse_bed(
+            "chr1\t100\t900\tA\t0\t+\t100\t100\t0\t1\t800,\t0,",
+            12
+        ).unwrap();
+        assert!(noncoding.utr_blocks().unwrap().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod test_exonic_overlap {
+    use super::*;
+
+    #[test]
+    fn shared_exonic_bases() {
+        let first = try_parse_bed(
+            "chr1\t100\t400\tA\t0\t+\t100\t400\t0\t2\t100,100,\t0,200,",
+            12
+        ).unwrap();
+        let second = try_parse_bed(
+            "chr1\t150\t350\tB\t0\t-\t150\t350\t0\t2\t20,100,\t0,100,",
+            12
+        ).unwrap();
+        // 150-170 overlaps the first exon, 250-350 covers the intron and a half of the second exon
+        assert_eq!(first.exonic_overlap(&second), 70);
+        assert_eq!(first.exonic_overlap_fraction(&second), Some(0.35));
+        assert_eq!(second.exonic_overlap_fraction(&second), Some(1.0));
+    }
+}
+
+#[cfg(test)]
+mod test_from_exons {
+    use super::*;
+
+    #[test]
+    fn assemble_from_exons() {
+        let exons: Vec<BedEntry> = ["chr1\t500\t600\tA\t0\t+", "chr1\t100\t200\tA\t0\t+", "chr1\t200\t250\tA\t0\t+"]
+            .iter()
+            .map(|x| try_parse_bed(x, 6).unwrap())
+            .collect();
+        let entry = BedEntry::from_exons(&exons, Some((150, 550))).unwrap();
+        assert_eq!(entry.exons().collect::<Vec<(u64, u64)>>(), vec![(100, 250), (500, 600)]);
+        assert_eq!((entry.thick_start(), entry.thick_end()), (Some(150), Some(550)));
+        assert!(BedEntry::from_exons(&exons, Some((50, 550))).is_err());
+        assert!(BedEntry::from_exons(&[], None).is_err());
+    }
+}
+
+#[cfg(test)]
+mod test_exon_editing {
+    use super::*;
+
+    #[test]
+    fn edit_exons() {
+        let mut entry = try_parse_bed(
+            "chr1\t100\t600\tA\t0\t+\t150\t550\t0\t3\t100,100,100,\t0,200,400,",
+            12
+        ).unwrap();
+        entry.remove_exon(0).unwrap();
+        assert_eq!((entry.thin_start(), entry.thick_start()), (Some(300), Some(300)));
+        assert_eq!(entry.exon_starts(), Some(&vec![0, 200]));
+        entry.insert_exon(50, 120).unwrap();
+        assert_eq!(entry.exons().collect::<Vec<(u64, u64)>>(), vec![(50, 120), (300, 400), (500, 600)]);
+        assert_eq!(entry.exon_num(), Some(3));
+        assert!(entry.insert_exon(350, 450).is_err());
+        entry.replace_exon(2, 500, 700).unwrap();
+        assert_eq!(entry.thin_end(), Some(700));
+        assert!(entry.replace_exon(1, 100, 310).is_err());
+        assert!(entry.remove_exon(5).is_err());
+
+        // removing an internal exon snaps the coding boundaries it contained to the adjacent exons
+        let bed = |thick_start: u64, thick_end: u64| try_parse_bed(
+            &format!("chr1\t100\t600\tB\t0\t+\t{}\t{}\t0\t3\t100,100,100,\t0,200,400,", thick_start, thick_end),
+            12
+        ).unwrap();
+        let mut entry = bed(350, 550);
+        entry.remove_exon(1).unwrap();
+        assert_eq!((entry.thick_start(), entry.thick_end()), (Some(500), Some(550)));
+        let mut entry = bed(150, 350);
+        entry.remove_exon(1).unwrap();
+        assert_eq!((entry.thick_start(), entry.thick_end()), (Some(150), Some(200)));
+        let mut entry = bed(320, 380);
+        entry.remove_exon(1).unwrap();
+        assert_eq!((entry.thick_start(), entry.thick_end()), (Some(100), Some(100)));
+    }
+}
+
+#[cfg(test)]
+mod test_codon_clipping {
+    use super::*;
+
+    #[test]
+    fn codon_aligned_clipping() {
+        // CDS 100-130 + 200-230, 60 bases or 20 codons
+        let line = String::from("chr1\t50\t300\tA\t0\t+\t100\t230\t0\t2\t80,100,\t0,150,");
+        let mut plus = try_parse_bed(&line.clone(), 12).unwrap();
+        let clipped = plus.clip_by_codons(Some(104), Some(211), CodonSnap::Inward, false).unwrap();
+        assert_eq!((clipped.thick_start(), clipped.thick_end()), (Some(106), Some(209)));
+        let clipped = plus.clip_by_codons(Some(104), Some(211), CodonSnap::Outward, false).unwrap();
+        assert_eq!((clipped.thin_start(), clipped.thin_end()), (Some(103), Some(212)));
+        // codons of the minus-strand entry are counted from thickEnd
+        let mut minus = try_parse_bed(&line.replace("\t+\t", "\t-\t").replace("230", "229"), 12).unwrap();
+        let clipped = minus.clip_by_codons(Some(104), None, CodonSnap::Inward, false).unwrap();
+        assert_eq!(clipped.thick_start(), Some(105));
+    }
+}
+
+#[cfg(test)]
+mod test_strand_trimming {
+    use super::*;
+
+    #[test]
+    fn strand_relative_trimming() {
+        let line = String::from("chr1\t100\t400\tA\t0\t+\t120\t380\t0\t2\t100,100,\t0,200,");
+        let mut plus = try_parse_bed(&line.clone(), 12).unwrap();
+        plus.trim_five_prime(110).unwrap();
+        assert_eq!(plus.exons().collect::<Vec<(u64, u64)>>(), vec![(310, 400)]);
+        assert_eq!((plus.thick_start(), plus.exon_num()), (Some(310), Some(1)));
+        let mut minus = try_parse_bed(&line.replace("\t+\t", "\t-\t"), 12).unwrap();
+        minus.trim_five_prime(30).unwrap();
+        minus.trim_three_prime(50).unwrap();
+        assert_eq!(minus.exons().collect::<Vec<(u64, u64)>>(), vec![(150, 200), (300, 370)]);
+        assert_eq!((minus.thick_start(), minus.thick_end()), (Some(150), Some(370)));
+        assert!(minus.trim_three_prime(120).is_err());
+
+        let mut unnamed = try_parse_bed(&line.clone(), 12).unwrap();
+        unnamed.name = None;
+        unnamed.trim_five_prime(10).unwrap();
+        assert_eq!(unnamed.thin_start(), Some(110));
+        unnamed.chrom = None;
+        assert!(matches!(unnamed.trim_three_prime(10), Err(CubiculumError::MissingTraitError(_))));
+        assert_eq!(unnamed.thin_end(), Some(400));
     }
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod test_graft {
     use super::*;
+    use crate::extract::extract::parse_bed;
 
     #[test]
     fn test_graft_upstream(){
@@ -771,6 +2497,11 @@ mod test_graft {
             false, 
             false
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(53297131, 53298145), (53298978, 53299152), (53305454, 53305606), (53308826, 53308962)]
+        );
         let graft2 = parse_bed(
             String::from("chr1	53298971	53298978	XM_047446425.1#ORMDL1#78	2	+"),
             6,
@@ -786,6 +2517,11 @@ mod test_graft {
             true, 
             false
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(53297131, 53298145), (53298971, 53299152), (53305454, 53305606), (53308826, 53308962)]
+        );
         let graft3 = parse_bed(
             String::from("chr1	53308962	53310298	XM_047446425.1#ORMDL1#78	3	+"),
             6,
@@ -801,6 +2537,11 @@ mod test_graft {
             false, 
             true
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(53297131, 53298145), (53298971, 53299152), (53305454, 53305606), (53308826, 53310298)]
+        );
         println!("{:#?}", input);
         println!("{}", to_line(&input, 12).unwrap());
     }
@@ -827,6 +2568,11 @@ mod test_graft {
             true, 
             false
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(49472245, 49490413), (49499635, 49499838), (49502654, 49503120)]
+        );
         println!("{}", to_line(&input, 12).unwrap());
         
         let graft2 = parse_bed(
@@ -843,6 +2589,11 @@ mod test_graft {
             false, 
             true
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(49472245, 49490413), (49499635, 49499838), (49502654, 49503179)]
+        );
         println!("{}", to_line(&input, 12).unwrap());
 
         let graft3 = parse_bed(
@@ -859,6 +2610,11 @@ mod test_graft {
             false, 
             false
         );
+        assert!(matches!(grafted, Ok(None)));
+        assert_eq!(
+            input.exons().collect::<Vec<(u64, u64)>>(),
+            vec![(49472245, 49490413), (49499635, 49499838), (49502654, 49503179), (49506738, 49510808)]
+        );
         println!("{}", to_line(&input, 12).unwrap());
     }
 
@@ -875,18 +2631,17 @@ mod test_graft {
             6,
             false
         ).unwrap();
-        let result = input.graft(
-            graft,
-            false,
-            true,
-            false,
-            false,
-            false,
-            true
-        ).unwrap();
-        println!(
-            "{}", to_line(&result, 12).unwrap()
+        // the graft overlaps the last block, so overlaps must be allowed explicitly
+        let options = GraftOptions::new().side(GraftSide::Downstream).allow_overlaps(true);
+        let result = input.graft_with(graft.clone(), &options, false).unwrap().unwrap();
+        assert_eq!(
+            to_line(&result, 12).unwrap(),
+            "chr4\t136609684\t136613132\tENST00000566855.4#TEX46#5\t0\t+\t136609684\t136613103\t0,0,200\t3\t2,160,239,\t0,843,3209,"
         );
+        assert!(matches!(
+            input.graft_with(graft, &options.allow_overlaps(false), false),
+            Err(CubiculumError::OverlapRejected(_))
+        ));
     }
 
     #[test]
@@ -909,7 +2664,7 @@ mod test_graft {
             false, 
             true, 
             false
-        ).unwrap();
+        ).unwrap().unwrap();
         println!(
             "{}", to_line(&grafted_up, 12).unwrap()
         );
@@ -927,7 +2682,7 @@ mod test_graft {
             false, 
             false, 
             false
-        ).unwrap();
+        ).unwrap().unwrap();
         println!(
             "{}", to_line(&grafted_down1, 12).unwrap()
         );
@@ -945,12 +2700,98 @@ mod test_graft {
             false, 
             false, 
             true
-        ).unwrap();
+        ).unwrap().unwrap();
         println!(
             "{}", to_line(&grafted_down2, 12).unwrap()
         );
     }
 
+    #[test]
+    fn graft_errors() {
+        let mut input = parse_bed(
+            String::from("chr1\t100\t400\tA\t0\t+\t150\t400\t0\t2\t100,100,\t0,200,"),
+            12,
+            false
+        ).unwrap();
+        let elsewhere = Interval::from(Some(String::from("chr2")), Some(50), Some(100), None);
+        let upstream = Interval::from(Some(String::from("chr1")), Some(50), Some(100), None);
+        let overlapping = Interval::from(Some(String::from("chr1")), Some(350), Some(450), None);
+        assert!(matches!(
+            input.graft(elsewhere, false, true, false, false, true, false),
+            Err(CubiculumError::IncompatibleChrom(_))
+        ));
+        assert!(matches!(
+            input.graft(upstream.clone(), false, true, false, true, true, false),
+            Err(CubiculumError::NonCodingFlank(_))
+        ));
+        assert!(matches!(
+            input.graft(overlapping, false, true, false, false, false, false),
+            Err(CubiculumError::OverlapRejected(_))
+        ));
+        assert!(input.graft(upstream, true, true, false, false, true, false).unwrap().is_none());
+        assert_eq!(input.thin_start(), Some(50));
+    }
+
+    #[test]
+    fn graft_with_options() {
+        let mut input = parse_bed(
+            String::from("chr1\t100\t400\tA\t0\t+\t100\t400\t0\t2\t100,100,\t0,200,"),
+            12,
+            false
+        ).unwrap();
+        let upstream = Interval::from(Some(String::from("chr1")), Some(50), Some(100), None);
+        let options = GraftOptions::new().side(GraftSide::Upstream).coding(true);
+        let grafted = input.graft_with(upstream.clone(), &options, false).unwrap().unwrap();
+        assert_eq!((grafted.thin_start(), grafted.thick_start()), (Some(50), Some(50)));
+        assert_eq!(grafted.exons().collect::<Vec<(u64, u64)>>(), vec![(50, 200), (300, 400)]);
+        let positional = input.graft(upstream, false, true, false, true, true, false).unwrap().unwrap();
+        assert_eq!(positional, grafted);
+    }
+
+    #[test]
+    fn strand_relative_graft() {
+        let line = String::from("chr1\t100\t400\tA\t0\t-\t100\t400\t0\t2\t100,100,\t0,200,");
+        let mut minus = parse_bed(line.clone(), 12, false).unwrap();
+        let flank = Interval::from(Some(String::from("chr1")), Some(400), Some(450), None);
+        let options = GraftOptions::new().side(GraftSide::FivePrime).coding(true);
+        let grafted = minus.graft_with(flank.clone(), &options, false).unwrap().unwrap();
+        assert_eq!((grafted.thin_end(), grafted.thick_end()), (Some(450), Some(450)));
+        let mut plus = parse_bed(line.replace("\t-\t", "\t+\t"), 12, false).unwrap();
+        let options = GraftOptions::new().side(GraftSide::ThreePrime).coding(true);
+        let grafted = plus.graft_with(flank, &options, false).unwrap().unwrap();
+        assert_eq!(grafted.thin_end(), Some(450));
+        let mut unstranded = parse_bed(line, 12, false).unwrap();
+        unstranded.strand = None;
+        let flank = Interval::from(Some(String::from("chr1")), Some(50), Some(100), None);
+        assert!(matches!(
+            unstranded.graft_with(flank, &options, false),
+            Err(CubiculumError::MissingTraitError(_))
+        ));
+    }
+
+    #[test]
+    fn edit_reports() {
+        let mut input = parse_bed(
+            String::from("chr1\t100\t400\tA\t0\t+\t150\t400\t0\t2\t100,100,\t0,200,"),
+            12,
+            false
+        ).unwrap();
+        let bridge = Interval::from(Some(String::from("chr1")), Some(180), Some(320), None);
+        let options = GraftOptions::new().allow_overlaps(true);
+        let (grafted, report) = input.graft_with_reported(bridge, &options, false).unwrap();
+        assert_eq!(grafted.unwrap().exons().len(), 1);
+        assert_eq!(
+            report,
+            EditReport {
+                bases_added: 100, bases_removed: 0, blocks_before: 2, blocks_after: 1,
+                blocks_merged: 2, thick_moved: false, absorbing_block: Some(0)
+            }
+        );
+        let (_, report) = input.clip_by_reported(Some(170), None, true);
+        assert_eq!((report.bases_removed, report.thick_moved), (70, true));
+        assert_eq!(input.thin_start(), Some(170));
+    }
+
     #[test]
     pub fn graft_problematic1() {
         let mut input = parse_bed(
@@ -975,11 +2816,6 @@ mod test_graft {
         println!(
             "{}", to_line(&input, 12).unwrap()
         );
-        let graft2 = parse_bed(
-            String::from("chr5\t33379225\t33379227\t1\t0\t+"),
-            6,
-            false
-        );
     }
 
     #[test]
@@ -1164,6 +3000,33 @@ impl UtrBlock {
     pub fn set_adjacency(&mut self, is_adjacent: bool) {
         self.adjacent = Some(is_adjacent)
     }
+
+    pub fn side(&self) -> Option<&UtrSide> {
+        self.side.as_ref()
+    }
+
+    /// Returns true if the block shares an exon with the coding sequence,
+    /// false if it is separated from the CDS by an intron
+    pub fn is_adjacent(&self) -> Option<bool> {
+        self.adjacent
+    }
+
+    /// Format the block into a BED6 line followed by the side ('5UTR' or '3UTR')
+    /// and adjacency ('adjacent' or 'separated') columns
+    pub fn to_line(&self) -> Option<String> {
+        let strand = if self.strand? {'+'} else {'-'};
+        let side = match self.side.as_ref()? {
+            UtrSide::FivePrime => {"5UTR"},
+            UtrSide::ThreePrime => {"3UTR"}
+        };
+        let adjacency = if self.adjacent? {"adjacent"} else {"separated"};
+        Some(
+            format!(
+                "{}\t{}\t{}\t{}\t0\t{}\t{}\t{}",
+                self.chrom.as_ref()?, self.start?, self.end?, self.name.as_ref()?, strand, side, adjacency
+            )
+        )
+    }
 }
 
 pub trait Coordinates{
@@ -1362,6 +3225,12 @@ impl Coordinates for UtrBlock {
 pub trait Stranded {
     fn strand(&self) -> bool;
 
+    /// Strand of the record, None if undefined; the strand-aware operations rely on it
+    /// so that unstranded records do not cause a panic
+    fn defined_strand(&self) -> Option<bool> {
+        Some(self.strand())
+    }
+
     fn update_strand(&mut self, strand: bool);
 }
 
@@ -1370,6 +3239,24 @@ impl Stranded for UtrBlock {
         self.strand.unwrap()
     }
 
+    fn defined_strand(&self) -> Option<bool> {
+        self.strand
+    }
+
+    fn update_strand(&mut self, strand: bool) {
+        self.strand = Some(strand)
+    }
+}
+
+impl Stranded for BedEntry {
+    fn strand(&self) -> bool {
+        self.strand.expect("Strand is not defined for the BED entry")
+    }
+
+    fn defined_strand(&self) -> Option<bool> {
+        self.strand
+    }
+
     fn update_strand(&mut self, strand: bool) {
         self.strand = Some(strand)
     }
@@ -1457,4 +3344,293 @@ impl<'a> Named for &'a UtrBlock{
     fn update_name(&mut self, new_name: &str ) {
         // self.name = Some(new_name.to_string());
     }
-}
\ No newline at end of file
+}
+/// Numeric access to the score field of annotation records
+pub trait Scored {
+    /// Returns the score as a floating-point number,
+    /// or None if the score is undefined or cannot be parsed as a number
+    fn score_value(&self) -> Option<f64>;
+}
+
+impl Scored for Interval {
+    // intervals carry no score field
+    fn score_value(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl Scored for &Interval {
+    fn score_value(&self) -> Option<f64> {
+        None
+    }
+}
+
+impl Scored for BedEntry {
+    fn score_value(&self) -> Option<f64> {
+        self.score.as_ref().and_then(|x| x.parse::<f64>().ok())
+    }
+}
+
+impl Scored for &BedEntry {
+    fn score_value(&self) -> Option<f64> {
+        self.score.as_ref().and_then(|x| x.parse::<f64>().ok())
+    }
+}
+
+impl Scored for UtrBlock {
+    fn score_value(&self) -> Option<f64> {
+        None
+    }
+}
+
+/// Serialization of lightweight interval records into BED lines
+///
+/// Records are reported as BED3, BED4 (with the name) or BED6; since neither Interval nor UtrBlock
+/// carries a score, BED6 lines have a zero score, and records without strand information are reported
+/// with the '.' strand. BedEntry objects are formatted with `BedEntry::write_line()` instead
+pub trait ToBedLine: Coordinates + Named {
+    /// Strand reported in BED6 lines; None stands for unstranded records
+    fn bed_strand(&self) -> Option<bool> {
+        None
+    }
+
+    /// Append the record as a BED3, BED4 or BED6 line to `out`, without the trailing newline
+    fn write_bed_line<W: fmt::Write>(&self, format: u8, out: &mut W) -> Result<(), CubiculumError> {
+        if format != 3 && format != 4 && format != 6 {
+            return Err(
+                CubiculumError::FormattingError(
+                    format!("Provided format BED{} is not supported. Accepted formats are : BED3,4,6", format)
+                )
+            );
+        }
+        let (chrom, start, end) = match (self.chrom(), self.start(), self.end()) {
+            (Some(x), Some(y), Some(z)) => {(x, y, z)},
+            _ => {return Err(CubiculumError::MissingTraitError("Undefined coordinate fields".to_string()))}
+        };
+        let written = |e: fmt::Error| CubiculumError::FormattingError(e.to_string());
+        write!(out, "{}\t{}\t{}", chrom, start, end).map_err(written)?;
+        if format == 3 {return Ok(())};
+        let name = match self.name() {
+            Some(x) => {x},
+            None => {return Err(CubiculumError::MissingTraitError("Undefined name field".to_string()))}
+        };
+        write!(out, "\t{}", name).map_err(written)?;
+        if format == 4 {return Ok(())};
+        let strand = match self.bed_strand() {
+            Some(true) => {'+'},
+            Some(false) => {'-'},
+            None => {'.'}
+        };
+        write!(out, "\t0\t{}", strand).map_err(written)
+    }
+
+    /// Format the record as a BED3, BED4 or BED6 line; see `write_bed_line()`
+    fn to_bed_line(&self, format: u8) -> Result<String, CubiculumError> {
+        let mut out = String::new();
+        self.write_bed_line(format, &mut out)?;
+        Ok(out)
+    }
+}
+
+impl ToBedLine for Interval {}
+
+impl ToBedLine for &Interval {}
+
+impl ToBedLine for UtrBlock {
+    fn bed_strand(&self) -> Option<bool> {
+        self.strand
+    }
+}
+
+impl ToBedLine for &UtrBlock {
+    fn bed_strand(&self) -> Option<bool> {
+        self.strand
+    }
+}
+
+/// Chromosome lengths for a genome assembly, stored in the order of their insertion
+///
+/// Can be read from UCSC chrom.sizes files or FASTA index (.fai) files,
+/// since only the first two columns are considered
+#[derive(Clone, Debug, Default)]
+pub struct ChromSizes {
+    order: Vec<String>,
+    sizes: FxHashMap<String, u64>
+}
+
+impl ChromSizes {
+    pub fn new() -> ChromSizes {
+        ChromSizes { order: Vec::new(), sizes: FxHashMap::default() }
+    }
+
+    /// Parse chromosome sizes from a tab-separated stream; blank lines and lines starting with '#' are skipped
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<ChromSizes, CubiculumError> {
+        let mut chrom_sizes = ChromSizes::new();
+        for (i, line_) in reader.lines().enumerate() {
+            let line = line_?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue};
+            let mut fields = line.split('\t');
+            let chrom = fields.next().unwrap();
+            let size = match fields.next().map(|x| x.trim().parse::<u64>()) {
+                Some(Ok(x)) => {x},
+                _ => {
+                    return Err(
+                        CubiculumError::ParseError(format!("Invalid chromosome size at line {}: {}", i + 1, line))
+                    )
+                }
+            };
+            chrom_sizes.insert(chrom.to_string(), size);
+        }
+        Ok(chrom_sizes)
+    }
+
+    /// Read chromosome sizes from a file
+    #[cfg(feature = "fs")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<ChromSizes, CubiculumError> {
+        let file = File::open(path)?;
+        ChromSizes::from_reader(BufReader::new(file))
+    }
+
+    /// Add a chromosome or update the size of an already recorded one
+    pub fn insert(&mut self, chrom: String, size: u64) {
+        if self.sizes.insert(chrom.clone(), size).is_none() {
+            self.order.push(chrom);
+        }
+    }
+
+    pub fn get(&self, chrom: &str) -> Option<u64> {
+        self.sizes.get(chrom).copied()
+    }
+
+    /// Iterate over chromosome names in the order of their insertion
+    pub fn chroms(&self) -> impl Iterator<Item = &String> {
+        self.order.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    /// Returns the summed length of all the chromosomes
+    pub fn total_length(&self) -> u64 {
+        self.sizes.values().sum()
+    }
+}
+
+/// Check whether the records are sorted by chromosome name (lexicographically) and start coordinate,
+/// as expected by the sweep-line operations
+pub fn is_sorted<T: Coordinates>(records: &[T]) -> bool {
+    check_sorted(records).is_ok()
+}
+
+/// Check whether the records are sorted by chromosome name (lexicographically) and start coordinate
+///
+/// # Returns
+/// An UnsortedInput error pointing to the first record out of order
+pub fn check_sorted<T: Coordinates>(records: &[T]) -> Result<(), CubiculumError> {
+    for (i, pair) in records.windows(2).enumerate() {
+        let prev = (pair[0].chrom(), pair[0].start());
+        let next = (pair[1].chrom(), pair[1].start());
+        if prev > next {
+            return Err(
+                CubiculumError::UnsortedInput(
+                    format!(
+                        "Records are not coordinate-sorted: record {} ({:?}:{:?}) follows {:?}:{:?}",
+                        i + 1, next.0, next.1, prev.0, prev.1
+                    )
+                )
+            )
+        }
+    }
+    Ok(())
+}
+
+/// A collection of records guaranteed to be sorted by chromosome name (lexicographically) and start coordinate
+///
+/// Sweep-line operations on SortedBed never fail due to unsorted input
+#[derive(Clone, Debug, Default)]
+pub struct SortedBed<T> {
+    records: Vec<T>
+}
+
+impl<T: Coordinates> SortedBed<T> {
+    /// Wrap the records already sorted, returning an error if they are not
+    pub fn new(records: Vec<T>) -> Result<SortedBed<T>, CubiculumError> {
+        check_sorted(&records)?;
+        Ok(SortedBed {records})
+    }
+
+    /// Sort the records by chromosome name, start and end coordinates and wrap them
+    pub fn from_unsorted(mut records: Vec<T>) -> SortedBed<T> {
+        records.sort_by(
+            |a, b| a.chrom().cmp(&b.chrom())
+                .then(a.start().cmp(&b.start()))
+                .then(a.end().cmp(&b.end()))
+        );
+        SortedBed {records}
+    }
+
+    pub fn records(&self) -> &[T] {
+        &self.records
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.records
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl<T: Coordinates + Named + Clone> SortedBed<T> {
+    /// Lazily merge the overlapping or book-ended records; see merge_stream()
+    pub fn merge(&self, keep_names: bool) -> MergeStream<std::iter::Cloned<std::slice::Iter<'_, T>>> {
+        merge_stream(self.records.iter().cloned(), keep_names)
+    }
+}
+
+impl<T: Coordinates + Clone> SortedBed<T> {
+    /// Intersect with another sorted collection; see intersect()
+    pub fn intersect<'a, B: Coordinates + Clone>(
+        &'a self, other: &'a SortedBed<B>
+    ) -> Intersect<std::iter::Cloned<std::slice::Iter<'a, T>>, std::iter::Cloned<std::slice::Iter<'a, B>>> {
+        intersect(self.records.iter().cloned(), other.records.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod test_sorted {
+    use super::*;
+
+    fn interval(chrom: &str, start: u64, end: u64) -> Interval {
+        Interval::from(Some(chrom.to_string()), Some(start), Some(end), None)
+    }
+
+    #[test]
+    fn sortedness_check() {
+        let sorted = vec![interval("chr1", 100, 200), interval("chr1", 100, 150), interval("chr2", 0, 10)];
+        assert!(is_sorted(&sorted));
+        let unsorted = vec![interval("chr2", 0, 10), interval("chr1", 100, 200)];
+        assert!(!is_sorted(&unsorted));
+        assert!(matches!(SortedBed::new(unsorted.clone()), Err(CubiculumError::UnsortedInput(_))));
+        let fixed = SortedBed::from_unsorted(unsorted);
+        assert_eq!(fixed.records()[0].chrom(), Some(&String::from("chr1")));
+        let other = SortedBed::new(sorted).unwrap();
+        assert_eq!(fixed.intersect(&other).count(), 3);
+        assert_eq!(other.merge(false).count(), 2);
+    }
+}
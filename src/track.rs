@@ -0,0 +1,5 @@
+/*!
+Module for UCSC genome browser custom track output
+*/
+
+pub mod track;
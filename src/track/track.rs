@@ -0,0 +1,455 @@
+//! # cubiculum::track
+//!
+//! Output of BED records as UCSC genome browser custom tracks
+//!
+//! `TrackWriter` writes an optional `browser` line and a `track` line built from `TrackOptions`
+//! ahead of the records, so that the output can be uploaded to the browser as is;
+//! `color_bed()` assigns itemRgb values by strand, score quantile or name prefix
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use std::cmp::min;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use crate::error::error::CubiculumError;
+use crate::extract::extract::{is_bed_header_line, is_track_line, to_line, try_parse_bed};
+use crate::structs::structs::BedEntry;
+
+/// Default display mode of a custom track
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackVisibility {
+    Hide,
+    Dense,
+    Squish,
+    Pack,
+    Full
+}
+
+impl fmt::Display for TrackVisibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackVisibility::Hide => {write!(f, "hide")},
+            TrackVisibility::Dense => {write!(f, "dense")},
+            TrackVisibility::Squish => {write!(f, "squish")},
+            TrackVisibility::Pack => {write!(f, "pack")},
+            TrackVisibility::Full => {write!(f, "full")}
+        }
+    }
+}
+
+/// Custom track line settings
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrackOptions {
+    pub name: String,
+    pub description: Option<String>,
+    /// If true, records are colored with their itemRgb values (BED9 and BED12 only)
+    pub item_rgb: bool,
+    /// If true, records are shaded by their scores
+    pub use_score: bool,
+    pub visibility: Option<TrackVisibility>,
+    /// Track color as RGB components
+    pub color: Option<(u8, u8, u8)>,
+    /// Position the browser opens at, e.g., "chr1:1000-2000"
+    pub position: Option<String>,
+    /// Any other track line attributes, written in the order of addition
+    pub attributes: Vec<(String, String)>
+}
+
+impl TrackOptions {
+    pub fn new(name: &str) -> TrackOptions {
+        TrackOptions {
+            name: name.to_string(),
+            description: None,
+            item_rgb: false,
+            use_score: false,
+            visibility: None,
+            color: None,
+            position: None,
+            attributes: Vec::new()
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> TrackOptions {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn item_rgb(mut self, item_rgb: bool) -> TrackOptions {
+        self.item_rgb = item_rgb;
+        self
+    }
+
+    pub fn use_score(mut self, use_score: bool) -> TrackOptions {
+        self.use_score = use_score;
+        self
+    }
+
+    pub fn visibility(mut self, visibility: TrackVisibility) -> TrackOptions {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    pub fn color(mut self, r: u8, g: u8, b: u8) -> TrackOptions {
+        self.color = Some((r, g, b));
+        self
+    }
+
+    pub fn position(mut self, position: &str) -> TrackOptions {
+        self.position = Some(position.to_string());
+        self
+    }
+
+    pub fn attribute(mut self, key: &str, value: &str) -> TrackOptions {
+        self.attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Format the `track` line; name and description are always quoted,
+    /// other values are quoted if they contain whitespace
+    pub fn track_line(&self) -> String {
+        let mut line = format!("track name=\"{}\"", self.name.replace('"', "'"));
+        if let Some(x) = &self.description {
+            line.push_str(&format!(" description=\"{}\"", x.replace('"', "'")));
+        }
+        if let Some(x) = self.visibility {line.push_str(&format!(" visibility={}", x))};
+        if let Some((r, g, b)) = self.color {line.push_str(&format!(" color={},{},{}", r, g, b))};
+        if self.item_rgb {line.push_str(" itemRgb=\"On\"")};
+        if self.use_score {line.push_str(" useScore=1")};
+        for (key, value) in self.attributes.iter() {
+            if value.contains(char::is_whitespace) {
+                line.push_str(&format!(" {}=\"{}\"", key, value.replace('"', "'")));
+            } else {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+        }
+        line
+    }
+}
+
+/// Writes BED records as a custom track
+///
+/// Header lines are written before the first record, or by `finish()` if no records were written.
+///
+/// # Examples
+/// ```
+/// use cubiculum::extract::extract::try_parse_bed;
+/// use cubiculum::track::track::{TrackOptions, TrackWriter};
+///
+/// let options = TrackOptions::new("genes").description("Annotated genes").item_rgb(true);
+/// let mut writer = TrackWriter::new(Vec::new(), options, 6);
+/// let entry = try_parse_bed("chr1\t100\t200\tA\t0\t+", 6).unwrap();
+/// writer.write_entry(&entry).unwrap();
+/// let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+/// assert_eq!(output, "track name=\"genes\" description=\"Annotated genes\" itemRgb=\"On\"\nchr1\t100\t200\tA\t0\t+\n");
+/// ```
+pub struct TrackWriter<W: Write> {
+    writer: W,
+    options: TrackOptions,
+    format: u8,
+    header_written: bool,
+    written: usize,
+    line: String
+}
+
+impl<W: Write> TrackWriter<W> {
+    /// Create a writer reporting records in the given BED format
+    pub fn new(writer: W, options: TrackOptions, format: u8) -> TrackWriter<W> {
+        TrackWriter {writer, options, format, header_written: false, written: 0, line: String::new()}
+    }
+
+    fn write_header(&mut self) -> Result<(), CubiculumError> {
+        if self.header_written {return Ok(())};
+        if let Some(x) = &self.options.position {
+            writeln!(self.writer, "browser position {}", x)?;
+        }
+        writeln!(self.writer, "{}", self.options.track_line())?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    pub fn write_entry(&mut self, entry: &BedEntry) -> Result<(), CubiculumError> {
+        self.write_header()?;
+        self.line.clear();
+        entry.write_line(self.format, &mut self.line)?;
+        writeln!(self.writer, "{}", self.line)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Write all records from the iterator, returning their number
+    pub fn write_all<'a, I>(&mut self, entries: I) -> Result<usize, CubiculumError>
+    where
+        I: IntoIterator<Item = &'a BedEntry>
+    {
+        let mut written: usize = 0;
+        for entry in entries {
+            self.write_entry(entry)?;
+            written += 1;
+        }
+        Ok(written)
+    }
+
+    /// Number of records written so far
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Write the header if no records were written, flush the output and return the underlying writer
+    pub fn finish(mut self) -> Result<W, CubiculumError> {
+        self.write_header()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// An itemRgb color
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl fmt::Display for Rgb {
+    /// Formats the color as an itemRgb column value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}", self.0, self.1, self.2)
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = CubiculumError;
+
+    /// Parses the "R,G,B" notation
+    fn from_str(s: &str) -> Result<Rgb, CubiculumError> {
+        let components: Vec<u8> = s.trim()
+            .split(',')
+            .map(|x| x.trim().parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| CubiculumError::ParseError(format!("Invalid RGB value: {}", s)))?;
+        match components[..] {
+            [r, g, b] => {Ok(Rgb(r, g, b))},
+            _ => {Err(CubiculumError::ParseError(format!("Invalid RGB value: {}", s)))}
+        }
+    }
+}
+
+/// Colors assigned by name prefix; the longest matching prefix wins
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CategoryColors {
+    prefixes: Vec<(String, Rgb)>,
+    default: Rgb
+}
+
+impl CategoryColors {
+    /// Create an empty map assigning `default` to every name
+    pub fn new(default: Rgb) -> CategoryColors {
+        CategoryColors {prefixes: Vec::new(), default}
+    }
+
+    pub fn category(mut self, prefix: &str, color: Rgb) -> CategoryColors {
+        self.prefixes.push((prefix.to_string(), color));
+        self
+    }
+
+    /// Read the map from a stream of "prefix\tR,G,B" lines; blank lines and comments are skipped
+    pub fn from_reader<R: BufRead>(reader: R, default: Rgb) -> Result<CategoryColors, CubiculumError> {
+        let mut colors = CategoryColors::new(default);
+        for line in reader.lines() {
+            let line = line?;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {continue};
+            let (prefix, color) = trimmed.split_once('\t')
+                .ok_or(CubiculumError::ParseError(format!("Invalid category line: {}", line)))?;
+            colors.prefixes.push((prefix.to_string(), color.parse::<Rgb>()?));
+        }
+        Ok(colors)
+    }
+
+    pub fn color(&self, name: &str) -> Rgb {
+        self.prefixes.iter()
+            .filter(|x| name.starts_with(&x.0))
+            .max_by_key(|x| x.0.len())
+            .map_or(self.default, |x| x.1)
+    }
+}
+
+/// Rule assigning itemRgb values to the records
+#[derive(Clone, Debug, PartialEq)]
+pub enum ColorScheme {
+    /// Colors for the plus strand, the minus strand and the unstranded records
+    Strand(Rgb, Rgb, Rgb),
+    /// Palette from the lowest to the highest score quantile; the number of quantiles equals the palette size.
+    /// Non-numeric scores get the first color
+    ScoreQuantile(Vec<Rgb>),
+    /// Colors assigned by name prefix; unnamed records get the default color
+    Category(CategoryColors)
+}
+
+/// Quantile cutoffs splitting the scores into `n` groups of (nearly) equal size
+pub fn score_breaks(scores: &[f64], n: usize) -> Vec<f64> {
+    let mut sorted: Vec<f64> = scores.iter().copied().filter(|x| !x.is_nan()).collect();
+    if sorted.is_empty() || n < 2 {return Vec::new()};
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    (1..n).map(|i| sorted[i * sorted.len() / n]).collect()
+}
+
+/// Palette color for the score given the quantile cutoffs from `score_breaks()`
+pub fn quantile_color(score: f64, breaks: &[f64], palette: &[Rgb]) -> Rgb {
+    let i = breaks.partition_point(|x| *x <= score);
+    palette[min(i, palette.len() - 1)]
+}
+
+fn numeric_score(entry: &BedEntry) -> f64 {
+    entry.score().and_then(|x| x.parse::<f64>().ok()).unwrap_or(f64::NAN)
+}
+
+/// Assign itemRgb values to the records according to the scheme
+///
+/// Records in formats below BED9 are promoted to BED9; see `BedEntry::update_rgb()`
+pub fn color_entries(entries: &mut [BedEntry], scheme: &ColorScheme) {
+    let breaks = match scheme {
+        ColorScheme::ScoreQuantile(palette) => {
+            let scores: Vec<f64> = entries.iter().map(numeric_score).collect();
+            score_breaks(&scores, palette.len())
+        },
+        _ => {Vec::new()}
+    };
+    for entry in entries.iter_mut() {
+        let color = match scheme {
+            ColorScheme::Strand(plus, minus, unstranded) => {
+                match entry.strand() {
+                    Some(true) => {*plus},
+                    Some(false) => {*minus},
+                    None => {*unstranded}
+                }
+            },
+            ColorScheme::ScoreQuantile(palette) => {
+                if palette.is_empty() {continue};
+                let score = numeric_score(entry);
+                if score.is_nan() {palette[0]} else {quantile_color(score, &breaks, palette)}
+            },
+            ColorScheme::Category(categories) => {
+                entry.name().map_or(categories.default, |x| categories.color(x))
+            }
+        };
+        entry.update_rgb(color.to_string());
+    }
+}
+
+/// Color all BED records from the stream according to the scheme
+///
+/// Records are reported in BED9 (BED12 for BED12 input). Since score quantiles are computed over the whole input,
+/// all records are read into memory first. Track and browser lines are copied to the output, blank lines and comments are skipped
+///
+/// # Returns
+/// The number of records written; an error if a line cannot be parsed or a record lacks the BED6 fields
+///
+pub fn color_bed<R: BufRead, W: Write>(
+    reader: R, format: usize, writer: &mut W, scheme: &ColorScheme
+) -> Result<usize, CubiculumError> {
+    let mut entries: Vec<BedEntry> = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if is_bed_header_line(&line) {
+            if is_track_line(&line) {
+                writeln!(writer, "{}", line.trim())?;
+            }
+            continue
+        }
+        entries.push(
+            try_parse_bed(&line, format)?
+        );
+    }
+    color_entries(&mut entries, scheme);
+    let out_format: u8 = if format == 12 {12} else {9};
+    for entry in entries.iter() {
+        writeln!(writer, "{}", to_line(entry, out_format)?)?;
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod test_track {
+    use super::*;
+    use crate::extract::extract::try_parse_bed;
+
+    #[test]
+    fn track_line_test() {
+        let options = TrackOptions::new("my track")
+            .description("Output of \"cubiculum\"")
+            .visibility(TrackVisibility::Pack)
+            .color(0, 0, 255)
+            .use_score(true)
+            .attribute("group", "genes")
+            .attribute("url", "http://x.org/$$ details");
+        assert_eq!(
+            options.track_line(),
+            "track name=\"my track\" description=\"Output of 'cubiculum'\" visibility=pack color=0,0,255 \
+            useScore=1 group=genes url=\"http://x.org/$$ details\""
+        );
+    }
+
+    #[test]
+    fn track_writer_test() {
+        let options = TrackOptions::new("exons").item_rgb(true).position("chr1:100-900");
+        let entries = [
+            try_parse_bed("chr1\t100\t600\tA\t0\t-\t150\t350\t255,0,0\t2\t100,100,\t0,400,", 12).unwrap(),
+            try_parse_bed("chr1\t700\t900\tB\t0\t+\t700\t900\t0,0,255\t1\t200,\t0,", 12).unwrap()
+        ];
+        let mut writer = TrackWriter::new(Vec::new(), options, 9);
+        assert_eq!(writer.write_all(entries.iter()).unwrap(), 2);
+        assert_eq!(writer.written(), 2);
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "browser position chr1:100-900\ntrack name=\"exons\" itemRgb=\"On\"\n\
+            chr1\t100\t600\tA\t0\t-\t150\t350\t255,0,0\n\
+            chr1\t700\t900\tB\t0\t+\t700\t900\t0,0,255\n"
+        );
+        let empty = TrackWriter::new(Vec::new(), TrackOptions::new("empty"), 6);
+        assert_eq!(String::from_utf8(empty.finish().unwrap()).unwrap(), "track name=\"empty\"\n");
+    }
+
+    #[test]
+    fn rgb_parsing() {
+        assert_eq!("255, 0,128".parse::<Rgb>().unwrap(), Rgb(255, 0, 128));
+        assert_eq!(Rgb(1, 2, 3).to_string(), "1,2,3");
+        assert!("255,0".parse::<Rgb>().is_err());
+        assert!("256,0,0".parse::<Rgb>().is_err());
+    }
+
+    #[test]
+    fn category_colors() {
+        let map = "# gene classes\nENSG\t0,0,255\nENSG00001\t255,0,0\n";
+        let colors = CategoryColors::from_reader(map.as_bytes(), Rgb(0, 0, 0)).unwrap();
+        assert_eq!(colors.color("ENSG00001234"), Rgb(255, 0, 0));
+        assert_eq!(colors.color("ENSG00002234"), Rgb(0, 0, 255));
+        assert_eq!(colors.color("lncRNA1"), Rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn score_quantiles() {
+        let breaks = score_breaks(&[5.0, 1.0, 3.0, 2.0, 4.0, 6.0], 3);
+        assert_eq!(breaks, vec![3.0, 5.0]);
+        let palette = [Rgb(0, 0, 0), Rgb(1, 1, 1), Rgb(2, 2, 2)];
+        assert_eq!(quantile_color(2.0, &breaks, &palette), Rgb(0, 0, 0));
+        assert_eq!(quantile_color(3.0, &breaks, &palette), Rgb(1, 1, 1));
+        assert_eq!(quantile_color(6.0, &breaks, &palette), Rgb(2, 2, 2));
+    }
+
+    #[test]
+    fn color_stream() {
+        let bed = "track name=test\nchr1\t100\t200\tA\t10\t+\nchr1\t300\t400\tB\t20\t-\n";
+        let scheme = ColorScheme::Strand(Rgb(255, 0, 0), Rgb(0, 0, 255), Rgb(0, 0, 0));
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(color_bed(bed.as_bytes(), 6, &mut output, &scheme).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "track name=test\nchr1\t100\t200\tA\t10\t+\t100\t200\t255,0,0\nchr1\t300\t400\tB\t20\t-\t300\t400\t0,0,255\n"
+        );
+        let scheme = ColorScheme::ScoreQuantile(vec![Rgb(0, 0, 0), Rgb(9, 9, 9)]);
+        let mut output: Vec<u8> = Vec::new();
+        color_bed(bed.as_bytes(), 6, &mut output, &scheme).unwrap();
+        assert!(String::from_utf8(output).unwrap().ends_with("\t20\t-\t300\t400\t9,9,9\n"));
+    }
+}
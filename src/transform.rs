@@ -0,0 +1,5 @@
+/*!
+Module for coordinate transformations and window generation
+*/
+
+pub mod transform;
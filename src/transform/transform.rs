@@ -0,0 +1,478 @@
+//! # cubiculum::transform
+//! 
+//! Window generation and coordinate transformations for annotation records
+//!
+//! Author: Yury V.Malovichko
+//!
+//! Year: 2025
+
+use crate::error::error::CubiculumError;
+use crate::structs::structs::{BedEntry, ChromSizes, Coordinates, Interval, Named, Stranded};
+
+/// Tile the genome with fixed-size windows
+///
+/// # Arguments
+/// `chrom_sizes`: chromosome sizes;
+/// `size`: window size;
+/// `step`: distance between the consecutive window starts;
+/// steps smaller than `size` produce overlapping windows
+///
+/// # Returns
+/// A vector of windows named as `{chrom}_{window number}`, following the chromosome order
+/// of `chrom_sizes`; window numbers are one-based, and the last window on each chromosome
+/// is truncated at the chromosome's end. An InputError if the window size or step is zero
+///
+pub fn make_windows(chrom_sizes: &ChromSizes, size: u64, step: u64) -> Result<Vec<Interval>, CubiculumError> {
+    if size == 0 || step == 0 {
+        return Err(CubiculumError::InputError(String::from("Window size and step must be positive")))
+    }
+    let mut windows: Vec<Interval> = Vec::new();
+    for chrom in chrom_sizes.chroms() {
+        let chrom_size = chrom_sizes.get(chrom).unwrap_or(0);
+        let mut start: u64 = 0;
+        let mut num: usize = 1;
+        while start < chrom_size {
+            let end = chrom_size.min(start.saturating_add(size));
+            windows.push(
+                Interval::from(Some(chrom.clone()), Some(start), Some(end), Some(format!("{}_{}", chrom, num)))
+            );
+            if end == chrom_size {break};
+            start = match start.checked_add(step) {
+                Some(x) => {x},
+                None => {break}
+            };
+            num += 1;
+        }
+    }
+    Ok(windows)
+}
+
+/// Modes for splitting intervals into sub-windows with split_windows()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowSplit {
+    /// Split each interval into the given number of windows of (nearly) equal size
+    Count(usize),
+    /// Split each interval into windows of the given size; the last window may be shorter
+    Size(u64)
+}
+
+/// Split each of the intervals into sub-windows
+///
+/// # Arguments
+/// `intervals`: intervals to split;
+/// `mode`: splitting mode
+///
+/// # Returns
+/// A vector of windows named as `{source name}_{window number}`, in the order of the input intervals;
+/// unnamed source intervals are referred to as `{chrom}:{start}-{end}`. Window numbers are one-based;
+/// when splitting into a given number of windows, intervals shorter than it produce one window per base.
+/// An InputError if the number or size of windows is zero
+///
+pub fn split_windows<T>(intervals: &[T], mode: WindowSplit) -> Result<Vec<Interval>, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    if matches!(mode, WindowSplit::Count(0) | WindowSplit::Size(0)) {
+        return Err(CubiculumError::InputError(String::from("Number and size of windows must be positive")))
+    }
+    let mut windows: Vec<Interval> = Vec::new();
+    for interval in intervals {
+        let (start, end) = match (interval.start(), interval.end()) {
+            (Some(s), Some(e)) if e > s => {(*s, *e)},
+            _ => {continue}
+        };
+        let chrom = interval.chrom().cloned();
+        let source_name = match interval.name() {
+            Some(x) => {x.to_string()},
+            None => {format!("{}:{}-{}", chrom.as_deref().unwrap_or(""), start, end)}
+        };
+        let bounds: Vec<u64> = match mode {
+            WindowSplit::Count(n) => {
+                let length = end - start;
+                let n = (n as u64).min(length);
+                (0..=n).map(|i| start + length * i / n).collect()
+            },
+            WindowSplit::Size(size) => {
+                let step = usize::try_from(size).unwrap_or(usize::MAX);
+                let mut bounds: Vec<u64> = (start..end).step_by(step).collect();
+                bounds.push(end);
+                bounds
+            }
+        };
+        for (i, window) in bounds.windows(2).enumerate() {
+            windows.push(
+                Interval::from(chrom.clone(), Some(window[0]), Some(window[1]), Some(format!("{}_{}", source_name, i + 1)))
+            );
+        }
+    }
+    Ok(windows)
+}
+
+/// Returns the end coordinate of the chromosome, or u64::MAX if its size is unknown
+pub(crate) fn chrom_end(chrom: Option<&String>, chrom_sizes: Option<&ChromSizes>) -> u64 {
+    match (chrom, chrom_sizes) {
+        (Some(c), Some(sizes)) => {sizes.get(c).unwrap_or(u64::MAX)},
+        _ => {u64::MAX}
+    }
+}
+
+/// Create the flanking intervals on both sides of the record
+///
+/// # Arguments
+/// `entry`: the source record;
+/// `left`: length of the flank preceding the record;
+/// `right`: length of the flank following the record;
+/// `chrom_sizes`: if provided, flanks are clamped to the chromosome's end
+///
+/// # Returns
+/// A tuple of the left and right flanks, named after the source record;
+/// flanks of zero length after clamping are reported as None
+///
+pub fn flank<T>(entry: &T, left: u64, right: u64, chrom_sizes: Option<&ChromSizes>) -> (Option<Interval>, Option<Interval>)
+where
+    T: Coordinates + Named
+{
+    let (start, end) = match (entry.start(), entry.end()) {
+        (Some(s), Some(e)) => {(*s, *e)},
+        _ => {return (None, None)}
+    };
+    let limit = chrom_end(entry.chrom(), chrom_sizes);
+    let new_flank = |flank_start: u64, flank_end: u64| -> Option<Interval> {
+        let flank_end = flank_end.min(limit);
+        if flank_start >= flank_end {return None};
+        Some(
+            Interval::from(
+                entry.chrom().cloned(), Some(flank_start), Some(flank_end), entry.name().map(|x| x.to_string())
+            )
+        )
+    };
+    (
+        new_flank(start.saturating_sub(left), start),
+        new_flank(end, end.saturating_add(right))
+    )
+}
+
+/// Create the upstream and downstream flanking intervals of the record
+///
+/// Same as `flank()`, except that flank lengths are defined relative to the record's strand;
+/// for records on the negative strand, the upstream flank follows the record's end.
+/// Unstranded records are treated as positive strand ones
+///
+/// # Returns
+/// A tuple of the upstream and downstream flanks
+///
+pub fn flank_stranded<T>(
+    entry: &T, upstream: u64, downstream: u64, chrom_sizes: Option<&ChromSizes>
+) -> (Option<Interval>, Option<Interval>)
+where
+    T: Coordinates + Named + Stranded
+{
+    if entry.defined_strand() != Some(false) {
+        flank(entry, upstream, downstream, chrom_sizes)
+    } else {
+        let (left, right) = flank(entry, downstream, upstream, chrom_sizes);
+        (right, left)
+    }
+}
+
+/// Extension lengths for slop()
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Extension {
+    /// Extend by the given number of bases
+    Bases(u64),
+    /// Extend by the given fraction of the record's length, rounded down; must be finite and non-negative
+    Fraction(f64)
+}
+
+impl Extension {
+    fn bases(&self, length: u64) -> Result<u64, CubiculumError> {
+        match self {
+            Extension::Bases(x) => {Ok(*x)},
+            Extension::Fraction(x) if x.is_finite() && *x >= 0.0 => {Ok((length as f64 * x).floor() as u64)},
+            Extension::Fraction(x) => {
+                Err(CubiculumError::InputError(format!("Extension fraction must be finite and non-negative, got {}", x)))
+            }
+        }
+    }
+}
+
+/// Coordinates of the [start, end) interval extended on both sides and clamped to `limit`
+fn extended_bounds(
+    start: u64, end: u64, left: Extension, right: Extension, limit: u64
+) -> Result<(u64, u64), CubiculumError> {
+    let length = end.saturating_sub(start);
+    let new_start = start.saturating_sub(left.bases(length)?).min(limit);
+    let new_end = end.saturating_add(right.bases(length)?).min(limit);
+    Ok((new_start, new_end))
+}
+
+/// Extend the interval in place on both sides
+///
+/// # Arguments
+/// `interval`: the interval to extend;
+/// `left`: extension preceding the interval's start;
+/// `right`: extension following the interval's end;
+/// `chrom_sizes`: if provided, the interval is clamped to the chromosome's end
+///
+/// Intervals with undefined coordinates are left intact
+///
+/// # Returns
+/// An InputError if either extension is a negative or non-finite fraction; the interval is not modified then
+///
+pub fn slop_in_place(
+    interval: &mut Interval, left: Extension, right: Extension, chrom_sizes: Option<&ChromSizes>
+) -> Result<(), CubiculumError> {
+    let (start, end) = match (interval.start(), interval.end()) {
+        (Some(s), Some(e)) => {(*s, *e)},
+        _ => {return Ok(())}
+    };
+    let limit = chrom_end(interval.chrom(), chrom_sizes);
+    let (new_start, new_end) = extended_bounds(start, end, left, right, limit)?;
+    interval.update_start(new_start);
+    interval.update_end(new_end);
+    Ok(())
+}
+
+/// Returns a copy of the record extended on both sides
+///
+/// Same as `slop_in_place()`, except that the source record is left intact
+///
+/// # Returns
+/// An Interval carrying the source record's chromosome and name at the extended coordinates;
+/// other fields, such as strand or blocks, are not kept (see `slop_entry()` for BED entries).
+/// An InputError for invalid extension fractions
+///
+pub fn slop<T>(entry: &T, left: Extension, right: Extension, chrom_sizes: Option<&ChromSizes>) -> Result<Interval, CubiculumError>
+where
+    T: Coordinates + Named
+{
+    let mut interval = Interval::from(
+        entry.chrom().cloned(), entry.start().copied(), entry.end().copied(), entry.name().map(|x| x.to_string())
+    );
+    slop_in_place(&mut interval, left, right, chrom_sizes)?;
+    Ok(interval)
+}
+
+/// Returns a copy of the BED entry extended on both sides, keeping all of its other fields
+///
+/// The extension is added to the first and last blocks of BED12 entries; the coding boundaries are left intact
+///
+/// # Returns
+/// The extended entry; an InputError for invalid extension fractions, a MissingTraitError if the entry
+/// has undefined chromosome or coordinates, and a CoordinateError if it extends past the chromosome's end
+///
+pub fn slop_entry(
+    entry: &BedEntry, left: Extension, right: Extension, chrom_sizes: Option<&ChromSizes>
+) -> Result<BedEntry, CubiculumError> {
+    let (chrom, start, end) = match (entry.chrom(), entry.start(), entry.end()) {
+        (Some(c), Some(s), Some(e)) => {(c.clone(), *s, *e)},
+        _ => {
+            return Err(
+                CubiculumError::MissingTraitError(String::from("Cannot extend an entry with undefined chromosome or coordinates"))
+            )
+        }
+    };
+    let limit = chrom_end(Some(&chrom), chrom_sizes);
+    if end > limit {
+        return Err(CubiculumError::CoordinateError(format!("Entry {:?} extends past the end of {}", entry.name(), chrom)))
+    }
+    let (new_start, new_end) = extended_bounds(start, end, left, right, limit)?;
+    let mut blocks: Vec<(u64, u64)> = entry.exons().collect();
+    if blocks.is_empty() {blocks.push((start, end))};
+    let last = blocks.len() - 1;
+    blocks[0].0 = new_start;
+    blocks[last].1 = new_end;
+    let thick = (entry.thick_start().unwrap_or(new_start), entry.thick_end().unwrap_or(new_start));
+    Ok(entry.relocated(chrom, blocks, thick, entry.strand()))
+}
+
+/// Translate the interval in place by a signed offset
+///
+/// # Arguments
+/// `interval`: the interval to shift;
+/// `delta`: offset in bases; negative values shift the interval towards smaller coordinates;
+/// `chrom_sizes`: if provided, the coordinates are clamped to the chromosome's end
+///
+/// Coordinates are clamped independently, so intervals pushed over the chromosome bounds are shortened
+///
+pub fn shift_in_place(interval: &mut Interval, delta: i64, chrom_sizes: Option<&ChromSizes>) {
+    let (start, end) = match (interval.start(), interval.end()) {
+        (Some(s), Some(e)) => {(*s, *e)},
+        _ => {return}
+    };
+    let limit = chrom_end(interval.chrom(), chrom_sizes);
+    let move_by = |x: u64| -> u64 {
+        let moved = if delta < 0 {
+            x.saturating_sub(delta.unsigned_abs())
+        } else {
+            x.saturating_add(delta as u64)
+        };
+        moved.min(limit)
+    };
+    interval.update_start(move_by(start));
+    interval.update_end(move_by(end));
+}
+
+/// Returns a copy of the record translated by a signed offset
+///
+/// Same as `shift_in_place()`, except that the source record is left intact;
+/// the shifted interval is named after the source record
+///
+pub fn shift<T>(entry: &T, delta: i64, chrom_sizes: Option<&ChromSizes>) -> Interval
+where
+    T: Coordinates + Named
+{
+    let mut interval = Interval::from(
+        entry.chrom().cloned(), entry.start().copied(), entry.end().copied(), entry.name().map(|x| x.to_string())
+    );
+    shift_in_place(&mut interval, delta, chrom_sizes);
+    interval
+}
+
+/// Returns a copy of the record translated by a signed offset relative to its strand
+///
+/// Same as `shift()`, except that positive offsets move records on the negative strand
+/// towards smaller coordinates; unstranded records are shifted as positive strand ones
+///
+/// # Returns
+/// The shifted interval; a CoordinateError if the offset cannot be reversed for a negative strand record,
+/// i.e. equals i64::MIN
+///
+pub fn shift_stranded<T>(entry: &T, delta: i64, chrom_sizes: Option<&ChromSizes>) -> Result<Interval, CubiculumError>
+where
+    T: Coordinates + Named + Stranded
+{
+    let delta = if entry.defined_strand() == Some(false) {
+        delta.checked_neg().ok_or_else(|| {
+            CubiculumError::CoordinateError(format!("Cannot reverse the offset of {} for a negative strand record", delta))
+        })?
+    } else {
+        delta
+    };
+    Ok(shift(entry, delta, chrom_sizes))
+}
+
+#[cfg(test)]
+mod test_transform {
+    use super::*;
+
+    fn summary(intervals: &[Interval]) -> Vec<(u64, u64, String)> {
+        intervals
+            .iter()
+            .map(|x| (*x.start().unwrap(), *x.end().unwrap(), Named::name(x).unwrap().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn genome_tiling() {
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr1"), 25);
+        chrom_sizes.insert(String::from("chr2"), 5);
+        assert_eq!(
+            summary(&make_windows(&chrom_sizes, 10, 10).unwrap()),
+            vec![
+                (0, 10, String::from("chr1_1")),
+                (10, 20, String::from("chr1_2")),
+                (20, 25, String::from("chr1_3")),
+                (0, 5, String::from("chr2_1")),
+            ]
+        );
+        let sliding = make_windows(&chrom_sizes, 10, 5).unwrap();
+        assert_eq!(sliding.len(), 5);
+        assert_eq!((*sliding[3].start().unwrap(), *sliding[3].end().unwrap()), (15, 25));
+        assert!(make_windows(&chrom_sizes, 10, 0).is_err());
+        assert!(make_windows(&chrom_sizes, 0, 10).is_err());
+    }
+
+    #[test]
+    fn interval_splitting() {
+        let input = vec![
+            Interval::from(Some(String::from("chr1")), Some(100), Some(110), Some(String::from("a"))),
+            Interval::from(Some(String::from("chr1")), Some(200), Some(202), None),
+        ];
+        assert_eq!(
+            summary(&split_windows(&input, WindowSplit::Count(3)).unwrap()),
+            vec![
+                (100, 103, String::from("a_1")),
+                (103, 106, String::from("a_2")),
+                (106, 110, String::from("a_3")),
+                (200, 201, String::from("chr1:200-202_1")),
+                (201, 202, String::from("chr1:200-202_2")),
+            ]
+        );
+        assert_eq!(
+            summary(&split_windows(&input[..1], WindowSplit::Size(4)).unwrap()),
+            vec![
+                (100, 104, String::from("a_1")),
+                (104, 108, String::from("a_2")),
+                (108, 110, String::from("a_3")),
+            ]
+        );
+        assert!(split_windows(&input, WindowSplit::Count(0)).is_err());
+        assert!(split_windows(&input, WindowSplit::Size(0)).is_err());
+    }
+
+    #[test]
+    fn flanking_intervals() {
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr1"), 250);
+        let plus = crate::extract::extract::try_parse_bed("chr1\t100\t200\ta\t0\t+", 6).unwrap();
+        let minus = crate::extract::extract::try_parse_bed("chr1\t100\t200\tb\t0\t-", 6).unwrap();
+        let coords = |x: Option<Interval>| x.map(|y| (*y.start().unwrap(), *y.end().unwrap()));
+        let (left, right) = flank(&plus, 150, 100, Some(&chrom_sizes));
+        assert_eq!((coords(left), coords(right)), (Some((0, 100)), Some((200, 250))));
+        let (left, right) = flank(&plus, 0, 100, None);
+        assert_eq!((coords(left), coords(right)), (None, Some((200, 300))));
+        let (up, down) = flank_stranded(&minus, 20, 10, Some(&chrom_sizes));
+        assert_eq!((coords(up), coords(down)), (Some((200, 220)), Some((90, 100))));
+        let (up, down) = flank_stranded(&plus, 20, 10, Some(&chrom_sizes));
+        assert_eq!((coords(up), coords(down)), (Some((80, 100)), Some((200, 210))));
+        let unstranded = crate::extract::extract::try_parse_bed("chr1\t100\t200\tc", 4).unwrap();
+        let (up, down) = flank_stranded(&unstranded, 20, 10, Some(&chrom_sizes));
+        assert_eq!((coords(up), coords(down)), (Some((80, 100)), Some((200, 210))));
+    }
+
+    #[test]
+    fn interval_extension() {
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr1"), 250);
+        let mut interval = Interval::from(Some(String::from("chr1")), Some(100), Some(200), Some(String::from("a")));
+        let extended = slop(&interval, Extension::Bases(150), Extension::Bases(20), Some(&chrom_sizes)).unwrap();
+        assert_eq!((*extended.start().unwrap(), *extended.end().unwrap()), (0, 220));
+        assert_eq!(Named::name(&extended), Some("a"));
+        slop_in_place(&mut interval, Extension::Fraction(0.1), Extension::Fraction(1.0), Some(&chrom_sizes)).unwrap();
+        assert_eq!((*interval.start().unwrap(), *interval.end().unwrap()), (90, 250));
+        slop_in_place(&mut interval, Extension::Bases(0), Extension::Bases(100), None).unwrap();
+        assert_eq!(*interval.end().unwrap(), 350);
+        assert!(slop_in_place(&mut interval, Extension::Fraction(-0.5), Extension::Bases(0), None).is_err());
+        assert!(slop(&interval, Extension::Bases(0), Extension::Fraction(f64::NAN), None).is_err());
+        assert_eq!(*interval.end().unwrap(), 350);
+
+        let entry = crate::extract::extract::try_parse_bed(
+            "chr1\t100\t200\tA\t5\t-\t120\t180\t0\t2\t30,40,\t0,60,", 12
+        ).unwrap();
+        let extended = slop_entry(&entry, Extension::Bases(10), Extension::Bases(100), Some(&chrom_sizes)).unwrap();
+        assert_eq!(
+            crate::extract::extract::to_line(&extended, 12).unwrap(),
+            "chr1\t90\t250\tA\t5\t-\t120\t180\t0\t2\t40,90,\t0,70,"
+        );
+        assert!(slop_entry(&entry, Extension::Fraction(-1.0), Extension::Bases(0), None).is_err());
+    }
+
+    #[test]
+    fn interval_shift() {
+        let mut chrom_sizes = ChromSizes::new();
+        chrom_sizes.insert(String::from("chr1"), 250);
+        let coords = |x: &Interval| (*x.start().unwrap(), *x.end().unwrap());
+        let interval = Interval::from(Some(String::from("chr1")), Some(100), Some(200), None);
+        assert_eq!(coords(&shift(&interval, 30, Some(&chrom_sizes))), (130, 230));
+        assert_eq!(coords(&shift(&interval, 100, Some(&chrom_sizes))), (200, 250));
+        assert_eq!(coords(&shift(&interval, -150, None)), (0, 50));
+        let minus = crate::extract::extract::try_parse_bed("chr1\t100\t200\tb\t0\t-", 6).unwrap();
+        assert_eq!(coords(&shift_stranded(&minus, 30, Some(&chrom_sizes)).unwrap()), (70, 170));
+        assert!(matches!(shift_stranded(&minus, i64::MIN, None), Err(CubiculumError::CoordinateError(_))));
+        let unstranded = crate::extract::extract::try_parse_bed("chr1\t100\t200\tc", 4).unwrap();
+        assert_eq!(coords(&shift_stranded(&unstranded, 30, Some(&chrom_sizes)).unwrap()), (130, 230));
+        assert_eq!(coords(&shift_stranded(&unstranded, i64::MIN, None).unwrap()), (0, 0));
+    }
+}